@@ -0,0 +1,55 @@
+use tokio::sync::mpsc::Receiver;
+use tokio_util::sync::CancellationToken;
+
+/* Receive the next message from `rx`, unless `cancel_token` is cancelled first.
+ * Returns None if the channel is closed or the token is cancelled, so a receive loop
+ * becomes `while let Some(msg) = recv_or_cancel(&mut rx, &cancel_token).await`.
+ * A message that is already queued is preferred over a simultaneous cancellation */
+pub async fn recv_or_cancel<T>(
+    rx: &mut Receiver<T>,
+    cancel_token: &CancellationToken,
+) -> Option<T> {
+    tokio::select! {
+        biased;
+        msg = rx.recv() => msg,
+        _ = cancel_token.cancelled() => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::time::Duration;
+    use tokio::sync::mpsc::channel;
+
+    #[tokio::test]
+    async fn received() {
+        let cancel_token = CancellationToken::new();
+        let (tx, mut rx) = channel(1);
+        tx.send(42).await.unwrap();
+        assert_eq!(recv_or_cancel(&mut rx, &cancel_token).await, Some(42));
+    }
+
+    #[tokio::test]
+    async fn closed() {
+        let cancel_token = CancellationToken::new();
+        let (tx, mut rx) = channel::<u32>(1);
+        drop(tx);
+        assert_eq!(recv_or_cancel(&mut rx, &cancel_token).await, None);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn cancelled() {
+        let cancel_token = CancellationToken::new();
+        let cloned_token = cancel_token.clone();
+        let (tx, mut rx) = channel::<u32>(1);
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_secs(5)).await;
+            cloned_token.cancel();
+        });
+        assert_eq!(recv_or_cancel(&mut rx, &cancel_token).await, None);
+
+        // The sender is still open, it was the cancellation that ended the wait
+        assert!(!tx.is_closed());
+    }
+}
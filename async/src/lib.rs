@@ -0,0 +1,15 @@
+#![no_std]
+
+pub mod sync;
+
+#[cfg(feature = "tokio")]
+pub mod channel;
+
+#[cfg(feature = "tokio")]
+pub mod runtime;
+
+#[cfg(feature = "tokio")]
+pub mod task;
+
+#[cfg(feature = "tokio")]
+pub mod time;
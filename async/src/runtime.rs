@@ -0,0 +1,86 @@
+use super::task::JoinHandle;
+use core::future::Future;
+
+/* A handle to the async runtime, for sync code that must wait on, or start, async work.
+ * Cloning is cheap, and all clones refer to the same runtime */
+#[derive(Debug, Clone)]
+pub struct Runtime {
+    handle: tokio::runtime::Handle,
+}
+
+impl From<tokio::runtime::Handle> for Runtime {
+    fn from(handle: tokio::runtime::Handle) -> Self {
+        Self { handle }
+    }
+}
+
+impl Runtime {
+    // The runtime of the calling context, panics if there isn't one
+    pub fn current() -> Self {
+        Self::try_current().expect("Not called from within an async runtime")
+    }
+
+    // The runtime of the calling context, if any
+    pub fn try_current() -> Option<Self> {
+        tokio::runtime::Handle::try_current().ok().map(Self::from)
+    }
+
+    /* Run `future` to completion on the runtime, blocking the calling thread.
+     * This must not be called from async code, as it would block a runtime worker */
+    pub fn block_on<F: Future>(&self, future: F) -> F::Output {
+        self.handle.block_on(future)
+    }
+
+    // Start `future` running on the runtime, without waiting for it
+    pub fn spawn<F>(&self, future: F) -> JoinHandle<F::Output>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        JoinHandle::new(self.handle.spawn(future))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::time::Duration;
+
+    fn runtime() -> tokio::runtime::Runtime {
+        tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(1)
+            .enable_all()
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn spawn_and_block_on() {
+        let rt = runtime();
+        let runtime = Runtime::from(rt.handle().clone());
+
+        let task = runtime.spawn(async {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            42
+        });
+        assert_eq!(runtime.block_on(task).unwrap(), 42);
+
+        // Aborted tasks report it when joined
+        let task = runtime.spawn(async { tokio::time::sleep(Duration::from_secs(60)).await });
+        task.abort();
+        assert!(runtime.block_on(task).unwrap_err().is_cancelled());
+    }
+
+    #[test]
+    fn current() {
+        assert!(Runtime::try_current().is_none());
+
+        // A sync entry point, called from within the runtime's context
+        let rt = runtime();
+        let _guard = rt.enter();
+        let runtime = Runtime::current();
+        let inner = runtime.clone();
+        let r = runtime.block_on(async move { inner.spawn(async { 42 }).await });
+        assert_eq!(r.unwrap(), 42);
+    }
+}
@@ -0,0 +1,107 @@
+use core::sync::atomic::{AtomicI64, AtomicU64, Ordering};
+
+/* A monotonic count, such as bundles or bytes sent, for when the OpenTelemetry metrics
+ * are not available.  Updates are relaxed, as a metric needs no ordering with other memory */
+#[derive(Debug, Default)]
+pub struct Counter(AtomicU64);
+
+impl Counter {
+    pub const fn new() -> Self {
+        Self(AtomicU64::new(0))
+    }
+
+    pub fn increment(&self) {
+        self.add(1)
+    }
+
+    // Wraps on overflow, which at u64 will not happen in practice
+    pub fn add(&self, v: u64) {
+        self.0.fetch_add(v, Ordering::Relaxed);
+    }
+
+    pub fn load(&self) -> u64 {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+// A value that can go up and down, such as the number of bundles in flight
+#[derive(Debug, Default)]
+pub struct Gauge(AtomicI64);
+
+impl Gauge {
+    pub const fn new() -> Self {
+        Self(AtomicI64::new(0))
+    }
+
+    pub fn increment(&self) {
+        self.add(1)
+    }
+
+    pub fn decrement(&self) {
+        self.add(-1)
+    }
+
+    pub fn add(&self, v: i64) {
+        self.0.fetch_add(v, Ordering::Relaxed);
+    }
+
+    pub fn set(&self, v: i64) {
+        self.0.store(v, Ordering::Relaxed)
+    }
+
+    pub fn load(&self) -> i64 {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use super::*;
+    use std::sync::Arc;
+
+    #[test]
+    fn counter() {
+        let counter = Counter::new();
+        assert_eq!(counter.load(), 0);
+        counter.increment();
+        counter.add(41);
+        assert_eq!(counter.load(), 42);
+
+        let gauge = Gauge::default();
+        gauge.increment();
+        gauge.add(10);
+        gauge.decrement();
+        assert_eq!(gauge.load(), 10);
+        gauge.add(-20);
+        assert_eq!(gauge.load(), -10);
+        gauge.set(3);
+        assert_eq!(gauge.load(), 3);
+    }
+
+    #[test]
+    fn concurrent() {
+        static COUNTER: Counter = Counter::new();
+        let gauge = Arc::new(Gauge::new());
+
+        let threads = (0..8)
+            .map(|_| {
+                let gauge = gauge.clone();
+                std::thread::spawn(move || {
+                    for _ in 0..1000 {
+                        COUNTER.increment();
+                        gauge.increment();
+                        gauge.decrement();
+                    }
+                })
+            })
+            .collect::<std::vec::Vec<_>>();
+        for t in threads {
+            t.join().unwrap();
+        }
+
+        assert_eq!(COUNTER.load(), 8000);
+        assert_eq!(gauge.load(), 0);
+    }
+}
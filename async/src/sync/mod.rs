@@ -0,0 +1,4 @@
+pub mod spin;
+
+#[cfg(target_has_atomic = "64")]
+pub mod metrics;
@@ -0,0 +1,164 @@
+use core::cell::UnsafeCell;
+use core::mem::MaybeUninit;
+use core::sync::atomic::{AtomicU8, Ordering};
+
+const INCOMPLETE: u8 = 0;
+const RUNNING: u8 = 1;
+const COMPLETE: u8 = 2;
+
+/* A one-time initialized value, for when a blocking std::sync::OnceLock is not available.
+ * Waiters spin while another thread runs the initializer, so initializers should be short */
+pub struct Once<T> {
+    state: AtomicU8,
+    value: UnsafeCell<MaybeUninit<T>>,
+}
+
+unsafe impl<T: Send + Sync> Sync for Once<T> {}
+unsafe impl<T: Send> Send for Once<T> {}
+
+impl<T> Default for Once<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Resets the state if the initializer fails or panics, so another caller can retry
+struct Guard<'a>(&'a AtomicU8);
+
+impl Drop for Guard<'_> {
+    fn drop(&mut self) {
+        self.0.store(INCOMPLETE, Ordering::Release);
+    }
+}
+
+impl<T> Once<T> {
+    pub const fn new() -> Self {
+        Self {
+            state: AtomicU8::new(INCOMPLETE),
+            value: UnsafeCell::new(MaybeUninit::uninit()),
+        }
+    }
+
+    pub fn get(&self) -> Option<&T> {
+        (self.state.load(Ordering::Acquire) == COMPLETE).then(|| unsafe { self.force_get() })
+    }
+
+    pub fn is_completed(&self) -> bool {
+        self.state.load(Ordering::Acquire) == COMPLETE
+    }
+
+    pub fn call_once<F: FnOnce() -> T>(&self, f: F) -> &T {
+        self.get_or_init(f)
+    }
+
+    pub fn get_or_init<F: FnOnce() -> T>(&self, f: F) -> &T {
+        match self.get_or_try_init(|| Ok::<T, core::convert::Infallible>(f())) {
+            Ok(v) => v,
+            Err(e) => match e {},
+        }
+    }
+
+    /* Initialize the value with `f` if it has not already been initialized.
+     * If `f` fails, the Once is left uninitialized, so a later call may retry */
+    pub fn get_or_try_init<F, E>(&self, f: F) -> Result<&T, E>
+    where
+        F: FnOnce() -> Result<T, E>,
+    {
+        loop {
+            match self.state.compare_exchange_weak(
+                INCOMPLETE,
+                RUNNING,
+                Ordering::Acquire,
+                Ordering::Acquire,
+            ) {
+                Ok(_) => {
+                    let guard = Guard(&self.state);
+                    let value = f()?;
+                    unsafe { (*self.value.get()).write(value) };
+                    core::mem::forget(guard);
+                    self.state.store(COMPLETE, Ordering::Release);
+                    return Ok(unsafe { self.force_get() });
+                }
+                Err(COMPLETE) => return Ok(unsafe { self.force_get() }),
+                Err(_) => {
+                    // Either another thread is running the initializer, or a spurious failure
+                    while self.state.load(Ordering::Acquire) == RUNNING {
+                        core::hint::spin_loop();
+                    }
+                }
+            }
+        }
+    }
+
+    // Safety: the state must be COMPLETE
+    unsafe fn force_get(&self) -> &T {
+        (*self.value.get()).assume_init_ref()
+    }
+}
+
+impl<T> Drop for Once<T> {
+    fn drop(&mut self) {
+        if *self.state.get_mut() == COMPLETE {
+            unsafe { self.value.get_mut().assume_init_drop() };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use super::*;
+    use core::sync::atomic::AtomicUsize;
+    use std::sync::Arc;
+
+    #[test]
+    fn init() {
+        let once = Once::new();
+        assert!(once.get().is_none());
+        assert_eq!(*once.call_once(|| 1), 1);
+        assert_eq!(*once.get_or_init(|| 2), 1);
+        assert_eq!(once.get_or_try_init(|| Err::<i32, ()>(())), Ok(&1));
+        assert_eq!(once.get(), Some(&1));
+    }
+
+    #[test]
+    fn try_init_error() {
+        let once = Once::new();
+        assert_eq!(
+            once.get_or_try_init(|| Err::<i32, _>("failed")),
+            Err("failed")
+        );
+        assert!(!once.is_completed());
+
+        // A failed initialization can be retried
+        assert_eq!(once.get_or_try_init(|| Ok::<_, ()>(2)), Ok(&2));
+        assert_eq!(once.get(), Some(&2));
+    }
+
+    #[test]
+    fn race() {
+        let once = Arc::new(Once::new());
+        let calls = Arc::new(AtomicUsize::new(0));
+        let threads = (0..8)
+            .map(|i| {
+                let once = once.clone();
+                let calls = calls.clone();
+                std::thread::spawn(move || {
+                    *once.get_or_init(|| {
+                        calls.fetch_add(1, Ordering::Relaxed);
+                        std::thread::sleep(std::time::Duration::from_millis(10));
+                        i
+                    })
+                })
+            })
+            .collect::<std::vec::Vec<_>>();
+
+        let results = threads
+            .into_iter()
+            .map(|t| t.join().unwrap())
+            .collect::<std::vec::Vec<_>>();
+        assert_eq!(calls.load(Ordering::Relaxed), 1);
+        assert!(results.iter().all(|r| *r == results[0]));
+    }
+}
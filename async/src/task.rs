@@ -0,0 +1,144 @@
+use core::future::Future;
+use core::pin::Pin;
+use core::task::{Context, Poll};
+use tokio_util::sync::CancellationToken;
+
+pub use tokio::task::JoinError;
+
+/* A handle to a single spawned task, which resolves to the task's result.
+ * Dropping the handle detaches the task, it does not abort it */
+#[derive(Debug)]
+pub struct JoinHandle<T> {
+    inner: tokio::task::JoinHandle<T>,
+}
+
+impl<T> JoinHandle<T> {
+    pub(crate) fn new(inner: tokio::task::JoinHandle<T>) -> Self {
+        Self { inner }
+    }
+
+    pub fn abort(&self) {
+        self.inner.abort()
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.inner.is_finished()
+    }
+}
+
+impl<T> Future for JoinHandle<T> {
+    type Output = Result<T, JoinError>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        Pin::new(&mut self.inner).poll(cx)
+    }
+}
+
+/* A set of spawned tasks, yielding their results in completion order.
+ * All tasks still running are aborted when the set is dropped */
+#[derive(Debug)]
+pub struct JoinSet<T> {
+    inner: tokio::task::JoinSet<T>,
+}
+
+impl<T> Default for JoinSet<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> JoinSet<T> {
+    pub fn new() -> Self {
+        Self {
+            inner: tokio::task::JoinSet::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.inner.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.inner.is_empty()
+    }
+}
+
+impl<T: Send + 'static> JoinSet<T> {
+    pub fn spawn<F>(&mut self, future: F)
+    where
+        F: Future<Output = T> + Send + 'static,
+    {
+        self.inner.spawn(future);
+    }
+
+    // Wait for the next task to complete, returns None if the set is empty
+    pub async fn join_next(&mut self) -> Option<Result<T, JoinError>> {
+        self.inner.join_next().await
+    }
+
+    /* Wait for the next task to complete, unless `cancel_token` is cancelled first.
+     * Returns None if the set is empty or the token is cancelled.
+     * A task that has already completed is preferred over a simultaneous cancellation */
+    pub async fn join_next_or_cancel(
+        &mut self,
+        cancel_token: &CancellationToken,
+    ) -> Option<Result<T, JoinError>> {
+        tokio::select! {
+            biased;
+            r = self.inner.join_next() => r,
+            _ = cancel_token.cancelled() => None,
+        }
+    }
+
+    // Abort every task, and wait for them all to finish
+    pub async fn shutdown(&mut self) {
+        self.inner.shutdown().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::time::Duration;
+
+    #[tokio::test(start_paused = true)]
+    async fn completion_order() {
+        let mut task_set = JoinSet::new();
+        for (v, secs) in [(1, 30), (2, 10), (3, 20)] {
+            task_set.spawn(async move {
+                tokio::time::sleep(Duration::from_secs(secs)).await;
+                v
+            });
+        }
+        assert_eq!(task_set.len(), 3);
+
+        let mut results = [0; 3];
+        for r in results.iter_mut() {
+            *r = task_set.join_next().await.unwrap().unwrap();
+        }
+        assert_eq!(results, [2, 3, 1]);
+        assert!(task_set.is_empty());
+        assert!(task_set.join_next().await.is_none());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn cancelled() {
+        let cancel_token = CancellationToken::new();
+        let cloned_token = cancel_token.clone();
+        let mut task_set = JoinSet::new();
+        task_set.spawn(async {
+            tokio::time::sleep(Duration::from_secs(60)).await;
+        });
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_secs(5)).await;
+            cloned_token.cancel();
+        });
+
+        assert!(task_set.join_next_or_cancel(&cancel_token).await.is_none());
+
+        // The task is still running until the set is shut down
+        assert_eq!(task_set.len(), 1);
+        task_set.shutdown().await;
+        assert!(task_set.is_empty());
+    }
+}
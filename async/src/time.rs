@@ -0,0 +1,82 @@
+use core::future::Future;
+use core::time::Duration;
+use tokio_util::sync::CancellationToken;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimeoutOrCancelled {
+    Timeout,
+    Cancelled,
+}
+
+impl core::fmt::Display for TimeoutOrCancelled {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            TimeoutOrCancelled::Timeout => f.write_str("Timed out"),
+            TimeoutOrCancelled::Cancelled => f.write_str("Cancelled"),
+        }
+    }
+}
+
+impl core::error::Error for TimeoutOrCancelled {}
+
+/* Race `future` against both a deadline and `cancel_token`.
+ * A future that completes is preferred over a simultaneous timeout or cancellation */
+pub async fn timeout<F: Future>(
+    duration: Duration,
+    cancel_token: &CancellationToken,
+    future: F,
+) -> Result<F::Output, TimeoutOrCancelled> {
+    tokio::select! {
+        biased;
+        r = tokio::time::timeout(duration, future) => r.map_err(|_| TimeoutOrCancelled::Timeout),
+        _ = cancel_token.cancelled() => Err(TimeoutOrCancelled::Cancelled),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test(start_paused = true)]
+    async fn completed() {
+        let cancel_token = CancellationToken::new();
+        let r = timeout(Duration::from_secs(10), &cancel_token, async {
+            tokio::time::sleep(Duration::from_secs(5)).await;
+            42
+        })
+        .await;
+        assert_eq!(r, Ok(42));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn timed_out() {
+        let cancel_token = CancellationToken::new();
+        let start = tokio::time::Instant::now();
+        let r = timeout(
+            Duration::from_secs(10),
+            &cancel_token,
+            tokio::time::sleep(Duration::from_secs(60)),
+        )
+        .await;
+        assert_eq!(r, Err(TimeoutOrCancelled::Timeout));
+        assert_eq!(start.elapsed(), Duration::from_secs(10));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn cancelled() {
+        let cancel_token = CancellationToken::new();
+        let cloned_token = cancel_token.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_secs(5)).await;
+            cloned_token.cancel();
+        });
+
+        let r = timeout(
+            Duration::from_secs(10),
+            &cancel_token,
+            tokio::time::sleep(Duration::from_secs(60)),
+        )
+        .await;
+        assert_eq!(r, Err(TimeoutOrCancelled::Cancelled));
+    }
+}
@@ -23,6 +23,46 @@ pub enum BundleStatus {
     Tombstone(time::OffsetDateTime),
 }
 
+// The number of bundles in metadata storage in each status
+#[derive(Debug, Default, Clone, Copy, Eq, PartialEq)]
+pub struct BundleCounts {
+    pub ingress_pending: u64,
+    pub dispatch_pending: u64,
+    pub reassembly_pending: u64,
+    pub collection_pending: u64,
+    pub forward_pending: u64,
+    pub forward_ack_pending: u64,
+    pub waiting: u64,
+    pub tombstone: u64,
+}
+
+impl BundleCounts {
+    pub fn add(&mut self, status: &BundleStatus, count: u64) {
+        let counter = match status {
+            BundleStatus::IngressPending => &mut self.ingress_pending,
+            BundleStatus::DispatchPending => &mut self.dispatch_pending,
+            BundleStatus::ReassemblyPending => &mut self.reassembly_pending,
+            BundleStatus::CollectionPending => &mut self.collection_pending,
+            BundleStatus::ForwardPending => &mut self.forward_pending,
+            BundleStatus::ForwardAckPending(..) => &mut self.forward_ack_pending,
+            BundleStatus::Waiting(_) => &mut self.waiting,
+            BundleStatus::Tombstone(_) => &mut self.tombstone,
+        };
+        *counter = counter.saturating_add(count);
+    }
+
+    // Every bundle that is still held, i.e. not a tombstone
+    pub fn total(&self) -> u64 {
+        self.ingress_pending
+            + self.dispatch_pending
+            + self.reassembly_pending
+            + self.collection_pending
+            + self.forward_pending
+            + self.forward_ack_pending
+            + self.waiting
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Bundle {
     pub bundle: bpv7::Bundle,
@@ -53,6 +93,10 @@ impl Bundle {
     }
 
     pub fn has_expired(&self) -> bool {
-        self.expiry() <= time::OffsetDateTime::now_utc()
+        self.has_expired_at(time::OffsetDateTime::now_utc())
+    }
+
+    pub fn has_expired_at(&self, now: time::OffsetDateTime) -> bool {
+        self.expiry() <= now
     }
 }
@@ -34,6 +34,27 @@ pub trait MetadataStorage: Send + Sync {
     async fn get_unconfirmed_bundles(&self, tx: Sender) -> Result<()>;
 
     async fn poll_for_collection(&self, destination: bpv7::Eid, tx: Sender) -> Result<()>;
+
+    // Every bundle received within `window`, excluding tombstones
+    async fn poll_received_between(
+        &self,
+        window: std::ops::Range<time::OffsetDateTime>,
+        tx: Sender,
+    ) -> Result<()>;
+
+    /* Every bundle whose destination matches `destination`, excluding tombstones.
+     * Engines that cannot index by pattern may return extra bundles, so callers must check.
+     * Querying is optional, so engines that do not support it need not implement this */
+    async fn poll_for_destination(
+        &self,
+        _destination: bpv7::EidPattern,
+        _tx: Sender,
+    ) -> Result<()> {
+        Err("Metadata storage does not support querying bundles by destination".into())
+    }
+
+    // The number of bundles in each status, including tombstones
+    async fn count_by_status(&self) -> Result<metadata::BundleCounts>;
 }
 
 pub type DataRef = std::sync::Arc<dyn AsRef<[u8]> + Send + Sync>;
@@ -48,4 +69,10 @@ pub trait BundleStorage: Send + Sync {
     async fn store(&self, data: &[u8]) -> Result<std::sync::Arc<str>>;
 
     async fn remove(&self, storage_name: &str) -> Result<()>;
+
+    /* Set aside data found to be corrupt, so an operator can inspect it, and stop listing it.
+     * Engines with nowhere to keep it simply remove it */
+    async fn quarantine(&self, storage_name: &str) -> Result<()> {
+        self.remove(storage_name).await
+    }
 }
@@ -26,8 +26,12 @@ fn setup() -> tokio::runtime::Runtime {
         // Get administrative endpoints
         let administrative_endpoints = utils::admin_endpoints::AdminEndpoints::init(&config);
 
-        // New store
-        let store = store::Store::new(&config, false);
+        // New store, on the system clock
+        let store = store::Store::new(
+            &config,
+            false,
+            std::sync::Arc::new(utils::clock::SystemClock),
+        );
 
         // New FIB
         let fib = fib::Fib::new(&config);
@@ -59,7 +63,12 @@ fn setup() -> tokio::runtime::Runtime {
 
         // Start the store - this can take a while as the store is walked
         store
-            .start(dispatcher.clone(), &mut task_set, cancel_token.clone())
+            .start(
+                dispatcher.clone(),
+                &mut task_set,
+                None,
+                cancel_token.clone(),
+            )
             .await;
 
         DISPATCHER.get_or_init(|| dispatcher);
@@ -4,13 +4,40 @@ use rand::distributions::{Alphanumeric, DistString};
 use rand::Rng;
 use std::collections::HashMap;
 use std::sync::Arc;
-use tokio::sync::{Mutex, RwLock};
+use tokio::sync::{RwLock, Semaphore};
+use utils::settings;
 
-type Channel = Arc<Mutex<application_client::ApplicationClient<tonic::transport::Channel>>>;
+// gRPC clients are cheap to clone, and clones can make concurrent requests
+type Channel = application_client::ApplicationClient<tonic::transport::Channel>;
 
 pub struct Endpoint {
     inner: Option<Channel>,
     token: String,
+    limiter: DeliveryLimiter,
+}
+
+// Throttles notifications to a single application, so a burst of bundles cannot overwhelm it
+#[derive(Clone, Default)]
+struct DeliveryLimiter(Option<Arc<Semaphore>>);
+
+impl DeliveryLimiter {
+    fn new(max_concurrent: u32) -> Self {
+        Self((max_concurrent != 0).then(|| Arc::new(Semaphore::new(max_concurrent as usize))))
+    }
+
+    async fn run<F: std::future::Future>(&self, f: F) -> F::Output {
+        // Hold a permit for the duration, the caller waits for a free slot
+        let _permit = match &self.0 {
+            Some(semaphore) => Some(
+                semaphore
+                    .acquire()
+                    .await
+                    .trace_expect("Delivery limiter semaphore closed"),
+            ),
+            None => None,
+        };
+        f.await
+    }
 }
 
 #[derive(Debug)]
@@ -23,29 +50,52 @@ pub enum StatusKind {
 
 struct Application {
     eid: bpv7::Eid,
+    pattern: Option<bpv7::EidPattern>,
     token: String,
     ident: String,
     endpoint: Option<Channel>,
+    limiter: DeliveryLimiter,
 }
 
 #[derive(Default)]
 struct Indexes {
     applications_by_eid: HashMap<bpv7::Eid, Arc<Application>>,
     applications_by_token: HashMap<String, Arc<Application>>,
+    applications_by_pattern: bpv7::EidPatternMap<String, String>,
+}
+
+#[derive(Clone)]
+struct Config {
+    max_concurrent_notifications: u32,
+}
+
+impl Config {
+    fn new(config: &config::Config) -> Self {
+        Self {
+            max_concurrent_notifications: settings::get_with_default(
+                config,
+                "max_concurrent_notifications",
+                1u32,
+            )
+            .trace_expect("Invalid 'max_concurrent_notifications' value in configuration"),
+        }
+    }
 }
 
 #[derive(Clone)]
 pub struct AppRegistry {
+    config: Config,
     admin_endpoints: utils::admin_endpoints::AdminEndpoints,
     applications: Arc<RwLock<Indexes>>,
 }
 
 impl AppRegistry {
     pub fn new(
-        _config: &config::Config,
+        config: &config::Config,
         admin_endpoints: utils::admin_endpoints::AdminEndpoints,
     ) -> Self {
         Self {
+            config: Config::new(config),
             admin_endpoints,
             applications: Default::default(),
         }
@@ -60,7 +110,7 @@ impl AppRegistry {
         let endpoint = if let Some(grpc_address) = request.grpc_address {
             application_client::ApplicationClient::connect(grpc_address.clone())
                 .await
-                .map(|endpoint| Some(Arc::new(Mutex::new(endpoint))))
+                .map(Some)
                 .map_err(|e| {
                     warn!("Failed to connect to application client at {grpc_address}: {e}");
                     tonic::Status::invalid_argument(e.to_string())
@@ -78,6 +128,21 @@ impl AppRegistry {
             token = Alphanumeric.sample_string(&mut rand::thread_rng(), 16);
         }
 
+        let pattern = match &request.endpoint {
+            Some(register_application_request::Endpoint::EidPattern(s)) => {
+                let pattern = s
+                    .parse::<bpv7::EidPattern>()
+                    .map_err(|e| tonic::Status::invalid_argument(e.to_string()))?;
+                if self.matches_admin_endpoint(&pattern) {
+                    return Err(tonic::Status::invalid_argument(
+                        "Cannot register the administrative endpoint",
+                    ));
+                }
+                Some(pattern)
+            }
+            _ => None,
+        };
+
         // Compose EID
         let eid = match &request.endpoint {
             Some(register_application_request::Endpoint::DtnService(s)) => {
@@ -108,7 +173,7 @@ impl AppRegistry {
                     ));
                 }
             }
-            None => loop {
+            Some(register_application_request::Endpoint::EidPattern(_)) | None => loop {
                 let eid = match (&self.admin_endpoints.ipn, &self.admin_endpoints.dtn) {
                     (None, Some(node_id)) => node_id
                         .to_eid(&format!(
@@ -129,7 +194,7 @@ impl AppRegistry {
             },
         };
 
-        if request.endpoint.is_some() {
+        if request.endpoint.is_some() && pattern.is_none() {
             if let Some(application) = applications.applications_by_eid.get(&eid) {
                 if application.ident != request.ident {
                     return Err(tonic::Status::already_exists(format!(
@@ -143,15 +208,33 @@ impl AppRegistry {
             token,
             endpoint_id: eid.to_string(),
         };
+        // The application may ask for a tighter limit than the configured default
+        let max_concurrent = match (
+            request.max_concurrent_notifications.unwrap_or(0),
+            self.config.max_concurrent_notifications,
+        ) {
+            (0, m) | (m, 0) => m,
+            (r, m) => r.min(m),
+        };
+
         let app = Arc::new(Application {
             eid,
+            pattern,
             ident: request.ident,
             token: response.token.clone(),
             endpoint,
+            limiter: DeliveryLimiter::new(max_concurrent),
         });
         applications
             .applications_by_eid
             .insert(app.eid.clone(), app.clone());
+        if let Some(pattern) = &app.pattern {
+            applications.applications_by_pattern.insert(
+                pattern,
+                app.token.clone(),
+                app.token.clone(),
+            );
+        }
         applications
             .applications_by_token
             .insert(app.token.clone(), app);
@@ -165,14 +248,35 @@ impl AppRegistry {
     ) -> Result<UnregisterApplicationResponse, tonic::Status> {
         let mut applications = self.applications.write().await;
 
-        applications
+        let app = applications
             .applications_by_token
             .remove(&request.token)
-            .and_then(|app| applications.applications_by_eid.remove(&app.eid))
+            .ok_or(tonic::Status::not_found("No such application registered"))?;
+        if let Some(pattern) = &app.pattern {
+            applications
+                .applications_by_pattern
+                .remove(pattern, &app.token);
+        }
+        applications
+            .applications_by_eid
+            .remove(&app.eid)
             .ok_or(tonic::Status::not_found("No such application registered"))
             .map(|_| UnregisterApplicationResponse {})
     }
 
+    fn matches_admin_endpoint(&self, pattern: &bpv7::EidPattern) -> bool {
+        self.admin_endpoints
+            .ipn
+            .as_ref()
+            .is_some_and(|node_id| pattern.is_match(&node_id.to_eid(0)))
+            || self
+                .admin_endpoints
+                .dtn
+                .as_ref()
+                .and_then(|node_id| node_id.to_eid("").ok())
+                .is_some_and(|eid| pattern.is_match(&eid))
+    }
+
     #[instrument(skip(self))]
     pub async fn find_by_token(&self, token: &str) -> Result<bpv7::Eid, tonic::Status> {
         self.applications
@@ -184,31 +288,117 @@ impl AppRegistry {
             .map(|app| app.eid.clone())
     }
 
+    // The destinations an application may collect bundles for: its own EID, and any registered pattern
     #[instrument(skip(self))]
-    pub async fn find_by_eid(&self, eid: &bpv7::Eid) -> Option<Endpoint> {
+    pub async fn find_destinations_by_token(
+        &self,
+        token: &str,
+    ) -> Result<bpv7::EidPattern, tonic::Status> {
+        self.applications
+            .read()
+            .await
+            .applications_by_token
+            .get(token)
+            .ok_or(tonic::Status::not_found("No such application"))
+            .map(|app| {
+                let eid = app.eid.clone().into();
+                match &app.pattern {
+                    Some(pattern) => pattern.clone().union(eid),
+                    None => eid,
+                }
+            })
+    }
+
+    // True if another application has registered `eid` exactly, so `token` cannot claim it by pattern
+    #[instrument(skip(self))]
+    pub async fn is_owned_by_other(&self, eid: &bpv7::Eid, token: &str) -> bool {
         self.applications
             .read()
             .await
             .applications_by_eid
             .get(eid)
-            .map(|app| Endpoint {
-                token: app.token.clone(),
-                inner: app.endpoint.clone(),
+            .is_some_and(|app| app.token != token)
+    }
+
+    #[instrument(skip(self))]
+    pub async fn find_endpoint_by_token(&self, token: &str) -> Option<Endpoint> {
+        self.applications
+            .read()
+            .await
+            .applications_by_token
+            .get(token)
+            .map(|app| app.as_endpoint())
+    }
+
+    #[instrument(skip(self))]
+    pub async fn find_by_eid(&self, eid: &bpv7::Eid) -> Option<Endpoint> {
+        let applications = self.applications.read().await;
+
+        // Exact registrations take precedence over patterns
+        applications
+            .applications_by_eid
+            .get(eid)
+            .or_else(|| {
+                applications
+                    .applications_by_pattern
+                    .find(eid)
+                    .into_iter()
+                    .find_map(|token| applications.applications_by_token.get(token))
             })
+            .map(|app| app.as_endpoint())
+    }
+
+    /* Every application registered for `eid`.  An exact registration owns its EID,
+     * so patterns only match EIDs no application has registered exactly */
+    #[instrument(skip(self))]
+    pub async fn find_all_by_eid(&self, eid: &bpv7::Eid) -> Vec<Endpoint> {
+        let applications = self.applications.read().await;
+
+        if let Some(app) = applications.applications_by_eid.get(eid) {
+            return vec![app.as_endpoint()];
+        }
+
+        let mut endpoints: Vec<Endpoint> = Vec::new();
+        for app in applications
+            .applications_by_pattern
+            .find(eid)
+            .into_iter()
+            .filter_map(|token| applications.applications_by_token.get(token))
+        {
+            if !endpoints.iter().any(|e| e.token == app.token) {
+                endpoints.push(app.as_endpoint());
+            }
+        }
+        endpoints
+    }
+}
+
+impl Application {
+    fn as_endpoint(&self) -> Endpoint {
+        Endpoint {
+            token: self.token.clone(),
+            inner: self.endpoint.clone(),
+            limiter: self.limiter.clone(),
+        }
     }
 }
 
 impl Endpoint {
+    pub fn token(&self) -> &str {
+        &self.token
+    }
+
     #[instrument(skip(self))]
     pub async fn collection_notify(&self, bundle_id: &bpv7::BundleId) {
         if let Some(endpoint) = &self.inner {
-            _ = endpoint
-                .lock()
-                .await
-                .collection_notify(tonic::Request::new(CollectionNotifyRequest {
-                    token: self.token.clone(),
-                    bundle_id: bundle_id.to_key(),
-                }))
+            _ = self
+                .limiter
+                .run(endpoint.clone().collection_notify(tonic::Request::new(
+                    CollectionNotifyRequest {
+                        token: self.token.clone(),
+                        bundle_id: bundle_id.to_key(),
+                    },
+                )))
                 .await
                 .inspect_err(|s| info!("collection_notify failed: {s}"));
         }
@@ -223,18 +413,228 @@ impl Endpoint {
         timestamp: Option<time::OffsetDateTime>,
     ) {
         if let Some(endpoint) = &self.inner {
-            _ = endpoint
-                .lock()
-                .await
-                .status_notify(tonic::Request::new(StatusNotifyRequest {
-                    token: self.token.clone(),
-                    bundle_id: bundle_id.to_key(),
-                    kind: kind as i32,
-                    reason: reason.into(),
-                    timestamp: timestamp.map(grpc::to_timestamp),
-                }))
+            _ = self
+                .limiter
+                .run(
+                    endpoint
+                        .clone()
+                        .status_notify(tonic::Request::new(StatusNotifyRequest {
+                            token: self.token.clone(),
+                            bundle_id: bundle_id.to_key(),
+                            kind: kind as i32,
+                            reason: reason.into(),
+                            timestamp: timestamp.map(grpc::to_timestamp),
+                        })),
+                )
                 .await
                 .inspect_err(|s| info!("status_notify failed: {s}"));
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[tokio::test]
+    async fn delivery_limit() {
+        const LIMIT: u32 = 3;
+
+        let limiter = DeliveryLimiter::new(LIMIT);
+        let current = Arc::new(AtomicUsize::new(0));
+        let peak = Arc::new(AtomicUsize::new(0));
+
+        let mut task_set = tokio::task::JoinSet::new();
+        for _ in 0..20 {
+            let limiter = limiter.clone();
+            let current = current.clone();
+            let peak = peak.clone();
+            task_set.spawn(async move {
+                limiter
+                    .run(async {
+                        // A slow application
+                        let now = current.fetch_add(1, Ordering::SeqCst) + 1;
+                        peak.fetch_max(now, Ordering::SeqCst);
+                        tokio::time::sleep(tokio::time::Duration::from_millis(10)).await;
+                        current.fetch_sub(1, Ordering::SeqCst);
+                    })
+                    .await
+            });
+        }
+        while let Some(r) = task_set.join_next().await {
+            r.unwrap();
+        }
+
+        assert!(peak.load(Ordering::SeqCst) <= LIMIT as usize);
+        assert_eq!(current.load(Ordering::SeqCst), 0);
+    }
+
+    #[tokio::test]
+    async fn pattern() {
+        let config = config::Config::builder()
+            .set_default("administrative_endpoint", "dtn://node/")
+            .unwrap()
+            .build()
+            .unwrap();
+        let registry = AppRegistry::new(
+            &config,
+            utils::admin_endpoints::AdminEndpoints::init(&config),
+        );
+
+        let response = registry
+            .register(RegisterApplicationRequest {
+                endpoint: Some(register_application_request::Endpoint::EidPattern(
+                    "dtn://node/sensor/**".to_string(),
+                )),
+                ident: "gateway".to_string(),
+                grpc_address: None,
+                max_concurrent_notifications: None,
+            })
+            .await
+            .unwrap();
+
+        // Every endpoint in the subtree is delivered to the one application
+        for eid in [
+            "dtn://node/sensor/1",
+            "dtn://node/sensor/2",
+            "dtn://node/sensor/nested/3",
+        ] {
+            let eid = eid.parse().unwrap();
+            let endpoint = registry.find_by_eid(&eid).await.unwrap();
+            assert_eq!(endpoint.token, response.token);
+            assert!(registry
+                .find_destinations_by_token(&response.token)
+                .await
+                .unwrap()
+                .is_match(&eid));
+        }
+        assert!(registry
+            .find_by_eid(&"dtn://node/actuator/1".parse().unwrap())
+            .await
+            .is_none());
+
+        registry
+            .unregister(UnregisterApplicationRequest {
+                token: response.token,
+            })
+            .await
+            .unwrap();
+        assert!(registry
+            .find_by_eid(&"dtn://node/sensor/1".parse().unwrap())
+            .await
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn overlapping() {
+        let config = config::Config::builder()
+            .set_default("administrative_endpoint", "dtn://node/")
+            .unwrap()
+            .build()
+            .unwrap();
+        let registry = AppRegistry::new(
+            &config,
+            utils::admin_endpoints::AdminEndpoints::init(&config),
+        );
+
+        let mut tokens = Vec::new();
+        for (pattern, ident) in [
+            ("dtn://node/sensor/**", "all"),
+            ("dtn://node/sensor/1", "one"),
+        ] {
+            tokens.push(
+                registry
+                    .register(RegisterApplicationRequest {
+                        endpoint: Some(register_application_request::Endpoint::EidPattern(
+                            pattern.to_string(),
+                        )),
+                        ident: ident.to_string(),
+                        grpc_address: None,
+                        max_concurrent_notifications: None,
+                    })
+                    .await
+                    .unwrap()
+                    .token,
+            );
+        }
+
+        // Both applications match the one destination
+        let mut found = registry
+            .find_all_by_eid(&"dtn://node/sensor/1".parse().unwrap())
+            .await
+            .into_iter()
+            .map(|e| e.token)
+            .collect::<Vec<_>>();
+        found.sort();
+        tokens.sort();
+        assert_eq!(found, tokens);
+
+        assert_eq!(
+            registry
+                .find_all_by_eid(&"dtn://node/sensor/2".parse().unwrap())
+                .await
+                .len(),
+            1
+        );
+    }
+
+    #[tokio::test]
+    async fn exact_and_pattern() {
+        let config = config::Config::builder()
+            .set_default("administrative_endpoint", "dtn://node/")
+            .unwrap()
+            .build()
+            .unwrap();
+        let registry = AppRegistry::new(
+            &config,
+            utils::admin_endpoints::AdminEndpoints::init(&config),
+        );
+
+        let all = registry
+            .register(RegisterApplicationRequest {
+                endpoint: Some(register_application_request::Endpoint::EidPattern(
+                    "dtn://node/sensor/**".to_string(),
+                )),
+                ident: "all".to_string(),
+                grpc_address: None,
+                max_concurrent_notifications: None,
+            })
+            .await
+            .unwrap();
+        let one = registry
+            .register(RegisterApplicationRequest {
+                endpoint: Some(register_application_request::Endpoint::DtnService(
+                    "sensor/1".to_string(),
+                )),
+                ident: "one".to_string(),
+                grpc_address: None,
+                max_concurrent_notifications: None,
+            })
+            .await
+            .unwrap();
+
+        // The exact registration owns its EID, the pattern gets the rest
+        let eid: bpv7::Eid = one.endpoint_id.parse().unwrap();
+        let found = registry.find_all_by_eid(&eid).await;
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].token, one.token);
+        assert_eq!(registry.find_by_eid(&eid).await.unwrap().token, one.token);
+        assert!(registry.is_owned_by_other(&eid, &all.token).await);
+        assert!(!registry.is_owned_by_other(&eid, &one.token).await);
+
+        let other = "dtn://node/sensor/2".parse().unwrap();
+        let found = registry.find_all_by_eid(&other).await;
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].token, all.token);
+        assert!(!registry.is_owned_by_other(&other, &all.token).await);
+
+        // Until the exact registration goes away
+        registry
+            .unregister(UnregisterApplicationRequest { token: one.token })
+            .await
+            .unwrap();
+        assert_eq!(registry.find_all_by_eid(&eid).await[0].token, all.token);
+        assert!(!registry.is_owned_by_other(&eid, &all.token).await);
+    }
+}
@@ -0,0 +1,55 @@
+use super::*;
+use tokio::time::{Duration, Instant};
+
+/* A token bucket that paces the bundles forwarded by a CLA, so its sustained throughput stays
+ * under `rate` bytes per second.  Up to a second's worth of bytes may be sent in a burst after
+ * an idle period.  A bundle larger than the bucket is sent once the bucket is full, and the
+ * debt is paid off by the bundles that follow.  Senders are paced in turn */
+pub struct EgressShaper {
+    rate: u64,
+    bucket: Mutex<(f64, Instant)>,
+}
+
+impl EgressShaper {
+    pub fn new(rate: u64) -> Self {
+        Self {
+            rate,
+            bucket: Mutex::new((rate as f64, Instant::now())),
+        }
+    }
+
+    // Wait until `len` bytes may be sent
+    pub async fn pace(&self, len: usize) {
+        let mut bucket = self.bucket.lock().await;
+        let (tokens, last) = &mut *bucket;
+
+        let now = Instant::now();
+        *tokens = (*tokens + (now - *last).as_secs_f64() * self.rate as f64).min(self.rate as f64)
+            - len as f64;
+        *last = now;
+
+        // Hold the lock while waiting, so senders queue in order
+        if *tokens < 0.0 {
+            tokio::time::sleep(Duration::from_secs_f64(-*tokens / self.rate as f64)).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test(start_paused = true)]
+    async fn rate() {
+        let shaper = EgressShaper::new(100_000);
+
+        // The first second's worth is sent at once, the rest at the configured rate.
+        // Timers are rounded up to the millisecond, so allow for a little over a second
+        let start = Instant::now();
+        for _ in 0..20 {
+            shaper.pace(10_000).await;
+        }
+        let elapsed = start.elapsed().as_secs_f64();
+        assert!((0.999..1.02).contains(&elapsed), "elapsed {elapsed}s");
+    }
+}
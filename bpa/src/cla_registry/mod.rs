@@ -0,0 +1,520 @@
+use super::*;
+use hardy_proto::cla::*;
+use rand::Rng;
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::{Mutex, RwLock};
+use tokio_util::bytes::Bytes;
+
+mod egress_shaper;
+
+#[cfg(any(test, feature = "test-util"))]
+mod null_cla;
+
+#[cfg(any(test, feature = "test-util"))]
+pub use null_cla::{NullCla, NullClaResponse};
+
+type Channel = Arc<Mutex<cla_client::ClaClient<tonic::transport::Channel>>>;
+
+// How bundles reach a CLA
+#[derive(Clone)]
+enum Link {
+    Grpc(Channel),
+    #[cfg(any(test, feature = "test-util"))]
+    Null(Arc<NullCla>),
+}
+
+pub struct Endpoint {
+    inner: Link,
+    handle: u32,
+    shaper: Option<Arc<egress_shaper::EgressShaper>>,
+}
+
+struct Cla {
+    ident: String,
+    name: String,
+    endpoint: Link,
+    shaper: Option<Arc<egress_shaper::EgressShaper>>,
+    neighbours: std::sync::Mutex<HashMap<bpv7::EidPattern, (u32, Option<u64>)>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Neighbour {
+    pub neighbour: bpv7::EidPattern,
+    pub cla_ident: String,
+    pub cla_name: String,
+    pub priority: u32,
+    pub max_bundle_size: Option<u64>,
+}
+
+#[derive(Clone)]
+struct Config {
+    // Egress byte-rate limits, by CLA ident
+    egress_rates: Arc<HashMap<String, u64>>,
+}
+
+impl Config {
+    fn new(config: &config::Config) -> Self {
+        let egress_rates = config
+            .get::<HashMap<String, u64>>("egress_rates")
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|(_, rate)| *rate != 0)
+            .collect::<HashMap<_, _>>();
+
+        for (cla, rate) in &egress_rates {
+            info!("Bundles forwarded by CLA '{cla}' are limited to {rate} bytes per second");
+        }
+
+        Self {
+            egress_rates: Arc::new(egress_rates),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct ClaRegistry {
+    config: Config,
+    clas: Arc<RwLock<HashMap<u32, Arc<Cla>>>>,
+    fib: Option<fib::Fib>,
+}
+
+impl ClaRegistry {
+    pub fn new(config: &config::Config, fib: Option<fib::Fib>) -> Self {
+        Self {
+            config: Config::new(config),
+            fib,
+            clas: Arc::new(RwLock::new(HashMap::new())),
+        }
+    }
+
+    #[instrument(skip(self))]
+    pub async fn register(
+        &self,
+        request: RegisterClaRequest,
+    ) -> Result<RegisterClaResponse, tonic::Status> {
+        // Connect to client gRPC address
+        let endpoint = Arc::new(Mutex::new(
+            cla_client::ClaClient::connect(request.grpc_address.clone())
+                .await
+                .map_err(|e| {
+                    warn!(
+                        "Failed to connect to CLA client at {}",
+                        request.grpc_address
+                    );
+                    tonic::Status::invalid_argument(e.to_string())
+                })?,
+        ));
+
+        self.insert(request.ident, request.name, Link::Grpc(endpoint))
+            .await
+            .map(|handle| RegisterClaResponse { handle })
+    }
+
+    // Register a NullCla, which discards everything forwarded to it
+    #[cfg(any(test, feature = "test-util"))]
+    pub async fn register_null_cla(
+        &self,
+        ident: &str,
+        name: &str,
+    ) -> Result<(u32, Arc<NullCla>), tonic::Status> {
+        let cla = Arc::new(NullCla::default());
+        self.insert(ident.to_string(), name.to_string(), Link::Null(cla.clone()))
+            .await
+            .map(|handle| (handle, cla))
+    }
+
+    async fn insert(&self, ident: String, name: String, link: Link) -> Result<u32, tonic::Status> {
+        let mut clas = self.clas.write().await;
+
+        // Compose a handle
+        let mut rng = rand::thread_rng();
+        let mut handle = rng.gen::<std::num::NonZeroU32>().into();
+
+        // Check handle is unique
+        while clas.contains_key(&handle) {
+            handle = rng.gen::<std::num::NonZeroU32>().into();
+        }
+
+        // Do a linear search for re-registration with the same name
+        for cla in clas.values() {
+            if cla.ident == ident {
+                return Err(tonic::Status::already_exists(format!(
+                    "CLA {ident} already registered"
+                )));
+            }
+        }
+
+        info!("Registered new CLA: {name}/{ident}");
+
+        let cla = Arc::new(Cla {
+            shaper: self
+                .config
+                .egress_rates
+                .get(&ident)
+                .map(|rate| Arc::new(egress_shaper::EgressShaper::new(*rate))),
+            ident,
+            name,
+            endpoint: link,
+            neighbours: Default::default(),
+        });
+
+        clas.insert(handle, cla);
+        Ok(handle)
+    }
+
+    #[instrument(skip(self))]
+    pub async fn unregister(
+        &self,
+        request: UnregisterClaRequest,
+    ) -> Result<UnregisterClaResponse, tonic::Status> {
+        let mut clas = self.clas.write().await;
+
+        clas.remove(&request.handle)
+            .ok_or(tonic::Status::not_found("No such CLA registered"))
+            .map(|cla| {
+                info!("Unregistered CLA: {}/{}", cla.name, cla.ident);
+                UnregisterClaResponse {}
+            })
+    }
+
+    #[instrument(skip(self))]
+    pub async fn exists(&self, handle: u32) -> Result<(), tonic::Status> {
+        if !self.clas.read().await.contains_key(&handle) {
+            Err(tonic::Status::not_found("No such CLA registered"))
+        } else {
+            Ok(())
+        }
+    }
+
+    #[instrument(skip(self))]
+    pub async fn ident(&self, handle: u32) -> Result<String, tonic::Status> {
+        self.clas
+            .read()
+            .await
+            .get(&handle)
+            .map(|cla| cla.ident.clone())
+            .ok_or(tonic::Status::not_found("No such CLA registered"))
+    }
+
+    #[instrument(skip(self))]
+    pub async fn find(&self, handle: u32) -> Option<Endpoint> {
+        self.clas.read().await.get(&handle).map(|cla| Endpoint {
+            handle,
+            inner: cla.endpoint.clone(),
+            shaper: cla.shaper.clone(),
+        })
+    }
+
+    #[instrument(skip(self))]
+    pub async fn add_neighbour(&self, request: AddNeighbourRequest) -> Result<(), tonic::Status> {
+        let cla = self
+            .clas
+            .read()
+            .await
+            .get(&request.handle)
+            .ok_or(tonic::Status::not_found("No such CLA registered"))?
+            .clone();
+
+        let neighbour = request
+            .neighbour
+            .parse::<bpv7::EidPattern>()
+            .map_err(|e| tonic::Status::invalid_argument(e.to_string()))?;
+
+        if let Some(fib) = &self.fib {
+            fib.add(
+                format!("cla:{}", cla.name),
+                &neighbour,
+                request.priority,
+                fib::Action::Forward(fib::Endpoint {
+                    handle: request.handle,
+                    max_bundle_size: request.max_bundle_size,
+                    cost: None,
+                    peer: None,
+                }),
+            )
+            .await
+            .map_err(tonic::Status::from_error)?;
+        }
+
+        cla.neighbours
+            .lock()
+            .trace_expect("Failed to lock neighbours mutex")
+            .insert(neighbour, (request.priority, request.max_bundle_size));
+        Ok(())
+    }
+
+    #[instrument(skip(self))]
+    pub async fn remove_neighbour(
+        &self,
+        request: RemoveNeighbourRequest,
+    ) -> Result<(), tonic::Status> {
+        let cla = self
+            .clas
+            .read()
+            .await
+            .get(&request.handle)
+            .ok_or(tonic::Status::not_found("No such CLA registered"))?
+            .clone();
+
+        let neighbour = request
+            .neighbour
+            .parse::<bpv7::EidPattern>()
+            .map_err(|e| tonic::Status::invalid_argument(e.to_string()))?;
+
+        let mut found = cla
+            .neighbours
+            .lock()
+            .trace_expect("Failed to lock neighbours mutex")
+            .remove(&neighbour)
+            .is_some();
+
+        if let Some(fib) = &self.fib {
+            found = fib
+                .remove(&format!("cla:{}", cla.name), &neighbour)
+                .await
+                .is_some()
+                || found;
+        }
+
+        if found {
+            Ok(())
+        } else {
+            Err(tonic::Status::not_found("No such neighbour"))
+        }
+    }
+
+    #[instrument(skip(self))]
+    pub async fn report_link_quality(
+        &self,
+        request: ReportLinkQualityRequest,
+    ) -> Result<(), tonic::Status> {
+        let cla = self
+            .clas
+            .read()
+            .await
+            .get(&request.handle)
+            .ok_or(tonic::Status::not_found("No such CLA registered"))?
+            .clone();
+
+        let neighbour = request
+            .neighbour
+            .parse::<bpv7::EidPattern>()
+            .map_err(|e| tonic::Status::invalid_argument(e.to_string()))?;
+
+        if request
+            .loss
+            .is_some_and(|loss| !(0.0..=1.0).contains(&loss))
+        {
+            return Err(tonic::Status::invalid_argument(
+                "Loss must be between 0 and 1",
+            ));
+        }
+
+        if !cla
+            .neighbours
+            .lock()
+            .trace_expect("Failed to lock neighbours mutex")
+            .contains_key(&neighbour)
+        {
+            return Err(tonic::Status::not_found("No such neighbour"));
+        }
+
+        if let Some(fib) = &self.fib {
+            fib.report_link_quality(
+                &format!("cla:{}", cla.name),
+                &neighbour,
+                request.handle,
+                fib::LinkMetric {
+                    rtt: request.rtt.map(std::time::Duration::from_micros),
+                    loss: request.loss,
+                    bandwidth: request.bandwidth,
+                },
+            )
+            .await;
+        }
+        Ok(())
+    }
+
+    #[instrument(skip(self))]
+    pub async fn list_neighbours(&self) -> Vec<Neighbour> {
+        let mut neighbours = Vec::new();
+        for cla in self.clas.read().await.values() {
+            neighbours.extend(
+                cla.neighbours
+                    .lock()
+                    .trace_expect("Failed to lock neighbours mutex")
+                    .iter()
+                    .map(|(neighbour, (priority, max_bundle_size))| Neighbour {
+                        neighbour: neighbour.clone(),
+                        cla_ident: cla.ident.clone(),
+                        cla_name: cla.name.clone(),
+                        priority: *priority,
+                        max_bundle_size: *max_bundle_size,
+                    }),
+            );
+        }
+        neighbours
+    }
+}
+
+pub enum ForwardBundleResult {
+    Sent,
+    Pending(u32, Option<time::OffsetDateTime>),
+    Congested(time::OffsetDateTime),
+}
+
+impl Endpoint {
+    #[instrument(skip(self))]
+    pub async fn forward_bundle(
+        &self,
+        destination: &bpv7::Eid,
+        bundle: Bytes,
+    ) -> Result<ForwardBundleResult, Error> {
+        if let Some(shaper) = &self.shaper {
+            shaper.pace(bundle.len()).await;
+        }
+
+        match &self.inner {
+            Link::Grpc(channel) => self.forward_grpc(channel, destination, bundle).await,
+            #[cfg(any(test, feature = "test-util"))]
+            Link::Null(cla) => cla.forward_bundle(self.handle, &bundle),
+        }
+    }
+
+    async fn forward_grpc(
+        &self,
+        channel: &Channel,
+        destination: &bpv7::Eid,
+        bundle: Bytes,
+    ) -> Result<ForwardBundleResult, Error> {
+        let r = channel
+            .lock()
+            .await
+            .forward_bundle(tonic::Request::new(ForwardBundleRequest {
+                handle: self.handle,
+                destination: destination.to_string(),
+                bundle,
+            }))
+            .await?
+            .into_inner();
+
+        let delay = if let Some(t) = r.delay {
+            Some(grpc::from_timestamp(t)?)
+        } else {
+            None
+        };
+
+        // This is just horrible
+        match r.result {
+            v if v == (forward_bundle_response::ForwardingResult::Sent as i32) => {
+                Ok(ForwardBundleResult::Sent)
+            }
+            v if v == (forward_bundle_response::ForwardingResult::Pending as i32) => {
+                Ok(ForwardBundleResult::Pending(self.handle, delay))
+            }
+            v if v == (forward_bundle_response::ForwardingResult::Congested as i32) => {
+                if let Some(delay) = delay {
+                    Ok(ForwardBundleResult::Congested(delay))
+                } else {
+                    Ok(ForwardBundleResult::Congested(
+                        time::OffsetDateTime::now_utc(),
+                    ))
+                }
+            }
+            v => {
+                Err(tonic::Status::invalid_argument(format!("Invalid result {v} received")).into())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn add(handle: u32, neighbour: &str, max_bundle_size: Option<u64>) -> AddNeighbourRequest {
+        AddNeighbourRequest {
+            handle,
+            priority: 0,
+            neighbour: neighbour.to_string(),
+            max_bundle_size,
+        }
+    }
+
+    #[tokio::test]
+    async fn neighbours() {
+        let config = config::Config::default();
+        let registry = ClaRegistry::new(&config, fib::Fib::new(&config));
+        let tcp = registry
+            .register_null_cla("tcp0", "TCPCLv4")
+            .await
+            .unwrap()
+            .0;
+        let udp = registry.register_null_cla("udp0", "UDPCL").await.unwrap().0;
+
+        registry
+            .add_neighbour(add(tcp, "ipn:2.*", Some(65536)))
+            .await
+            .unwrap();
+        registry
+            .add_neighbour(add(tcp, "ipn:3.*", None))
+            .await
+            .unwrap();
+        registry
+            .add_neighbour(add(udp, "ipn:4.*", None))
+            .await
+            .unwrap();
+
+        let mut neighbours = registry.list_neighbours().await;
+        neighbours.sort_by_key(|n| n.neighbour.to_string());
+        assert_eq!(
+            neighbours
+                .iter()
+                .map(|n| (
+                    n.neighbour.to_string(),
+                    n.cla_name.as_str(),
+                    n.max_bundle_size
+                ))
+                .collect::<Vec<_>>(),
+            vec![
+                (
+                    "ipn:2.*".parse::<bpv7::EidPattern>().unwrap().to_string(),
+                    "TCPCLv4",
+                    Some(65536)
+                ),
+                (
+                    "ipn:3.*".parse::<bpv7::EidPattern>().unwrap().to_string(),
+                    "TCPCLv4",
+                    None
+                ),
+                (
+                    "ipn:4.*".parse::<bpv7::EidPattern>().unwrap().to_string(),
+                    "UDPCL",
+                    None
+                ),
+            ]
+        );
+
+        registry
+            .remove_neighbour(RemoveNeighbourRequest {
+                handle: tcp,
+                neighbour: "ipn:2.*".to_string(),
+            })
+            .await
+            .unwrap();
+        assert!(registry
+            .remove_neighbour(RemoveNeighbourRequest {
+                handle: udp,
+                neighbour: "ipn:2.*".to_string(),
+            })
+            .await
+            .is_err());
+
+        let neighbours = registry.list_neighbours().await;
+        assert_eq!(neighbours.len(), 2);
+        assert!(neighbours
+            .iter()
+            .all(|n| n.neighbour != "ipn:2.*".parse().unwrap()));
+    }
+}
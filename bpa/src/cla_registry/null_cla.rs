@@ -0,0 +1,136 @@
+use super::*;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+
+// How a NullCla responds to the next bundles it is asked to forward
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum NullClaResponse {
+    #[default]
+    Sent,
+    Pending,
+    Congested(time::OffsetDateTime),
+    Fail,
+}
+
+/* A CLA that lives inside the BPA rather than behind gRPC, for tests.
+ * Every bundle forwarded to it is discarded, but counted */
+#[derive(Debug, Default)]
+pub struct NullCla {
+    response: std::sync::Mutex<NullClaResponse>,
+    attempts: AtomicUsize,
+    forwarded: AtomicUsize,
+    forwarded_bytes: AtomicU64,
+    last_bundle: std::sync::Mutex<Option<Bytes>>,
+}
+
+impl NullCla {
+    pub fn set_response(&self, response: NullClaResponse) {
+        *self
+            .response
+            .lock()
+            .trace_expect("Failed to lock response mutex") = response;
+    }
+
+    // Every bundle offered, whatever the response
+    pub fn attempts(&self) -> usize {
+        self.attempts.load(Ordering::Relaxed)
+    }
+
+    // The bundles accepted, with Sent or Pending
+    pub fn forwarded(&self) -> usize {
+        self.forwarded.load(Ordering::Relaxed)
+    }
+
+    pub fn forwarded_bytes(&self) -> u64 {
+        self.forwarded_bytes.load(Ordering::Relaxed)
+    }
+
+    // The most recent bundle accepted, as it was sent
+    pub fn last_bundle(&self) -> Option<Bytes> {
+        self.last_bundle
+            .lock()
+            .trace_expect("Failed to lock last bundle mutex")
+            .clone()
+    }
+
+    pub(super) fn forward_bundle(
+        &self,
+        handle: u32,
+        bundle: &Bytes,
+    ) -> Result<ForwardBundleResult, Error> {
+        self.attempts.fetch_add(1, Ordering::Relaxed);
+
+        let response = *self
+            .response
+            .lock()
+            .trace_expect("Failed to lock response mutex");
+        let result = match response {
+            NullClaResponse::Sent => ForwardBundleResult::Sent,
+            NullClaResponse::Pending => ForwardBundleResult::Pending(handle, None),
+            NullClaResponse::Congested(until) => return Ok(ForwardBundleResult::Congested(until)),
+            NullClaResponse::Fail => {
+                return Err(tonic::Status::unavailable("Null CLA told to fail").into())
+            }
+        };
+
+        self.forwarded.fetch_add(1, Ordering::Relaxed);
+        self.forwarded_bytes
+            .fetch_add(bundle.len() as u64, Ordering::Relaxed);
+        *self
+            .last_bundle
+            .lock()
+            .trace_expect("Failed to lock last bundle mutex") = Some(bundle.clone());
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn responses() {
+        let registry = ClaRegistry::new(&config::Config::default(), None);
+        let (handle, cla) = registry.register_null_cla("null0", "Null").await.unwrap();
+        let endpoint = registry.find(handle).await.unwrap();
+        let destination = "ipn:2.1".parse::<bpv7::Eid>().unwrap();
+        let bundle = Bytes::from_static(&[0; 10]);
+
+        assert!(matches!(
+            endpoint
+                .forward_bundle(&destination, bundle.clone())
+                .await
+                .unwrap(),
+            ForwardBundleResult::Sent
+        ));
+
+        cla.set_response(NullClaResponse::Pending);
+        assert!(matches!(
+            endpoint
+                .forward_bundle(&destination, bundle.clone())
+                .await
+                .unwrap(),
+            ForwardBundleResult::Pending(h, None) if h == handle
+        ));
+
+        let until = time::OffsetDateTime::now_utc() + time::Duration::seconds(10);
+        cla.set_response(NullClaResponse::Congested(until));
+        assert!(matches!(
+            endpoint
+                .forward_bundle(&destination, bundle.clone())
+                .await
+                .unwrap(),
+            ForwardBundleResult::Congested(u) if u == until
+        ));
+
+        cla.set_response(NullClaResponse::Fail);
+        assert!(endpoint
+            .forward_bundle(&destination, bundle.clone())
+            .await
+            .is_err());
+
+        // Only the Sent and Pending bundles were taken
+        assert_eq!(cla.attempts(), 4);
+        assert_eq!(cla.forwarded(), 2);
+        assert_eq!(cla.forwarded_bytes(), 20);
+    }
+}
@@ -1,5 +1,16 @@
 use super::*;
 
+// What to do with administrative records received at an administrative endpoint
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum AdminRecords {
+    // Pass status reports on to the local service that sourced the reported bundle
+    #[default]
+    Notify,
+    // Only surface them as dispatch events
+    EventsOnly,
+}
+
 impl Dispatcher {
     #[instrument(skip(self))]
     pub(super) async fn administrative_bundle(
@@ -19,13 +30,59 @@ impl Dispatcher {
             return Ok(DispatchResult::Done);
         };
 
-        match cbor::decode::parse(data.as_ref().as_ref()) {
+        let Some(payload) = bundle.bundle.blocks.get(&1) else {
+            return Ok(DispatchResult::Drop(Some(
+                bpv7::StatusReportReasonCode::BlockUnintelligible,
+            )));
+        };
+
+        let record = cbor::decode::parse_value(
+            payload.payload(data.as_ref().as_ref()),
+            |v, _, tags| match v {
+                cbor::decode::Value::Bytes(data) => {
+                    bpv7::AdministrativeRecord::parse(data).map_err(Into::into)
+                }
+                cbor::decode::Value::ByteStream(data) => {
+                    bpv7::AdministrativeRecord::parse(&data.concat()).map_err(Into::into)
+                }
+                _ => Err::<_, Error>(
+                    cbor::decode::Error::IncorrectType(
+                        "Byte String".to_string(),
+                        v.type_name(!tags.is_empty()),
+                    )
+                    .into(),
+                ),
+            },
+        )
+        .map(|(record, _)| record);
+
+        if let Ok(record) = &record {
+            self.events.emit(|| {
+                DispatchEvent::AdminRecord(
+                    bundle.bundle.id.clone(),
+                    match record {
+                        bpv7::AdministrativeRecord::BundleStatusReport(_) => {
+                            bpv7::AdminRecordType::BundleStatusReport
+                        }
+                        bpv7::AdministrativeRecord::BundleInBundle(_) => {
+                            bpv7::AdminRecordType::BundleInBundle
+                        }
+                    },
+                )
+            });
+        }
+
+        match record {
             Err(e) => {
                 trace!("Failed to parse administrative record: {e}");
                 Ok(DispatchResult::Drop(Some(
                     bpv7::StatusReportReasonCode::BlockUnintelligible,
                 )))
             }
+            Ok(_) if self.config.admin_records == AdminRecords::EventsOnly => {
+                trace!("Consumed administrative record");
+                Ok(DispatchResult::Drop(None))
+            }
             Ok(bpv7::AdministrativeRecord::BundleStatusReport(report)) => {
                 // Check if the report is for a bundle sourced from a local service
                 if !self
@@ -89,6 +146,13 @@ impl Dispatcher {
                     Ok(DispatchResult::Drop(None))
                 }
             }
+            Ok(bpv7::AdministrativeRecord::BundleInBundle(pdu)) => {
+                trace!(
+                    "Bundle-in-bundle decapsulation is not supported, dropping transmission {}",
+                    pdu.transmission_id
+                );
+                Ok(DispatchResult::Drop(None))
+            }
         }
     }
 }
@@ -0,0 +1,152 @@
+use super::*;
+use utils::settings;
+
+// What to do with a bundle whose creation time is later than it was received
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum FutureBundles {
+    #[default]
+    Accept,
+    Clamp,
+    Drop,
+}
+
+/* Bundles created in the future, by a source with a clock ahead of ours, would outlive their
+ * lifetime here.  Skew up to `tolerance` is accepted as it is */
+pub struct ClockSkew {
+    action: FutureBundles,
+    tolerance: time::Duration,
+}
+
+impl ClockSkew {
+    pub fn new(config: &::config::Config) -> Self {
+        let action = settings::get_with_default(config, "future_bundles", FutureBundles::default())
+            .trace_expect("Invalid 'future_bundles' value in configuration");
+        let tolerance = settings::get_with_default::<u64, _>(config, "clock_skew_tolerance", 0)
+            .trace_expect("Invalid 'clock_skew_tolerance' value in configuration");
+
+        match action {
+            FutureBundles::Accept => {}
+            FutureBundles::Clamp => info!(
+                "Bundles created more than {tolerance} seconds in the future will have their lifetime clamped"
+            ),
+            FutureBundles::Drop => info!(
+                "Bundles created more than {tolerance} seconds in the future will be dropped"
+            ),
+        }
+
+        Self {
+            action,
+            tolerance: time::Duration::seconds(tolerance.min(i64::MAX as u64) as i64),
+        }
+    }
+
+    /* Apply the policy to a bundle received at `now`, returning a reason to drop it, if any.
+     * Clamping shortens the lifetime of the bundle, so it expires as if it was created at `now` */
+    pub fn apply(
+        &self,
+        bundle: &mut metadata::Bundle,
+        now: time::OffsetDateTime,
+    ) -> Option<bpv7::StatusReportReasonCode> {
+        let creation_time = time::OffsetDateTime::from(bundle.bundle.id.timestamp.creation_time?);
+        let skew = creation_time - now;
+        if skew <= self.tolerance {
+            return None;
+        }
+
+        match self.action {
+            FutureBundles::Accept => None,
+            FutureBundles::Clamp => {
+                trace!("Bundle was created {skew} in the future, clamping its lifetime");
+                bundle.bundle.lifetime = bundle
+                    .bundle
+                    .lifetime
+                    .saturating_sub(skew.whole_milliseconds().clamp(0, u64::MAX as i128) as u64);
+                None
+            }
+            FutureBundles::Drop => {
+                trace!("Bundle was created {skew} in the future");
+                Some(bpv7::StatusReportReasonCode::NoAdditionalInformation)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn build(action: &str, tolerance: u64) -> ClockSkew {
+        ClockSkew::new(
+            &::config::Config::builder()
+                .set_override("future_bundles", action)
+                .unwrap()
+                .set_override("clock_skew_tolerance", tolerance)
+                .unwrap()
+                .build()
+                .unwrap(),
+        )
+    }
+
+    // A bundle with a 60 second lifetime, created `ahead` seconds after `now`
+    fn bundle(now: time::OffsetDateTime, ahead: i64) -> metadata::Bundle {
+        metadata::Bundle {
+            metadata: metadata::Metadata {
+                received_at: Some(now),
+                ..Default::default()
+            },
+            bundle: bpv7::Bundle {
+                id: bpv7::BundleId {
+                    source: "ipn:2.1".parse().unwrap(),
+                    timestamp: bpv7::CreationTimestamp {
+                        creation_time: Some(
+                            (now + time::Duration::seconds(ahead)).try_into().unwrap(),
+                        ),
+                        sequence_number: 0,
+                    },
+                    fragment_info: None,
+                },
+                lifetime: 60_000,
+                ..Default::default()
+            },
+        }
+    }
+
+    #[test]
+    fn future_bundles() {
+        // DTN time has millisecond precision
+        let now = time::OffsetDateTime::from(bpv7::DtnTime::now());
+
+        // Accepted as is
+        let mut b = bundle(now, 30);
+        assert_eq!(build("accept", 0).apply(&mut b, now), None);
+        assert_eq!(b.bundle.lifetime, 60_000);
+
+        // Expires a minute after we received it, not a minute after it was created
+        let mut b = bundle(now, 30);
+        assert_eq!(build("clamp", 0).apply(&mut b, now), None);
+        assert_eq!(b.bundle.lifetime, 30_000);
+        assert!(b.has_expired_at(now + time::Duration::seconds(61)));
+        assert!(!b.has_expired_at(now + time::Duration::seconds(59)));
+
+        let mut b = bundle(now, 30);
+        assert_eq!(
+            build("drop", 0).apply(&mut b, now),
+            Some(bpv7::StatusReportReasonCode::NoAdditionalInformation)
+        );
+
+        // Within tolerance, whatever the policy
+        for action in ["clamp", "drop"] {
+            let mut b = bundle(now, 30);
+            assert_eq!(build(action, 45).apply(&mut b, now), None);
+            assert_eq!(b.bundle.lifetime, 60_000);
+        }
+
+        // Bundles from the past, and from sources without a clock, are not touched
+        let mut b = bundle(now, -30);
+        assert_eq!(build("drop", 0).apply(&mut b, now), None);
+        let mut b = bundle(now, 0);
+        b.bundle.id.timestamp.creation_time = None;
+        assert_eq!(build("drop", 0).apply(&mut b, now), None);
+    }
+}
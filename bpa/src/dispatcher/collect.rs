@@ -8,14 +8,18 @@ pub struct CollectResponse {
 }
 
 impl Dispatcher {
+    /* Hand a bundle to an application.  If `acknowledge` is set the bundle is kept,
+     * and is only delivered once the application acknowledges it, see acknowledge() */
     #[instrument(skip(self))]
     pub async fn collect(
         &self,
-        destination: bpv7::Eid,
+        destinations: bpv7::EidPattern,
+        token: &str,
         bundle_id: String,
+        acknowledge: bool,
     ) -> Result<Option<CollectResponse>, Error> {
         // Lookup bundle
-        let Some(bundle) = self
+        let Some(mut bundle) = self
             .store
             .load(&bpv7::BundleId::from_key(&bundle_id)?)
             .await?
@@ -28,19 +32,54 @@ impl Dispatcher {
             return Ok(None);
         };
 
-        if bundle.bundle.destination != destination || bundle.has_expired() {
+        if !destinations.is_match(&bundle.bundle.destination)
+            || self
+                .app_registry
+                .is_owned_by_other(&bundle.bundle.destination, token)
+                .await
+            || bundle.has_expired_at(self.clock.now())
+        {
             return Ok(None);
         }
 
+        let collection = if acknowledge {
+            if !self.fan_out.is_pending(&bundle.bundle.id, token) {
+                return Ok(None);
+            }
+
+            let eid = self.app_registry.find_by_token(token).await?;
+            if !self.delivery_acks.collected(
+                &bundle.bundle.id,
+                &eid,
+                self.config.max_delivery_attempts,
+            ) {
+                warn!(
+                    "Application lost bundle {:?} too many times, giving up",
+                    bundle.bundle.id
+                );
+                self.complete_delivery(
+                    &bundle.bundle.id,
+                    token,
+                    Some(bpv7::StatusReportReasonCode::NoAdditionalInformation),
+                )
+                .await?;
+                return Ok(None);
+            }
+            None
+        } else {
+            let collection = self.fan_out.collect(&bundle.bundle.id, token);
+            if collection == fan_out::Collection::Repeated {
+                return Ok(None);
+            }
+            Some(collection)
+        };
+
         // Get the data!
-        let Some(data) = self.load_data(&bundle).await? else {
+        let Some(data) = self.load_data(&mut bundle).await? else {
             // Bundle data was deleted sometime during processing
             return Ok(None);
         };
 
-        // By the time we get here, we're safe to report delivery
-        self.report_bundle_delivery(&bundle).await?;
-
         // Prepare the response
         let response = CollectResponse {
             bundle_id: bundle.bundle.id.to_key(),
@@ -49,12 +88,146 @@ impl Dispatcher {
             app_ack_requested: bundle.bundle.flags.app_ack_requested,
         };
 
-        // And we are done with the bundle
-        self.drop_bundle(bundle, None).await?;
+        match collection {
+            None => {
+                // Wait for the application to acknowledge
+                return Ok(Some(response));
+            }
+            Some(fan_out::Collection::Copy) => {
+                // Other applications have yet to collect the bundle
+                return Ok(Some(response));
+            }
+            _ => {}
+        }
+
+        // By the time we get here, we're safe to report delivery
+        self.local_delivery_complete(bundle, None).await?;
 
         Ok(Some(response))
     }
 
+    /* Complete the delivery of a bundle collected with `acknowledge` set.  If the application
+     * failed to process it, it is notified that the bundle is ready for collection again,
+     * until it has failed 'max_delivery_attempts' times.
+     * Returns false if there was nothing to acknowledge */
+    #[instrument(skip(self))]
+    pub async fn acknowledge(
+        &self,
+        token: &str,
+        bundle_id: String,
+        processed: bool,
+    ) -> Result<bool, Error> {
+        let bundle_id = bpv7::BundleId::from_key(&bundle_id)?;
+        let eid = self.app_registry.find_by_token(token).await?;
+        let acknowledged = self.delivery_acks.acknowledge(
+            &bundle_id,
+            &eid,
+            processed,
+            self.config.max_delivery_attempts,
+        );
+
+        let reason = match acknowledged {
+            delivery_ack::Acknowledged::Unexpected => return Ok(false),
+            delivery_ack::Acknowledged::Redeliver => {
+                trace!("Application failed to process bundle, redelivering");
+                if let Some(endpoint) = self.app_registry.find_endpoint_by_token(token).await {
+                    endpoint.collection_notify(&bundle_id).await;
+                }
+                return Ok(true);
+            }
+            delivery_ack::Acknowledged::Delivered => None,
+            delivery_ack::Acknowledged::Abandoned => {
+                warn!("Application failed to process bundle {bundle_id:?}, giving up");
+                Some(bpv7::StatusReportReasonCode::NoAdditionalInformation)
+            }
+        };
+
+        self.complete_delivery(&bundle_id, token, reason).await?;
+        Ok(true)
+    }
+
+    /* Finish with a bundle an application has processed, or given up on with `reason`,
+     * dropping it once every application has collected it */
+    async fn complete_delivery(
+        &self,
+        bundle_id: &bpv7::BundleId,
+        token: &str,
+        reason: Option<bpv7::StatusReportReasonCode>,
+    ) -> Result<(), Error> {
+        // An abandoned delivery still counts towards a fan-out, the other applications may succeed
+        match self.fan_out.collect(bundle_id, token) {
+            fan_out::Collection::Copy | fan_out::Collection::Repeated => Ok(()),
+            fan_out::Collection::Sole | fan_out::Collection::Last => {
+                self.finish_delivery(bundle_id, reason).await
+            }
+        }
+    }
+
+    // No application has yet to collect the bundle
+    async fn finish_delivery(
+        &self,
+        bundle_id: &bpv7::BundleId,
+        reason: Option<bpv7::StatusReportReasonCode>,
+    ) -> Result<(), Error> {
+        // The bundle may have expired, or been deleted, while the application had it
+        let Some(bundle) = self.store.load(bundle_id).await? else {
+            return Ok(());
+        };
+        let metadata::BundleStatus::CollectionPending = &bundle.metadata.status else {
+            return Ok(());
+        };
+        self.local_delivery_complete(bundle, reason).await
+    }
+
+    /* Does a bundle continue on to other nodes once delivered locally.  Every member of a
+     * non-singleton endpoint should receive the bundle, and the others are elsewhere */
+    pub(super) fn forwards_onward(&self, bundle: &metadata::Bundle) -> bool {
+        self.fib.is_some() && !bundle.bundle.destination.is_singleton()
+    }
+
+    /* Every local application has finished with the bundle, or given up on it with `reason`.
+     * It is dropped, unless it continues on to other nodes */
+    pub(super) async fn local_delivery_complete(
+        &self,
+        mut bundle: metadata::Bundle,
+        reason: Option<bpv7::StatusReportReasonCode>,
+    ) -> Result<(), Error> {
+        if reason.is_none() {
+            self.report_bundle_delivery(&bundle).await?;
+        }
+
+        if !self.forwards_onward(&bundle) {
+            return self.drop_bundle(bundle, reason).await;
+        }
+
+        // Nothing local can acknowledge it now
+        self.delivery_acks.forget_bundle(&bundle.bundle.id);
+        self.store
+            .set_status(&mut bundle, metadata::BundleStatus::ForwardPending)
+            .await?;
+        self.dispatch_bundle(bundle).await
+    }
+
+    /* Unregister an application, forgetting the bundles it has yet to acknowledge or collect.
+     * Fanned out bundles it was the last to collect are delivered to the others, so are finished now */
+    #[instrument(skip(self))]
+    pub async fn unregister_application(
+        &self,
+        request: hardy_proto::application::UnregisterApplicationRequest,
+    ) -> Result<hardy_proto::application::UnregisterApplicationResponse, tonic::Status> {
+        let eid = self.app_registry.find_by_token(&request.token).await?;
+        let token = request.token.clone();
+        let response = self.app_registry.unregister(request).await?;
+        self.delivery_acks.forget_application(&eid);
+
+        for bundle_id in self.fan_out.forget_application(&token) {
+            if let Err(e) = self.finish_delivery(&bundle_id, None).await {
+                error!("Failed to complete delivery of bundle {bundle_id:?}: {e}");
+            }
+        }
+        Ok(response)
+    }
+
     #[instrument(skip(self))]
     pub async fn poll_for_collection(
         &self,
@@ -64,3 +237,102 @@ impl Dispatcher {
         self.store.poll_for_collection(destination, tx).await
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::tests::{
+        dispatcher_config, new_test_dispatcher, test_store, TestBundles, TestDispatcher,
+        TestMetadata,
+    };
+
+    #[tokio::test]
+    async fn group_delivery() {
+        let store = Arc::new(test_store(
+            Arc::new(TestMetadata::default()),
+            Arc::new(TestBundles::default()),
+        ));
+
+        // A route onwards to the other members of the group
+        let mut task_set = tokio::task::JoinSet::new();
+        let cancel_token = tokio_util::sync::CancellationToken::new();
+        let TestDispatcher {
+            dispatcher,
+            app_registry,
+            clas,
+            ..
+        } = new_test_dispatcher(
+            store,
+            &dispatcher_config()
+                .set_override("administrative_endpoint", "dtn://node/")
+                .unwrap()
+                .build()
+                .unwrap(),
+            &[("dtn://node/**", None)],
+            &mut task_set,
+            cancel_token.clone(),
+        )
+        .await;
+        let cla = &clas[0];
+        let token = app_registry
+            .register(hardy_proto::application::RegisterApplicationRequest {
+                endpoint: Some(
+                    hardy_proto::application::register_application_request::Endpoint::DtnService(
+                        "~all".to_string(),
+                    ),
+                ),
+                ident: "member".to_string(),
+                grpc_address: None,
+                max_concurrent_notifications: None,
+            })
+            .await
+            .unwrap()
+            .token;
+
+        let (bundle, data) = bpv7::Builder::new()
+            .source("dtn://other/app".parse().unwrap())
+            .destination("dtn://node/~all".parse().unwrap())
+            .lifetime(60_000)
+            .add_payload_block(vec![1, 2, 3])
+            .build();
+        dispatcher.receive_bundle(data.into(), None).await.unwrap();
+
+        // Nothing is forwarded until the local member has collected the bundle
+        let mut collected = None;
+        for _ in 0..100 {
+            collected = dispatcher
+                .collect(
+                    "dtn://node/~all".parse().unwrap(),
+                    &token,
+                    bundle.id.to_key(),
+                    false,
+                )
+                .await
+                .unwrap();
+            if collected.is_some() {
+                break;
+            }
+            tokio::time::sleep(tokio::time::Duration::from_millis(10)).await;
+        }
+        assert_eq!(collected.unwrap().data.as_ref(), [1, 2, 3]);
+        assert_eq!(cla.attempts(), 0);
+
+        // Then it carries on to the rest of the group
+        for _ in 0..100 {
+            if cla.attempts() > 0 {
+                break;
+            }
+            tokio::time::sleep(tokio::time::Duration::from_millis(10)).await;
+        }
+        assert_eq!(cla.forwarded(), 1);
+        let bpv7::ValidBundle::Valid(forwarded, _) =
+            bpv7::ValidBundle::parse(&cla.last_bundle().unwrap(), |_, _| Ok(None)).unwrap()
+        else {
+            panic!("Invalid bundle forwarded");
+        };
+        assert_eq!(forwarded.id, bundle.id);
+
+        cancel_token.cancel();
+        while task_set.join_next().await.is_some() {}
+    }
+}
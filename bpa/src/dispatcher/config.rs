@@ -1,15 +1,89 @@
 use super::*;
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
+use thiserror::Error;
 use utils::settings;
 
 const MAX_FORWARDING_DELAY_SECS: u32 = 5;
+pub const STATUS_REPORT_WINDOW_SECS: u64 = 60;
+pub const FORWARD_ACK_TIMEOUT_SECS: u64 = 60;
+const MAX_DELIVERY_ATTEMPTS: u32 = 3;
+
+// These settings are fixed for the lifetime of the process
+const STRUCTURAL_SETTINGS: &[&str] = &[
+    "administrative_endpoint",
+    "node_id_selection",
+    "metadata_storage",
+    "bundle_storage",
+    "ipn_2_element",
+    "max_in_flight_per_peer",
+    "max_ingress_queue",
+    "unsupported_blocks",
+    "storage_capacity",
+    "storage_early_drop",
+    "storage_retries",
+    "storage_retry_backoff",
+    "status_report_limit",
+    "status_report_window",
+    "spoofed_sources",
+    "local_delivery",
+    "admin_records",
+    "max_delivery_attempts",
+    "forward_ack_timeout",
+    "max_dispatch_per_wakeup",
+    "future_bundles",
+    "clock_skew_tolerance",
+    "dedup_filter_window",
+    "dedup_filter_size",
+];
+
+/* What to do with a bundle carrying an unsupported block that has the
+ * 'Report status if block cannot be processed' flag set, see RFC 9171 Section 5.6.
+ * The 'Delete bundle' and 'Discard block' flags are always honoured by the parser */
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum UnsupportedBlocks {
+    #[default]
+    Report,
+    Ignore,
+    Drop,
+}
+
+impl UnsupportedBlocks {
+    /* Returns a reason to drop the bundle, if any, and whether to send a 'Block unsupported' report */
+    pub fn apply(self, report_unsupported: bool) -> (Option<bpv7::StatusReportReasonCode>, bool) {
+        match self {
+            _ if !report_unsupported => (None, false),
+            Self::Report => (None, true),
+            Self::Ignore => (None, false),
+            // The deletion report carries the reason
+            Self::Drop => (Some(bpv7::StatusReportReasonCode::BlockUnsupported), false),
+        }
+    }
+}
+
+#[derive(Error, Debug)]
+#[error("Configuration changes to {0:?} require a restart, and have been ignored")]
+pub struct ReloadError(pub Vec<&'static str>);
 
-#[derive(Clone)]
 pub struct Config {
     pub admin_endpoints: utils::admin_endpoints::AdminEndpoints,
-    pub status_reports: bool,
-    pub wait_sample_interval: u64,
-    pub max_forwarding_delay: u32,
     pub ipn_2_element: bpv7::EidPatternMap<(), ()>,
+    pub max_in_flight: u32,
+    pub max_ingress_queue: u32,
+    pub unsupported_blocks: UnsupportedBlocks,
+    pub local_delivery: LocalDelivery,
+    pub admin_records: AdminRecords,
+    pub max_delivery_attempts: u32,
+    pub status_report_limit: u32,
+    pub status_report_window: u64,
+    pub forward_ack_timeout: u64,
+    status_reports: AtomicBool,
+    wait_sample_interval: AtomicU64,
+    max_forwarding_delay: AtomicU32,
+    trace_propagation: AtomicBool,
+    reflect_loop_prevention: AtomicBool,
+    accept_source_routes: AtomicBool,
+    structural: Vec<Option<Vec<String>>>,
 }
 
 impl Config {
@@ -19,35 +93,186 @@ impl Config {
     ) -> Self {
         let config = Self {
             admin_endpoints,
-            status_reports: settings::get_with_default(config, "status_reports", false)
-                .trace_expect("Invalid 'status_reports' value in configuration"),
-            wait_sample_interval: settings::get_with_default(
+            ipn_2_element: Self::load_ipn_2_element(config),
+            max_in_flight: settings::get_with_default(config, "max_in_flight_per_peer", 0u32)
+                .trace_expect("Invalid 'max_in_flight_per_peer' value in configuration"),
+            max_ingress_queue: settings::get_with_default(config, "max_ingress_queue", 0u32)
+                .trace_expect("Invalid 'max_ingress_queue' value in configuration"),
+            unsupported_blocks: settings::get_with_default(
                 config,
-                "wait_sample_interval",
-                settings::WAIT_SAMPLE_INTERVAL_SECS,
+                "unsupported_blocks",
+                UnsupportedBlocks::default(),
             )
-            .trace_expect("Invalid 'wait_sample_interval' value in configuration"),
-            max_forwarding_delay: settings::get_with_default::<u32, _>(
+            .trace_expect("Invalid 'unsupported_blocks' value in configuration"),
+            local_delivery: settings::get_with_default(
                 config,
-                "max_forwarding_delay",
-                MAX_FORWARDING_DELAY_SECS,
+                "local_delivery",
+                LocalDelivery::default(),
             )
-            .trace_expect("Invalid 'max_forwarding_delay' value in configuration")
-            .min(1u32),
-            ipn_2_element: Self::load_ipn_2_element(config),
+            .trace_expect("Invalid 'local_delivery' value in configuration"),
+            admin_records: settings::get_with_default(
+                config,
+                "admin_records",
+                AdminRecords::default(),
+            )
+            .trace_expect("Invalid 'admin_records' value in configuration"),
+            max_delivery_attempts: settings::get_with_default(
+                config,
+                "max_delivery_attempts",
+                MAX_DELIVERY_ATTEMPTS,
+            )
+            .trace_expect("Invalid 'max_delivery_attempts' value in configuration"),
+            status_report_limit: settings::get_with_default(config, "status_report_limit", 0u32)
+                .trace_expect("Invalid 'status_report_limit' value in configuration"),
+            status_report_window: settings::get_with_default(
+                config,
+                "status_report_window",
+                STATUS_REPORT_WINDOW_SECS,
+            )
+            .trace_expect("Invalid 'status_report_window' value in configuration"),
+            forward_ack_timeout: settings::get_with_default(
+                config,
+                "forward_ack_timeout",
+                FORWARD_ACK_TIMEOUT_SECS,
+            )
+            .trace_expect("Invalid 'forward_ack_timeout' value in configuration"),
+            status_reports: AtomicBool::new(Self::load_status_reports(config)),
+            wait_sample_interval: AtomicU64::new(Self::load_wait_sample_interval(config)),
+            max_forwarding_delay: AtomicU32::new(Self::load_max_forwarding_delay(config)),
+            trace_propagation: AtomicBool::new(Self::load_trace_propagation(config)),
+            reflect_loop_prevention: AtomicBool::new(Self::load_reflect_loop_prevention(config)),
+            accept_source_routes: AtomicBool::new(Self::load_accept_source_routes(config)),
+            structural: Self::load_structural(config),
         };
 
-        if !config.status_reports {
+        if !config.status_reports() {
             info!("Bundle status reports are disabled by configuration");
         }
 
-        if config.max_forwarding_delay == 0 {
+        if config.max_forwarding_delay() == 0 {
             info!("Forwarding synchronization delay disabled by configuration");
         }
 
         config
     }
 
+    pub fn status_reports(&self) -> bool {
+        self.status_reports.load(Ordering::Relaxed)
+    }
+
+    pub fn wait_sample_interval(&self) -> u64 {
+        self.wait_sample_interval.load(Ordering::Relaxed)
+    }
+
+    pub fn max_forwarding_delay(&self) -> u32 {
+        self.max_forwarding_delay.load(Ordering::Relaxed)
+    }
+
+    pub fn trace_propagation(&self) -> bool {
+        self.trace_propagation.load(Ordering::Relaxed)
+    }
+
+    pub fn reflect_loop_prevention(&self) -> bool {
+        self.reflect_loop_prevention.load(Ordering::Relaxed)
+    }
+
+    pub fn accept_source_routes(&self) -> bool {
+        self.accept_source_routes.load(Ordering::Relaxed)
+    }
+
+    /* Apply any settings that can change while running, and report any that cannot.
+     * The reloadable settings are applied even if an error is returned */
+    pub fn reload(&self, config: &::config::Config) -> Result<(), ReloadError> {
+        let status_reports = Self::load_status_reports(config);
+        if self.status_reports.swap(status_reports, Ordering::Relaxed) != status_reports {
+            info!(
+                "Bundle status reports are now {}",
+                if status_reports {
+                    "enabled"
+                } else {
+                    "disabled"
+                }
+            );
+        }
+
+        self.wait_sample_interval
+            .store(Self::load_wait_sample_interval(config), Ordering::Relaxed);
+        self.max_forwarding_delay
+            .store(Self::load_max_forwarding_delay(config), Ordering::Relaxed);
+        self.trace_propagation
+            .store(Self::load_trace_propagation(config), Ordering::Relaxed);
+        self.reflect_loop_prevention.store(
+            Self::load_reflect_loop_prevention(config),
+            Ordering::Relaxed,
+        );
+        self.accept_source_routes
+            .store(Self::load_accept_source_routes(config), Ordering::Relaxed);
+
+        let ignored = STRUCTURAL_SETTINGS
+            .iter()
+            .zip(self.structural.iter().zip(Self::load_structural(config)))
+            .filter_map(|(key, (old, new))| (*old != new).then_some(*key))
+            .collect::<Vec<_>>();
+        if ignored.is_empty() {
+            Ok(())
+        } else {
+            Err(ReloadError(ignored))
+        }
+    }
+
+    fn load_status_reports(config: &::config::Config) -> bool {
+        settings::get_with_default(config, "status_reports", false)
+            .trace_expect("Invalid 'status_reports' value in configuration")
+    }
+
+    fn load_wait_sample_interval(config: &::config::Config) -> u64 {
+        settings::get_with_default(
+            config,
+            "wait_sample_interval",
+            settings::WAIT_SAMPLE_INTERVAL_SECS,
+        )
+        .trace_expect("Invalid 'wait_sample_interval' value in configuration")
+    }
+
+    fn load_max_forwarding_delay(config: &::config::Config) -> u32 {
+        settings::get_with_default::<u32, _>(
+            config,
+            "max_forwarding_delay",
+            MAX_FORWARDING_DELAY_SECS,
+        )
+        .trace_expect("Invalid 'max_forwarding_delay' value in configuration")
+        .min(1u32)
+    }
+
+    fn load_trace_propagation(config: &::config::Config) -> bool {
+        settings::get_with_default(config, "trace_propagation", false)
+            .trace_expect("Invalid 'trace_propagation' value in configuration")
+    }
+
+    fn load_reflect_loop_prevention(config: &::config::Config) -> bool {
+        settings::get_with_default(config, "reflect_loop_prevention", true)
+            .trace_expect("Invalid 'reflect_loop_prevention' value in configuration")
+    }
+
+    fn load_accept_source_routes(config: &::config::Config) -> bool {
+        settings::get_with_default(config, "accept_source_routes", false)
+            .trace_expect("Invalid 'accept_source_routes' value in configuration")
+    }
+
+    fn load_structural(config: &::config::Config) -> Vec<Option<Vec<String>>> {
+        // All structural settings are either a string or an array of strings
+        STRUCTURAL_SETTINGS
+            .iter()
+            .map(|key| {
+                config
+                    .get::<String>(key)
+                    .map(|s| vec![s])
+                    .or_else(|_| config.get::<Vec<String>>(key))
+                    .ok()
+            })
+            .collect()
+    }
+
     fn load_ipn_2_element(config: &::config::Config) -> bpv7::EidPatternMap<(), ()> {
         let mut m = bpv7::EidPatternMap::new();
         for s in config
@@ -60,3 +285,198 @@ impl Config {
         m
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::tests::{
+        dispatcher_config, new_test_dispatcher, test_store, TestBundles, TestDispatcher,
+        TestMetadata,
+    };
+
+    fn build_config(status_reports: bool, admin_endpoint: &str) -> ::config::Config {
+        ::config::Config::builder()
+            .set_override("administrative_endpoint", admin_endpoint)
+            .unwrap()
+            .set_override("status_reports", status_reports)
+            .unwrap()
+            .build()
+            .unwrap()
+    }
+
+    /* What the dispatcher does with a bundle carrying an unsupported block: the reason it was
+     * dropped, whether the block was reported, and whether the block was removed before forwarding */
+    async fn action(
+        policy: &str,
+        flags: bpv7::BlockFlags,
+    ) -> (Option<bpv7::StatusReportReasonCode>, bool, bool) {
+        let store = Arc::new(test_store(
+            Arc::new(TestMetadata::default()),
+            Arc::new(TestBundles::default()),
+        ));
+
+        // Bundles go onwards through one CLA, and reports back through another
+        let mut task_set = tokio::task::JoinSet::new();
+        let cancel_token = tokio_util::sync::CancellationToken::new();
+        let TestDispatcher {
+            dispatcher, clas, ..
+        } = new_test_dispatcher(
+            store,
+            &dispatcher_config()
+                .set_override("status_reports", true)
+                .unwrap()
+                .set_override("unsupported_blocks", policy)
+                .unwrap()
+                .build()
+                .unwrap(),
+            &[("ipn:3.*", None), ("ipn:2.*", None)],
+            &mut task_set,
+            cancel_token.clone(),
+        )
+        .await;
+        let mut events = dispatcher.subscribe_events();
+
+        let (bundle, data) = bpv7::Builder::new()
+            .flags(bpv7::BundleFlags {
+                receipt_report_requested: true,
+                ..Default::default()
+            })
+            .source("ipn:2.1".parse().unwrap())
+            .destination("ipn:3.1".parse().unwrap())
+            .report_to("ipn:2.1".parse().unwrap())
+            .lifetime(60_000)
+            .add_extension_block(bpv7::BlockType::Unrecognised(200))
+            .report_on_failure(flags.report_on_failure)
+            .delete_block_on_failure(flags.delete_block_on_failure)
+            .delete_bundle_on_failure(flags.delete_bundle_on_failure)
+            .data(vec![0x40])
+            .build()
+            .add_payload_block(b"Hello".to_vec())
+            .build();
+        dispatcher.receive_bundle(data.into(), None).await.unwrap();
+
+        let reason = tokio::time::timeout(std::time::Duration::from_secs(1), async {
+            loop {
+                match events.recv().await.unwrap() {
+                    DispatchEvent::Forwarded(id) if id == bundle.id => break None,
+                    DispatchEvent::Dropped(id, reason) if id == bundle.id => break Some(reason),
+                    _ => {}
+                }
+            }
+        })
+        .await
+        .expect("No dispatch decision");
+
+        let removed = reason.is_none() && {
+            let bpv7::ValidBundle::Valid(forwarded, _) =
+                bpv7::ValidBundle::parse(&clas[0].last_bundle().unwrap(), |_, _| Ok(None)).unwrap()
+            else {
+                panic!("Invalid forwarded bundle");
+            };
+            !forwarded
+                .blocks
+                .values()
+                .any(|block| block.block_type == bpv7::BlockType::Unrecognised(200))
+        };
+
+        // Reception is always reported, 'Block unsupported' is reported as well if the policy allows
+        for _ in 0..100 {
+            if clas[1].forwarded() > 0 {
+                break;
+            }
+            tokio::time::sleep(tokio::time::Duration::from_millis(10)).await;
+        }
+        tokio::time::sleep(tokio::time::Duration::from_millis(50)).await;
+        let reported = match clas[1].forwarded() {
+            1 => false,
+            2 => true,
+            n => panic!("{n} status reports sent"),
+        };
+
+        cancel_token.cancel();
+        while task_set.join_next().await.is_some() {}
+
+        (reason, reported, removed)
+    }
+
+    #[tokio::test]
+    async fn unsupported_blocks() {
+        let report = bpv7::BlockFlags {
+            report_on_failure: true,
+            ..Default::default()
+        };
+        let remove = bpv7::BlockFlags {
+            delete_block_on_failure: true,
+            ..Default::default()
+        };
+        let remove_and_report = bpv7::BlockFlags {
+            report_on_failure: true,
+            delete_block_on_failure: true,
+            ..Default::default()
+        };
+        let delete = bpv7::BlockFlags {
+            delete_bundle_on_failure: true,
+            report_on_failure: true,
+            ..Default::default()
+        };
+
+        for policy in ["report", "ignore", "drop"] {
+            // No flags: forward the bundle untouched
+            assert_eq!(
+                action(policy, bpv7::BlockFlags::default()).await,
+                (None, false, false)
+            );
+
+            // 'Discard block' removes the block, and the bundle is forwarded
+            assert_eq!(action(policy, remove).await, (None, false, true));
+
+            // 'Delete bundle' always drops the bundle
+            assert_eq!(
+                action(policy, delete).await,
+                (
+                    Some(bpv7::StatusReportReasonCode::BlockUnsupported),
+                    false,
+                    false
+                )
+            );
+        }
+
+        // The 'report' flag is handled according to policy
+        assert_eq!(action("report", report).await, (None, true, false));
+        assert_eq!(
+            action("report", remove_and_report).await,
+            (None, true, true)
+        );
+        assert_eq!(action("ignore", report).await, (None, false, false));
+        assert_eq!(
+            action("drop", remove_and_report).await,
+            (
+                Some(bpv7::StatusReportReasonCode::BlockUnsupported),
+                false,
+                false
+            )
+        );
+    }
+
+    #[test]
+    fn reload() {
+        let initial = build_config(false, "ipn:1.0");
+        let config = Config::new(
+            &initial,
+            utils::admin_endpoints::AdminEndpoints::init(&initial),
+        );
+        assert!(!config.status_reports());
+
+        // Non-structural changes are applied
+        config.reload(&build_config(true, "ipn:1.0")).unwrap();
+        assert!(config.status_reports());
+
+        // Node-id changes are rejected, but other changes still apply
+        let ReloadError(ignored) = config.reload(&build_config(false, "ipn:2.0")).unwrap_err();
+        assert_eq!(ignored, vec!["administrative_endpoint"]);
+        assert!(!config.status_reports());
+        assert!(config
+            .admin_endpoints
+            .is_admin_endpoint(&"ipn:1.0".parse().unwrap()));
+    }
+}
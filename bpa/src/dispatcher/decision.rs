@@ -0,0 +1,219 @@
+use super::*;
+use hardy_async::sync::metrics::Counter;
+
+const OUTCOMES: [&str; 5] = ["deliver", "forward", "wait", "reflect", "drop"];
+
+// Upper bounds of the dispatch latency buckets in milliseconds, the last bucket counts anything slower
+const LATENCY_BUCKETS_MS: [u64; 7] = [1, 10, 100, 1_000, 10_000, 60_000, 600_000];
+
+/* A histogram of the time from the reception of each bundle to its dispatch decision, per outcome.
+ * Operators read it through the bundle_sink service */
+#[derive(Default)]
+pub struct DispatchLatency([[Counter; LATENCY_BUCKETS_MS.len() + 1]; OUTCOMES.len()]);
+
+impl DispatchLatency {
+    fn add(&self, outcome: usize, latency: time::Duration) {
+        let ms = latency.whole_milliseconds().max(0) as u64;
+        let bucket = LATENCY_BUCKETS_MS
+            .iter()
+            .position(|bound| ms <= *bound)
+            .unwrap_or(LATENCY_BUCKETS_MS.len());
+        self.0[outcome][bucket].increment();
+    }
+
+    /* The count in each bucket for every outcome, with the upper bound of the bucket in milliseconds,
+     * or None for the last bucket */
+    pub fn buckets(&self) -> impl Iterator<Item = (&'static str, Vec<(Option<u64>, u64)>)> + '_ {
+        OUTCOMES.iter().zip(&self.0).map(|(outcome, counts)| {
+            (
+                *outcome,
+                counts
+                    .iter()
+                    .enumerate()
+                    .map(|(i, count)| (LATENCY_BUCKETS_MS.get(i).copied(), count.load()))
+                    .collect(),
+            )
+        })
+    }
+}
+
+/* Why a bundle went where it did.  Each decision is recorded as an event on the
+ * current span, so a trace of a bundle explains the route it took.
+ * The time from reception to the decision is added to the DispatchLatency histogram */
+#[derive(Debug)]
+pub(super) enum Decision<'a> {
+    Deliver,
+    Forward {
+        next_hop: &'a bpv7::Eid,
+        cla: u32,
+        priority: Option<u32>,
+    },
+    Wait(time::OffsetDateTime),
+    Reflect {
+        next_hop: &'a bpv7::Eid,
+    },
+    Drop(Option<bpv7::StatusReportReasonCode>),
+}
+
+impl Decision<'_> {
+    // An index into OUTCOMES
+    fn outcome(&self) -> usize {
+        match self {
+            Decision::Deliver => 0,
+            Decision::Forward { .. } => 1,
+            Decision::Wait(_) => 2,
+            Decision::Reflect { .. } => 3,
+            Decision::Drop(_) => 4,
+        }
+    }
+
+    pub(super) fn record(&self, bundle: &metadata::Bundle, latency: &DispatchLatency) {
+        let bundle_id = &bundle.bundle.id;
+        match self {
+            Decision::Deliver => {
+                debug!(bundle_id = ?bundle_id, action = "deliver", "Dispatch decision")
+            }
+            Decision::Forward {
+                next_hop,
+                cla,
+                priority,
+            } => debug!(
+                bundle_id = ?bundle_id,
+                action = "forward",
+                next_hop = %next_hop,
+                cla,
+                priority,
+                "Dispatch decision"
+            ),
+            Decision::Wait(until) => debug!(
+                bundle_id = ?bundle_id,
+                action = "wait",
+                until = %until,
+                "Dispatch decision"
+            ),
+            Decision::Reflect { next_hop } => debug!(
+                bundle_id = ?bundle_id,
+                action = "reflect",
+                next_hop = %next_hop,
+                "Dispatch decision"
+            ),
+            Decision::Drop(reason) => debug!(
+                bundle_id = ?bundle_id,
+                action = "drop",
+                reason = reason.map(tracing::field::debug),
+                "Dispatch decision"
+            ),
+        }
+
+        if let Some(received_at) = bundle.metadata.received_at {
+            latency.add(
+                self.outcome(),
+                time::OffsetDateTime::now_utc() - received_at,
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    // Captures the fields of every event as "name=value" strings
+    #[derive(Clone, Default)]
+    struct Capture(Arc<Mutex<Vec<Vec<String>>>>);
+
+    struct Fields<'a>(&'a mut Vec<String>);
+
+    impl tracing::field::Visit for Fields<'_> {
+        fn record_debug(&mut self, field: &tracing::field::Field, value: &dyn std::fmt::Debug) {
+            self.0.push(format!("{}={value:?}", field.name()));
+        }
+
+        fn record_str(&mut self, field: &tracing::field::Field, value: &str) {
+            self.0.push(format!("{}={value}", field.name()));
+        }
+    }
+
+    impl<S: tracing::Subscriber> tracing_subscriber::Layer<S> for Capture {
+        fn on_event(
+            &self,
+            event: &tracing::Event<'_>,
+            _ctx: tracing_subscriber::layer::Context<'_, S>,
+        ) {
+            let mut fields = Vec::new();
+            event.record(&mut Fields(&mut fields));
+            self.0.lock().unwrap().push(fields);
+        }
+    }
+
+    #[test]
+    fn events() {
+        let capture = Capture::default();
+        let subscriber = tracing_subscriber::registry().with(capture.clone());
+
+        let bundle = metadata::Bundle {
+            bundle: bpv7::Bundle {
+                id: bpv7::BundleId {
+                    source: "ipn:1.1".parse().unwrap(),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            metadata: Default::default(),
+        };
+        let bundle_id = &bundle.bundle.id;
+        let next_hop: bpv7::Eid = "ipn:2.0".parse().unwrap();
+        tracing::subscriber::with_default(subscriber, || {
+            Decision::Forward {
+                next_hop: &next_hop,
+                cla: 3,
+                priority: Some(100),
+            }
+            .record(&bundle, &DispatchLatency::default());
+            Decision::Drop(Some(
+                bpv7::StatusReportReasonCode::NoKnownRouteToDestinationFromHere,
+            ))
+            .record(&bundle, &DispatchLatency::default());
+        });
+
+        let events = capture.0.lock().unwrap();
+        assert_eq!(events.len(), 2);
+
+        let forwarded = &events[0];
+        assert!(forwarded.contains(&"action=forward".to_string()));
+        assert!(forwarded.contains(&"next_hop=ipn:2.0".to_string()));
+        assert!(forwarded.contains(&"cla=3".to_string()));
+        assert!(forwarded.contains(&"priority=100".to_string()));
+        assert!(forwarded.contains(&format!("bundle_id={bundle_id:?}")));
+
+        let dropped = &events[1];
+        assert!(dropped.contains(&"action=drop".to_string()));
+        assert!(dropped.contains(&"reason=NoKnownRouteToDestinationFromHere".to_string()));
+        assert!(!dropped.iter().any(|f| f.starts_with("next_hop=")));
+    }
+
+    #[test]
+    fn latency() {
+        // A bundle that was received 2 seconds ago
+        let delay = time::Duration::seconds(2);
+        let bundle = metadata::Bundle {
+            bundle: Default::default(),
+            metadata: metadata::Metadata {
+                received_at: Some(time::OffsetDateTime::now_utc() - delay),
+                ..Default::default()
+            },
+        };
+        let latency = DispatchLatency::default();
+        Decision::Deliver.record(&bundle, &latency);
+
+        // Only the deliver outcome has a sample, in the bucket between 1 and 10 seconds
+        for (outcome, buckets) in latency.buckets() {
+            for (bound, count) in buckets {
+                let expected = outcome == "deliver" && bound == Some(10_000);
+                assert_eq!(count, expected as u64, "{outcome} {bound:?}");
+            }
+        }
+    }
+}
@@ -0,0 +1,191 @@
+use super::*;
+use std::hash::{BuildHasher, RandomState};
+use utils::settings;
+
+// The chance of a new bundle being mistaken for a duplicate, while the filter is within its size
+const FALSE_POSITIVE_RATE: f64 = 1e-6;
+const DEFAULT_SIZE: usize = 100_000;
+
+struct Bloom(Box<[u64]>);
+
+impl Bloom {
+    fn new(bit_count: u64) -> Self {
+        Self(vec![0; bit_count.div_ceil(64) as usize].into())
+    }
+
+    fn contains(&self, positions: &[u64]) -> bool {
+        positions
+            .iter()
+            .all(|p| self.0[(p / 64) as usize] & (1 << (p % 64)) != 0)
+    }
+
+    fn insert(&mut self, positions: &[u64]) {
+        for p in positions {
+            self.0[(p / 64) as usize] |= 1 << (p % 64);
+        }
+    }
+}
+
+struct Generations {
+    current: Bloom,
+    previous: Bloom,
+    started: Option<time::OffsetDateTime>,
+}
+
+/* A probabilistic record of recently seen bundle ids, checked before the metadata store.
+ * Two generations of bloom filter are kept, and the oldest is discarded every `window`,
+ * so an id is remembered for between one and two windows.  `size` is the number of bundles
+ * expected per window: beyond it the false positive rate climbs, and new bundles are dropped */
+pub struct DedupFilter {
+    window: time::Duration,
+    bit_count: u64,
+    hashes: u64,
+    hashers: (RandomState, RandomState),
+    generations: std::sync::Mutex<Generations>,
+}
+
+impl DedupFilter {
+    pub fn new(config: &::config::Config) -> Option<Self> {
+        let window = settings::get_with_default::<u64, _>(config, "dedup_filter_window", 0)
+            .trace_expect("Invalid 'dedup_filter_window' value in configuration");
+        let size = settings::get_with_default(config, "dedup_filter_size", DEFAULT_SIZE)
+            .trace_expect("Invalid 'dedup_filter_size' value in configuration");
+        if window == 0 || size == 0 {
+            return None;
+        }
+
+        info!(
+            "Probabilistic duplicate detection enabled for {size} bundles every {window} seconds"
+        );
+        Some(Self::with_size(
+            time::Duration::seconds(window.min(i64::MAX as u64) as i64),
+            size,
+        ))
+    }
+
+    fn with_size(window: time::Duration, size: usize) -> Self {
+        // The optimal filter for `size` entries at FALSE_POSITIVE_RATE
+        let ln2 = std::f64::consts::LN_2;
+        let bit_count = (size as f64 * -FALSE_POSITIVE_RATE.ln() / (ln2 * ln2)).ceil() as u64;
+        let hashes = ((bit_count as f64 / size as f64) * ln2).round().max(1.0) as u64;
+
+        Self {
+            window,
+            bit_count,
+            hashes,
+            hashers: (RandomState::new(), RandomState::new()),
+            generations: std::sync::Mutex::new(Generations {
+                current: Bloom::new(bit_count),
+                previous: Bloom::new(bit_count),
+                started: None,
+            }),
+        }
+    }
+
+    // Double hashing, rather than `hashes` independent hash functions
+    fn positions(&self, bundle_id: &bpv7::BundleId) -> Vec<u64> {
+        let h1 = self.hashers.0.hash_one(bundle_id);
+        let h2 = self.hashers.1.hash_one(bundle_id) | 1;
+        (0..self.hashes)
+            .map(|i| h1.wrapping_add(i.wrapping_mul(h2)) % self.bit_count)
+            .collect()
+    }
+
+    // Lock the generations, discarding the oldest once a window has passed
+    fn lock(&self, now: time::OffsetDateTime) -> std::sync::MutexGuard<'_, Generations> {
+        let mut generations = self
+            .generations
+            .lock()
+            .trace_expect("Failed to lock dedup filter mutex");
+
+        match generations.started {
+            Some(started) if now - started >= self.window.saturating_mul(2) => {
+                generations.current = Bloom::new(self.bit_count);
+                generations.previous = Bloom::new(self.bit_count);
+                generations.started = Some(now);
+            }
+            Some(started) if now - started >= self.window => {
+                generations.previous =
+                    std::mem::replace(&mut generations.current, Bloom::new(self.bit_count));
+                generations.started = Some(now);
+            }
+            Some(_) => {}
+            None => generations.started = Some(now),
+        }
+        generations
+    }
+
+    // Returns true if `bundle_id` has probably been seen recently
+    pub fn contains(&self, bundle_id: &bpv7::BundleId, now: time::OffsetDateTime) -> bool {
+        let positions = self.positions(bundle_id);
+        let generations = self.lock(now);
+        generations.current.contains(&positions) || generations.previous.contains(&positions)
+    }
+
+    /* Record `bundle_id` as seen.  Only accepted bundles should be recorded,
+     * otherwise the retransmission of a bundle that failed ingress is dropped */
+    pub fn insert(&self, bundle_id: &bpv7::BundleId, now: time::OffsetDateTime) {
+        let positions = self.positions(bundle_id);
+        self.lock(now).current.insert(&positions);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bundle_id(sequence_number: u64) -> bpv7::BundleId {
+        bpv7::BundleId {
+            source: "ipn:2.1".parse().unwrap(),
+            timestamp: bpv7::CreationTimestamp {
+                creation_time: None,
+                sequence_number,
+            },
+            fragment_info: None,
+        }
+    }
+
+    #[test]
+    fn duplicates() {
+        let window = time::Duration::seconds(10);
+        let filter = DedupFilter::with_size(window, 1000);
+        let now = time::OffsetDateTime::now_utc();
+
+        for i in 0..1000 {
+            assert!(!filter.contains(&bundle_id(i), now));
+            filter.insert(&bundle_id(i), now);
+        }
+        for i in 0..1000 {
+            assert!(filter.contains(&bundle_id(i), now));
+        }
+
+        // Still remembered in the next window, forgotten in the one after
+        assert!(filter.contains(&bundle_id(1), now + window));
+        assert!(!filter.contains(&bundle_id(2), now + window * 3));
+        filter.insert(&bundle_id(2), now + window * 3);
+        assert!(filter.contains(&bundle_id(2), now + window * 3));
+    }
+
+    #[test]
+    fn long_window() {
+        let filter = DedupFilter::with_size(time::Duration::seconds(i64::MAX), 10);
+        let now = time::OffsetDateTime::now_utc();
+        filter.insert(&bundle_id(1), now);
+        assert!(filter.contains(&bundle_id(1), now + time::Duration::days(365)));
+    }
+
+    #[test]
+    fn false_positives() {
+        let filter = DedupFilter::with_size(time::Duration::seconds(10), 10_000);
+        let now = time::OffsetDateTime::now_utc();
+        for i in 0..10_000 {
+            filter.insert(&bundle_id(i), now);
+        }
+
+        // At 1e-6, 100,000 new ids should see no false positives, allow a few
+        let false_positives = (10_000..110_000)
+            .filter(|i| filter.contains(&bundle_id(*i), now))
+            .count();
+        assert!(false_positives <= 5, "{false_positives} false positives");
+    }
+}
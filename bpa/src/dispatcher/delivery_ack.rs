@@ -0,0 +1,215 @@
+use super::*;
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum Acknowledged {
+    // The application has not collected the bundle, or has already acknowledged it
+    Unexpected,
+    // The application has processed the bundle
+    Delivered,
+    // Processing failed, the application should collect the bundle again
+    Redeliver,
+    // Processing has failed too many times
+    Abandoned,
+}
+
+#[derive(Default)]
+struct Attempts {
+    failed: u32,
+    collected: bool,
+}
+
+/* Tracks bundles collected by applications that acknowledge them once processed.
+ * Until then the bundle stays pending collection, so an application that fails, or crashes,
+ * while processing a bundle is given it again.  Attempts are keyed by the application's EID,
+ * so they survive an application re-registering after a crash.  Like fan-out, attempts are not persisted */
+#[derive(Default)]
+pub(super) struct DeliveryAcks {
+    pending: std::sync::Mutex<HashMap<bpv7::BundleId, HashMap<bpv7::Eid, Attempts>>>,
+}
+
+impl DeliveryAcks {
+    /* A bundle collected again before it was acknowledged was lost by the application, most likely
+     * in a crash, which counts as a failed attempt.  `max_attempts` of 0 is unlimited.
+     * Returns false if the application has now failed too many times */
+    pub(super) fn collected(
+        &self,
+        bundle_id: &bpv7::BundleId,
+        eid: &bpv7::Eid,
+        max_attempts: u32,
+    ) -> bool {
+        let mut pending = self
+            .pending
+            .lock()
+            .trace_expect("Failed to lock delivery ack mutex");
+        let apps = pending.entry(bundle_id.clone()).or_default();
+        let attempts = apps.entry(eid.clone()).or_default();
+        if attempts.collected {
+            attempts.failed += 1;
+            if max_attempts != 0 && attempts.failed >= max_attempts {
+                apps.remove(eid);
+                if apps.is_empty() {
+                    pending.remove(bundle_id);
+                }
+                return false;
+            }
+        }
+        attempts.collected = true;
+        true
+    }
+
+    // `max_attempts` of 0 is unlimited
+    pub(super) fn acknowledge(
+        &self,
+        bundle_id: &bpv7::BundleId,
+        eid: &bpv7::Eid,
+        processed: bool,
+        max_attempts: u32,
+    ) -> Acknowledged {
+        let mut pending = self
+            .pending
+            .lock()
+            .trace_expect("Failed to lock delivery ack mutex");
+        let Some(apps) = pending.get_mut(bundle_id) else {
+            return Acknowledged::Unexpected;
+        };
+        let Some(attempts) = apps.get_mut(eid).filter(|a| a.collected) else {
+            return Acknowledged::Unexpected;
+        };
+
+        let acknowledged = if processed {
+            Acknowledged::Delivered
+        } else {
+            attempts.collected = false;
+            attempts.failed += 1;
+            if max_attempts != 0 && attempts.failed >= max_attempts {
+                Acknowledged::Abandoned
+            } else {
+                return Acknowledged::Redeliver;
+            }
+        };
+
+        apps.remove(eid);
+        if apps.is_empty() {
+            pending.remove(bundle_id);
+        }
+        acknowledged
+    }
+
+    // The bundle has gone, so nothing more can be acknowledged
+    pub(super) fn forget_bundle(&self, bundle_id: &bpv7::BundleId) {
+        self.pending
+            .lock()
+            .trace_expect("Failed to lock delivery ack mutex")
+            .remove(bundle_id);
+    }
+
+    // The application has unregistered, and will not acknowledge what it has collected
+    pub(super) fn forget_application(&self, eid: &bpv7::Eid) {
+        self.pending
+            .lock()
+            .trace_expect("Failed to lock delivery ack mutex")
+            .retain(|_, apps| {
+                apps.remove(eid);
+                !apps.is_empty()
+            });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn attempts() {
+        let acks = DeliveryAcks::default();
+        let bundle_id = bpv7::BundleId {
+            source: "ipn:2.1".parse().unwrap(),
+            ..Default::default()
+        };
+        let eid = "ipn:1.5".parse().unwrap();
+
+        // Nothing to acknowledge until the bundle is collected
+        assert_eq!(
+            acks.acknowledge(&bundle_id, &eid, true, 2),
+            Acknowledged::Unexpected
+        );
+
+        assert!(acks.collected(&bundle_id, &eid, 2));
+        assert_eq!(
+            acks.acknowledge(&bundle_id, &eid, false, 2),
+            Acknowledged::Redeliver
+        );
+        assert_eq!(
+            acks.acknowledge(&bundle_id, &eid, false, 2),
+            Acknowledged::Unexpected
+        );
+        assert!(acks.collected(&bundle_id, &eid, 2));
+        assert_eq!(
+            acks.acknowledge(&bundle_id, &eid, false, 2),
+            Acknowledged::Abandoned
+        );
+
+        assert!(acks.collected(&bundle_id, &eid, 2));
+        assert_eq!(
+            acks.acknowledge(&bundle_id, &eid, true, 2),
+            Acknowledged::Delivered
+        );
+        assert_eq!(
+            acks.acknowledge(&bundle_id, &eid, true, 2),
+            Acknowledged::Unexpected
+        );
+        assert!(acks.pending.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn crash() {
+        let acks = DeliveryAcks::default();
+        let bundle_id = bpv7::BundleId {
+            source: "ipn:2.1".parse().unwrap(),
+            ..Default::default()
+        };
+        let eid = "ipn:1.5".parse().unwrap();
+
+        // Collecting again without acknowledging counts as a failure
+        assert!(acks.collected(&bundle_id, &eid, 3));
+        assert!(acks.collected(&bundle_id, &eid, 3));
+        assert_eq!(
+            acks.acknowledge(&bundle_id, &eid, false, 3),
+            Acknowledged::Abandoned
+        );
+
+        assert!(acks.collected(&bundle_id, &eid, 2));
+        assert!(!acks.collected(&bundle_id, &eid, 2));
+        assert!(acks.pending.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn forget() {
+        let acks = DeliveryAcks::default();
+        let bundle_ids = [1, 2].map(|seq| bpv7::BundleId {
+            source: format!("ipn:2.{seq}").parse().unwrap(),
+            ..Default::default()
+        });
+        let eids: [bpv7::Eid; 2] = ["ipn:1.5".parse().unwrap(), "ipn:1.6".parse().unwrap()];
+        for bundle_id in &bundle_ids {
+            for eid in &eids {
+                acks.collected(bundle_id, eid, 0);
+            }
+        }
+
+        // Nothing is left once every application has gone, or the bundles have gone
+        acks.forget_application(&eids[0]);
+        assert_eq!(
+            acks.acknowledge(&bundle_ids[0], &eids[0], true, 0),
+            Acknowledged::Unexpected
+        );
+        acks.forget_bundle(&bundle_ids[0]);
+        assert_eq!(
+            acks.acknowledge(&bundle_ids[0], &eids[1], true, 0),
+            Acknowledged::Unexpected
+        );
+        acks.forget_application(&eids[1]);
+        assert!(acks.pending.lock().unwrap().is_empty());
+    }
+}
@@ -14,14 +14,35 @@ impl Dispatcher {
         Ok(())
     }
 
+    /* Re-dispatch stored bundles for `filter` received within `window`, for recovering
+     * from downstream data loss.  Reception is not reported again */
+    #[instrument(skip(self))]
+    pub async fn replay(
+        &self,
+        filter: &bpv7::EidPattern,
+        window: std::ops::Range<time::OffsetDateTime>,
+    ) -> Result<usize, Error> {
+        let (tx, mut rx) = tokio::sync::mpsc::channel::<metadata::Bundle>(16);
+        let dispatch = async {
+            while let Some(bundle) = rx.recv().await {
+                self.dispatch_bundle(bundle).await?;
+            }
+            Ok::<_, Error>(())
+        };
+
+        let (replayed, dispatched) = tokio::join!(self.store.replay(filter, window, tx), dispatch);
+        dispatched?;
+        let replayed = replayed?;
+        info!("Replayed {replayed} bundles");
+        Ok(replayed)
+    }
+
     #[instrument(skip(self))]
     async fn process_bundle(&self, mut bundle: metadata::Bundle) -> Result<(), Error> {
         /* This is a classic looped state machine */
         loop {
             let result = match &bundle.metadata.status {
-                metadata::BundleStatus::IngressPending
-                | metadata::BundleStatus::ForwardPending
-                | metadata::BundleStatus::Tombstone(_) => {
+                metadata::BundleStatus::IngressPending | metadata::BundleStatus::Tombstone(_) => {
                     unreachable!()
                 }
                 metadata::BundleStatus::DispatchPending => {
@@ -43,6 +64,7 @@ impl Dispatcher {
                         } else {
                             // The bundle is ready for collection
                             trace!("Bundle is ready for local delivery");
+                            Decision::Deliver.record(&bundle, &self.dispatch_latency);
                             self.store
                                 .set_status(&mut bundle, metadata::BundleStatus::CollectionPending)
                                 .await
@@ -57,9 +79,38 @@ impl Dispatcher {
                     // Wait for other fragments to arrive
                     DispatchResult::Done
                 }
+                metadata::BundleStatus::ForwardPending => {
+                    // Local delivery is complete, carry on to the other members of the endpoint
+                    self.forward_bundle(&mut bundle).await?
+                }
                 metadata::BundleStatus::CollectionPending => {
-                    // Check if we have a local service registered
-                    if let Some(endpoint) = self
+                    /* Every member of a non-singleton endpoint must collect the bundle,
+                     * whatever the configured local delivery */
+                    if self.config.local_delivery == LocalDelivery::AllMatch
+                        || !bundle.bundle.destination.is_singleton()
+                    {
+                        let endpoints = self
+                            .app_registry
+                            .find_all_by_eid(&bundle.bundle.destination)
+                            .await;
+                        if endpoints.is_empty() && self.forwards_onward(&bundle) {
+                            // No local members to wait for, carry on to the others
+                            self.store
+                                .set_status(&mut bundle, metadata::BundleStatus::ForwardPending)
+                                .await?;
+                            continue;
+                        }
+                        if endpoints.len() > 1 {
+                            self.fan_out.start(
+                                &bundle.bundle.id,
+                                endpoints.iter().map(|e| e.token().to_string()),
+                            );
+                        }
+                        for endpoint in endpoints {
+                            trace!("Notifying application that bundle is ready for collection");
+                            endpoint.collection_notify(&bundle.bundle.id).await;
+                        }
+                    } else if let Some(endpoint) = self
                         .app_registry
                         .find_by_eid(&bundle.bundle.destination)
                         .await
@@ -87,6 +138,29 @@ impl Dispatcher {
         }
     }
 
+    /* Processing failed, most likely because storage is failing, so park the bundle as Waiting
+     * for the store to wake it again once the next wait sample interval has passed, rather than
+     * leaving it stranded until restart.  If even that fails, recovery is left to the restart */
+    pub(super) async fn requeue_bundle(&self, bundle_id: &bpv7::BundleId) {
+        let until =
+            self.clock.now() + time::Duration::new(self.config.wait_sample_interval() as i64, 0);
+        let r: Result<(), Error> = async {
+            let Some(mut bundle) = self.store.load(bundle_id).await? else {
+                return Ok(());
+            };
+            if matches!(bundle.metadata.status, metadata::BundleStatus::Tombstone(_)) {
+                return Ok(());
+            }
+            self.store
+                .set_status(&mut bundle, metadata::BundleStatus::Waiting(until))
+                .await
+        }
+        .await;
+        if let Err(e) = r {
+            error!("Failed to requeue bundle, it will be recovered on restart: {e}");
+        }
+    }
+
     pub(super) async fn bundle_wait(
         &self,
         bundle: &mut metadata::Bundle,
@@ -100,8 +174,8 @@ impl Dispatcher {
             )));
         }
 
-        let wait = until - time::OffsetDateTime::now_utc();
-        if wait > time::Duration::new(self.config.wait_sample_interval as i64, 0) {
+        let wait = until - self.clock.now();
+        if wait > time::Duration::new(self.config.wait_sample_interval() as i64, 0) {
             // Nothing to do now, it will be picked up later
             trace!("Bundle will wait offline until: {until}");
             return self
@@ -134,8 +208,8 @@ impl Dispatcher {
                 bpv7::StatusReportReasonCode::NoTimelyContactWithNextNodeOnRoute,
             )));
         }
-        let wait = until - time::OffsetDateTime::now_utc();
-        if wait > time::Duration::new(self.config.wait_sample_interval as i64, 0) {
+        let wait = until - self.clock.now();
+        if wait > time::Duration::new(self.config.wait_sample_interval() as i64, 0) {
             // Nothing to do now, it will be picked up later
             return Ok(DispatchResult::Done);
         }
@@ -161,8 +235,8 @@ impl Dispatcher {
         bundle: &mut metadata::Bundle,
     ) -> Result<DispatchResult, Error> {
         // Check if it's worth us waiting inline
-        let wait = until - time::OffsetDateTime::now_utc();
-        if wait > time::Duration::new(self.config.wait_sample_interval as i64, 0) {
+        let wait = until - self.clock.now();
+        if wait > time::Duration::new(self.config.wait_sample_interval() as i64, 0) {
             // Nothing to do now, it will be picked up later
             trace!("Bundle will wait offline until: {until}");
             return Ok(DispatchResult::Done);
@@ -184,7 +258,8 @@ impl Dispatcher {
             }
             Some(status) => {
                 if status == bundle.metadata.status {
-                    // Clear the wait state
+                    // No acknowledgement arrived, clear the wait state and forward again
+                    trace!("Forwarding acknowledgement timed out, retransmitting");
                     self.store
                         .set_status(bundle, metadata::BundleStatus::DispatchPending)
                         .await?;
@@ -225,7 +300,11 @@ pub(super) async fn dispatch_task(
                 let bundle = bundle.trace_expect("Dispatcher channel unexpectedly closed");
 
                 task_set.spawn(async move {
-                    dispatcher.process_bundle(bundle).await.trace_expect("Failed to dispatch bundle");
+                    let bundle_id = bundle.bundle.id.clone();
+                    if let Err(e) = dispatcher.process_bundle(bundle).await {
+                        error!("Failed to dispatch bundle: {e}");
+                        dispatcher.requeue_bundle(&bundle_id).await;
+                    }
                 });
             },
             Some(r) = task_set.join_next(), if !task_set.is_empty() => {
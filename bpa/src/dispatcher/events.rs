@@ -0,0 +1,109 @@
+use super::*;
+use tokio::sync::broadcast;
+
+// How many events a subscriber can fall behind before it starts missing them
+const EVENT_CAPACITY: usize = 256;
+
+// What happened to a bundle, for observers outside of the dispatcher
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DispatchEvent {
+    Received(bpv7::BundleId),
+    Forwarded(bpv7::BundleId),
+    Delivered(bpv7::BundleId),
+    Dropped(bpv7::BundleId, bpv7::StatusReportReasonCode),
+    Expired(bpv7::BundleId),
+    // An administrative record was received at an administrative endpoint, and consumed
+    AdminRecord(bpv7::BundleId, bpv7::AdminRecordType),
+}
+
+pub struct Events {
+    tx: broadcast::Sender<DispatchEvent>,
+}
+
+impl Default for Events {
+    fn default() -> Self {
+        Self {
+            tx: broadcast::channel(EVENT_CAPACITY).0,
+        }
+    }
+}
+
+impl Events {
+    pub fn subscribe(&self) -> EventReceiver {
+        EventReceiver {
+            rx: self.tx.subscribe(),
+            lagged: 0,
+        }
+    }
+
+    // Only build the event if someone is listening
+    pub fn emit(&self, f: impl FnOnce() -> DispatchEvent) {
+        if self.tx.receiver_count() != 0 {
+            // The last subscriber may have gone in the meantime, which is fine
+            _ = self.tx.send(f());
+        }
+    }
+}
+
+/* A subscription to dispatch events.  Dispatch never waits for a slow subscriber,
+ * instead the subscriber misses the oldest events, and lagged() counts them */
+pub struct EventReceiver {
+    rx: broadcast::Receiver<DispatchEvent>,
+    lagged: u64,
+}
+
+impl EventReceiver {
+    // Returns None once the dispatcher has gone
+    pub async fn recv(&mut self) -> Option<DispatchEvent> {
+        loop {
+            match self.rx.recv().await {
+                Ok(event) => return Some(event),
+                Err(broadcast::error::RecvError::Lagged(missed)) => {
+                    debug!("Event subscriber missed {missed} events");
+                    self.lagged = self.lagged.saturating_add(missed);
+                }
+                Err(broadcast::error::RecvError::Closed) => return None,
+            }
+        }
+    }
+
+    pub fn lagged(&self) -> u64 {
+        self.lagged
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn lagged() {
+        let events = Events::default();
+        let bundle_id = bpv7::BundleId {
+            source: "ipn:2.1".parse().unwrap(),
+            ..Default::default()
+        };
+
+        // Nobody is listening
+        events.emit(|| panic!("Event built without a subscriber"));
+
+        let mut rx = events.subscribe();
+        for _ in 0..EVENT_CAPACITY + 10 {
+            events.emit(|| DispatchEvent::Received(bundle_id.clone()));
+        }
+        events.emit(|| DispatchEvent::Expired(bundle_id.clone()));
+
+        // The oldest events were lost, but the rest arrive in order
+        for _ in 0..EVENT_CAPACITY - 1 {
+            assert_eq!(
+                rx.recv().await,
+                Some(DispatchEvent::Received(bundle_id.clone()))
+            );
+        }
+        assert_eq!(rx.lagged(), 11);
+        assert_eq!(rx.recv().await, Some(DispatchEvent::Expired(bundle_id)));
+
+        drop(events);
+        assert_eq!(rx.recv().await, None);
+    }
+}
@@ -0,0 +1,269 @@
+use super::*;
+use std::collections::{HashMap, HashSet};
+
+// Which applications a bundle is delivered to when several register for its destination
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum LocalDelivery {
+    #[default]
+    FirstMatch,
+    AllMatch,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum Collection {
+    // The bundle was not fanned out, the collector has it
+    Sole,
+    // Other applications have yet to collect the bundle
+    Copy,
+    // The final application has collected the bundle
+    Last,
+    // The application has already collected the bundle
+    Repeated,
+}
+
+/* Tracks which applications have yet to collect a bundle delivered to several of them.
+ * The bundle is only reported as delivered, and dropped, once every application has collected it.
+ * Pending collections are not persisted, so after a restart each application may be notified again */
+#[derive(Default)]
+pub(super) struct FanOut {
+    pending: std::sync::Mutex<HashMap<bpv7::BundleId, HashSet<String>>>,
+}
+
+impl FanOut {
+    pub(super) fn start(
+        &self,
+        bundle_id: &bpv7::BundleId,
+        tokens: impl IntoIterator<Item = String>,
+    ) {
+        self.pending
+            .lock()
+            .trace_expect("Failed to lock fan-out mutex")
+            .entry(bundle_id.clone())
+            .or_insert_with(|| tokens.into_iter().collect());
+    }
+
+    // Has the application yet to collect the bundle, without collecting it
+    pub(super) fn is_pending(&self, bundle_id: &bpv7::BundleId, token: &str) -> bool {
+        self.pending
+            .lock()
+            .trace_expect("Failed to lock fan-out mutex")
+            .get(bundle_id)
+            .is_none_or(|tokens| tokens.contains(token))
+    }
+
+    pub(super) fn collect(&self, bundle_id: &bpv7::BundleId, token: &str) -> Collection {
+        let mut pending = self
+            .pending
+            .lock()
+            .trace_expect("Failed to lock fan-out mutex");
+        let Some(tokens) = pending.get_mut(bundle_id) else {
+            return Collection::Sole;
+        };
+        if !tokens.remove(token) {
+            return Collection::Repeated;
+        }
+        if !tokens.is_empty() {
+            return Collection::Copy;
+        }
+        pending.remove(bundle_id);
+        Collection::Last
+    }
+
+    // The application will never collect, returns the bundles no other application has yet to collect
+    pub(super) fn forget_application(&self, token: &str) -> Vec<bpv7::BundleId> {
+        let mut finished = Vec::new();
+        self.pending
+            .lock()
+            .trace_expect("Failed to lock fan-out mutex")
+            .retain(|bundle_id, tokens| {
+                if tokens.remove(token) && tokens.is_empty() {
+                    finished.push(bundle_id.clone());
+                    false
+                } else {
+                    true
+                }
+            });
+        finished
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::tests::{
+        dispatcher_config, new_test_dispatcher, test_store, TestBundles, TestDispatcher,
+        TestMetadata,
+    };
+
+    #[test]
+    fn all_collect() {
+        let fan_out = FanOut::default();
+        let bundle_id = bpv7::BundleId {
+            source: "ipn:2.1".parse().unwrap(),
+            ..Default::default()
+        };
+
+        // Bundles that were not fanned out are collected once
+        assert_eq!(fan_out.collect(&bundle_id, "a"), Collection::Sole);
+
+        // Both applications receive the bundle, and the last one finishes it
+        fan_out.start(&bundle_id, ["a".to_string(), "b".to_string()]);
+        assert_eq!(fan_out.collect(&bundle_id, "b"), Collection::Copy);
+        assert!(!fan_out.is_pending(&bundle_id, "b"));
+        assert!(fan_out.is_pending(&bundle_id, "a"));
+        assert_eq!(fan_out.collect(&bundle_id, "b"), Collection::Repeated);
+        assert_eq!(fan_out.collect(&bundle_id, "a"), Collection::Last);
+        assert_eq!(fan_out.collect(&bundle_id, "a"), Collection::Sole);
+
+        // Re-dispatching a fanned out bundle keeps the outstanding collections
+        fan_out.start(&bundle_id, ["a".to_string(), "b".to_string()]);
+        assert_eq!(fan_out.collect(&bundle_id, "a"), Collection::Copy);
+        fan_out.start(&bundle_id, ["a".to_string(), "b".to_string()]);
+        assert_eq!(fan_out.collect(&bundle_id, "a"), Collection::Repeated);
+        assert_eq!(fan_out.collect(&bundle_id, "b"), Collection::Last);
+    }
+
+    #[test]
+    fn forget_application() {
+        let fan_out = FanOut::default();
+        let bundle_id = |seq| bpv7::BundleId {
+            source: "ipn:2.1".parse().unwrap(),
+            timestamp: bpv7::CreationTimestamp {
+                creation_time: None,
+                sequence_number: seq,
+            },
+            ..Default::default()
+        };
+
+        fan_out.start(&bundle_id(1), ["a".to_string(), "b".to_string()]);
+        fan_out.start(&bundle_id(2), ["a".to_string(), "b".to_string()]);
+        assert_eq!(fan_out.collect(&bundle_id(1), "a"), Collection::Copy);
+
+        // Only the bundle the others have collected is finished
+        assert_eq!(fan_out.forget_application("b"), vec![bundle_id(1)]);
+        assert_eq!(fan_out.collect(&bundle_id(1), "b"), Collection::Sole);
+        assert!(!fan_out.is_pending(&bundle_id(2), "b"));
+        assert_eq!(fan_out.collect(&bundle_id(2), "a"), Collection::Last);
+    }
+
+    #[tokio::test]
+    async fn all_match() {
+        let store = Arc::new(test_store(
+            Arc::new(TestMetadata::default()),
+            Arc::new(TestBundles::default()),
+        ));
+
+        // Every application registered for the destination receives the bundle
+        let mut task_set = tokio::task::JoinSet::new();
+        let cancel_token = tokio_util::sync::CancellationToken::new();
+        let TestDispatcher {
+            dispatcher,
+            app_registry,
+            ..
+        } = new_test_dispatcher(
+            store.clone(),
+            &dispatcher_config()
+                .set_default("local_delivery", "all-match")
+                .unwrap()
+                .build()
+                .unwrap(),
+            &[],
+            &mut task_set,
+            cancel_token.clone(),
+        )
+        .await;
+        let mut tokens = Vec::new();
+        for ident in ["a", "b"] {
+            tokens.push(
+                app_registry
+                    .register(hardy_proto::application::RegisterApplicationRequest {
+                        endpoint: Some(
+                            hardy_proto::application::register_application_request::Endpoint::EidPattern(
+                                "ipn:1.5".to_string(),
+                            ),
+                        ),
+                        ident: ident.to_string(),
+                        grpc_address: None,
+                        max_concurrent_notifications: None,
+                    })
+                    .await
+                    .unwrap()
+                    .token,
+            );
+        }
+
+        let receive = |seq: u8| {
+            let (bundle, data) = bpv7::Builder::new()
+                .source("ipn:2.1".parse().unwrap())
+                .destination("ipn:1.5".parse().unwrap())
+                .lifetime(60_000)
+                .add_payload_block(vec![seq])
+                .build();
+            let dispatcher = dispatcher.clone();
+            async move {
+                dispatcher.receive_bundle(data.into(), None).await.unwrap();
+                bundle.id
+            }
+        };
+        let collect = |token: &str, bundle_id: &bpv7::BundleId| {
+            let dispatcher = dispatcher.clone();
+            let token = token.to_string();
+            let bundle_id = bundle_id.to_key();
+            async move {
+                for _ in 0..100 {
+                    if let Some(response) = dispatcher
+                        .collect("ipn:1.5".parse().unwrap(), &token, bundle_id.clone(), false)
+                        .await
+                        .unwrap()
+                    {
+                        return Some(response);
+                    }
+                    tokio::time::sleep(tokio::time::Duration::from_millis(10)).await;
+                }
+                None
+            }
+        };
+        let is_delivered = |bundle_id: &bpv7::BundleId| {
+            let store = store.clone();
+            let bundle_id = bundle_id.clone();
+            async move {
+                matches!(
+                    store.check_status(&bundle_id).await.unwrap(),
+                    Some(metadata::BundleStatus::Tombstone(_))
+                )
+            }
+        };
+
+        // The bundle is only finished once both applications have collected it
+        let first = receive(1).await;
+        assert_eq!(
+            collect(&tokens[0], &first).await.unwrap().data.as_ref(),
+            [1]
+        );
+        assert!(!is_delivered(&first).await);
+        assert_eq!(
+            collect(&tokens[1], &first).await.unwrap().data.as_ref(),
+            [1]
+        );
+        assert!(is_delivered(&first).await);
+
+        // An application that unregisters without collecting no longer holds the bundle up
+        let second = receive(2).await;
+        assert_eq!(
+            collect(&tokens[0], &second).await.unwrap().data.as_ref(),
+            [2]
+        );
+        assert!(!is_delivered(&second).await);
+        dispatcher
+            .unregister_application(hardy_proto::application::UnregisterApplicationRequest {
+                token: tokens[1].clone(),
+            })
+            .await
+            .unwrap();
+        assert!(is_delivered(&second).await);
+
+        cancel_token.cancel();
+        while task_set.join_next().await.is_some() {}
+    }
+}
@@ -1,4 +1,37 @@
 use super::*;
+use source_route::SourceRoute;
+
+// The outcome of offering a bundle to the CLAs towards a next hop
+enum Offered {
+    Done(DispatchResult),
+    Congested(time::OffsetDateTime),
+    Refused,
+}
+
+/* Returning a bundle to this node would loop it through the dispatcher, and returning one that has
+ * no hops left is pointless as the next node will drop it.  Returns the reason to drop it instead */
+fn reflect_loop(
+    admin_endpoints: &utils::admin_endpoints::AdminEndpoints,
+    bundle: &bpv7::Bundle,
+    next_hop: &bpv7::Eid,
+) -> Option<bpv7::StatusReportReasonCode> {
+    if admin_endpoints.is_local_service(next_hop) {
+        trace!("Bundle would be returned to this node");
+        return Some(bpv7::StatusReportReasonCode::NoKnownRouteToDestinationFromHere);
+    }
+
+    if let Some(hop_info) = &bundle.hop_count {
+        if hop_info.count.saturating_add(1) > hop_info.limit {
+            trace!(
+                "Returning bundle would exceed hop-limit {}/{}",
+                hop_info.count,
+                hop_info.limit
+            );
+            return Some(bpv7::StatusReportReasonCode::HopLimitExceeded);
+        }
+    }
+    None
+}
 
 impl Dispatcher {
     pub(super) async fn forward_bundle(
@@ -10,9 +43,9 @@ impl Dispatcher {
              * As we have decided that the bundle is not for a local service, we cannot deliver.
              * Therefore, we respond with a Destination endpoint ID unavailable report */
             trace!("Bundle should be forwarded, but forwarding is disabled");
-            return Ok(DispatchResult::Drop(Some(
-                bpv7::StatusReportReasonCode::DestinationEndpointIDUnavailable,
-            )));
+            let reason = Some(bpv7::StatusReportReasonCode::DestinationEndpointIDUnavailable);
+            Decision::Drop(reason).record(bundle, &self.dispatch_latency);
+            return Ok(DispatchResult::Drop(reason));
         };
 
         // TODO: Pluggable Egress filters!
@@ -21,88 +54,47 @@ impl Dispatcher {
          * But it might be rebooting or jammed, so we keep retrying for a "reasonable" amount of time */
         let mut previous = false;
         let mut retries = 0;
-        let mut destination = &bundle.bundle.destination;
+        let mut destination = bundle.bundle.destination.clone();
 
         loop {
             // Check bundle expiry
-            if bundle.has_expired() {
+            if bundle.has_expired_at(self.clock.now()) {
                 trace!("Bundle lifetime has expired");
-                return Ok(DispatchResult::Drop(Some(
-                    bpv7::StatusReportReasonCode::LifetimeExpired,
-                )));
+                let reason = Some(bpv7::StatusReportReasonCode::LifetimeExpired);
+                Decision::Drop(reason).record(bundle, &self.dispatch_latency);
+                return Ok(DispatchResult::Drop(reason));
             }
 
-            // Lookup/Perform actions
-            let action = match fib.find(destination).await {
+            // Lookup/Perform actions, local policy for the destination comes before any path
+            let action = match fib.find(&destination).await {
                 Err(reason) => {
                     trace!("Bundle is black-holed");
+                    Decision::Drop(reason).record(bundle, &self.dispatch_latency);
                     return Ok(DispatchResult::Drop(reason));
                 }
-                Ok(fib::ForwardAction {
-                    clas,
-                    until: Some(until),
-                }) if clas.is_empty() => {
-                    return self.bundle_wait(bundle, until).await;
-                }
                 Ok(action) => action,
             };
 
-            let mut congestion_wait = None;
-
-            // For each CLA
-            for endpoint in &action.clas {
-                // Find the named CLA
-                if let Some(e) = self.cla_registry.find(endpoint.handle).await {
-                    // Get bundle data from store, now we know we need it!
-                    let Some(source_data) = self.load_data(bundle).await? else {
-                        // Bundle data was deleted sometime during processing
-                        return Ok(DispatchResult::Done);
-                    };
-
-                    // Increment Hop Count, etc...
-                    let data = self.update_extension_blocks(bundle, source_data);
-
-                    match e.forward_bundle(destination, data.into()).await {
-                        Ok(cla_registry::ForwardBundleResult::Sent) => {
-                            // We have successfully forwarded!
-                            return self
-                                .report_bundle_forwarded(bundle)
-                                .await
-                                .map(|_| DispatchResult::Drop(None));
-                        }
-                        Ok(cla_registry::ForwardBundleResult::Pending(handle, until)) => {
-                            // CLA will report successful forwarding
-                            // Don't wait longer than expiry
-                            let until = until.unwrap_or_else(|| {
-                                warn!("CLA endpoint has not provided a suitable AckPending delay, defaulting to 1 minute");
-                                time::OffsetDateTime::now_utc() + time::Duration::minutes(1)
-                            }).min(bundle.expiry());
-
-                            // Set the bundle status to 'Forward Acknowledgement Pending' and re-dispatch
-                            return self
-                                .store
-                                .set_status(
-                                    bundle,
-                                    metadata::BundleStatus::ForwardAckPending(handle, until),
-                                )
-                                .await
-                                .map(|_| DispatchResult::Continue);
-                        }
-                        Ok(cla_registry::ForwardBundleResult::Congested(until)) => {
-                            trace!("CLA reported congestion, retry at: {until}");
+            // Follow an explicit path first, if there is one
+            if !previous {
+                if let Some(result) = self.forward_source_route(bundle, fib, &action).await? {
+                    return Ok(result);
+                }
+            }
 
-                            // Remember the shortest wait for a retry, in case we have ECMP
-                            congestion_wait = congestion_wait
-                                .map_or(Some(until), |w: time::OffsetDateTime| Some(w.min(until)))
-                        }
-                        Err(e) => trace!("CLA failed to forward {e}"),
-                    }
-                } else {
-                    trace!("FIB has entry for unknown CLA: {endpoint:?}");
+            if action.clas.is_empty() {
+                if let Some(until) = action.until {
+                    Decision::Wait(until).record(bundle, &self.dispatch_latency);
+                    return self.bundle_wait(bundle, until).await;
                 }
-                // Try the next CLA, this one is busy, broken or missing
             }
 
+            let congestion_wait = match self.offer(bundle, &destination, &action, &[]).await? {
+                Offered::Done(result) => return Ok(result),
+                Offered::Congested(until) => Some(until),
+                Offered::Refused => None,
+            };
+
             // By the time we get here, we have tried every CLA
 
             // Check for congestion
@@ -115,14 +107,16 @@ impl Dispatcher {
                     until = wait.min(until);
                 }
 
+                Decision::Wait(until).record(bundle, &self.dispatch_latency);
                 return self.bundle_wait(bundle, until).await;
-            } else if retries >= self.config.max_forwarding_delay {
+            } else if retries >= self.config.max_forwarding_delay() {
                 if previous {
                     // We have delayed long enough trying to find a route to previous_node
                     trace!("Failed to return bundle to previous node, no route");
-                    return Ok(DispatchResult::Drop(Some(
-                        bpv7::StatusReportReasonCode::NoKnownRouteToDestinationFromHere,
-                    )));
+                    let reason =
+                        Some(bpv7::StatusReportReasonCode::NoKnownRouteToDestinationFromHere);
+                    Decision::Drop(reason).record(bundle, &self.dispatch_latency);
+                    return Ok(DispatchResult::Drop(reason));
                 }
 
                 trace!("Failed to forward bundle, no route");
@@ -132,9 +126,24 @@ impl Dispatcher {
                     .bundle
                     .previous_node
                     .as_ref()
-                    .unwrap_or(&bundle.bundle.id.source);
+                    .unwrap_or(&bundle.bundle.id.source)
+                    .clone();
+
+                if self.config.reflect_loop_prevention() {
+                    if let Some(reason) =
+                        reflect_loop(&self.config.admin_endpoints, &bundle.bundle, &destination)
+                    {
+                        let reason = Some(reason);
+                        Decision::Drop(reason).record(bundle, &self.dispatch_latency);
+                        return Ok(DispatchResult::Drop(reason));
+                    }
+                }
 
                 trace!("Returning bundle to previous node: {destination}");
+                Decision::Reflect {
+                    next_hop: &destination,
+                }
+                .record(bundle, &self.dispatch_latency);
 
                 // Reset retry counter as we are attempting to return the bundle
                 retries = 0;
@@ -153,10 +162,190 @@ impl Dispatcher {
         }
     }
 
+    /* Offer the bundle to each CLA of `action` in turn, towards `next_hop`, carrying the rest of an
+     * explicit `path` with it */
+    async fn offer(
+        &self,
+        bundle: &mut metadata::Bundle,
+        next_hop: &bpv7::Eid,
+        action: &fib::ForwardAction,
+        path: &[bpv7::Eid],
+    ) -> Result<Offered, Error> {
+        let mut congestion_wait = None;
+
+        // For each CLA
+        for endpoint in &action.clas {
+            // Find the named CLA
+            if let Some(e) = self.cla_registry.find(endpoint.handle).await {
+                // The neighbour the FIB resolved `next_hop` to
+                let peer = endpoint.peer.as_ref().unwrap_or(next_hop);
+
+                /* Wait our turn, so a single neighbour cannot exhaust memory, however many
+                 * destinations are reached through it */
+                let _permit = self
+                    .in_flight
+                    .acquire(peer, store::Priority::of(&bundle.bundle))
+                    .await;
+
+                // Get bundle data from store, now we know we need it!
+                let Some(source_data) = self.load_data(bundle).await? else {
+                    // Bundle data was deleted sometime during processing
+                    return Ok(Offered::Done(DispatchResult::Done));
+                };
+
+                // Increment Hop Count, etc...
+                let data = self.update_extension_blocks(bundle, source_data.clone(), path);
+
+                // Fragment bundles that are too big for the CLA, the fragments are dispatched afresh
+                if let Some(max_bundle_size) = endpoint.max_bundle_size {
+                    if data.len() as u64 > max_bundle_size {
+                        let source_data = source_data.as_ref().as_ref();
+                        if let Some(result) = self
+                            .fragment(
+                                bundle,
+                                source_data,
+                                data.len().saturating_sub(source_data.len()),
+                                max_bundle_size as usize,
+                            )
+                            .await?
+                        {
+                            return Ok(Offered::Done(result));
+                        }
+
+                        trace!("Bundle is larger than the CLA maximum of {max_bundle_size} bytes, and cannot be fragmented");
+                        continue;
+                    }
+                }
+
+                match e.forward_bundle(next_hop, data.into()).await {
+                    Ok(cla_registry::ForwardBundleResult::Sent) => {
+                        // We have successfully forwarded!
+                        Decision::Forward {
+                            next_hop: peer,
+                            cla: endpoint.handle,
+                            priority: action.priority,
+                        }
+                        .record(bundle, &self.dispatch_latency);
+                        return self
+                            .report_bundle_forwarded(bundle)
+                            .await
+                            .map(|_| Offered::Done(DispatchResult::Drop(None)));
+                    }
+                    Ok(cla_registry::ForwardBundleResult::Pending(handle, until)) => {
+                        // CLA will report successful forwarding
+                        Decision::Forward {
+                            next_hop: peer,
+                            cla: endpoint.handle,
+                            priority: action.priority,
+                        }
+                        .record(bundle, &self.dispatch_latency);
+                        // Don't wait longer than expiry
+                        let until = until.unwrap_or_else(|| {
+                            let timeout = self.config.forward_ack_timeout;
+                            trace!("CLA endpoint has not provided an AckPending delay, defaulting to {timeout} seconds");
+                            i64::try_from(timeout)
+                                .ok()
+                                .and_then(|t| self.clock.now().checked_add(time::Duration::seconds(t)))
+                                .unwrap_or_else(|| bundle.expiry())
+                        }).min(bundle.expiry());
+
+                        /* Set the bundle status to 'Forward Acknowledgement Pending' and re-dispatch.
+                         * The CLA clears it with confirm_forwarding(), otherwise the bundle is
+                         * forwarded again once `until` has passed */
+                        return self
+                            .store
+                            .set_status(
+                                bundle,
+                                metadata::BundleStatus::ForwardAckPending(handle, until),
+                            )
+                            .await
+                            .map(|_| Offered::Done(DispatchResult::Continue));
+                    }
+                    Ok(cla_registry::ForwardBundleResult::Congested(until)) => {
+                        trace!("CLA reported congestion, retry at: {until}");
+
+                        // Remember the shortest wait for a retry, in case we have ECMP
+                        congestion_wait = congestion_wait
+                            .map_or(Some(until), |w: time::OffsetDateTime| Some(w.min(until)))
+                    }
+                    Err(e) => trace!("CLA failed to forward {e}"),
+                }
+            } else {
+                trace!("FIB has entry for unknown CLA: {endpoint:?}");
+            }
+            // Try the next CLA, this one is busy, broken or missing
+        }
+
+        Ok(congestion_wait.map_or(Offered::Refused, Offered::Congested))
+    }
+
+    /* Follow an explicit path, either given by a source route in the FIB `action` for the
+     * destination, or carried by the bundle from the previous node on the path, if configured to
+     * accept one.  The bundle goes to the first hop that takes it, skipping any that are
+     * unreachable.  Returns None if there is no path, or no hop on it is reachable */
+    async fn forward_source_route(
+        &self,
+        bundle: &mut metadata::Bundle,
+        fib: &fib::Fib,
+        action: &fib::ForwardAction,
+    ) -> Result<Option<DispatchResult>, Error> {
+        let path = if self.config.accept_source_routes()
+            && bundle
+                .bundle
+                .blocks
+                .values()
+                .any(|block| block.block_type == SourceRoute::block_type())
+        {
+            let Some(source_data) = self.load_data(bundle).await? else {
+                // Bundle data was deleted sometime during processing
+                return Ok(Some(DispatchResult::Done));
+            };
+            SourceRoute::extract(&bundle.bundle, source_data.as_ref().as_ref())
+                .map_or_else(|| action.source_route.clone(), |route| route.0)
+        } else {
+            action.source_route.clone()
+        };
+
+        let mut congestion_wait: Option<time::OffsetDateTime> = None;
+        for (idx, next_hop) in path.iter().enumerate() {
+            if self.config.admin_endpoints.is_local_service(next_hop) {
+                trace!("Skipping source route hop {next_hop}, it is this node");
+                continue;
+            }
+
+            let action = match fib.find(next_hop).await {
+                Ok(action) if !action.clas.is_empty() => action,
+                _ => {
+                    trace!("Source route hop {next_hop} is unreachable");
+                    continue;
+                }
+            };
+
+            match self
+                .offer(bundle, next_hop, &action, &path[idx + 1..])
+                .await?
+            {
+                Offered::Done(result) => return Ok(Some(result)),
+                Offered::Congested(until) => {
+                    congestion_wait = Some(congestion_wait.map_or(until, |w| w.min(until)))
+                }
+                Offered::Refused => trace!("Source route hop {next_hop} refused the bundle"),
+            }
+        }
+
+        // Rather than leave the path, wait for a congested hop
+        if let Some(until) = congestion_wait {
+            Decision::Wait(until).record(bundle, &self.dispatch_latency);
+            return self.bundle_wait(bundle, until).await.map(Some);
+        }
+        Ok(None)
+    }
+
     fn update_extension_blocks(
         &self,
         bundle: &metadata::Bundle,
         source_data: hardy_bpa_api::storage::DataRef,
+        path: &[bpv7::Eid],
     ) -> Vec<u8> {
         let mut editor = bpv7::Editor::new(&bundle.bundle, source_data.as_ref().as_ref());
 
@@ -169,16 +358,48 @@ impl Dispatcher {
             }
         }
 
+        // Propagate the trace context, starting a new trace if we are the first hop
+        if self.config.trace_propagation() {
+            let ctx =
+                trace_context::TraceContext::extract(&bundle.bundle, source_data.as_ref().as_ref())
+                    .map_or_else(trace_context::TraceContext::new_root, |ctx| ctx.new_child());
+
+            trace!(
+                "Forwarding with trace_id {}, span_id {}",
+                ctx.trace_id_hex(),
+                ctx.span_id_hex()
+            );
+
+            editor = editor
+                .replace_extension_block(trace_context::TraceContext::block_type())
+                .data(cbor::encode::emit(&ctx))
+                .build();
+        }
+
+        // Carry the rest of an explicit path, or remove it once it has been followed
+        if path.is_empty() {
+            if let Some((block_number, _)) = bundle
+                .bundle
+                .blocks
+                .iter()
+                .find(|(_, block)| block.block_type == SourceRoute::block_type())
+            {
+                editor = editor.remove_extension_block(*block_number);
+            }
+        } else {
+            editor = editor
+                .replace_extension_block(SourceRoute::block_type())
+                .data(cbor::encode::emit(&SourceRoute(path.to_vec())))
+                .build();
+        }
+
         // Previous Node Block
-        editor = editor
-            .replace_extension_block(bpv7::BlockType::PreviousNode)
-            .data(cbor::encode::emit(
-                &self
-                    .config
-                    .admin_endpoints
-                    .get_admin_endpoint(&bundle.bundle.destination),
-            ))
-            .build();
+        editor = editor.previous_node(
+            &self
+                .config
+                .admin_endpoints
+                .get_admin_endpoint(&bundle.bundle.destination),
+        );
 
         // Increment Hop Count
         if let Some(hop_count) = &bundle.bundle.hop_count {
@@ -195,7 +416,7 @@ impl Dispatcher {
         if bundle.bundle.age.is_some() || bundle.bundle.id.timestamp.creation_time.is_none() {
             // We have a bundle age block already, or no valid clock at bundle source
             // So we must add an updated bundle age block
-            let bundle_age = (time::OffsetDateTime::now_utc() - bundle.creation_time())
+            let bundle_age = (self.clock.now() - bundle.creation_time())
                 .whole_milliseconds()
                 .clamp(0, u64::MAX as i128) as u64;
 
@@ -208,6 +429,31 @@ impl Dispatcher {
         editor.build()
     }
 
+    /* The most update_extension_blocks() can add to a bundle on its way to `destination`,
+     * found by making the same additions, at their largest, to an empty bundle */
+    pub(super) fn egress_growth(&self, destination: &bpv7::Eid) -> usize {
+        let node_id = self.config.admin_endpoints.get_admin_endpoint(destination);
+        let (bundle, data) = bpv7::Builder::new()
+            .source(node_id.clone())
+            .destination(destination.clone())
+            .add_payload_block(Vec::new())
+            .build();
+
+        let mut editor = bpv7::Editor::new(&bundle, &data).previous_node(&node_id);
+        if self.config.trace_propagation() {
+            editor = editor
+                .replace_extension_block(trace_context::TraceContext::block_type())
+                .data(cbor::encode::emit(&trace_context::TraceContext::new_root()))
+                .build();
+        }
+        editor = editor
+            .replace_extension_block(bpv7::BlockType::BundleAge)
+            .data(cbor::encode::emit(u64::MAX))
+            .build();
+
+        editor.build().len().saturating_sub(data.len())
+    }
+
     #[instrument(skip(self))]
     pub async fn confirm_forwarding(
         &self,
@@ -242,3 +488,689 @@ impl Dispatcher {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::tests::{
+        dispatcher_config, new_test_dispatcher, test_store, TestBundles, TestDispatcher,
+        TestMetadata,
+    };
+
+    #[test]
+    fn reflect() {
+        let admin_endpoints = utils::admin_endpoints::AdminEndpoints::init(
+            &::config::Config::builder()
+                .set_override("administrative_endpoint", "ipn:1.0")
+                .unwrap()
+                .build()
+                .unwrap(),
+        );
+
+        // A bundle whose previous node is the reflecting node
+        let this_node: bpv7::Eid = "ipn:1.0".parse().unwrap();
+        let bundle = bpv7::Bundle {
+            previous_node: Some(this_node.clone()),
+            ..Default::default()
+        };
+        assert_eq!(
+            reflect_loop(&admin_endpoints, &bundle, &this_node),
+            Some(bpv7::StatusReportReasonCode::NoKnownRouteToDestinationFromHere)
+        );
+
+        // Returning to another node is fine, until the hops run out
+        let previous_node: bpv7::Eid = "ipn:2.0".parse().unwrap();
+        let mut bundle = bpv7::Bundle {
+            previous_node: Some(previous_node.clone()),
+            hop_count: Some(bpv7::HopInfo { limit: 3, count: 1 }),
+            ..Default::default()
+        };
+        assert_eq!(
+            reflect_loop(&admin_endpoints, &bundle, &previous_node),
+            None
+        );
+
+        // The last hop may be used
+        bundle.hop_count = Some(bpv7::HopInfo { limit: 3, count: 2 });
+        assert_eq!(
+            reflect_loop(&admin_endpoints, &bundle, &previous_node),
+            None
+        );
+
+        bundle.hop_count = Some(bpv7::HopInfo { limit: 3, count: 3 });
+        assert_eq!(
+            reflect_loop(&admin_endpoints, &bundle, &previous_node),
+            Some(bpv7::StatusReportReasonCode::HopLimitExceeded)
+        );
+    }
+
+    #[tokio::test]
+    async fn forward_ack() {
+        let metadata_storage = Arc::new(TestMetadata::default());
+        let bundle_storage = Arc::new(TestBundles::default());
+        let store = Arc::new(test_store(metadata_storage.clone(), bundle_storage.clone()));
+
+        // A route to node 3, through a CLA that accepts everything
+        let mut task_set = tokio::task::JoinSet::new();
+        let cancel_token = tokio_util::sync::CancellationToken::new();
+        let TestDispatcher {
+            dispatcher, clas, ..
+        } = new_test_dispatcher(
+            store.clone(),
+            &dispatcher_config().build().unwrap(),
+            &[("ipn:3.*", None)],
+            &mut task_set,
+            cancel_token.clone(),
+        )
+        .await;
+        let cla = &clas[0];
+
+        // Bundles forwarded by CLA 7, awaiting acknowledgement
+        let now = time::OffsetDateTime::now_utc();
+        let mut pending = Vec::new();
+        for until in [
+            now + time::Duration::hours(1),
+            now - time::Duration::seconds(1),
+        ] {
+            let (bundle, data) = bpv7::Builder::new()
+                .source("ipn:2.1".parse().unwrap())
+                .destination("ipn:3.1".parse().unwrap())
+                .add_payload_block(vec![1, 2, 3])
+                .build();
+            let metadata = store
+                .store(
+                    &bundle,
+                    &data,
+                    metadata::BundleStatus::ForwardAckPending(7, until),
+                    None,
+                )
+                .await
+                .unwrap()
+                .unwrap();
+            pending.push(metadata::Bundle { metadata, bundle });
+        }
+        let status = |bundle_id: &bpv7::BundleId| {
+            metadata_storage
+                .0
+                .lock()
+                .unwrap()
+                .iter()
+                .find(|b| &b.bundle.id == bundle_id)
+                .map(|b| b.metadata.status.clone())
+                .unwrap()
+        };
+
+        // Only the CLA that forwarded the bundle can acknowledge it, which clears it
+        let acked = &pending[0];
+        let key = acked.bundle.id.to_key();
+        assert_eq!(
+            dispatcher
+                .confirm_forwarding(8, &key)
+                .await
+                .unwrap_err()
+                .code(),
+            tonic::Code::NotFound
+        );
+        dispatcher.confirm_forwarding(7, &key).await.unwrap();
+        assert!(matches!(
+            status(&acked.bundle.id),
+            metadata::BundleStatus::Tombstone(_)
+        ));
+        assert!(!bundle_storage
+            .0
+            .lock()
+            .unwrap()
+            .contains_key(acked.metadata.storage_name.as_deref().unwrap()));
+
+        // An unacknowledged bundle is forwarded again once the timeout has passed
+        let timed_out = pending.pop().unwrap();
+        let bundle_id = timed_out.bundle.id.clone();
+        dispatcher.dispatch_bundle(timed_out).await.unwrap();
+        for _ in 0..100 {
+            if cla.forwarded() > 0 {
+                break;
+            }
+            tokio::time::sleep(tokio::time::Duration::from_millis(10)).await;
+        }
+        assert_eq!(cla.forwarded(), 1);
+        let bpv7::ValidBundle::Valid(resent, _) =
+            bpv7::ValidBundle::parse(&cla.last_bundle().unwrap(), |_, _| Ok(None)).unwrap()
+        else {
+            panic!("Invalid bundle forwarded");
+        };
+        assert_eq!(resent.id, bundle_id);
+        assert!(!matches!(
+            status(&bundle_id),
+            metadata::BundleStatus::ForwardAckPending(..)
+        ));
+
+        // So a late acknowledgement is ignored
+        assert_eq!(
+            dispatcher
+                .confirm_forwarding(7, &bundle_id.to_key())
+                .await
+                .unwrap_err()
+                .code(),
+            tonic::Code::NotFound
+        );
+
+        cancel_token.cancel();
+        while task_set.join_next().await.is_some() {}
+    }
+
+    #[tokio::test]
+    async fn forward_decision() {
+        use tracing_subscriber::layer::SubscriberExt;
+
+        // Captures the next hop of every forwarding decision
+        #[derive(Clone, Default)]
+        struct NextHops(Arc<std::sync::Mutex<Vec<String>>>);
+
+        struct Visitor(Option<String>, bool);
+
+        impl tracing::field::Visit for Visitor {
+            fn record_debug(&mut self, field: &tracing::field::Field, value: &dyn std::fmt::Debug) {
+                if field.name() == "next_hop" {
+                    self.0 = Some(format!("{value:?}"));
+                }
+            }
+
+            fn record_str(&mut self, field: &tracing::field::Field, value: &str) {
+                if field.name() == "action" {
+                    self.1 = value == "forward";
+                }
+            }
+        }
+
+        impl<S: tracing::Subscriber> tracing_subscriber::Layer<S> for NextHops {
+            fn on_event(
+                &self,
+                event: &tracing::Event<'_>,
+                _ctx: tracing_subscriber::layer::Context<'_, S>,
+            ) {
+                let mut visitor = Visitor(None, false);
+                event.record(&mut visitor);
+                if let (Some(next_hop), true) = visitor {
+                    self.0.lock().unwrap().push(next_hop);
+                }
+            }
+        }
+
+        let next_hops = NextHops::default();
+        let _guard = tracing::subscriber::set_default(
+            tracing_subscriber::registry().with(next_hops.clone()),
+        );
+
+        let store = Arc::new(test_store(
+            Arc::new(TestMetadata::default()),
+            Arc::new(TestBundles::default()),
+        ));
+
+        // The destination is reached via a neighbour
+        let mut task_set = tokio::task::JoinSet::new();
+        let cancel_token = tokio_util::sync::CancellationToken::new();
+        let TestDispatcher {
+            dispatcher,
+            fib,
+            clas,
+            ..
+        } = new_test_dispatcher(
+            store,
+            &dispatcher_config().build().unwrap(),
+            &[("ipn:2.*", None)],
+            &mut task_set,
+            cancel_token.clone(),
+        )
+        .await;
+        let cla = &clas[0];
+        fib.unwrap()
+            .add(
+                "test".to_string(),
+                &"ipn:3.*".parse().unwrap(),
+                0,
+                fib::Action::Via("ipn:2.0".parse().unwrap()),
+            )
+            .await
+            .unwrap();
+
+        dispatcher
+            .send(
+                None,
+                "ipn:3.1".parse().unwrap(),
+                vec![1, 2, 3].into(),
+                Some(std::time::Duration::from_secs(60)),
+                None,
+            )
+            .await
+            .unwrap();
+        for _ in 0..100 {
+            if cla.forwarded() > 0 {
+                break;
+            }
+            tokio::time::sleep(tokio::time::Duration::from_millis(10)).await;
+        }
+        assert_eq!(cla.forwarded(), 1);
+
+        // The decision records the neighbour the bundle went to, not its destination
+        assert_eq!(*next_hops.0.lock().unwrap(), vec!["ipn:2.0".to_string()]);
+
+        cancel_token.cancel();
+        while task_set.join_next().await.is_some() {}
+    }
+
+    #[tokio::test]
+    async fn path_budget() {
+        let store = Arc::new(test_store(
+            Arc::new(TestMetadata::default()),
+            Arc::new(TestBundles::default()),
+        ));
+
+        // A neighbour with a bundle size limit, and extension blocks added on the way out
+        let mut task_set = tokio::task::JoinSet::new();
+        let cancel_token = tokio_util::sync::CancellationToken::new();
+        let TestDispatcher {
+            dispatcher, clas, ..
+        } = new_test_dispatcher(
+            store,
+            &dispatcher_config()
+                .set_default("trace_propagation", true)
+                .unwrap()
+                .build()
+                .unwrap(),
+            &[("ipn:2.*", Some(65536))],
+            &mut task_set,
+            cancel_token.clone(),
+        )
+        .await;
+        let cla = &clas[0];
+
+        // The budget leaves room for the blocks added on the way out
+        let budget = dispatcher
+            .path_budget(&"ipn:2.1".parse().unwrap())
+            .await
+            .unwrap();
+        assert!(budget < 65536);
+        let margin = 65536 - budget;
+
+        let (_, data) = bpv7::Builder::new()
+            .source("ipn:3.1".parse().unwrap())
+            .destination("ipn:2.1".parse().unwrap())
+            .lifetime(60_000)
+            .add_payload_block(vec![0; 100])
+            .build();
+        dispatcher
+            .receive_bundle(data.clone().into(), None)
+            .await
+            .unwrap();
+        for _ in 0..100 {
+            if cla.forwarded() > 0 {
+                break;
+            }
+            tokio::time::sleep(tokio::time::Duration::from_millis(10)).await;
+        }
+
+        // Everything added to the bundle fits in the margin
+        let forwarded = cla.last_bundle().unwrap();
+        assert!(forwarded.len() > data.len());
+        assert!(forwarded.len() <= data.len() + margin);
+
+        cancel_token.cancel();
+        while task_set.join_next().await.is_some() {}
+    }
+
+    #[tokio::test]
+    async fn source_route() {
+        let store = Arc::new(test_store(
+            Arc::new(TestMetadata::default()),
+            Arc::new(TestBundles::default()),
+        ));
+
+        // Bundles for node 2 go by way of node 3, then node 4
+        let mut task_set = tokio::task::JoinSet::new();
+        let cancel_token = tokio_util::sync::CancellationToken::new();
+        let TestDispatcher {
+            dispatcher,
+            fib,
+            clas,
+            ..
+        } = new_test_dispatcher(
+            store,
+            &dispatcher_config().build().unwrap(),
+            &[("ipn:3.*", None), ("ipn:4.*", None)],
+            &mut task_set,
+            cancel_token.clone(),
+        )
+        .await;
+        let fib = fib.unwrap();
+        fib.add(
+            "test".to_string(),
+            &"ipn:2.*".parse().unwrap(),
+            0,
+            fib::Action::SourceRoute(vec!["ipn:3.0".parse().unwrap(), "ipn:4.0".parse().unwrap()]),
+        )
+        .await
+        .unwrap();
+
+        let send = || {
+            dispatcher.send(
+                None,
+                "ipn:2.1".parse().unwrap(),
+                vec![1, 2, 3].into(),
+                Some(std::time::Duration::from_secs(60)),
+                None,
+            )
+        };
+        let wait_for = |cla: &Arc<cla_registry::NullCla>, attempts: usize| {
+            let cla = cla.clone();
+            async move {
+                for _ in 0..100 {
+                    if cla.attempts() >= attempts {
+                        break;
+                    }
+                    tokio::time::sleep(tokio::time::Duration::from_millis(10)).await;
+                }
+            }
+        };
+
+        // The rest of the path carried by the last bundle a CLA took
+        let path = |cla: &cla_registry::NullCla| {
+            let data = cla.last_bundle().unwrap();
+            let bpv7::ValidBundle::Valid(bundle, _) =
+                bpv7::ValidBundle::parse(&data, |_, _| Ok(None)).unwrap()
+            else {
+                panic!("Invalid bundle forwarded");
+            };
+            SourceRoute::extract(&bundle, &data).map(|route| route.0)
+        };
+
+        // Offered to the first hop, which carries it on to the second
+        send().await.unwrap();
+        wait_for(&clas[0], 1).await;
+        assert_eq!(clas[0].forwarded(), 1);
+        assert_eq!(clas[1].attempts(), 0);
+        assert_eq!(path(&clas[0]), Some(vec!["ipn:4.0".parse().unwrap()]));
+
+        // An unreachable first hop is skipped, and the path ends at the second
+        clas[0].set_response(cla_registry::NullClaResponse::Fail);
+        send().await.unwrap();
+        wait_for(&clas[1], 1).await;
+        assert_eq!(clas[0].attempts(), 2);
+        assert_eq!(clas[1].forwarded(), 1);
+        assert_eq!(path(&clas[1]), None);
+
+        // A path carried by a received bundle is only followed when accepted, and never overrides a
+        // drop route for the destination
+        fib.add(
+            "test".to_string(),
+            &"ipn:5.*".parse().unwrap(),
+            0,
+            fib::Action::Drop(Some(
+                bpv7::StatusReportReasonCode::NoKnownRouteToDestinationFromHere,
+            )),
+        )
+        .await
+        .unwrap();
+        fib.add(
+            "test".to_string(),
+            &"ipn:6.*".parse().unwrap(),
+            0,
+            fib::Action::Via("ipn:3.0".parse().unwrap()),
+        )
+        .await
+        .unwrap();
+        clas[0].set_response(cla_registry::NullClaResponse::Sent);
+        let mut events = dispatcher.subscribe_events();
+
+        let receive = |destination: &str| {
+            let (bundle, data) = bpv7::Builder::new()
+                .source("ipn:7.1".parse().unwrap())
+                .destination(destination.parse().unwrap())
+                .lifetime(60_000)
+                .add_extension_block(SourceRoute::block_type())
+                .data(cbor::encode::emit(&SourceRoute(vec!["ipn:4.0"
+                    .parse()
+                    .unwrap()])))
+                .build()
+                .add_payload_block(vec![1, 2, 3])
+                .build();
+            let dispatcher = dispatcher.clone();
+            async move {
+                dispatcher.receive_bundle(data.into(), None).await.unwrap();
+                bundle.id
+            }
+        };
+
+        // Ignored by default, the bundle follows the route to its destination
+        receive("ipn:6.1").await;
+        wait_for(&clas[0], 3).await;
+        assert_eq!(clas[0].forwarded(), 2);
+        assert_eq!(clas[1].attempts(), 1);
+
+        let accept = dispatcher_config()
+            .set_override("accept_source_routes", true)
+            .unwrap()
+            .build()
+            .unwrap();
+        dispatcher.reload_config(&accept).unwrap();
+
+        receive("ipn:6.1").await;
+        wait_for(&clas[1], 2).await;
+        assert_eq!(clas[1].forwarded(), 2);
+
+        let bundle_id = receive("ipn:5.1").await;
+        loop {
+            match events.recv().await {
+                Some(DispatchEvent::Dropped(id, reason)) if id == bundle_id => {
+                    assert_eq!(
+                        reason,
+                        bpv7::StatusReportReasonCode::NoKnownRouteToDestinationFromHere
+                    );
+                    break;
+                }
+                Some(_) => {}
+                None => panic!("Event stream closed"),
+            }
+        }
+        assert_eq!(clas[0].attempts(), 3);
+        assert_eq!(clas[1].attempts(), 2);
+
+        cancel_token.cancel();
+        while task_set.join_next().await.is_some() {}
+    }
+
+    #[tokio::test]
+    async fn reflect_hop_limit() {
+        let store = Arc::new(test_store(
+            Arc::new(TestMetadata::default()),
+            Arc::new(TestBundles::default()),
+        ));
+
+        // Give up looking for a route at once
+        let mut task_set = tokio::task::JoinSet::new();
+        let cancel_token = tokio_util::sync::CancellationToken::new();
+        let TestDispatcher {
+            dispatcher, clas, ..
+        } = new_test_dispatcher(
+            store,
+            &dispatcher_config()
+                .set_override("max_forwarding_delay", 0)
+                .unwrap()
+                .build()
+                .unwrap(),
+            &[("ipn:2.*", None)],
+            &mut task_set,
+            cancel_token.clone(),
+        )
+        .await;
+        let cla = &clas[0];
+
+        // A bundle with no route onwards, from node 2 with one hop left
+        let (_, data) = bpv7::Builder::new()
+            .source("ipn:7.1".parse().unwrap())
+            .destination("ipn:9.1".parse().unwrap())
+            .lifetime(60_000)
+            .add_extension_block(bpv7::BlockType::PreviousNode)
+            .data(cbor::encode::emit(&"ipn:2.0".parse::<bpv7::Eid>().unwrap()))
+            .build()
+            .add_extension_block(bpv7::BlockType::HopCount)
+            .data(cbor::encode::emit(&bpv7::HopInfo { limit: 3, count: 2 }))
+            .build()
+            .add_payload_block(vec![1, 2, 3])
+            .build();
+        dispatcher.receive_bundle(data.into(), None).await.unwrap();
+
+        // It is returned to node 2, using the last hop
+        for _ in 0..100 {
+            if cla.attempts() > 0 {
+                break;
+            }
+            tokio::time::sleep(tokio::time::Duration::from_millis(10)).await;
+        }
+        assert_eq!(cla.forwarded(), 1);
+        let bpv7::ValidBundle::Valid(returned, _) =
+            bpv7::ValidBundle::parse(&cla.last_bundle().unwrap(), |_, _| Ok(None)).unwrap()
+        else {
+            panic!("Invalid bundle forwarded");
+        };
+        let hop_count = returned.hop_count.unwrap();
+        assert_eq!((hop_count.count, hop_count.limit), (3, 3));
+
+        cancel_token.cancel();
+        while task_set.join_next().await.is_some() {}
+    }
+
+    #[tokio::test]
+    async fn class_of_service() {
+        let store = Arc::new(test_store(
+            Arc::new(TestMetadata::default()),
+            Arc::new(TestBundles::default()),
+        ));
+
+        let mut task_set = tokio::task::JoinSet::new();
+        let cancel_token = tokio_util::sync::CancellationToken::new();
+        let TestDispatcher {
+            dispatcher, clas, ..
+        } = new_test_dispatcher(
+            store.clone(),
+            &dispatcher_config().build().unwrap(),
+            &[("ipn:3.*", None)],
+            &mut task_set,
+            cancel_token.clone(),
+        )
+        .await;
+        let cla = &clas[0];
+
+        // A stored expedited bundle
+        let (expedited, data) = bpv7::Builder::new()
+            .source("ipn:2.1".parse().unwrap())
+            .destination("ipn:3.1".parse().unwrap())
+            .lifetime(60_000)
+            .class_of_service(bpv7::ClassOfService::Expedited)
+            .add_payload_block(vec![0; 64])
+            .build();
+        store
+            .store(
+                &expedited,
+                &data,
+                metadata::BundleStatus::DispatchPending,
+                None,
+            )
+            .await
+            .unwrap()
+            .unwrap();
+        let stored = store.load(&expedited.id).await.unwrap().unwrap();
+
+        // Is still expedited when forwarded
+        dispatcher.dispatch_bundle(stored).await.unwrap();
+        for _ in 0..100 {
+            if cla.attempts() != 0 {
+                break;
+            }
+            tokio::time::sleep(tokio::time::Duration::from_millis(10)).await;
+        }
+        assert_eq!(cla.forwarded(), 1);
+
+        let data = cla.last_bundle().unwrap();
+        let bpv7::ValidBundle::Valid(forwarded, _) =
+            bpv7::ValidBundle::parse(&data, |_, _| Ok(None)).unwrap()
+        else {
+            panic!("Invalid bundle forwarded");
+        };
+        assert_eq!(forwarded.id, expedited.id);
+        assert_eq!(
+            forwarded.class_of_service,
+            Some(bpv7::ClassOfService::Expedited)
+        );
+
+        cancel_token.cancel();
+        while task_set.join_next().await.is_some() {}
+    }
+
+    #[tokio::test]
+    async fn forward() {
+        let metadata_storage = Arc::new(TestMetadata::default());
+        let store = Arc::new(test_store(
+            metadata_storage.clone(),
+            Arc::new(TestBundles::default()),
+        ));
+
+        let mut task_set = tokio::task::JoinSet::new();
+        let cancel_token = tokio_util::sync::CancellationToken::new();
+        let TestDispatcher {
+            dispatcher, clas, ..
+        } = new_test_dispatcher(
+            store,
+            &dispatcher_config().build().unwrap(),
+            &[("ipn:2.*", None)],
+            &mut task_set,
+            cancel_token.clone(),
+        )
+        .await;
+        let cla = &clas[0];
+
+        let send = || {
+            dispatcher.send(
+                None,
+                "ipn:2.1".parse().unwrap(),
+                vec![1, 2, 3].into(),
+                Some(std::time::Duration::from_secs(60)),
+                None,
+            )
+        };
+        let wait_for = |attempts: usize| {
+            let cla = cla.clone();
+            async move {
+                for _ in 0..100 {
+                    if cla.attempts() >= attempts {
+                        break;
+                    }
+                    tokio::time::sleep(tokio::time::Duration::from_millis(10)).await;
+                }
+            }
+        };
+
+        send().await.unwrap();
+        wait_for(1).await;
+        assert_eq!(cla.forwarded(), 1);
+
+        // A failing CLA takes nothing, the bundle waits for another route
+        cla.set_response(cla_registry::NullClaResponse::Fail);
+        let bundle_id = send().await.unwrap();
+        wait_for(2).await;
+        assert_eq!(cla.attempts(), 2);
+        assert_eq!(cla.forwarded(), 1);
+        assert!(matches!(
+            metadata_storage
+                .0
+                .lock()
+                .unwrap()
+                .iter()
+                .find(|b| b.bundle.id == bundle_id)
+                .map(|b| &b.metadata.status),
+            Some(status) if !matches!(status, metadata::BundleStatus::Tombstone(_))
+        ));
+
+        cancel_token.cancel();
+        while task_set.join_next().await.is_some() {}
+    }
+}
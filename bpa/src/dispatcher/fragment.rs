@@ -1,6 +1,82 @@
 use super::*;
 
+// Fragments after the first gain a Previous Node block when forwarded
+const PREVIOUS_NODE_HEADROOM: usize = 32;
+
 impl Dispatcher {
+    /* Split an oversized bundle into fragments that will fit within `max_bundle_size` once forwarded,
+     * allowing `growth` bytes for the extension block updates.  A fragment that still does not fit
+     * will be fragmented again when it is forwarded.  Returns what to do with the original bundle,
+     * or None if it cannot be fragmented */
+    #[instrument(skip(self, bundle, source_data))]
+    pub(super) async fn fragment(
+        &self,
+        bundle: &metadata::Bundle,
+        source_data: &[u8],
+        growth: usize,
+        max_bundle_size: usize,
+    ) -> Result<Option<DispatchResult>, Error> {
+        let fragments = match bundle.bundle.fragment(
+            source_data,
+            max_bundle_size.saturating_sub(growth + PREVIOUS_NODE_HEADROOM),
+        ) {
+            Ok(fragments) => fragments,
+            Err(e) => {
+                trace!("Failed to fragment bundle: {e}");
+                return Ok(None);
+            }
+        };
+
+        let mut parsed = Vec::with_capacity(fragments.len());
+        for data in fragments {
+            match bpv7::ValidBundle::parse(&data, |_, _| Ok(None))? {
+                bpv7::ValidBundle::Valid(fragment, _) => parsed.push((fragment, data)),
+                _ => {
+                    warn!("Fragmentation produced an invalid bundle");
+                    return Ok(None);
+                }
+            }
+        }
+
+        trace!("Bundle split into {} fragments", parsed.len());
+
+        // Store every fragment before dispatching any, as a partial set is of no use to anyone
+        let mut stored = Vec::with_capacity(parsed.len());
+        for (fragment, data) in parsed {
+            match self
+                .store
+                .store(
+                    &fragment,
+                    &data,
+                    metadata::BundleStatus::DispatchPending,
+                    bundle.metadata.received_at,
+                )
+                .await
+            {
+                Ok(Some(metadata)) => stored.push(metadata::Bundle {
+                    metadata,
+                    bundle: fragment,
+                }),
+                Ok(None) => {}
+                Err(e) if e.is::<store::StorageFull>() => {
+                    info!("Bundle storage is full, dropping bundle that cannot be fragmented");
+                    for fragment in stored {
+                        self.drop_bundle(fragment, None).await?;
+                    }
+                    return Ok(Some(DispatchResult::Drop(Some(
+                        bpv7::StatusReportReasonCode::DepletedStorage,
+                    ))));
+                }
+                Err(e) => return Err(e),
+            }
+        }
+
+        for fragment in stored {
+            self.dispatch_bundle(fragment).await?;
+        }
+        Ok(Some(DispatchResult::Drop(None)))
+    }
+
     #[instrument(skip(self))]
     pub(super) async fn reassemble(
         &self,
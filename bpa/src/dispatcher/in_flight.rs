@@ -0,0 +1,250 @@
+use super::*;
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, HashMap};
+use tokio::sync::oneshot;
+
+type WaiterKey = (Reverse<store::Priority>, u64);
+
+struct Slots {
+    available: usize,
+    seq: u64,
+    // Highest priority first, then first come first served
+    waiting: BTreeMap<WaiterKey, oneshot::Sender<()>>,
+}
+
+/* Bounds the number of bundles concurrently loaded and forwarded towards a single peer,
+ * so a burst for one peer cannot pull an unbounded amount of bundle data into memory.
+ * When a slot frees up it goes to the highest priority bundle waiting, so expedited bundles
+ * are not held up behind a backlog of bulk traffic */
+pub struct InFlightLimiter {
+    max_in_flight: usize,
+    slots: std::sync::Mutex<HashMap<bpv7::Eid, Slots>>,
+}
+
+pub struct InFlightPermit<'a> {
+    limiter: &'a InFlightLimiter,
+    peer: bpv7::Eid,
+    held: bool,
+}
+
+// A place in the queue, given up if the waiting future is dropped
+struct Waiter<'a> {
+    limiter: &'a InFlightLimiter,
+    peer: &'a bpv7::Eid,
+    key: Option<WaiterKey>,
+}
+
+impl InFlightLimiter {
+    pub fn new(max_in_flight: u32) -> Self {
+        Self {
+            max_in_flight: max_in_flight as usize,
+            slots: Default::default(),
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<bpv7::Eid, Slots>> {
+        self.slots
+            .lock()
+            .trace_expect("Failed to lock in-flight mutex")
+    }
+
+    // Wait for a free slot for `peer`, which is released when the permit is dropped
+    pub async fn acquire(&self, peer: &bpv7::Eid, priority: store::Priority) -> InFlightPermit<'_> {
+        let mut permit = InFlightPermit {
+            limiter: self,
+            peer: peer.clone(),
+            held: false,
+        };
+        if self.max_in_flight == 0 {
+            return permit;
+        }
+
+        let (key, rx) = {
+            let mut slots = self.lock();
+            let slots = slots.entry(peer.clone()).or_insert_with(|| Slots {
+                available: self.max_in_flight,
+                seq: 0,
+                waiting: BTreeMap::new(),
+            });
+
+            // There are only ever waiters when no slots are available
+            if slots.available != 0 {
+                slots.available -= 1;
+                permit.held = true;
+                return permit;
+            }
+
+            slots.seq += 1;
+            let key = (Reverse(priority), slots.seq);
+            let (tx, rx) = oneshot::channel();
+            slots.waiting.insert(key, tx);
+            (key, rx)
+        };
+
+        let mut waiter = Waiter {
+            limiter: self,
+            peer,
+            key: Some(key),
+        };
+        rx.await.trace_expect("In-flight waiter dropped from queue");
+        waiter.key = None;
+
+        permit.held = true;
+        permit
+    }
+
+    // Hand a slot to the next waiter, or return it
+    fn release(&self, peer: &bpv7::Eid) {
+        let mut slots = self.lock();
+        if let Some(s) = slots.get_mut(peer) {
+            while let Some((_, tx)) = s.waiting.pop_first() {
+                if tx.send(()).is_ok() {
+                    return;
+                }
+            }
+
+            // Forget idle peers
+            s.available += 1;
+            if s.available == self.max_in_flight {
+                slots.remove(peer);
+            }
+        }
+    }
+
+    #[cfg(test)]
+    fn peers(&self) -> usize {
+        self.slots.lock().unwrap().len()
+    }
+}
+
+impl Drop for Waiter<'_> {
+    fn drop(&mut self) {
+        if let Some(key) = self.key.take() {
+            let removed = self
+                .limiter
+                .lock()
+                .get_mut(self.peer)
+                .and_then(|s| s.waiting.remove(&key))
+                .is_some();
+
+            // If we were no longer waiting, we were handed a slot we will never use
+            if !removed {
+                self.limiter.release(self.peer);
+            }
+        }
+    }
+}
+
+impl Drop for InFlightPermit<'_> {
+    fn drop(&mut self) {
+        if self.held {
+            self.limiter.release(&self.peer);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicUsize;
+
+    #[tokio::test]
+    async fn flood() {
+        const MAX_IN_FLIGHT: u32 = 3;
+
+        let limiter = Arc::new(InFlightLimiter::new(MAX_IN_FLIGHT));
+        let loaded = Arc::new(AtomicUsize::new(0));
+        let high_water = Arc::new(AtomicUsize::new(0));
+        let peer: bpv7::Eid = "ipn:2.1".parse().unwrap();
+
+        let mut task_set = tokio::task::JoinSet::new();
+        for _ in 0..32 {
+            let limiter = limiter.clone();
+            let loaded = loaded.clone();
+            let high_water = high_water.clone();
+            let peer = peer.clone();
+            task_set.spawn(async move {
+                let _permit = limiter.acquire(&peer, store::Priority::BestEffort).await;
+
+                // Pretend to load and forward the bundle data
+                let n = loaded.fetch_add(1, Ordering::SeqCst) + 1;
+                high_water.fetch_max(n, Ordering::SeqCst);
+                tokio::time::sleep(std::time::Duration::from_millis(1)).await;
+                loaded.fetch_sub(1, Ordering::SeqCst);
+            });
+        }
+
+        // Other peers are not held up
+        let other = limiter
+            .acquire(&"ipn:3.1".parse().unwrap(), store::Priority::BestEffort)
+            .await;
+
+        while let Some(r) = task_set.join_next().await {
+            r.unwrap();
+        }
+
+        assert_eq!(high_water.load(Ordering::SeqCst), MAX_IN_FLIGHT as usize);
+        assert_eq!(limiter.peers(), 1);
+        drop(other);
+        assert_eq!(limiter.peers(), 0);
+    }
+
+    #[tokio::test]
+    async fn priority() {
+        let limiter = Arc::new(InFlightLimiter::new(1));
+        let order = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let peer: bpv7::Eid = "ipn:2.1".parse().unwrap();
+        let busy = limiter.acquire(&peer, store::Priority::BestEffort).await;
+
+        let wait = |priority| {
+            let limiter = limiter.clone();
+            let order = order.clone();
+            let peer = peer.clone();
+            tokio::spawn(async move {
+                let _permit = limiter.acquire(&peer, priority).await;
+                order.lock().unwrap().push(priority);
+            })
+        };
+
+        // Queue up behind the busy slot, letting each waiter take its place in turn
+        let mut waiters = Vec::new();
+        for priority in [
+            store::Priority::Bulk,
+            store::Priority::BestEffort,
+            store::Priority::Expedited,
+            store::Priority::Expedited,
+        ] {
+            waiters.push(wait(priority));
+            tokio::task::yield_now().await;
+        }
+
+        // A waiter that gives up does not hold up the rest
+        waiters.pop().unwrap().abort();
+        tokio::task::yield_now().await;
+
+        drop(busy);
+        for waiter in waiters {
+            waiter.await.unwrap();
+        }
+        assert_eq!(
+            *order.lock().unwrap(),
+            [
+                store::Priority::Expedited,
+                store::Priority::BestEffort,
+                store::Priority::Bulk
+            ]
+        );
+        assert_eq!(limiter.peers(), 0);
+    }
+
+    #[tokio::test]
+    async fn unlimited() {
+        let limiter = InFlightLimiter::new(0);
+        let peer: bpv7::Eid = "ipn:2.1".parse().unwrap();
+        let _permits = [
+            limiter.acquire(&peer, store::Priority::BestEffort).await,
+            limiter.acquire(&peer, store::Priority::BestEffort).await,
+        ];
+        assert_eq!(limiter.peers(), 0);
+    }
+}
@@ -1,10 +1,21 @@
 use super::*;
+use tracing::Instrument;
+use tracing_opentelemetry::OpenTelemetrySpanExt;
 
 impl Dispatcher {
+    /* Receive a bundle from the CLA `cla_ident`, or from elsewhere if None */
     #[instrument(skip(self, data))]
-    pub async fn receive_bundle(&self, data: Bytes) -> Result<(), Error> {
+    pub async fn receive_bundle(&self, data: Bytes, cla_ident: Option<&str>) -> Result<(), Error> {
         // Capture received_at as soon as possible
-        let received_at = Some(time::OffsetDateTime::now_utc());
+        let received_at = Some(self.clock.now());
+
+        // Hold a place in the ingress queue until the bundle has been handed to dispatch
+        let _permit = self.ingress_queue.enter()?;
+
+        // Refuse new bundles while storage is failing, rather than accepting bundles we may lose
+        if self.store.is_degraded() {
+            return Err(store::StorageDegraded.into());
+        }
 
         // Do a fast pre-check
         if data.is_empty() {
@@ -18,39 +29,65 @@ impl Dispatcher {
             .into());
         }
 
+        // Check declared lengths before parsing anything from an untrusted peer
+        hardy_cbor::decode::check_limits(&data, &hardy_cbor::decode::DecodeLimits::default())?;
+
         // Parse the bundle
-        match bpv7::ValidBundle::parse(&data, |_, _| Ok(None))? {
+        let bundle = bpv7::ValidBundle::parse(&data, |_, _| Ok(None))?;
+
+        // Drop spoofed bundles before they are stored or reported on
+        if let bpv7::ValidBundle::Valid(bundle, _)
+        | bpv7::ValidBundle::Rewritten(bundle, _, _)
+        | bpv7::ValidBundle::Invalid(bundle, _, _) = &bundle
+        {
+            if self.source_filter.reject(cla_ident, &bundle.id.source) {
+                return Ok(());
+            }
+        }
+
+        // Link our processing to the upstream trace, if there is one
+        let span = match &bundle {
+            bpv7::ValidBundle::Valid(bundle, _)
+            | bpv7::ValidBundle::Rewritten(bundle, _, _)
+            | bpv7::ValidBundle::Invalid(bundle, _, _)
+                if self.config.trace_propagation() =>
+            {
+                trace_context::TraceContext::extract(bundle, &data).map(|ctx| {
+                    let span = tracing::info_span!("bundle");
+                    span.set_parent(ctx.otel_context());
+                    span
+                })
+            }
+            _ => None,
+        }
+        .unwrap_or_else(tracing::Span::none);
+
+        match bundle {
             bpv7::ValidBundle::Valid(bundle, report_unsupported) => {
+                let (reason, report_unsupported) =
+                    self.config.unsupported_blocks.apply(report_unsupported);
+
                 // Write the bundle data to the store
-                let (storage_name, hash) = self.store.store_data(&data).await?;
+                let (metadata, reason) = self
+                    .store_received(&data, &bundle, received_at, reason)
+                    .await?;
                 self.ingress_bundle(
-                    metadata::Bundle {
-                        metadata: metadata::Metadata {
-                            storage_name: Some(storage_name),
-                            hash: Some(hash),
-                            received_at,
-                            ..Default::default()
-                        },
-                        bundle,
-                    },
-                    None,
+                    metadata::Bundle { metadata, bundle },
+                    reason,
                     report_unsupported,
                 )
             }
             bpv7::ValidBundle::Rewritten(bundle, data, report_unsupported) => {
+                let (reason, report_unsupported) =
+                    self.config.unsupported_blocks.apply(report_unsupported);
+
                 // Write the bundle data to the store
-                let (storage_name, hash) = self.store.store_data(&data).await?;
+                let (metadata, reason) = self
+                    .store_received(&data, &bundle, received_at, reason)
+                    .await?;
                 self.ingress_bundle(
-                    metadata::Bundle {
-                        metadata: metadata::Metadata {
-                            storage_name: Some(storage_name),
-                            hash: Some(hash),
-                            received_at,
-                            ..Default::default()
-                        },
-                        bundle,
-                    },
-                    None,
+                    metadata::Bundle { metadata, bundle },
+                    reason,
                     report_unsupported,
                 )
             }
@@ -61,9 +98,7 @@ impl Dispatcher {
                 self.ingress_bundle(
                     metadata::Bundle {
                         metadata: metadata::Metadata {
-                            status: metadata::BundleStatus::Tombstone(
-                                time::OffsetDateTime::now_utc(),
-                            ),
+                            status: metadata::BundleStatus::Tombstone(self.clock.now()),
                             received_at,
                             ..Default::default()
                         },
@@ -74,16 +109,77 @@ impl Dispatcher {
                 )
             }
         }
+        .instrument(span)
         .await
     }
 
+    /* Write the bundle data to the store.  If the store refuses the bundle,
+     * it is tombstoned and the reason becomes 'Depleted Storage' */
+    async fn store_received(
+        &self,
+        data: &[u8],
+        bundle: &bpv7::Bundle,
+        received_at: Option<time::OffsetDateTime>,
+        reason: Option<bpv7::StatusReportReasonCode>,
+    ) -> Result<(metadata::Metadata, Option<bpv7::StatusReportReasonCode>), Error> {
+        Ok(
+            match self
+                .store
+                .store_data(data, store::Priority::of(bundle))
+                .await?
+            {
+                Some((storage_name, hash)) => (
+                    metadata::Metadata {
+                        storage_name: Some(storage_name),
+                        hash: Some(hash),
+                        received_at,
+                        ..Default::default()
+                    },
+                    reason,
+                ),
+                None => (
+                    metadata::Metadata {
+                        status: metadata::BundleStatus::Tombstone(self.clock.now()),
+                        received_at,
+                        ..Default::default()
+                    },
+                    Some(bpv7::StatusReportReasonCode::DepletedStorage),
+                ),
+            },
+        )
+    }
+
     #[instrument(skip(self))]
     pub async fn ingress_bundle(
         &self,
-        bundle: metadata::Bundle,
-        reason: Option<bpv7::StatusReportReasonCode>,
+        mut bundle: metadata::Bundle,
+        mut reason: Option<bpv7::StatusReportReasonCode>,
         report_unsupported: bool,
     ) -> Result<(), Error> {
+        // A cheap check for recent duplicates first, if enabled
+        if let Some(dedup_filter) = &self.dedup_filter {
+            if dedup_filter.contains(&bundle.bundle.id, self.clock.now()) {
+                return self.drop_duplicate(&bundle).await;
+            }
+        }
+
+        // Drop duplicates before we report reception again
+        if self.store.check_status(&bundle.bundle.id).await?.is_some() {
+            return self.drop_duplicate(&bundle).await;
+        }
+
+        // Deal with bundles from sources whose clock is ahead of ours, before the lifetime is stored
+        if reason.is_none() {
+            let now = bundle
+                .metadata
+                .received_at
+                .unwrap_or_else(|| self.clock.now());
+            reason = self.clock_skew.apply(&mut bundle, now);
+        }
+
+        self.events
+            .emit(|| DispatchEvent::Received(bundle.bundle.id.clone()));
+
         // Report we have received the bundle
         let mut r = self
             .report_bundle_reception(
@@ -111,16 +207,16 @@ impl Dispatcher {
                 .store_metadata(&bundle.metadata, &bundle.bundle)
                 .await
             {
-                Ok(true) => Ok(()),
-                Ok(false) => {
-                    // Bundle with matching id already exists in the metadata store
-                    trace!("Bundle with matching id already exists in the metadata store");
-
-                    // Drop the stored data if it was valid, and do not process further
-                    if let Some(storage_name) = &bundle.metadata.storage_name {
-                        self.store.delete_data(storage_name).await?;
+                Ok(true) => {
+                    // Only remember accepted bundles, so a retransmission after a failure is not dropped
+                    if let Some(dedup_filter) = &self.dedup_filter {
+                        dedup_filter.insert(&bundle.bundle.id, self.clock.now());
                     }
-                    return Ok(());
+                    Ok(())
+                }
+                Ok(false) => {
+                    // Bundle with matching id arrived while we were reporting
+                    return self.drop_duplicate(&bundle).await;
                 }
                 Err(e) => Err(e),
             };
@@ -141,6 +237,22 @@ impl Dispatcher {
         r
     }
 
+    async fn drop_duplicate(&self, bundle: &metadata::Bundle) -> Result<(), Error> {
+        let duplicates = self.duplicates.fetch_add(1, Ordering::Relaxed) + 1;
+        trace!("Bundle with matching id already exists in the metadata store, {duplicates} duplicates dropped");
+
+        // Drop the stored data if it was valid, and do not process further
+        if let Some(storage_name) = &bundle.metadata.storage_name {
+            self.store.delete_data(storage_name).await?;
+        }
+        Ok(())
+    }
+
+    // The number of bundles dropped on arrival as copies of one already held, for operators
+    pub fn duplicates(&self) -> u64 {
+        self.duplicates.load(Ordering::Relaxed)
+    }
+
     #[instrument(skip(self))]
     pub async fn check_bundle(
         &self,
@@ -160,7 +272,7 @@ impl Dispatcher {
 
         if reason.is_none() {
             // Check some basic semantic validity, lifetime first
-            if bundle.has_expired() {
+            if bundle.has_expired_at(self.clock.now()) {
                 trace!("Bundle lifetime has expired");
                 reason = Some(bpv7::StatusReportReasonCode::LifetimeExpired);
             } else if let Some(hop_info) = bundle.bundle.hop_count.as_ref() {
@@ -185,3 +297,105 @@ impl Dispatcher {
         self.dispatch_bundle(bundle).await
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::tests::{
+        dispatcher_config, new_dispatcher, new_test_dispatcher, test_store, TestBundles,
+        TestMetadata,
+    };
+
+    #[tokio::test]
+    async fn trace_parent() {
+        use opentelemetry::trace::{SpanId, TraceId, TracerProvider};
+        use tracing_subscriber::layer::SubscriberExt;
+
+        let exporter = opentelemetry_sdk::testing::trace::InMemorySpanExporter::default();
+        let provider = opentelemetry_sdk::trace::TracerProvider::builder()
+            .with_simple_exporter(exporter.clone())
+            .build();
+        let _guard = tracing::subscriber::set_default(
+            tracing_subscriber::registry()
+                .with(tracing_opentelemetry::layer().with_tracer(provider.tracer("test"))),
+        );
+
+        let store = Arc::new(test_store(
+            Arc::new(TestMetadata::default()),
+            Arc::new(TestBundles::default()),
+        ));
+        let mut task_set = tokio::task::JoinSet::new();
+        let cancel_token = tokio_util::sync::CancellationToken::new();
+        let dispatcher = new_test_dispatcher(
+            store,
+            &dispatcher_config()
+                .set_default("trace_propagation", true)
+                .unwrap()
+                .build()
+                .unwrap(),
+            &[],
+            &mut task_set,
+            cancel_token.clone(),
+        )
+        .await
+        .dispatcher;
+
+        // A bundle from an upstream node that is already part of a trace
+        let upstream = trace_context::TraceContext::new_root();
+        let (_, data) = bpv7::Builder::new()
+            .source("ipn:2.1".parse().unwrap())
+            .destination("ipn:3.1".parse().unwrap())
+            .add_extension_block(trace_context::TraceContext::block_type())
+            .data(cbor::encode::emit(&upstream))
+            .build()
+            .add_payload_block(vec![1, 2, 3])
+            .build();
+        dispatcher.receive_bundle(data.into(), None).await.unwrap();
+
+        // Our processing of the bundle continues the upstream trace
+        let spans = exporter.get_finished_spans().unwrap();
+        let span = spans
+            .iter()
+            .find(|span| span.name == "bundle")
+            .expect("No bundle span");
+        assert_eq!(
+            span.span_context.trace_id(),
+            TraceId::from_bytes(upstream.trace_id)
+        );
+        assert_eq!(span.parent_span_id, SpanId::from_bytes(upstream.span_id));
+
+        cancel_token.cancel();
+        while task_set.join_next().await.is_some() {}
+    }
+
+    #[tokio::test]
+    async fn duplicate() {
+        let metadata_storage = Arc::new(TestMetadata::default());
+        let bundle_storage = Arc::new(TestBundles::default());
+        let store = Arc::new(test_store(metadata_storage.clone(), bundle_storage.clone()));
+
+        let mut task_set = tokio::task::JoinSet::new();
+        let cancel_token = tokio_util::sync::CancellationToken::new();
+        let dispatcher = new_dispatcher(store, &mut task_set, cancel_token.clone());
+
+        let (_, data) = bpv7::Builder::new()
+            .source("ipn:2.1".parse().unwrap())
+            .destination("ipn:1.1".parse().unwrap())
+            .add_payload_block(vec![1, 2, 3])
+            .build();
+        let data = tokio_util::bytes::Bytes::from(data);
+
+        dispatcher.receive_bundle(data.clone(), None).await.unwrap();
+        assert_eq!(dispatcher.duplicates(), 0);
+
+        dispatcher.receive_bundle(data, None).await.unwrap();
+        assert_eq!(dispatcher.duplicates(), 1);
+
+        // Only the first copy is kept
+        assert_eq!(metadata_storage.0.lock().unwrap().len(), 1);
+        assert!(!bundle_storage.0.lock().unwrap().contains_key("stored1"));
+
+        cancel_token.cancel();
+        while task_set.join_next().await.is_some() {}
+    }
+}
@@ -0,0 +1,55 @@
+use super::*;
+use thiserror::Error;
+use tokio::sync::{Semaphore, SemaphorePermit};
+
+#[derive(Error, Debug)]
+#[error("Ingress queue is full")]
+pub struct Backpressure;
+
+/* Bounds the number of received bundles being processed at once.  When full, further bundles
+ * are refused with Backpressure rather than queued, so a CLA can slow its reads instead of
+ * the BPA buffering without bound */
+pub struct IngressQueue {
+    semaphore: Option<Semaphore>,
+}
+
+impl IngressQueue {
+    pub fn new(depth: u32) -> Self {
+        Self {
+            semaphore: (depth != 0).then(|| Semaphore::new(depth as usize)),
+        }
+    }
+
+    // Take a place in the queue, which is released when the permit is dropped
+    pub fn enter(&self) -> Result<Option<SemaphorePermit<'_>>, Backpressure> {
+        self.semaphore
+            .as_ref()
+            .map(|s| s.try_acquire().map_err(|_| Backpressure))
+            .transpose()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn saturate() {
+        let queue = IngressQueue::new(2);
+        let first = queue.enter().unwrap();
+        let _second = queue.enter().unwrap();
+
+        // Full, so signal backpressure rather than queue
+        for _ in 0..100 {
+            assert!(matches!(queue.enter(), Err(Backpressure)));
+        }
+
+        // Until there is room again
+        drop(first);
+        assert!(queue.enter().unwrap().is_some());
+
+        // A zero depth is unlimited
+        let queue = IngressQueue::new(0);
+        let _permits = (0..100).map(|_| queue.enter().unwrap()).collect::<Vec<_>>();
+    }
+}
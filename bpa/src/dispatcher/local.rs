@@ -10,8 +10,45 @@ pub struct SendRequest {
 }
 
 impl Dispatcher {
+    /* Originate a bundle in one call, returning its id for status tracking.
+     * If `source` is None, the bundle is sent from the administrative endpoint */
+    #[instrument(skip(self, payload))]
+    pub async fn send(
+        &self,
+        source: Option<bpv7::Eid>,
+        destination: bpv7::Eid,
+        payload: Bytes,
+        lifetime: Option<std::time::Duration>,
+        flags: Option<bpv7::BundleFlags>,
+    ) -> Result<bpv7::BundleId, Error> {
+        self.local_dispatch(SendRequest {
+            source: source
+                .unwrap_or_else(|| self.config.admin_endpoints.get_admin_endpoint(&destination)),
+            destination,
+            data: payload,
+            lifetime: lifetime.map(|l| l.as_millis().try_into().unwrap_or(u64::MAX)),
+            flags,
+        })
+        .await
+    }
+
+    /* The largest bundle the outbound path to `destination` will carry unfragmented,
+     * so services can split their data up front, or None if there is no known limit.
+     * Room is left for the extension blocks we add as the bundle is forwarded */
+    pub async fn path_budget(&self, destination: &bpv7::Eid) -> Option<usize> {
+        self.fib
+            .as_ref()?
+            .path_budget(destination)
+            .await
+            .map(|max_bundle_size| {
+                usize::try_from(max_bundle_size)
+                    .unwrap_or(usize::MAX)
+                    .saturating_sub(self.egress_growth(destination))
+            })
+    }
+
     #[instrument(skip(self))]
-    pub async fn local_dispatch(&self, mut request: SendRequest) -> Result<(), Error> {
+    async fn local_dispatch(&self, mut request: SendRequest) -> Result<bpv7::BundleId, Error> {
         // Check to see if we should use ipn 2-element encoding
         if let bpv7::Eid::Ipn {
             allocator_id: da,
@@ -68,7 +105,7 @@ impl Dispatcher {
             .source(request.source)
             .destination(request.destination)
             .add_payload_block(request.data.into())
-            .build();
+            .try_build()?;
 
         // Store to store
         let metadata = self
@@ -78,7 +115,9 @@ impl Dispatcher {
             .trace_expect("Duplicate bundle generated by builder!");
 
         // And get it dispatched
+        let bundle_id = bundle.id.clone();
         self.dispatch_bundle(metadata::Bundle { metadata, bundle })
             .await
+            .map(|_| bundle_id)
     }
 }
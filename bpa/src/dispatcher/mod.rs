@@ -1,29 +1,66 @@
 mod admin;
+mod clock_skew;
 mod collect;
 mod config;
+mod decision;
+mod dedup_filter;
+mod delivery_ack;
 mod dispatch;
+mod events;
+mod fan_out;
 mod forward;
 mod fragment;
+mod in_flight;
 mod ingress;
+mod ingress_queue;
 mod local;
 mod report;
+mod report_throttle;
+mod source_filter;
+mod source_route;
+mod trace_context;
 
 use super::*;
+use decision::Decision;
 use dispatch::DispatchResult;
 use hardy_cbor as cbor;
-pub use local::SendRequest;
-use std::sync::Arc;
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc,
+};
 use tokio_util::bytes::Bytes;
 use utils::cancel::cancellable_sleep;
 
+pub use self::config::{UnsupportedBlocks, FORWARD_ACK_TIMEOUT_SECS, STATUS_REPORT_WINDOW_SECS};
+pub use admin::AdminRecords;
+pub use clock_skew::FutureBundles;
+pub use decision::DispatchLatency;
+pub use events::{DispatchEvent, EventReceiver};
+pub use fan_out::LocalDelivery;
+pub use ingress_queue::Backpressure;
+pub use source_filter::SpoofedSources;
+pub use source_route::SourceRoute;
+
 pub struct Dispatcher {
     config: self::config::Config,
     cancel_token: tokio_util::sync::CancellationToken,
     store: Arc<store::Store>,
+    clock: Arc<dyn utils::clock::Clock>,
     tx: tokio::sync::mpsc::Sender<metadata::Bundle>,
     cla_registry: cla_registry::ClaRegistry,
     app_registry: app_registry::AppRegistry,
     fib: Option<fib::Fib>,
+    duplicates: AtomicU64,
+    in_flight: in_flight::InFlightLimiter,
+    ingress_queue: ingress_queue::IngressQueue,
+    report_throttle: report_throttle::ReportThrottle,
+    source_filter: source_filter::SourceFilter,
+    clock_skew: clock_skew::ClockSkew,
+    dedup_filter: Option<dedup_filter::DedupFilter>,
+    fan_out: fan_out::FanOut,
+    delivery_acks: delivery_ack::DeliveryAcks,
+    events: events::Events,
+    dispatch_latency: DispatchLatency,
 }
 
 impl Dispatcher {
@@ -40,14 +77,33 @@ impl Dispatcher {
     ) -> Arc<Self> {
         // Create a channel for bundles
         let (tx, rx) = tokio::sync::mpsc::channel(16);
+        let source_filter = source_filter::SourceFilter::new(config);
+        let clock_skew = clock_skew::ClockSkew::new(config);
+        let dedup_filter = dedup_filter::DedupFilter::new(config);
+        let config = self::config::Config::new(config, admin_endpoints);
         let dispatcher = Arc::new(Self {
-            config: self::config::Config::new(config, admin_endpoints),
+            source_filter,
+            clock_skew,
+            dedup_filter,
+            fan_out: Default::default(),
+            delivery_acks: Default::default(),
+            events: Default::default(),
+            dispatch_latency: Default::default(),
+            in_flight: in_flight::InFlightLimiter::new(config.max_in_flight),
+            ingress_queue: ingress_queue::IngressQueue::new(config.max_ingress_queue),
+            report_throttle: report_throttle::ReportThrottle::new(
+                config.status_report_limit,
+                config.status_report_window,
+            ),
+            config,
             cancel_token,
+            clock: store.clock().clone(),
             store,
             tx,
             cla_registry,
             app_registry,
             fib,
+            duplicates: AtomicU64::new(0),
         });
 
         // Spawn the dispatch task
@@ -57,17 +113,50 @@ impl Dispatcher {
         dispatcher
     }
 
+    /* Observe what happens to bundles as they pass through, without polling storage.
+     * Events are only emitted while there is at least one subscriber */
+    pub fn subscribe_events(&self) -> EventReceiver {
+        self.events.subscribe()
+    }
+
+    #[inline]
+    pub async fn query_bundles(
+        &self,
+        destination: &bpv7::EidPattern,
+        limit: usize,
+    ) -> Result<Vec<store::BundleSummary>, Error> {
+        self.store.query_bundles(destination, limit).await
+    }
+
+    // The number of bundles held in each status, for operators watching queue depths
+    #[inline]
+    pub async fn bundle_counts(&self) -> Result<metadata::BundleCounts, Error> {
+        self.store.bundle_counts().await
+    }
+
+    #[inline]
+    pub fn dispatch_latency(&self) -> &DispatchLatency {
+        &self.dispatch_latency
+    }
+
+    pub fn reload_config(&self, config: &::config::Config) -> Result<(), Error> {
+        utils::logger::reload(config);
+        self.config.reload(config).map_err(Into::into)
+    }
+
     async fn load_data(
         &self,
-        bundle: &metadata::Bundle,
+        bundle: &mut metadata::Bundle,
     ) -> Result<Option<hardy_bpa_api::storage::DataRef>, Error> {
         // Try to load the data, but treat errors as 'Storage Depleted'
-        let storage_name = bundle.metadata.storage_name.as_ref().unwrap();
-        if let Some(data) = self.store.load_data(storage_name).await? {
+        if let Some(data) = self.store.load_bundle_data(bundle).await? {
             return Ok(Some(data));
         }
 
-        warn!("Bundle data {storage_name} has gone from storage");
+        warn!(
+            "Bundle data {} has gone from storage",
+            bundle.metadata.storage_name.as_deref().unwrap_or_default()
+        );
 
         // Report the bundle has gone
         self.report_bundle_deletion(bundle, bpv7::StatusReportReasonCode::DepletedStorage)
@@ -85,6 +174,9 @@ impl Dispatcher {
             self.report_bundle_deletion(&bundle, reason).await?;
         }
 
+        // Nothing can acknowledge a dropped bundle
+        self.delivery_acks.forget_bundle(&bundle.bundle.id);
+
         // Leave a tombstone in the metadata, so we can ignore duplicates
         if let metadata::BundleStatus::Tombstone(_) = bundle.metadata.status {
             // Don't update Tombstone timestamp
@@ -92,7 +184,7 @@ impl Dispatcher {
             self.store
                 .set_status(
                     &mut bundle,
-                    metadata::BundleStatus::Tombstone(time::OffsetDateTime::now_utc()),
+                    metadata::BundleStatus::Tombstone(self.clock.now()),
                 )
                 .await?;
         }
@@ -34,6 +34,8 @@ impl Dispatcher {
                 },
             )),
             &bundle.bundle.report_to,
+            report_throttle::ReportKind::Received,
+            reason,
         )
         .await
     }
@@ -43,6 +45,9 @@ impl Dispatcher {
         &self,
         bundle: &metadata::Bundle,
     ) -> Result<(), Error> {
+        self.events
+            .emit(|| DispatchEvent::Forwarded(bundle.bundle.id.clone()));
+
         // Check if a report is requested
         if !bundle.bundle.flags.forward_report_requested {
             return Ok(());
@@ -68,6 +73,8 @@ impl Dispatcher {
                 },
             )),
             &bundle.bundle.report_to,
+            report_throttle::ReportKind::Forwarded,
+            bpv7::StatusReportReasonCode::NoAdditionalInformation,
         )
         .await
     }
@@ -77,6 +84,9 @@ impl Dispatcher {
         &self,
         bundle: &metadata::Bundle,
     ) -> Result<(), Error> {
+        self.events
+            .emit(|| DispatchEvent::Delivered(bundle.bundle.id.clone()));
+
         // Check if a report is requested
         if !bundle.bundle.flags.delivery_report_requested {
             return Ok(());
@@ -100,6 +110,8 @@ impl Dispatcher {
                 },
             )),
             &bundle.bundle.report_to,
+            report_throttle::ReportKind::Delivered,
+            bpv7::StatusReportReasonCode::NoAdditionalInformation,
         )
         .await
     }
@@ -110,6 +122,13 @@ impl Dispatcher {
         bundle: &metadata::Bundle,
         reason: bpv7::StatusReportReasonCode,
     ) -> Result<(), Error> {
+        self.events.emit(|| match reason {
+            bpv7::StatusReportReasonCode::LifetimeExpired => {
+                DispatchEvent::Expired(bundle.bundle.id.clone())
+            }
+            reason => DispatchEvent::Dropped(bundle.bundle.id.clone(), reason),
+        });
+
         // Check if a report is requested
         if !bundle.bundle.flags.delete_report_requested {
             return Ok(());
@@ -134,6 +153,8 @@ impl Dispatcher {
                 },
             )),
             &bundle.bundle.report_to,
+            report_throttle::ReportKind::Deleted,
+            reason,
         )
         .await
     }
@@ -143,9 +164,11 @@ impl Dispatcher {
         &self,
         payload: Vec<u8>,
         report_to: &bpv7::Eid,
+        kind: report_throttle::ReportKind,
+        reason: bpv7::StatusReportReasonCode,
     ) -> Result<(), Error> {
         // Check reports are enabled
-        if !self.config.status_reports {
+        if !self.config.status_reports() {
             return Ok(());
         }
 
@@ -154,6 +177,17 @@ impl Dispatcher {
             return Ok(());
         }
 
+        // The null endpoint cannot receive reports, and a group would multiply them
+        if !report_to.is_singleton() {
+            return Ok(());
+        }
+
+        // Don't add to a report storm
+        if !self.report_throttle.allow(report_to, kind, reason) {
+            trace!("Suppressing {kind:?} {reason:?} status report to {report_to}");
+            return Ok(());
+        }
+
         // Build the bundle
         let (bundle, data) = bpv7::Builder::new()
             .flags(bpv7::BundleFlags {
@@ -165,12 +199,19 @@ impl Dispatcher {
             .add_payload_block(payload)
             .build();
 
-        // Store to store
-        let metadata = self
+        // Store to store, reports are best effort so a full store just loses them
+        let metadata = match self
             .store
             .store(&bundle, &data, metadata::BundleStatus::default(), None)
-            .await?
-            .trace_expect("Duplicate bundle generated by builder!");
+            .await
+        {
+            Ok(metadata) => metadata.trace_expect("Duplicate bundle generated by builder!"),
+            Err(e) if e.is::<store::StorageFull>() => {
+                info!("Bundle storage is full, dropping {reason:?} status report to {report_to}");
+                return Ok(());
+            }
+            Err(e) => return Err(e),
+        };
 
         // Put bundle into channel
         self.dispatch_bundle(metadata::Bundle { metadata, bundle })
@@ -0,0 +1,153 @@
+use super::*;
+use std::collections::HashMap;
+
+// The assertion a status report makes about a bundle
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ReportKind {
+    Received,
+    Forwarded,
+    Delivered,
+    Deleted,
+}
+
+type Key = (bpv7::Eid, ReportKind, bpv7::StatusReportReasonCode);
+
+// The reports sent in the current window
+struct Bucket {
+    start: time::OffsetDateTime,
+    counts: HashMap<Key, u32>,
+}
+
+/* Limits the number of status reports of each kind and reason generated for each report-to endpoint
+ * within a window, so a flood of undeliverable bundles cannot become a flood of reports, or a report loop.
+ * Every count is dropped together when the window ends, so nothing needs pruning as it goes */
+pub struct ReportThrottle {
+    max_reports: u32,
+    window: time::Duration,
+    bucket: std::sync::Mutex<Bucket>,
+}
+
+impl ReportThrottle {
+    pub fn new(max_reports: u32, window_secs: u64) -> Self {
+        Self {
+            max_reports,
+            window: time::Duration::seconds(window_secs.min(i64::MAX as u64) as i64),
+            bucket: std::sync::Mutex::new(Bucket {
+                start: time::OffsetDateTime::now_utc(),
+                counts: HashMap::new(),
+            }),
+        }
+    }
+
+    // Should a `kind` report for `reason` be sent to `report_to` now?
+    pub fn allow(
+        &self,
+        report_to: &bpv7::Eid,
+        kind: ReportKind,
+        reason: bpv7::StatusReportReasonCode,
+    ) -> bool {
+        if self.max_reports == 0 {
+            return true;
+        }
+
+        let now = time::OffsetDateTime::now_utc();
+        let mut bucket = self
+            .bucket
+            .lock()
+            .trace_expect("Failed to lock report throttle mutex");
+
+        if now - bucket.start >= self.window {
+            bucket.start = now;
+            bucket.counts.clear();
+        }
+
+        let count = bucket
+            .counts
+            .entry((report_to.clone(), kind, reason))
+            .or_default();
+        if *count < self.max_reports {
+            *count += 1;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn storm() {
+        let throttle = ReportThrottle::new(5, 60);
+        let report_to: bpv7::Eid = "ipn:2.1".parse().unwrap();
+
+        // Many deletions reported to the same endpoint
+        let sent = (0..100)
+            .filter(|_| {
+                throttle.allow(
+                    &report_to,
+                    ReportKind::Deleted,
+                    bpv7::StatusReportReasonCode::LifetimeExpired,
+                )
+            })
+            .count();
+        assert_eq!(sent, 5);
+
+        // Other reasons and other endpoints have their own allowance
+        assert!(throttle.allow(
+            &report_to,
+            ReportKind::Deleted,
+            bpv7::StatusReportReasonCode::NoKnownRouteToDestinationFromHere
+        ));
+        assert!(throttle.allow(
+            &"ipn:3.1".parse().unwrap(),
+            ReportKind::Deleted,
+            bpv7::StatusReportReasonCode::LifetimeExpired
+        ));
+
+        // As do other kinds of report with the same reason
+        let sent = (0..100)
+            .filter(|_| {
+                throttle.allow(
+                    &report_to,
+                    ReportKind::Forwarded,
+                    bpv7::StatusReportReasonCode::NoAdditionalInformation,
+                )
+            })
+            .count();
+        assert_eq!(sent, 5);
+        assert!(throttle.allow(
+            &report_to,
+            ReportKind::Delivered,
+            bpv7::StatusReportReasonCode::NoAdditionalInformation
+        ));
+
+        // A zero limit is unlimited
+        let throttle = ReportThrottle::new(0, 60);
+        assert!((0..100).all(|_| throttle.allow(
+            &report_to,
+            ReportKind::Deleted,
+            bpv7::StatusReportReasonCode::LifetimeExpired
+        )));
+    }
+
+    #[test]
+    fn window() {
+        let throttle = ReportThrottle::new(1, 0);
+        let report_to: bpv7::Eid = "ipn:2.1".parse().unwrap();
+
+        // A zero length window has always expired
+        assert!(throttle.allow(
+            &report_to,
+            ReportKind::Deleted,
+            bpv7::StatusReportReasonCode::LifetimeExpired
+        ));
+        assert!(throttle.allow(
+            &report_to,
+            ReportKind::Deleted,
+            bpv7::StatusReportReasonCode::LifetimeExpired
+        ));
+    }
+}
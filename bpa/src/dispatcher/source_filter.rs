@@ -0,0 +1,96 @@
+use super::*;
+use std::collections::HashMap;
+use utils::settings;
+
+// What to do with a bundle whose source a CLA is not allowed to originate
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SpoofedSources {
+    #[default]
+    Drop,
+    Log,
+}
+
+/* Restricts the source EIDs that bundles received from each CLA may claim, by CLA ident.
+ * Bundles from CLAs without a configured pattern are not checked */
+pub struct SourceFilter {
+    allowed: HashMap<String, bpv7::EidPattern>,
+    action: SpoofedSources,
+}
+
+impl SourceFilter {
+    pub fn new(config: &::config::Config) -> Self {
+        let allowed = config
+            .get::<HashMap<String, String>>("ingress_sources")
+            .unwrap_or_default()
+            .into_iter()
+            .map(|(cla, s)| {
+                let p = s.parse().trace_expect(&format!(
+                    "Invalid EID pattern '{s}' for CLA '{cla}' in 'ingress_sources'"
+                ));
+                (cla, p)
+            })
+            .collect::<HashMap<_, _>>();
+
+        let action =
+            settings::get_with_default(config, "spoofed_sources", SpoofedSources::default())
+                .trace_expect("Invalid 'spoofed_sources' value in configuration");
+
+        for (cla, pattern) in &allowed {
+            info!("Bundles received by CLA '{cla}' must have a source matching '{pattern}'");
+        }
+
+        Self { allowed, action }
+    }
+
+    // Should a bundle from `source`, received by the CLA `cla_ident`, be dropped?
+    pub fn reject(&self, cla_ident: Option<&str>, source: &bpv7::Eid) -> bool {
+        let Some(pattern) = cla_ident.and_then(|cla| self.allowed.get(cla)) else {
+            return false;
+        };
+        if pattern.is_match(source) {
+            return false;
+        }
+
+        warn!(
+            "CLA '{}' received a bundle from disallowed source {source}",
+            cla_ident.unwrap_or_default()
+        );
+        self.action == SpoofedSources::Drop
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn build_filter(action: &str) -> SourceFilter {
+        SourceFilter::new(
+            &::config::Config::builder()
+                .set_override("ingress_sources.tcpcl", "ipn:2.*")
+                .unwrap()
+                .set_override("spoofed_sources", action)
+                .unwrap()
+                .build()
+                .unwrap(),
+        )
+    }
+
+    #[test]
+    fn spoofed() {
+        let filter = build_filter("drop");
+        let allowed: bpv7::Eid = "ipn:2.1".parse().unwrap();
+        let spoofed: bpv7::Eid = "ipn:3.1".parse().unwrap();
+
+        assert!(!filter.reject(Some("tcpcl"), &allowed));
+        assert!(filter.reject(Some("tcpcl"), &spoofed));
+
+        // Unrestricted CLAs, and bundles not received by a CLA, are not checked
+        assert!(!filter.reject(Some("udp"), &spoofed));
+        assert!(!filter.reject(None, &spoofed));
+
+        // Spoofed bundles can be logged but still accepted
+        let filter = build_filter("log");
+        assert!(!filter.reject(Some("tcpcl"), &spoofed));
+    }
+}
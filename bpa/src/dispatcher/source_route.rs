@@ -0,0 +1,58 @@
+use super::*;
+
+// From the RFC 9171 private/experimental use range
+pub const SOURCE_ROUTE_BLOCK_TYPE: u64 = 193;
+
+/* The next hops of an explicit path that a bundle has yet to visit, in order.
+ * The path is carried with the bundle, so each node on it can pop its own next hop */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourceRoute(pub Vec<bpv7::Eid>);
+
+impl SourceRoute {
+    pub fn block_type() -> bpv7::BlockType {
+        bpv7::BlockType::Unrecognised(SOURCE_ROUTE_BLOCK_TYPE)
+    }
+
+    pub fn extract(bundle: &bpv7::Bundle, data: &[u8]) -> Option<Self> {
+        let block = bundle
+            .blocks
+            .values()
+            .find(|block| block.block_type == Self::block_type())?;
+
+        cbor::decode::parse_value(block.payload(data), |value, _, _| match value {
+            cbor::decode::Value::Bytes(data) => cbor::decode::parse(data),
+            value => Err(bpv7::EidError::from(cbor::decode::Error::IncorrectType(
+                "Byte String".to_string(),
+                value.type_name(false),
+            ))),
+        })
+        .map(|(route, _)| route)
+        .inspect_err(|e| trace!("Ignoring invalid source route block: {e}"))
+        .ok()
+    }
+}
+
+impl cbor::encode::ToCbor for &SourceRoute {
+    fn to_cbor(self, encoder: &mut cbor::encode::Encoder) {
+        encoder.emit_array(Some(self.0.len()), |a| {
+            for hop in &self.0 {
+                a.emit(hop);
+            }
+        })
+    }
+}
+
+impl cbor::decode::FromCbor for SourceRoute {
+    type Error = bpv7::EidError;
+
+    fn try_from_cbor(data: &[u8]) -> Result<Option<(Self, bool, usize)>, Self::Error> {
+        cbor::decode::try_parse_array(data, |a, shortest, tags| {
+            let mut hops = Vec::new();
+            while let Some(hop) = a.try_parse()? {
+                hops.push(hop);
+            }
+            Ok::<_, bpv7::EidError>((Self(hops), shortest && tags.is_empty() && a.is_definite()))
+        })
+        .map(|o| o.map(|((v, s), len)| (v, s, len)))
+    }
+}
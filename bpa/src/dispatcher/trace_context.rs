@@ -0,0 +1,179 @@
+use super::*;
+use rand::Rng;
+
+// From the RFC 9171 private/experimental use range
+pub const TRACE_CONTEXT_BLOCK_TYPE: u64 = 192;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceContext {
+    pub trace_id: [u8; 16],
+    pub span_id: [u8; 8],
+}
+
+impl TraceContext {
+    pub fn new_root() -> Self {
+        let mut rng = rand::thread_rng();
+        Self {
+            trace_id: rng.gen(),
+            span_id: rng.gen(),
+        }
+    }
+
+    pub fn new_child(&self) -> Self {
+        Self {
+            trace_id: self.trace_id,
+            span_id: rand::thread_rng().gen(),
+        }
+    }
+
+    pub fn trace_id_hex(&self) -> String {
+        hex_string(&self.trace_id)
+    }
+
+    pub fn span_id_hex(&self) -> String {
+        hex_string(&self.span_id)
+    }
+
+    // The upstream span, as a remote parent for our own spans
+    pub fn otel_context(&self) -> opentelemetry::Context {
+        use opentelemetry::trace::{
+            SpanContext, SpanId, TraceContextExt, TraceFlags, TraceId, TraceState,
+        };
+
+        opentelemetry::Context::new().with_remote_span_context(SpanContext::new(
+            TraceId::from_bytes(self.trace_id),
+            SpanId::from_bytes(self.span_id),
+            TraceFlags::SAMPLED,
+            true,
+            TraceState::default(),
+        ))
+    }
+
+    pub fn block_type() -> bpv7::BlockType {
+        bpv7::BlockType::Unrecognised(TRACE_CONTEXT_BLOCK_TYPE)
+    }
+
+    pub fn extract(bundle: &bpv7::Bundle, data: &[u8]) -> Option<Self> {
+        let block = bundle
+            .blocks
+            .values()
+            .find(|block| block.block_type == Self::block_type())?;
+
+        cbor::decode::parse_value(block.payload(data), |value, _, _| match value {
+            cbor::decode::Value::Bytes(data) => cbor::decode::parse(data),
+            value => Err(cbor::decode::Error::IncorrectType(
+                "Byte String".to_string(),
+                value.type_name(false),
+            )),
+        })
+        .map(|(ctx, _)| ctx)
+        .inspect_err(|e| trace!("Ignoring invalid trace context block: {e}"))
+        .ok()
+    }
+}
+
+fn hex_string(v: &[u8]) -> String {
+    v.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+fn parse_fixed<const N: usize>(
+    a: &mut cbor::decode::Array,
+) -> Result<[u8; N], cbor::decode::Error> {
+    a.parse_value(|value, _, _| match value {
+        cbor::decode::Value::Bytes(v) => v.try_into().map_err(|_| {
+            cbor::decode::Error::IncorrectType(
+                format!("{N} byte Byte String"),
+                format!("{} byte Byte String", v.len()),
+            )
+        }),
+        value => Err(cbor::decode::Error::IncorrectType(
+            "Byte String".to_string(),
+            value.type_name(false),
+        )),
+    })
+}
+
+impl cbor::encode::ToCbor for &TraceContext {
+    fn to_cbor(self, encoder: &mut cbor::encode::Encoder) {
+        encoder.emit_array(Some(2), |a| {
+            a.emit(&self.trace_id);
+            a.emit(&self.span_id);
+        })
+    }
+}
+
+impl cbor::decode::FromCbor for TraceContext {
+    type Error = cbor::decode::Error;
+
+    fn try_from_cbor(data: &[u8]) -> Result<Option<(Self, bool, usize)>, Self::Error> {
+        cbor::decode::try_parse_array(data, |a, shortest, tags| {
+            Ok::<_, cbor::decode::Error>((
+                Self {
+                    trace_id: parse_fixed(a)?,
+                    span_id: parse_fixed(a)?,
+                },
+                shortest && tags.is_empty() && a.is_definite(),
+            ))
+        })
+        .map(|o| o.map(|((v, s), len)| (v, s, len)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn extract_and_inject() {
+        let ctx = TraceContext::new_root();
+
+        let (bundle, data) = bpv7::Builder::new()
+            .source("ipn:1.1".parse().unwrap())
+            .destination("ipn:2.1".parse().unwrap())
+            .add_extension_block(TraceContext::block_type())
+            .data(cbor::encode::emit(&ctx))
+            .build()
+            .add_payload_block(Vec::new())
+            .build();
+
+        let bpv7::ValidBundle::Valid(bundle, _) =
+            bpv7::ValidBundle::parse(&data, |_, _| Ok(None)).unwrap()
+        else {
+            panic!("Invalid bundle");
+        };
+
+        // Extracted on ingest
+        assert_eq!(TraceContext::extract(&bundle, &data), Some(ctx.clone()));
+
+        // Re-injected on egress
+        let child = ctx.new_child();
+        let data = bpv7::Editor::new(&bundle, &data)
+            .replace_extension_block(TraceContext::block_type())
+            .data(cbor::encode::emit(&child))
+            .build()
+            .build();
+
+        let bpv7::ValidBundle::Valid(bundle, _) =
+            bpv7::ValidBundle::parse(&data, |_, _| Ok(None)).unwrap()
+        else {
+            panic!("Invalid bundle");
+        };
+
+        let extracted = TraceContext::extract(&bundle, &data).unwrap();
+        assert_eq!(extracted.trace_id, ctx.trace_id);
+        assert_eq!(extracted, child);
+    }
+
+    #[test]
+    fn wrong_length() {
+        // A 16 byte trace id, then a 4 byte span id
+        let mut data = vec![0x82, 0x50];
+        data.extend([1u8; 16]);
+        data.extend([0x44, 2, 2, 2, 2]);
+
+        assert!(matches!(
+            cbor::decode::parse::<TraceContext>(&data),
+            Err(cbor::decode::Error::IncorrectType(_, _))
+        ));
+    }
+}
@@ -8,7 +8,43 @@ use utils::settings;
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
 pub struct Endpoint {
     pub handle: u32, // The CLA handle
-                     // TODO: Metrics, e.g.: Bandwidth, Contact deadline
+
+    // Bundles larger than this must be fragmented
+    pub max_bundle_size: Option<u64>,
+
+    // The cost of the link from its reported quality, lower is better
+    pub cost: Option<u64>,
+
+    // The neighbour a lookup resolved to through this endpoint, filled in by find()
+    pub peer: Option<bpv7::Eid>,
+    // TODO: Metrics, e.g.: Contact deadline
+}
+
+/* The quality of the link to a neighbour, as reported by its CLA.
+ * Any measure may be left unreported */
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct LinkMetric {
+    pub rtt: Option<std::time::Duration>,
+    pub loss: Option<f32>,      // Fraction of bundles lost, from 0 to 1
+    pub bandwidth: Option<u64>, // Bytes per second
+}
+
+// The bundle size that link costs are estimated for
+const REFERENCE_BUNDLE_SIZE: f64 = 65536.0;
+
+impl LinkMetric {
+    /* The expected time in microseconds to deliver a reference sized bundle over the link,
+     * allowing for retransmission of lost bundles.  Unreported measures are assumed perfect */
+    pub fn cost(&self) -> u64 {
+        let rtt = self.rtt.unwrap_or_default().as_secs_f64();
+        let transmit = self
+            .bandwidth
+            .map_or(0.0, |b| REFERENCE_BUNDLE_SIZE / b.max(1) as f64);
+        let delivery = 1.0 - self.loss.unwrap_or(0.0).clamp(0.0, 0.999) as f64;
+
+        // Even a perfect link takes some time, so that loss always counts
+        ((rtt + transmit).max(0.000_001) / delivery * 1_000_000.0) as u64
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
@@ -17,6 +53,7 @@ pub enum Action {
     Forward(Endpoint),                          // Forward to CLA by Handle
     Via(bpv7::Eid),                             // Recursive lookup
     Wait(time::OffsetDateTime),                 // Wait for later availability
+    SourceRoute(Vec<bpv7::Eid>),                // Forward via each next hop in turn
 }
 
 impl std::fmt::Display for Action {
@@ -32,6 +69,14 @@ impl std::fmt::Display for Action {
             Action::Forward(c) => write!(f, "forward {}", c.handle),
             Action::Via(eid) => write!(f, "via {eid}"),
             Action::Wait(until) => write!(f, "Wait until {until}"),
+            Action::SourceRoute(hops) => write!(
+                f,
+                "source-route {}",
+                hops.iter()
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>()
+                    .join(",")
+            ),
         }
     }
 }
@@ -39,6 +84,8 @@ impl std::fmt::Display for Action {
 pub struct ForwardAction {
     pub clas: Vec<Endpoint>,                 // Available endpoints for forwarding
     pub until: Option<time::OffsetDateTime>, // Timestamp of next forwarding opportunity
+    pub priority: Option<u32>,               // Priority of the matched route
+    pub source_route: Vec<bpv7::Eid>,        // Explicit path to follow, if any
 }
 
 type ForwardResult = Result<ForwardAction, Option<bpv7::StatusReportReasonCode>>;
@@ -99,6 +146,34 @@ impl Fib {
         })
     }
 
+    /* Record the quality of the link to the neighbours matching `pattern`, as forwarded to by
+     * the CLA `handle`.  Returns false if there is no such route */
+    #[instrument(skip(self))]
+    pub async fn report_link_quality(
+        &self,
+        id: &str,
+        pattern: &bpv7::EidPattern,
+        handle: u32,
+        metric: LinkMetric,
+    ) -> bool {
+        let mut entries = self.entries.write().await;
+        let Some(mut prev) = entries.remove(pattern, id) else {
+            return false;
+        };
+
+        let mut found = false;
+        for entry in &mut prev {
+            if let Action::Forward(endpoint) = &mut entry.action {
+                if endpoint.handle == handle {
+                    endpoint.cost = Some(metric.cost());
+                    found = true;
+                }
+            }
+        }
+        entries.insert(pattern, id.to_string(), prev);
+        found
+    }
+
     #[instrument(skip(self))]
     pub async fn find(&self, to: &bpv7::Eid) -> ForwardResult {
         let mut action = {
@@ -110,9 +185,24 @@ impl Fib {
         if action.clas.len() > 1 {
             // For ECMP, we need a random order
             action.clas.shuffle(&mut rand::thread_rng());
+
+            // Prefer the best reported link quality, the stable sort keeps ECMP between equals
+            action.clas.sort_by_key(|c| c.cost.unwrap_or(u64::MAX));
         }
         Ok(action)
     }
+
+    /* The largest bundle that can currently be sent towards `to` without fragmentation.
+     * As ECMP may pick any of the next hops, this is the smallest of their limits */
+    pub async fn path_budget(&self, to: &bpv7::Eid) -> Option<u64> {
+        self.find(to)
+            .await
+            .ok()?
+            .clas
+            .iter()
+            .filter_map(|endpoint| endpoint.max_bundle_size)
+            .min()
+    }
 }
 
 #[instrument(skip(table, trail))]
@@ -122,6 +212,8 @@ fn find_recurse(table: &Table, to: &bpv7::Eid, trail: &mut HashSet<bpv7::Eid>) -
     let mut new_action = ForwardAction {
         clas: Vec::new(),
         until: None,
+        priority: None,
+        source_route: Vec::new(),
     };
 
     // Recursion check
@@ -139,6 +231,7 @@ fn find_recurse(table: &Table, to: &bpv7::Eid, trail: &mut HashSet<bpv7::Eid>) -
             priority = Some(entry.priority);
             entries.push(entry.action.clone());
         }
+        new_action.priority = priority;
 
         for action in entries {
             match action {
@@ -151,15 +244,25 @@ fn find_recurse(table: &Table, to: &bpv7::Eid, trail: &mut HashSet<bpv7::Eid>) -
                             Some(new_until.min(current_until))
                         }
                     };
-                    new_action.clas.extend(action.clas)
+                    new_action.clas.extend(action.clas);
+                    if new_action.source_route.is_empty() {
+                        new_action.source_route = action.source_route;
+                    }
                 }
-                Action::Forward(c) => {
+                Action::Forward(mut c) => {
+                    c.peer = Some(to.clone());
                     new_action.clas.push(c);
                 }
                 Action::Drop(reason) => {
                     // Drop trumps everything else
                     return Err(reason);
                 }
+                Action::SourceRoute(hops) => {
+                    // Only one explicit path can be followed, the first found wins
+                    if new_action.source_route.is_empty() {
+                        new_action.source_route = hops;
+                    }
+                }
                 Action::Wait(until) => {
                     // Check we don't have a deadline in the past
                     if until >= time::OffsetDateTime::now_utc() {
@@ -176,3 +279,143 @@ fn find_recurse(table: &Table, to: &bpv7::Eid, trail: &mut HashSet<bpv7::Eid>) -
     }
     Ok(new_action)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn forward(handle: u32, max_bundle_size: Option<u64>) -> Action {
+        Action::Forward(Endpoint {
+            handle,
+            max_bundle_size,
+            cost: None,
+            peer: None,
+        })
+    }
+
+    #[tokio::test]
+    async fn path_budget() {
+        let fib = Fib::default();
+        let destination = "ipn:2.1".parse().unwrap();
+
+        // No route, no budget
+        assert_eq!(fib.path_budget(&destination).await, None);
+
+        // A route via a neighbour with a small MRU
+        fib.add(
+            "test".to_string(),
+            &"ipn:2.*".parse().unwrap(),
+            0,
+            Action::Via("ipn:3.0".parse().unwrap()),
+        )
+        .await
+        .unwrap();
+        fib.add(
+            "test".to_string(),
+            &"ipn:3.0".parse().unwrap(),
+            0,
+            forward(1, Some(1024)),
+        )
+        .await
+        .unwrap();
+        assert_eq!(fib.path_budget(&destination).await, Some(1024));
+
+        // The lookup resolves to the neighbour, not the destination
+        assert_eq!(
+            fib.find(&destination).await.unwrap().clas[0].peer,
+            Some("ipn:3.0".parse().unwrap())
+        );
+
+        // ECMP is limited by the smallest next hop, and unlimited CLAs do not count
+        fib.add(
+            "ecmp".to_string(),
+            &"ipn:3.0".parse().unwrap(),
+            0,
+            forward(2, Some(512)),
+        )
+        .await
+        .unwrap();
+        fib.add(
+            "unlimited".to_string(),
+            &"ipn:3.0".parse().unwrap(),
+            0,
+            forward(3, None),
+        )
+        .await
+        .unwrap();
+        assert_eq!(fib.path_budget(&destination).await, Some(512));
+
+        // Black-holed destinations have no budget
+        fib.add(
+            "drop".to_string(),
+            &"ipn:2.1".parse().unwrap(),
+            0,
+            Action::Drop(None),
+        )
+        .await
+        .unwrap();
+        assert_eq!(fib.path_budget(&destination).await, None);
+    }
+
+    #[tokio::test]
+    async fn link_quality() {
+        let fib = Fib::default();
+        let destination = "ipn:2.1".parse().unwrap();
+        let neighbour = "ipn:2.*".parse().unwrap();
+
+        // Two equal priority routes to the same neighbour
+        for (id, handle) in [("cla:a", 1), ("cla:b", 2)] {
+            fib.add(id.to_string(), &neighbour, 0, forward(handle, None))
+                .await
+                .unwrap();
+        }
+
+        // Links that report quality are preferred to those that do not
+        assert!(
+            fib.report_link_quality(
+                "cla:a",
+                &neighbour,
+                1,
+                LinkMetric {
+                    rtt: Some(std::time::Duration::from_millis(600)),
+                    loss: Some(0.2),
+                    ..Default::default()
+                },
+            )
+            .await
+        );
+        for _ in 0..10 {
+            assert_eq!(fib.find(&destination).await.unwrap().clas[0].handle, 1);
+        }
+
+        // The better quality link is chosen
+        assert!(
+            fib.report_link_quality(
+                "cla:b",
+                &neighbour,
+                2,
+                LinkMetric {
+                    rtt: Some(std::time::Duration::from_millis(600)),
+                    bandwidth: Some(1_000_000),
+                    ..Default::default()
+                },
+            )
+            .await
+        );
+        for _ in 0..10 {
+            let clas = fib.find(&destination).await.unwrap().clas;
+            assert_eq!(clas.len(), 2);
+            assert_eq!(clas[0].handle, 2);
+        }
+
+        // Only existing routes can be reported on
+        assert!(
+            !fib.report_link_quality("cla:a", &neighbour, 2, LinkMetric::default())
+                .await
+        );
+        assert!(
+            !fib.report_link_quality("cla:c", &neighbour, 3, LinkMetric::default())
+                .await
+        );
+    }
+}
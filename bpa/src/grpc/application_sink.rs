@@ -40,8 +40,8 @@ impl ApplicationSink for Service {
         &self,
         request: Request<UnregisterApplicationRequest>,
     ) -> Result<Response<UnregisterApplicationResponse>, Status> {
-        self.app_registry
-            .unregister(request.into_inner())
+        self.dispatcher
+            .unregister_application(request.into_inner())
             .await
             .map(Response::new)
     }
@@ -49,24 +49,19 @@ impl ApplicationSink for Service {
     #[instrument(skip(self))]
     async fn send(&self, request: Request<SendRequest>) -> Result<Response<SendResponse>, Status> {
         let request = request.into_inner();
-        let mut send_request = dispatcher::SendRequest {
-            source: self.app_registry.find_by_token(&request.token).await?,
-            destination: match request
-                .destination
-                .parse::<bpv7::Eid>()
-                .map_err(|e| Status::from_error(e.into()))?
-            {
-                bpv7::Eid::Null => {
-                    return Err(Status::invalid_argument("Cannot send to Null endpoint"))
-                }
-                eid => eid,
-            },
-            data: request.data,
-            lifetime: request.lifetime,
-            ..Default::default()
+        let source = self.app_registry.find_by_token(&request.token).await?;
+        let destination = match request
+            .destination
+            .parse::<bpv7::Eid>()
+            .map_err(|e| Status::from_error(e.into()))?
+        {
+            bpv7::Eid::Null => {
+                return Err(Status::invalid_argument("Cannot send to Null endpoint"))
+            }
+            eid => eid,
         };
 
-        if let Some(flags) = request.flags {
+        let flags = request.flags.map(|flags| {
             let mut bundle_flags = bpv7::BundleFlags::default();
             if flags & (send_request::SendFlags::DoNotFragment as u32) != 0 {
                 bundle_flags.do_not_fragment = true;
@@ -89,16 +84,43 @@ impl ApplicationSink for Service {
             if flags & (send_request::SendFlags::NotifyDeletion as u32) != 0 {
                 bundle_flags.delete_report_requested = true;
             }
-            send_request.flags = Some(bundle_flags);
-        }
+            bundle_flags
+        });
 
         self.dispatcher
-            .local_dispatch(send_request)
+            .send(
+                Some(source),
+                destination,
+                request.data,
+                request.lifetime.map(std::time::Duration::from_millis),
+                flags,
+            )
             .await
             .map(|_| Response::new(SendResponse {}))
             .map_err(Status::from_error)
     }
 
+    #[instrument(skip(self))]
+    async fn path_budget(
+        &self,
+        request: Request<PathBudgetRequest>,
+    ) -> Result<Response<PathBudgetResponse>, Status> {
+        let request = request.into_inner();
+        self.app_registry.find_by_token(&request.token).await?;
+        let destination = request
+            .destination
+            .parse::<bpv7::Eid>()
+            .map_err(|e| Status::from_error(e.into()))?;
+
+        Ok(Response::new(PathBudgetResponse {
+            max_bundle_size: self
+                .dispatcher
+                .path_budget(&destination)
+                .await
+                .map(|max_bundle_size| max_bundle_size as u64),
+        }))
+    }
+
     #[instrument(skip(self))]
     async fn collect(
         &self,
@@ -108,8 +130,12 @@ impl ApplicationSink for Service {
         let Some(response) = self
             .dispatcher
             .collect(
-                self.app_registry.find_by_token(&request.token).await?,
+                self.app_registry
+                    .find_destinations_by_token(&request.token)
+                    .await?,
+                &request.token,
                 request.bundle_id,
+                request.acknowledge,
             )
             .await
             .map_err(Status::from_error)?
@@ -125,6 +151,25 @@ impl ApplicationSink for Service {
         }))
     }
 
+    #[instrument(skip(self))]
+    async fn acknowledge(
+        &self,
+        request: Request<AcknowledgeRequest>,
+    ) -> Result<Response<AcknowledgeResponse>, Status> {
+        let request = request.into_inner();
+        self.app_registry.find_by_token(&request.token).await?;
+        if self
+            .dispatcher
+            .acknowledge(&request.token, request.bundle_id, request.processed)
+            .await
+            .map_err(Status::from_error)?
+        {
+            Ok(Response::new(AcknowledgeResponse {}))
+        } else {
+            Err(Status::not_found("No such bundle awaiting acknowledgement"))
+        }
+    }
+
     type PollStream = tokio_stream::wrappers::ReceiverStream<Result<PollResponse, Status>>;
 
     #[instrument(skip(self))]
@@ -0,0 +1,128 @@
+use super::*;
+use bundle_sink_server::{BundleSink, BundleSinkServer};
+use hardy_proto::bundles::*;
+use tonic::{Request, Response, Status};
+
+// Returned when a query does not set a limit, and the most any query may return
+const DEFAULT_QUERY_LIMIT: u32 = 1000;
+
+pub struct Service {
+    dispatcher: Arc<dispatcher::Dispatcher>,
+}
+
+impl Service {
+    fn new(_config: &config::Config, dispatcher: Arc<dispatcher::Dispatcher>) -> Self {
+        Service { dispatcher }
+    }
+}
+
+fn from_summary(summary: store::BundleSummary) -> BundleSummary {
+    let (status, until) = match summary.status {
+        metadata::BundleStatus::IngressPending => (bundle_summary::Status::IngressPending, None),
+        metadata::BundleStatus::DispatchPending => (bundle_summary::Status::DispatchPending, None),
+        metadata::BundleStatus::ReassemblyPending => {
+            (bundle_summary::Status::ReassemblyPending, None)
+        }
+        metadata::BundleStatus::CollectionPending => {
+            (bundle_summary::Status::CollectionPending, None)
+        }
+        metadata::BundleStatus::ForwardPending => (bundle_summary::Status::ForwardPending, None),
+        metadata::BundleStatus::ForwardAckPending(_, until) => (
+            bundle_summary::Status::ForwardAckPending,
+            Some(to_timestamp(until)),
+        ),
+        metadata::BundleStatus::Waiting(until) => {
+            (bundle_summary::Status::Waiting, Some(to_timestamp(until)))
+        }
+        metadata::BundleStatus::Tombstone(_) => unreachable!(),
+    };
+    BundleSummary {
+        bundle_id: summary.id.to_key(),
+        status: status.into(),
+        until,
+        size: summary.size as u64,
+        expiry: Some(to_timestamp(summary.expiry)),
+    }
+}
+
+#[tonic::async_trait]
+impl BundleSink for Service {
+    #[instrument(skip(self))]
+    async fn query_bundles(
+        &self,
+        request: Request<QueryBundlesRequest>,
+    ) -> Result<Response<QueryBundlesResponse>, Status> {
+        let request = request.into_inner();
+        let destination = request
+            .destination
+            .parse()
+            .map_err(|e: bpv7::EidPatternError| Status::invalid_argument(e.to_string()))?;
+        let limit = match request.limit {
+            0 => DEFAULT_QUERY_LIMIT,
+            limit => limit.min(DEFAULT_QUERY_LIMIT),
+        };
+
+        let bundles = self
+            .dispatcher
+            .query_bundles(&destination, limit as usize)
+            .await
+            .map_err(Status::from_error)?
+            .into_iter()
+            .map(from_summary)
+            .collect();
+        Ok(Response::new(QueryBundlesResponse { bundles }))
+    }
+
+    #[instrument(skip(self))]
+    async fn get_dispatch_latency(
+        &self,
+        _request: Request<DispatchLatencyRequest>,
+    ) -> Result<Response<DispatchLatencyResponse>, Status> {
+        let outcomes = self
+            .dispatcher
+            .dispatch_latency()
+            .buckets()
+            .map(|(outcome, buckets)| OutcomeLatency {
+                outcome: outcome.to_string(),
+                buckets: buckets
+                    .into_iter()
+                    .map(|(upper_bound_ms, count)| LatencyBucket {
+                        upper_bound_ms,
+                        count,
+                    })
+                    .collect(),
+            })
+            .collect();
+        Ok(Response::new(DispatchLatencyResponse { outcomes }))
+    }
+
+    #[instrument(skip(self))]
+    async fn get_bundle_counts(
+        &self,
+        _request: Request<BundleCountsRequest>,
+    ) -> Result<Response<BundleCountsResponse>, Status> {
+        let counts = self
+            .dispatcher
+            .bundle_counts()
+            .await
+            .map_err(Status::from_error)?;
+        Ok(Response::new(BundleCountsResponse {
+            ingress_pending: counts.ingress_pending,
+            dispatch_pending: counts.dispatch_pending,
+            reassembly_pending: counts.reassembly_pending,
+            collection_pending: counts.collection_pending,
+            forward_pending: counts.forward_pending,
+            forward_ack_pending: counts.forward_ack_pending,
+            waiting: counts.waiting,
+            tombstone: counts.tombstone,
+            duplicates_dropped: self.dispatcher.duplicates(),
+        }))
+    }
+}
+
+pub fn new_service(
+    config: &config::Config,
+    dispatcher: Arc<dispatcher::Dispatcher>,
+) -> BundleSinkServer<Service> {
+    BundleSinkServer::new(Service::new(config, dispatcher))
+}
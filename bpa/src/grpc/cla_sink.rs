@@ -51,12 +51,22 @@ impl ClaSink for Service {
         request: Request<ReceiveBundleRequest>,
     ) -> Result<Response<ReceiveBundleResponse>, Status> {
         let request = request.into_inner();
-        self.cla_registry.exists(request.handle).await?;
+        let cla_ident = self.cla_registry.ident(request.handle).await?;
         self.dispatcher
-            .receive_bundle(request.bundle)
+            .receive_bundle(request.bundle, Some(&cla_ident))
             .await
             .map(|_| Response::new(ReceiveBundleResponse {}))
-            .map_err(Status::from_error)
+            .map_err(|e| {
+                if e.is::<dispatcher::Backpressure>() {
+                    // Tell the CLA to back off and retry
+                    Status::resource_exhausted(e.to_string())
+                } else if e.is::<store::StorageDegraded>() {
+                    // Tell the CLA we are unhealthy, and to try elsewhere or later
+                    Status::unavailable(e.to_string())
+                } else {
+                    Status::from_error(e)
+                }
+            })
     }
 
     #[instrument(skip(self))]
@@ -93,6 +103,39 @@ impl ClaSink for Service {
             .await
             .map(|_| Response::new(RemoveNeighbourResponse {}))
     }
+
+    #[instrument(skip(self))]
+    async fn report_link_quality(
+        &self,
+        request: Request<ReportLinkQualityRequest>,
+    ) -> Result<Response<ReportLinkQualityResponse>, Status> {
+        self.cla_registry
+            .report_link_quality(request.into_inner())
+            .await
+            .map(|_| Response::new(ReportLinkQualityResponse {}))
+    }
+
+    #[instrument(skip(self))]
+    async fn list_neighbours(
+        &self,
+        _request: Request<ListNeighboursRequest>,
+    ) -> Result<Response<ListNeighboursResponse>, Status> {
+        Ok(Response::new(ListNeighboursResponse {
+            neighbours: self
+                .cla_registry
+                .list_neighbours()
+                .await
+                .into_iter()
+                .map(|n| NeighbourInfo {
+                    neighbour: n.neighbour.to_string(),
+                    cla_ident: n.cla_ident,
+                    cla_name: n.cla_name,
+                    priority: n.priority,
+                    max_bundle_size: n.max_bundle_size,
+                })
+                .collect(),
+        }))
+    }
 }
 
 pub fn new_service(
@@ -3,7 +3,9 @@ use std::sync::Arc;
 use utils::settings;
 
 mod application_sink;
+mod bundle_sink;
 mod cla_sink;
+mod route_sink;
 
 #[instrument(skip_all)]
 pub fn init(
@@ -11,6 +13,7 @@ pub fn init(
     cla_registry: cla_registry::ClaRegistry,
     app_registry: app_registry::AppRegistry,
     dispatcher: Arc<dispatcher::Dispatcher>,
+    fib: Option<fib::Fib>,
     task_set: &mut tokio::task::JoinSet<()>,
     cancel_token: tokio_util::sync::CancellationToken,
 ) {
@@ -31,8 +34,11 @@ pub fn init(
         .add_service(application_sink::new_service(
             config,
             app_registry,
-            dispatcher,
-        ));
+            dispatcher.clone(),
+        ))
+        .add_service(bundle_sink::new_service(config, dispatcher))
+        // Route management is only offered if we are forwarding
+        .add_optional_service(fib.map(|fib| route_sink::new_service(config, fib)));
 
     // Start serving
     task_set.spawn(async move {
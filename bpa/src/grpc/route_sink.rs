@@ -0,0 +1,290 @@
+use super::*;
+use hardy_proto::routes::*;
+use route_sink_server::{RouteSink, RouteSinkServer};
+use std::collections::HashMap;
+use tonic::{Request, Response, Status};
+
+// The FIB source of routes added over gRPC
+const ROUTE_SOURCE: &str = "grpc";
+
+pub struct Service {
+    fib: fib::Fib,
+    routes: tokio::sync::Mutex<HashMap<bpv7::EidPattern, Vec<fib::TableEntry>>>,
+}
+
+impl Service {
+    fn new(_config: &config::Config, fib: fib::Fib) -> Self {
+        Service {
+            fib,
+            routes: Default::default(),
+        }
+    }
+}
+
+fn parse_pattern(pattern: &str) -> Result<bpv7::EidPattern, Status> {
+    pattern
+        .parse()
+        .map_err(|e: bpv7::EidPatternError| Status::invalid_argument(e.to_string()))
+}
+
+fn to_action(action: Option<route::Action>) -> Result<fib::Action, Status> {
+    match action {
+        None => Err(Status::invalid_argument("Route has no action")),
+        Some(route::Action::Drop(route::DropAction { reason })) => Ok(fib::Action::Drop(
+            reason
+                .map(bpv7::StatusReportReasonCode::try_from)
+                .transpose()
+                .map_err(|e| Status::invalid_argument(e.to_string()))?,
+        )),
+        Some(route::Action::Via(via)) => parse_next_hop(&via).map(fib::Action::Via),
+        Some(route::Action::Wait(until)) => from_timestamp(until)
+            .map(fib::Action::Wait)
+            .map_err(|e| Status::invalid_argument(e.to_string())),
+        Some(route::Action::SourceRoute(route::SourceRoute { hops })) => {
+            if hops.is_empty() {
+                return Err(Status::invalid_argument("Source route has no hops"));
+            }
+            hops.iter()
+                .map(|hop| parse_next_hop(hop))
+                .collect::<Result<_, _>>()
+                .map(fib::Action::SourceRoute)
+        }
+    }
+}
+
+fn parse_next_hop(next_hop: &str) -> Result<bpv7::Eid, Status> {
+    match next_hop
+        .parse::<bpv7::Eid>()
+        .map_err(|e| Status::invalid_argument(e.to_string()))?
+    {
+        bpv7::Eid::Null => Err(Status::invalid_argument("Cannot route via Null endpoint")),
+        next_hop => Ok(next_hop),
+    }
+}
+
+fn from_action(action: &fib::Action) -> Option<route::Action> {
+    match action {
+        fib::Action::Drop(reason) => Some(route::Action::Drop(route::DropAction {
+            reason: reason.map(u64::from),
+        })),
+        fib::Action::Via(via) => Some(route::Action::Via(via.to_string())),
+        fib::Action::Wait(until) => Some(route::Action::Wait(to_timestamp(*until))),
+        fib::Action::SourceRoute(hops) => Some(route::Action::SourceRoute(route::SourceRoute {
+            hops: hops.iter().map(ToString::to_string).collect(),
+        })),
+        // Only added by CLAs, not over this API
+        fib::Action::Forward(_) => None,
+    }
+}
+
+#[tonic::async_trait]
+impl RouteSink for Service {
+    #[instrument(skip(self))]
+    async fn add_route(
+        &self,
+        request: Request<AddRouteRequest>,
+    ) -> Result<Response<AddRouteResponse>, Status> {
+        let Some(route) = request.into_inner().route else {
+            return Err(Status::invalid_argument("Missing route"));
+        };
+        let pattern = parse_pattern(&route.pattern)?;
+        let entry = fib::TableEntry {
+            priority: route.priority,
+            action: to_action(route.action)?,
+        };
+
+        let mut routes = self.routes.lock().await;
+        self.fib
+            .add(
+                ROUTE_SOURCE.to_string(),
+                &pattern,
+                entry.priority,
+                entry.action.clone(),
+            )
+            .await
+            .map_err(Status::from_error)?;
+
+        let entries = routes.entry(pattern).or_default();
+        if !entries.contains(&entry) {
+            entries.push(entry);
+        }
+        Ok(Response::new(AddRouteResponse {}))
+    }
+
+    #[instrument(skip(self))]
+    async fn remove_route(
+        &self,
+        request: Request<RemoveRouteRequest>,
+    ) -> Result<Response<RemoveRouteResponse>, Status> {
+        let pattern = parse_pattern(&request.into_inner().pattern)?;
+
+        let mut routes = self.routes.lock().await;
+        if routes.remove(&pattern).is_none() {
+            return Err(Status::not_found("No such route"));
+        }
+        self.fib.remove(ROUTE_SOURCE, &pattern).await;
+        Ok(Response::new(RemoveRouteResponse {}))
+    }
+
+    #[instrument(skip(self))]
+    async fn list_routes(
+        &self,
+        _request: Request<ListRoutesRequest>,
+    ) -> Result<Response<ListRoutesResponse>, Status> {
+        let routes = self
+            .routes
+            .lock()
+            .await
+            .iter()
+            .flat_map(|(pattern, entries)| {
+                entries.iter().map(|entry| Route {
+                    pattern: pattern.to_string(),
+                    priority: entry.priority,
+                    action: from_action(&entry.action),
+                })
+            })
+            .collect();
+        Ok(Response::new(ListRoutesResponse { routes }))
+    }
+}
+
+pub fn new_service(config: &config::Config, fib: fib::Fib) -> RouteSinkServer<Service> {
+    RouteSinkServer::new(Service::new(config, fib))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn route(pattern: &str, priority: u32, action: route::Action) -> AddRouteRequest {
+        AddRouteRequest {
+            route: Some(Route {
+                pattern: pattern.to_string(),
+                priority,
+                action: Some(action),
+            }),
+        }
+    }
+
+    #[tokio::test]
+    async fn routes() {
+        let fib = fib::Fib::default();
+        let service = Service::new(&config::Config::default(), fib.clone());
+        let destination = "ipn:3.1".parse::<bpv7::Eid>().unwrap();
+
+        service
+            .add_route(Request::new(route(
+                "ipn:3.*",
+                10,
+                route::Action::Drop(route::DropAction {
+                    reason: Some(u64::from(
+                        bpv7::StatusReportReasonCode::NoKnownRouteToDestinationFromHere,
+                    )),
+                }),
+            )))
+            .await
+            .unwrap();
+        assert_eq!(
+            fib.find(&destination).await.err(),
+            Some(Some(
+                bpv7::StatusReportReasonCode::NoKnownRouteToDestinationFromHere
+            ))
+        );
+
+        let until = time::OffsetDateTime::now_utc() + time::Duration::hours(1);
+        service
+            .add_route(Request::new(route(
+                "ipn:4.*",
+                10,
+                route::Action::Wait(to_timestamp(until)),
+            )))
+            .await
+            .unwrap();
+        service
+            .add_route(Request::new(route(
+                "ipn:3.*",
+                1,
+                route::Action::Via("ipn:4.0".to_string()),
+            )))
+            .await
+            .unwrap();
+
+        // The higher priority route wins, and the via is followed
+        let action = fib.find(&destination).await.unwrap();
+        assert_eq!(action.priority, Some(1));
+        assert!(action.clas.is_empty());
+        assert!(action.until.is_some());
+
+        let mut routes = service
+            .list_routes(Request::new(ListRoutesRequest {}))
+            .await
+            .unwrap()
+            .into_inner()
+            .routes;
+        // Patterns are listed in their canonical form
+        routes.sort_by_key(|r| (r.pattern.clone(), r.priority));
+        assert_eq!(
+            routes
+                .iter()
+                .map(|r| (r.pattern.as_str(), r.priority))
+                .collect::<Vec<_>>(),
+            [("ipn:0.3.*", 1), ("ipn:0.3.*", 10), ("ipn:0.4.*", 10)]
+        );
+        assert_eq!(
+            routes[0].action,
+            Some(route::Action::Via("ipn:4.0".to_string()))
+        );
+
+        service
+            .remove_route(Request::new(RemoveRouteRequest {
+                pattern: "ipn:3.*".to_string(),
+            }))
+            .await
+            .unwrap();
+        assert!(fib.find(&destination).await.unwrap().priority.is_none());
+        assert_eq!(
+            service
+                .list_routes(Request::new(ListRoutesRequest {}))
+                .await
+                .unwrap()
+                .into_inner()
+                .routes
+                .len(),
+            1
+        );
+
+        // Invalid requests are refused, and change nothing
+        for request in [
+            route(
+                "not a pattern",
+                0,
+                route::Action::Via("ipn:4.0".to_string()),
+            ),
+            route("ipn:5.*", 0, route::Action::Via("ipn:0.0".to_string())),
+            route(
+                "ipn:5.*",
+                0,
+                route::Action::Drop(route::DropAction { reason: Some(255) }),
+            ),
+        ] {
+            assert_eq!(
+                service
+                    .add_route(Request::new(request))
+                    .await
+                    .unwrap_err()
+                    .code(),
+                tonic::Code::InvalidArgument
+            );
+        }
+        assert_eq!(
+            service
+                .remove_route(Request::new(RemoveRouteRequest {
+                    pattern: "ipn:3.*".to_string(),
+                }))
+                .await
+                .unwrap_err()
+                .code(),
+            tonic::Code::NotFound
+        );
+    }
+}
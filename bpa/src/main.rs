@@ -14,12 +14,12 @@ type Error = Box<dyn std::error::Error + Send + Sync>;
 use hardy_bpa_api::metadata;
 use hardy_bpv7::prelude as bpv7;
 use trace_err::*;
-use tracing::{error, info, instrument, trace, warn};
+use tracing::{debug, error, info, instrument, trace, warn};
 
 #[tokio::main]
 async fn main() {
     // Parse command line
-    let Some((config, upgrade, config_source)) = utils::settings::init() else {
+    let Some((config, upgrade, command, config_source)) = utils::settings::init() else {
         return;
     };
 
@@ -32,11 +32,41 @@ async fn main() {
     );
     info!("{config_source}");
 
+    // Check the configuration before starting anything
+    if let Err(errors) = utils::validate::validate(&config) {
+        for e in &errors {
+            error!("{e}");
+        }
+        eprintln!("Invalid configuration:");
+        for e in errors {
+            eprintln!("  {e}");
+        }
+        std::process::exit(1);
+    }
+
     // Get administrative endpoints
     let administrative_endpoints = utils::admin_endpoints::AdminEndpoints::init(&config);
 
-    // New store
-    let store = store::Store::new(&config, upgrade);
+    // New store, on the system clock
+    let store = store::Store::new(
+        &config,
+        upgrade,
+        std::sync::Arc::new(utils::clock::SystemClock),
+    );
+
+    // Run any one-shot store command instead of the BPA
+    let replay = match command {
+        Some(utils::settings::Command::Replay(replay)) => Some(replay),
+        Some(command) => {
+            if let Err(e) = store::run_command(&store, command).await {
+                error!("{e}");
+                eprintln!("{e}");
+                std::process::exit(1);
+            }
+            return;
+        }
+        None => None,
+    };
 
     // New FIB
     let fib = fib::Fib::new(&config);
@@ -60,23 +90,52 @@ async fn main() {
         store.clone(),
         cla_registry.clone(),
         app_registry.clone(),
-        fib,
+        fib.clone(),
+        &mut task_set,
+        cancel_token.clone(),
+    );
+
+    // Apply configuration changes on SIGHUP
+    let dispatcher_cloned = dispatcher.clone();
+    utils::settings::listen_for_reload(
+        move |config| {
+            if let Err(e) = dispatcher_cloned.reload_config(&config) {
+                warn!("{e}");
+            }
+        },
         &mut task_set,
         cancel_token.clone(),
     );
 
     // Start the store - this can take a while as the store is walked
     store
-        .start(dispatcher.clone(), &mut task_set, cancel_token.clone())
+        .start(
+            dispatcher.clone(),
+            &mut task_set,
+            None,
+            cancel_token.clone(),
+        )
         .await;
 
     if !cancel_token.is_cancelled() {
+        // Replay stored bundles, if requested on the command line
+        if let Some(replay) = replay {
+            info!(
+                "Replaying bundles for {} received between {} and {}",
+                replay.destinations, replay.window.start, replay.window.end
+            );
+            if let Err(e) = dispatcher.replay(&replay.destinations, replay.window).await {
+                error!("Failed to replay bundles: {e}");
+            }
+        }
+
         // Init gRPC services
         grpc::init(
             &config,
             cla_registry,
             app_registry,
             dispatcher,
+            fib,
             &mut task_set,
             cancel_token.clone(),
         );
@@ -19,9 +19,15 @@ pub struct Config {
 
 impl Config {
     pub fn new(config: &::config::Config) -> Option<Self> {
-        let mut config =
-            settings::get_with_default::<Option<config::Config>, _>(config, "static_routes", None)
-                .trace_expect("Invalid 'static_routes' section in configuration")?;
+        Self::try_new(config).trace_expect("Invalid 'static_routes' section in configuration")
+    }
+
+    pub fn try_new(config: &::config::Config) -> Result<Option<Self>, ::config::ConfigError> {
+        let Some(mut config) =
+            settings::get_with_default::<Option<config::Config>, _>(config, "static_routes", None)?
+        else {
+            return Ok(None);
+        };
 
         // Try to create canonical file path
         if let Ok(r) = config.routes_file.canonicalize() {
@@ -34,7 +40,7 @@ impl Config {
             path.push(&config.routes_file);
             config.routes_file = path;
         }
-        Some(config)
+        Ok(Some(config))
     }
 
     fn default_path() -> PathBuf {
@@ -121,33 +121,32 @@ impl StaticRoutes {
                 .watch(&routes_dir, RecursiveMode::NonRecursive)
                 .trace_expect("Failed to watch file");
 
-            loop {
-                tokio::select! {
-                    res = rx.recv() => match res {
-                        None => break,
-                        Some(Ok(events)) => {
-                            for DebouncedEvent{ event, .. } in events {
-                                if match event.kind {
-                                    EventKind::Create(CreateKind::File)|
-                                    EventKind::Modify(_)|
-                                    EventKind::Remove(RemoveKind::File) => {
-                                        info!("Detected change in static routes file: {:?}, looking for {:?}", event.paths, routes_file);
-                                        event.paths.iter().any(|p| p == &routes_file)
-                                    }
-                                    _ => false
-                                } {
-                                    info!("Reloading static routes from '{}'",routes_file.to_string_lossy());
-                                    self_cloned.refresh_routes(false).await.trace_expect("Failed to process static routes file");
+            while let Some(res) = hardy_async::channel::recv_or_cancel(&mut rx, &cancel_token).await {
+                match res {
+                    Ok(events) => {
+                        for DebouncedEvent { event, .. } in events {
+                            if match event.kind {
+                                EventKind::Create(CreateKind::File)
+                                | EventKind::Modify(_)
+                                | EventKind::Remove(RemoveKind::File) => {
+                                    info!("Detected change in static routes file: {:?}, looking for {:?}", event.paths, routes_file);
+                                    event.paths.iter().any(|p| p == &routes_file)
                                 }
+                                _ => false,
+                            } {
+                                info!("Reloading static routes from '{}'", routes_file.to_string_lossy());
+                                self_cloned
+                                    .refresh_routes(false)
+                                    .await
+                                    .trace_expect("Failed to process static routes file");
                             }
-                        },
-                        Some(Err(errors)) => {
-                            for err in errors {
-                                error!("Watch error: {:?}", err)
-                            }
                         }
-                    },
-                    _ = cancel_token.cancelled() => break
+                    }
+                    Err(errors) => {
+                        for err in errors {
+                            error!("Watch error: {:?}", err)
+                        }
+                    }
                 }
             }
         });
@@ -155,6 +154,35 @@ impl StaticRoutes {
 }
 
 #[instrument(skip_all)]
+pub fn validate(config: &::config::Config, errors: &mut Vec<utils::validate::ConfigError>) {
+    let config = match config::Config::try_new(config) {
+        Ok(Some(config)) => config,
+        Ok(None) => return,
+        Err(e) => {
+            errors.push(utils::validate::ConfigError::Invalid {
+                key: "static_routes".to_string(),
+                reason: e.to_string(),
+            });
+            return;
+        }
+    };
+
+    let file = config.routes_file.to_string_lossy().into_owned();
+    match std::fs::read_to_string(&config.routes_file) {
+        Err(e) => errors.push(utils::validate::ConfigError::StaticRoutesFile {
+            file,
+            reason: e.to_string(),
+        }),
+        Ok(routes) => errors.extend(parse::check_routes(&routes).into_iter().map(
+            |(line, reason)| utils::validate::ConfigError::StaticRoute {
+                file: file.clone(),
+                line,
+                reason,
+            },
+        )),
+    }
+}
+
 pub async fn init(
     config: &::config::Config,
     fib: fib::Fib,
@@ -140,6 +140,11 @@ impl std::str::FromStr for RouteLine {
                     arg: ArgOption::Some(3),
                     group: Some(0),
                 },
+                Arg {
+                    name: "source-route",
+                    arg: ArgOption::Some(1),
+                    group: Some(0),
+                },
                 Arg {
                     name: "priority",
                     arg: ArgOption::Some(1),
@@ -167,6 +172,10 @@ impl std::str::FromStr for RouteLine {
                 } else if let Some(Some(until)) = parts.get("wait") {
                     fib::Action::Wait(time::OffsetDateTime::parse(until,
                         format_description!("[year]-[month]-[day] [hour]:[minute]:[second] [offset_hour sign:mandatory]:[offset_minute]:[offset_second]"))?)
+                } else if let Some(Some(hops)) = parts.get("source-route") {
+                    // A comma separated list of next hops, in order
+                    let hops: Result<Vec<_>, _> = hops.split(',').map(str::parse).collect();
+                    fib::Action::SourceRoute(hops?)
                 } else {
                     return Err(ParseError::MissingAction);
                 },
@@ -175,6 +184,19 @@ impl std::str::FromStr for RouteLine {
     }
 }
 
+// Check every line of a routes file, returning the line number and reason for each failure
+pub fn check_routes(routes: &str) -> Vec<(usize, String)> {
+    routes
+        .lines()
+        .enumerate()
+        .filter_map(|(idx, line)| {
+            line.parse::<RouteLine>()
+                .err()
+                .map(|e| (idx + 1, e.to_string()))
+        })
+        .collect()
+}
+
 pub async fn load_routes(
     routes_file: &PathBuf,
     ignore_errors: bool,
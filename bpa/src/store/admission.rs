@@ -0,0 +1,189 @@
+use super::*;
+use rand::Rng;
+use std::collections::{BTreeMap, HashMap};
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+#[error("Bundle storage is full")]
+pub struct StorageFull;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Priority {
+    Bulk,
+    #[default]
+    BestEffort,
+    Expedited,
+}
+
+impl Priority {
+    /* The class of service the bundle asks for.  Administrative records are always expedited,
+     * so status reports are not lost behind the traffic they report on */
+    pub fn of(bundle: &bpv7::Bundle) -> Self {
+        if bundle.flags.is_admin_record {
+            return Self::Expedited;
+        }
+        match bundle.class_of_service.unwrap_or_default() {
+            bpv7::ClassOfService::Bulk => Self::Bulk,
+            bpv7::ClassOfService::Normal => Self::BestEffort,
+            bpv7::ClassOfService::Expedited => Self::Expedited,
+        }
+    }
+}
+
+/* Tracks the bundle data held in storage against a configured capacity.
+ * At capacity, a new bundle is only admitted if enough strictly lower priority bundles
+ * can be evicted to make room, oldest first, otherwise it is refused.
+ * Above the early drop threshold, bulk and best-effort bundles are randomly refused with a
+ * probability that rises with occupancy, so sustained overload does not meet a hard cliff at capacity.
+ * Bundles already held at start-up are accounted for by the store consistency check */
+pub struct Admission {
+    capacity: u64,
+    early_drop: u64,
+    used: u64,
+    seq: u64,
+    entries: HashMap<Arc<str>, (Priority, u64, u64)>,
+    order: BTreeMap<(Priority, u64), Arc<str>>,
+}
+
+impl Admission {
+    pub fn init(config: &config::Config) -> Option<std::sync::Mutex<Self>> {
+        let capacity = settings::get_with_default(config, "storage_capacity", 0u64)
+            .trace_expect("Invalid 'storage_capacity' value in configuration");
+        if capacity == 0 {
+            return None;
+        }
+
+        let early_drop = settings::get_with_default(config, "storage_early_drop", 0u8)
+            .trace_expect("Invalid 'storage_early_drop' value in configuration");
+
+        info!("Bundle storage is limited to {capacity} bytes");
+        let mut admission = Self::new(capacity);
+        if early_drop != 0 {
+            info!(
+                "Bulk and best-effort bundles will be dropped early above {early_drop}% occupancy"
+            );
+            admission = admission.with_early_drop(early_drop);
+        }
+        Some(std::sync::Mutex::new(admission))
+    }
+
+    pub fn new(capacity: u64) -> Self {
+        Self {
+            capacity,
+            early_drop: capacity,
+            used: 0,
+            seq: 0,
+            entries: HashMap::new(),
+            order: BTreeMap::new(),
+        }
+    }
+
+    // Start dropping bulk and best-effort bundles once `percent` of capacity is used
+    pub fn with_early_drop(mut self, percent: u8) -> Self {
+        self.early_drop = (self.capacity as u128 * percent.min(100) as u128 / 100) as u64;
+        self
+    }
+
+    /* The probability of refusing a bundle at the current occupancy, rising linearly from 0 at the
+     * early drop threshold to 1 at capacity.  Expedited bundles are never dropped early */
+    pub fn drop_probability(&self, priority: Priority) -> f64 {
+        if priority == Priority::Expedited || self.used < self.early_drop {
+            return 0.0;
+        }
+        if self.used >= self.capacity {
+            return 1.0;
+        }
+        (self.used - self.early_drop) as f64 / (self.capacity - self.early_drop) as f64
+    }
+
+    /* Reserve room for `len` bytes, returning the storage names of the bundles that must be
+     * evicted to make room, or None if the bundle must be refused */
+    pub fn reserve(&mut self, len: u64, priority: Priority) -> Option<Vec<Arc<str>>> {
+        let p = self.drop_probability(priority);
+        if p > 0.0 && rand::thread_rng().gen_bool(p) {
+            return None;
+        }
+
+        let mut needed = self.used.saturating_add(len).saturating_sub(self.capacity);
+        let mut victims = Vec::new();
+        for ((victim_priority, _), storage_name) in &self.order {
+            if needed == 0 || *victim_priority >= priority {
+                break;
+            }
+            needed = needed.saturating_sub(self.entries.get(storage_name)?.2);
+            victims.push(storage_name.clone());
+        }
+        if needed != 0 {
+            return None;
+        }
+
+        for storage_name in &victims {
+            self.release(storage_name);
+        }
+        self.used += len;
+        Some(victims)
+    }
+
+    // Record the storage name of a reserved bundle, once it has been stored
+    pub fn commit(&mut self, storage_name: Arc<str>, len: u64, priority: Priority) {
+        self.seq += 1;
+        self.order
+            .insert((priority, self.seq), storage_name.clone());
+        self.entries.insert(storage_name, (priority, self.seq, len));
+    }
+
+    // Account for a bundle found in storage at start-up, which may leave storage over capacity
+    pub fn restore(&mut self, storage_name: Arc<str>, len: u64, priority: Priority) {
+        self.used = self.used.saturating_add(len);
+        self.commit(storage_name, len, priority);
+    }
+
+    // Return a reservation that was never committed
+    pub fn cancel(&mut self, len: u64) {
+        self.used = self.used.saturating_sub(len);
+    }
+
+    pub fn release(&mut self, storage_name: &str) {
+        if let Some((priority, seq, len)) = self.entries.remove(storage_name) {
+            self.order.remove(&(priority, seq));
+            self.used = self.used.saturating_sub(len);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn early_drop() {
+        let mut admission = Admission::new(100).with_early_drop(50);
+
+        // Below the threshold nothing is dropped
+        let mut last = 0.0;
+        for _ in 0..5 {
+            assert_eq!(admission.drop_probability(Priority::BestEffort), 0.0);
+            admission.reserve(10, Priority::Expedited).unwrap();
+        }
+
+        // Above it, best-effort drop probability rises with occupancy
+        for _ in 0..5 {
+            let p = admission.drop_probability(Priority::BestEffort);
+            assert!(p >= last && p < 1.0);
+            last = p;
+            assert_eq!(admission.drop_probability(Priority::Expedited), 0.0);
+            admission.reserve(10, Priority::Expedited).unwrap();
+        }
+        assert!(last > 0.5);
+
+        // Until at capacity best-effort bundles are always refused, but expedited are not
+        assert_eq!(admission.drop_probability(Priority::BestEffort), 1.0);
+        assert!(admission.reserve(1, Priority::BestEffort).is_none());
+        assert_eq!(admission.drop_probability(Priority::Expedited), 0.0);
+
+        // Without a threshold, there is no early drop
+        let mut admission = Admission::new(100);
+        admission.reserve(99, Priority::Expedited).unwrap();
+        assert_eq!(admission.drop_probability(Priority::BestEffort), 0.0);
+    }
+}
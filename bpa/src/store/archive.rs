@@ -0,0 +1,178 @@
+use super::*;
+use hardy_cbor as cbor;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+/* A store archive is a sequence of records, each an 8 byte big-endian length followed by a CBOR
+ * array of [status code, wait until, received at, bundle data], with times in Unix milliseconds.
+ * Storage names and hashes belong to the backends, so are not archived.  Forwarding acknowledgement
+ * handles belong to CLA sessions that will not exist when the archive is imported, so those bundles
+ * are archived as waiting until the acknowledgement timeout */
+
+// The length of a record is read from the archive, so is not trusted beyond the largest bundle we accept
+const MAX_RECORD_LEN: u64 = 0x1_0000_0000;
+
+fn millis(t: time::OffsetDateTime) -> i64 {
+    (t.unix_timestamp_nanos() / 1_000_000) as i64
+}
+
+fn from_millis(millis: i64) -> Result<time::OffsetDateTime, Error> {
+    Ok(time::OffsetDateTime::from_unix_timestamp_nanos(
+        millis as i128 * 1_000_000,
+    )?)
+}
+
+// Tombstones have no data to archive, so have no record
+fn emit_record(bundle: &metadata::Bundle, data: &[u8]) -> Option<Vec<u8>> {
+    let (code, until) = match &bundle.metadata.status {
+        metadata::BundleStatus::IngressPending => (0, None),
+        metadata::BundleStatus::DispatchPending => (1, None),
+        metadata::BundleStatus::ReassemblyPending => (2, None),
+        metadata::BundleStatus::CollectionPending => (3, None),
+        metadata::BundleStatus::ForwardPending => (4, None),
+        metadata::BundleStatus::ForwardAckPending(_, until)
+        | metadata::BundleStatus::Waiting(until) => (5, Some(millis(*until))),
+        metadata::BundleStatus::Tombstone(_) => return None,
+    };
+
+    Some(cbor::encode::emit_array(Some(4), |a| {
+        a.emit(code);
+        a.emit(until);
+        a.emit(bundle.metadata.received_at.map(millis));
+        a.emit(data);
+    }))
+}
+
+type Record = (
+    metadata::BundleStatus,
+    Option<time::OffsetDateTime>,
+    Vec<u8>,
+);
+
+fn parse_record(record: &[u8]) -> Result<Record, Error> {
+    cbor::decode::parse_array(record, |a, _, _| {
+        let code = a.parse::<u64>()?;
+        let until = a.parse::<Option<i64>>()?.map(from_millis).transpose()?;
+        let status = match (code, until) {
+            (0, None) => metadata::BundleStatus::IngressPending,
+            (1, None) => metadata::BundleStatus::DispatchPending,
+            (2, None) => metadata::BundleStatus::ReassemblyPending,
+            (3, None) => metadata::BundleStatus::CollectionPending,
+            (4, None) => metadata::BundleStatus::ForwardPending,
+            (5, Some(until)) => metadata::BundleStatus::Waiting(until),
+            _ => return Err(format!("Invalid bundle status {code} in archive").into()),
+        };
+        let received_at = a.parse::<Option<i64>>()?.map(from_millis).transpose()?;
+        let data = a.parse_value(|v, _, tags| match v {
+            cbor::decode::Value::Bytes(data) => Ok(data.to_vec()),
+            cbor::decode::Value::ByteStream(data) => Ok(data.concat()),
+            _ => Err(cbor::decode::Error::IncorrectType(
+                "Byte String".to_string(),
+                v.type_name(!tags.is_empty()),
+            )),
+        })?;
+        Ok::<_, Error>((status, received_at, data))
+    })
+    .map(|(r, _)| r)
+}
+
+impl Store {
+    /* Write every stored bundle, and its metadata, to `writer` as an archive that can be imported
+     * into a store using any backend.  Tombstones are not exported.  Returns the number of bundles */
+    #[instrument(skip_all)]
+    pub async fn export(&self, writer: &mut (impl AsyncWrite + Unpin)) -> Result<usize, Error> {
+        let mut exported = 0;
+        for (storage_name, _) in self
+            .list_stored_bundles(tokio_util::sync::CancellationToken::new())
+            .await
+        {
+            let Some(data) = self.bundle_storage.load(&storage_name).await? else {
+                continue;
+            };
+
+            let Ok(bpv7::ValidBundle::Valid(bundle, _)) =
+                bpv7::ValidBundle::parse(data.as_ref().as_ref(), |_, _| Ok(None))
+            else {
+                warn!("Not exporting invalid bundle data {storage_name}");
+                continue;
+            };
+
+            // Only export data the metadata refers to, skipping tombstones and duplicates
+            let Some(bundle) = self.metadata_storage.load(&bundle.id).await? else {
+                continue;
+            };
+            if bundle.metadata.storage_name.as_ref() != Some(&storage_name) {
+                continue;
+            }
+            let Some(record) = emit_record(&bundle, data.as_ref().as_ref()) else {
+                continue;
+            };
+
+            writer.write_u64(record.len() as u64).await?;
+            writer.write_all(&record).await?;
+            exported += 1;
+        }
+        writer.flush().await?;
+        Ok(exported)
+    }
+
+    /* Store every bundle in an archive written by export().  Bundles that are already stored are
+     * skipped.  Returns the number of bundles imported */
+    #[instrument(skip_all)]
+    pub async fn import(&self, reader: &mut (impl AsyncRead + Unpin)) -> Result<usize, Error> {
+        let mut imported = 0;
+        loop {
+            let len = match reader.read_u64().await {
+                Ok(len) => len,
+                Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => break,
+                Err(e) => return Err(e.into()),
+            };
+            if len > MAX_RECORD_LEN {
+                return Err(format!("Archive record of {len} bytes is too large").into());
+            }
+
+            // Grow the record as it is read, so a corrupt length cannot exhaust memory
+            let mut record = Vec::new();
+            (&mut *reader).take(len).read_to_end(&mut record).await?;
+            if record.len() as u64 != len {
+                return Err("Archive is truncated".into());
+            }
+
+            let (status, received_at, data) = parse_record(&record)?;
+            let (bundle, data) = match bpv7::ValidBundle::parse(&data, |_, _| Ok(None))? {
+                bpv7::ValidBundle::Valid(bundle, _) => (bundle, data),
+                bpv7::ValidBundle::Rewritten(bundle, data, _) => (bundle, data.into()),
+                bpv7::ValidBundle::Invalid(_, _, e) => return Err(e.into()),
+            };
+
+            if self
+                .store(&bundle, &data, status, received_at)
+                .await?
+                .is_some()
+            {
+                imported += 1;
+            }
+        }
+        info!("Imported {imported} bundles");
+        Ok(imported)
+    }
+}
+
+/* Run a store archive command from the command line, see utils::settings::Command.
+ * The BPA is not started, so the store is not changed underneath the archive */
+pub async fn run(store: &Store, command: utils::settings::Command) -> Result<(), Error> {
+    match command {
+        utils::settings::Command::ExportStore(path) => {
+            let mut file = tokio::io::BufWriter::new(tokio::fs::File::create(&path).await?);
+            let exported = store.export(&mut file).await?;
+            info!("Exported {exported} bundles to {}", path.display());
+        }
+        utils::settings::Command::ImportStore(path) => {
+            let mut file = tokio::io::BufReader::new(tokio::fs::File::open(&path).await?);
+            store.import(&mut file).await?;
+        }
+        utils::settings::Command::Replay(_) => {
+            return Err("A replay is run by the BPA, not the store".into());
+        }
+    }
+    Ok(())
+}
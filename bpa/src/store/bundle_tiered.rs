@@ -0,0 +1,250 @@
+use super::*;
+use hardy_bpa_api::async_trait;
+use std::collections::{BTreeMap, HashMap};
+use tokio::sync::Mutex;
+
+pub const CONFIG_KEY: &str = "tiered";
+
+const DEFAULT_CAPACITY: u64 = 64 * 1024 * 1024;
+
+struct DataRefWrapper(Arc<[u8]>);
+
+impl AsRef<[u8]> for DataRefWrapper {
+    #[inline]
+    fn as_ref(&self) -> &[u8] {
+        self.0.as_ref()
+    }
+}
+
+#[derive(Default)]
+struct Lru {
+    entries: HashMap<Arc<str>, (Arc<[u8]>, u64)>,
+    order: BTreeMap<u64, Arc<str>>,
+    tick: u64,
+    size: u64,
+}
+
+impl Lru {
+    fn get(&mut self, storage_name: &str) -> Option<Arc<[u8]>> {
+        let (storage_name, (data, tick)) = self.entries.get_key_value(storage_name)?;
+        let (storage_name, data, tick) = (storage_name.clone(), data.clone(), *tick);
+
+        // Touch
+        self.order.remove(&tick);
+        self.tick += 1;
+        self.order.insert(self.tick, storage_name.clone());
+        self.entries.insert(storage_name, (data.clone(), self.tick));
+        Some(data)
+    }
+
+    fn insert(&mut self, storage_name: Arc<str>, data: Arc<[u8]>, capacity: u64) {
+        // Bundles bigger than the whole tier go straight to the inner storage
+        if data.len() as u64 > capacity {
+            return;
+        }
+
+        self.remove(&storage_name);
+        self.size += data.len() as u64;
+        self.tick += 1;
+        self.order.insert(self.tick, storage_name.clone());
+        self.entries.insert(storage_name, (data, self.tick));
+
+        // Spill the least recently used
+        while self.size > capacity {
+            let Some((_, storage_name)) = self.order.pop_first() else {
+                break;
+            };
+            if let Some((data, _)) = self.entries.remove(&storage_name) {
+                self.size -= data.len() as u64;
+            }
+        }
+    }
+
+    fn remove(&mut self, storage_name: &str) {
+        if let Some((data, tick)) = self.entries.remove(storage_name) {
+            self.order.remove(&tick);
+            self.size -= data.len() as u64;
+        }
+    }
+}
+
+/* A write-through memory tier in front of a persistent bundle storage.
+ * Every bundle is written to the inner storage, so storage names are stable and
+ * survive restarts, but recently touched bundles are served from memory */
+pub struct Storage {
+    capacity: u64,
+    inner: Arc<dyn storage::BundleStorage>,
+    lru: Mutex<Lru>,
+}
+
+impl Storage {
+    #[instrument(skip_all)]
+    pub fn init(
+        config: &HashMap<String, config::Value>,
+        inner: Arc<dyn storage::BundleStorage>,
+    ) -> Arc<dyn storage::BundleStorage> {
+        let capacity = config.get("capacity").map_or(DEFAULT_CAPACITY, |v| {
+            v.clone()
+                .into_uint()
+                .trace_expect("Invalid 'capacity' value in configuration")
+        });
+
+        info!("Using {capacity} bytes of memory for recently used bundles");
+
+        Arc::new(Self::new(capacity, inner))
+    }
+
+    fn new(capacity: u64, inner: Arc<dyn storage::BundleStorage>) -> Self {
+        Self {
+            capacity,
+            inner,
+            lru: Mutex::new(Lru::default()),
+        }
+    }
+}
+
+#[async_trait]
+impl storage::BundleStorage for Storage {
+    async fn list(
+        &self,
+        tx: tokio::sync::mpsc::Sender<storage::ListResponse>,
+    ) -> storage::Result<()> {
+        // The inner storage holds every bundle, including those cached in memory
+        self.inner.list(tx).await
+    }
+
+    async fn load(&self, storage_name: &str) -> storage::Result<Option<storage::DataRef>> {
+        if let Some(data) = self.lru.lock().await.get(storage_name) {
+            return Ok(Some(Arc::new(DataRefWrapper(data))));
+        }
+
+        let Some(data) = self.inner.load(storage_name).await? else {
+            return Ok(None);
+        };
+
+        // Promote back into the memory tier
+        let data: Arc<[u8]> = Arc::from(data.as_ref().as_ref());
+        self.lru
+            .lock()
+            .await
+            .insert(storage_name.into(), data.clone(), self.capacity);
+        Ok(Some(Arc::new(DataRefWrapper(data))))
+    }
+
+    async fn store(&self, data: &[u8]) -> storage::Result<Arc<str>> {
+        let storage_name = self.inner.store(data).await?;
+        self.lru
+            .lock()
+            .await
+            .insert(storage_name.clone(), Arc::from(data), self.capacity);
+        Ok(storage_name)
+    }
+
+    async fn remove(&self, storage_name: &str) -> storage::Result<()> {
+        self.lru.lock().await.remove(storage_name);
+        self.inner.remove(storage_name).await
+    }
+
+    async fn quarantine(&self, storage_name: &str) -> storage::Result<()> {
+        self.lru.lock().await.remove(storage_name);
+        self.inner.quarantine(storage_name).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use storage::BundleStorage;
+
+    // A trivial persistent storage that counts loads, so we can tell which tier served a bundle
+    #[derive(Default)]
+    struct Inner {
+        bundles: std::sync::Mutex<HashMap<Arc<str>, Arc<[u8]>>>,
+        loads: std::sync::atomic::AtomicUsize,
+    }
+
+    #[async_trait]
+    impl storage::BundleStorage for Inner {
+        async fn list(
+            &self,
+            tx: tokio::sync::mpsc::Sender<storage::ListResponse>,
+        ) -> storage::Result<()> {
+            let names = self
+                .bundles
+                .lock()
+                .unwrap()
+                .keys()
+                .cloned()
+                .collect::<Vec<_>>();
+            for storage_name in names {
+                tx.send((storage_name, None)).await?;
+            }
+            Ok(())
+        }
+
+        async fn load(&self, storage_name: &str) -> storage::Result<Option<storage::DataRef>> {
+            self.loads
+                .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+            Ok(self
+                .bundles
+                .lock()
+                .unwrap()
+                .get(storage_name)
+                .map(|v| Arc::new(DataRefWrapper(v.clone())) as storage::DataRef))
+        }
+
+        async fn store(&self, data: &[u8]) -> storage::Result<Arc<str>> {
+            let mut bundles = self.bundles.lock().unwrap();
+            let storage_name: Arc<str> = format!("{}", bundles.len()).into();
+            bundles.insert(storage_name.clone(), Arc::from(data));
+            Ok(storage_name)
+        }
+
+        async fn remove(&self, storage_name: &str) -> storage::Result<()> {
+            self.bundles.lock().unwrap().remove(storage_name);
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn spill_and_recover() {
+        let inner = Arc::new(Inner::default());
+        let storage = Storage::new(4 * 100, inner.clone());
+
+        // Fill past the memory tier
+        let mut names = Vec::new();
+        for i in 0..10u8 {
+            names.push((storage.store(&[i; 100]).await.unwrap(), i));
+        }
+
+        // Recently stored bundles come from memory
+        for (storage_name, i) in &names[6..] {
+            let data = storage.load(storage_name).await.unwrap().unwrap();
+            assert_eq!(data.as_ref().as_ref(), &[*i; 100]);
+        }
+        assert_eq!(inner.loads.load(std::sync::atomic::Ordering::Relaxed), 0);
+
+        // Spilled bundles still load, via the inner storage
+        for (storage_name, i) in &names[..6] {
+            let data = storage.load(storage_name).await.unwrap().unwrap();
+            assert_eq!(data.as_ref().as_ref(), &[*i; 100]);
+        }
+        assert_eq!(inner.loads.load(std::sync::atomic::Ordering::Relaxed), 6);
+
+        // Recovery enumerates bundles in both tiers
+        let (tx, mut rx) = tokio::sync::mpsc::channel(16);
+        storage.list(tx).await.unwrap();
+        let mut listed = Vec::new();
+        while let Some((storage_name, _)) = rx.recv().await {
+            listed.push(storage_name);
+        }
+        listed.sort();
+        let mut expected = names.into_iter().map(|(n, _)| n).collect::<Vec<_>>();
+        expected.sort();
+        assert_eq!(listed, expected);
+
+        // Removal clears both tiers
+        storage.remove(&listed[9]).await.unwrap();
+        assert!(storage.load(&listed[9]).await.unwrap().is_none());
+    }
+}
@@ -9,6 +9,9 @@ use tokio::sync::RwLock;
 
 pub const CONFIG_KEY: &str = "mem-storage";
 
+// Seconds to keep tombstones for
+const DEFAULT_TOMBSTONE_RETENTION: u64 = 5;
+
 #[derive(Error, Debug)]
 pub enum Error {
     #[error("No such bundle")]
@@ -17,14 +20,48 @@ pub enum Error {
 
 pub struct Storage {
     entries: RwLock<HashMap<bpv7::BundleId, metadata::Bundle>>,
+    tombstone_retention: time::Duration,
 }
 
 impl Storage {
     #[instrument(skip_all)]
-    pub fn init(_config: &HashMap<String, config::Value>) -> Arc<dyn storage::MetadataStorage> {
-        Arc::new(Self {
+    pub fn init(config: &HashMap<String, config::Value>) -> Arc<dyn storage::MetadataStorage> {
+        let tombstone_retention =
+            config
+                .get("tombstone_retention")
+                .map_or(DEFAULT_TOMBSTONE_RETENTION, |v| {
+                    v.clone()
+                        .into_uint()
+                        .trace_expect("Invalid 'tombstone_retention' value in configuration")
+                });
+
+        info!("Keeping tombstones for {tombstone_retention} seconds");
+
+        Arc::new(Self::new(tombstone_retention))
+    }
+
+    fn new(tombstone_retention: u64) -> Self {
+        Self {
             entries: RwLock::new(HashMap::new()),
-        })
+            tombstone_retention: time::Duration::seconds(
+                tombstone_retention.min(i64::MAX as u64) as i64
+            ),
+        }
+    }
+
+    /* Remove the tombstones that have outlived the retention period, returning how many were removed.
+     * A duplicate of a bundle that arrives after its tombstone has been reaped will be accepted again */
+    async fn reap_tombstones(&self, now: time::OffsetDateTime) -> usize {
+        let mut entries = self.entries.write().await;
+        let count = entries.len();
+        entries.retain(|_, bundle| match bundle.metadata.status {
+            // A retention that runs past the end of time never expires
+            metadata::BundleStatus::Tombstone(from) => from
+                .checked_add(self.tombstone_retention)
+                .is_none_or(|until| until >= now),
+            _ => true,
+        });
+        count - entries.len()
     }
 }
 
@@ -96,18 +133,14 @@ impl storage::MetadataStorage for Storage {
         limit: time::OffsetDateTime,
         tx: storage::Sender,
     ) -> storage::Result<()> {
-        // Drop all tombstones and collect waiting
-        let mut tombstones = Vec::new();
-
-        let mut entries = self.entries.write().await;
+        // Reap tombstones while we are sweeping anyway
+        let reaped = self.reap_tombstones(time::OffsetDateTime::now_utc()).await;
+        if reaped != 0 {
+            trace!("Reaped {reaped} tombstones");
+        }
 
-        for (bundle_id, bundle) in entries.iter() {
+        for bundle in self.entries.read().await.values() {
             match bundle.metadata.status {
-                metadata::BundleStatus::Tombstone(from)
-                    if from + time::Duration::seconds(5) < time::OffsetDateTime::now_utc() =>
-                {
-                    tombstones.push(bundle_id.clone());
-                }
                 metadata::BundleStatus::ForwardAckPending(_, until)
                 | metadata::BundleStatus::Waiting(until)
                     if until <= limit =>
@@ -119,11 +152,6 @@ impl storage::MetadataStorage for Storage {
                 _ => {}
             }
         }
-
-        // Remove tombstones from index
-        for bundle_id in tombstones {
-            entries.remove(&bundle_id);
-        }
         Ok(())
     }
 
@@ -139,4 +167,166 @@ impl storage::MetadataStorage for Storage {
     ) -> storage::Result<()> {
         todo!()
     }
+
+    async fn poll_received_between(
+        &self,
+        window: std::ops::Range<time::OffsetDateTime>,
+        tx: storage::Sender,
+    ) -> storage::Result<()> {
+        for bundle in self.entries.read().await.values() {
+            match (&bundle.metadata.status, bundle.metadata.received_at) {
+                (metadata::BundleStatus::Tombstone(_), _) => {}
+                (_, Some(received_at)) if window.contains(&received_at) => {
+                    if tx.send(bundle.clone()).await.is_err() {
+                        break;
+                    }
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    async fn poll_for_destination(
+        &self,
+        destination: bpv7::EidPattern,
+        tx: storage::Sender,
+    ) -> storage::Result<()> {
+        for bundle in self.entries.read().await.values() {
+            if !matches!(bundle.metadata.status, metadata::BundleStatus::Tombstone(_))
+                && destination.is_match(&bundle.bundle.destination)
+                && tx.send(bundle.clone()).await.is_err()
+            {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    async fn count_by_status(&self) -> storage::Result<metadata::BundleCounts> {
+        let mut counts = metadata::BundleCounts::default();
+        for bundle in self.entries.read().await.values() {
+            counts.add(&bundle.metadata.status, 1);
+        }
+        Ok(counts)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use storage::MetadataStorage;
+
+    fn bundle(seq: u64, status: metadata::BundleStatus) -> (metadata::Metadata, bpv7::Bundle) {
+        (
+            metadata::Metadata {
+                status,
+                ..Default::default()
+            },
+            bpv7::Bundle {
+                id: bpv7::BundleId {
+                    source: "ipn:1.1".parse().unwrap(),
+                    timestamp: bpv7::CreationTimestamp {
+                        creation_time: None,
+                        sequence_number: seq,
+                    },
+                    fragment_info: None,
+                },
+                ..Default::default()
+            },
+        )
+    }
+
+    #[tokio::test]
+    async fn reap_tombstones() {
+        let storage = Storage::new(60);
+        let now = time::OffsetDateTime::now_utc();
+
+        let bundles = [
+            bundle(1, metadata::BundleStatus::Tombstone(now)),
+            bundle(
+                2,
+                metadata::BundleStatus::Tombstone(now + time::Duration::seconds(50)),
+            ),
+            bundle(3, metadata::BundleStatus::DispatchPending),
+        ];
+        for (metadata, bundle) in &bundles {
+            assert!(storage.store(metadata, bundle).await.unwrap());
+        }
+
+        // Nothing has outlived retention yet
+        assert_eq!(storage.reap_tombstones(now).await, 0);
+
+        // Past the retention of the first tombstone, but not the second
+        assert_eq!(
+            storage
+                .reap_tombstones(now + time::Duration::seconds(90))
+                .await,
+            1
+        );
+        let status = |i: usize| storage.get_bundle_status(&bundles[i].1.id);
+        assert_eq!(status(0).await.unwrap(), None);
+        assert!(matches!(
+            status(1).await.unwrap(),
+            Some(metadata::BundleStatus::Tombstone(_))
+        ));
+        assert_eq!(
+            status(2).await.unwrap(),
+            Some(metadata::BundleStatus::DispatchPending)
+        );
+
+        // An enormous retention keeps tombstones forever
+        let storage = Storage::new(u64::MAX);
+        let (metadata, bundle) = &bundles[0];
+        assert!(storage.store(metadata, bundle).await.unwrap());
+        assert_eq!(
+            storage
+                .reap_tombstones(now + time::Duration::days(365))
+                .await,
+            0
+        );
+    }
+
+    #[tokio::test]
+    async fn count_by_status() {
+        let storage = Storage::new(60);
+        let now = time::OffsetDateTime::now_utc();
+
+        let bundles = [
+            bundle(1, metadata::BundleStatus::IngressPending),
+            bundle(2, metadata::BundleStatus::DispatchPending),
+            bundle(3, metadata::BundleStatus::DispatchPending),
+            bundle(4, metadata::BundleStatus::ForwardAckPending(1, now)),
+            bundle(5, metadata::BundleStatus::Waiting(now)),
+            bundle(6, metadata::BundleStatus::Waiting(now)),
+            bundle(7, metadata::BundleStatus::Waiting(now)),
+            bundle(8, metadata::BundleStatus::Tombstone(now)),
+        ];
+        for (metadata, bundle) in &bundles {
+            assert!(storage.store(metadata, bundle).await.unwrap());
+        }
+
+        let counts = storage.count_by_status().await.unwrap();
+        assert_eq!(
+            counts,
+            metadata::BundleCounts {
+                ingress_pending: 1,
+                dispatch_pending: 2,
+                forward_ack_pending: 1,
+                waiting: 3,
+                tombstone: 1,
+                ..Default::default()
+            }
+        );
+        assert_eq!(counts.total(), 7);
+
+        // Moving a bundle on moves its count
+        storage
+            .set_bundle_status(&bundles[4].1.id, &metadata::BundleStatus::ForwardPending)
+            .await
+            .unwrap();
+        let counts = storage.count_by_status().await.unwrap();
+        assert_eq!(counts.waiting, 2);
+        assert_eq!(counts.forward_pending, 1);
+    }
 }
@@ -10,12 +10,26 @@ mod metadata_mem;
 #[cfg(feature = "mem-storage")]
 mod bundle_mem;
 
+mod admission;
+mod archive;
+mod bundle_tiered;
+mod resilience;
+mod scrub;
+
+pub use admission::{Priority, StorageFull};
+pub use archive::run as run_command;
+pub use resilience::StorageDegraded;
+
 fn hash(data: &[u8]) -> Arc<[u8]> {
     sha2::Sha256::digest(data).to_vec().into()
 }
 
 struct Config {
     wait_sample_interval: u64,
+    verify_on_load: bool,
+    max_dispatch_per_wakeup: usize,
+    scrub_interval: u64,
+    scrub_rate: u64,
 }
 
 impl Config {
@@ -27,6 +41,19 @@ impl Config {
                 settings::WAIT_SAMPLE_INTERVAL_SECS,
             )
             .trace_expect("Invalid 'wait_sample_interval' value in configuration"),
+            verify_on_load: settings::get_with_default(config, "verify_on_load", false)
+                .trace_expect("Invalid 'verify_on_load' value in configuration"),
+            max_dispatch_per_wakeup: settings::get_with_default::<u32, _>(
+                config,
+                "max_dispatch_per_wakeup",
+                0u32,
+            )
+            .trace_expect("Invalid 'max_dispatch_per_wakeup' value in configuration")
+                as usize,
+            scrub_interval: settings::get_with_default(config, "scrub_interval", 0u64)
+                .trace_expect("Invalid 'scrub_interval' value in configuration"),
+            scrub_rate: settings::get_with_default(config, "scrub_rate", scrub::DEFAULT_SCRUB_RATE)
+                .trace_expect("Invalid 'scrub_rate' value in configuration"),
         };
 
         if config.wait_sample_interval > i64::MAX as u64 {
@@ -34,14 +61,88 @@ impl Config {
             panic!("wait_sample_interval is too large");
         }
 
+        if config.scrub_interval > i64::MAX as u64 {
+            error!("scrub_interval is too large");
+            panic!("scrub_interval is too large");
+        }
+
         config
     }
 }
 
+/* Progress of the bundle storage consistency check performed by Store::start */
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct CheckProgress {
+    pub processed: u64,
+    pub orphans: u64,
+    pub bad: u64,
+    pub total: u64,
+}
+
+impl std::fmt::Display for CheckProgress {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} of {} bundles processed, {} orphan and {} bad bundles found",
+            self.processed, self.total, self.orphans, self.bad
+        )
+    }
+}
+
+// What an operator needs to know about a held bundle, without loading its data
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BundleSummary {
+    pub id: bpv7::BundleId,
+    pub status: metadata::BundleStatus,
+    pub size: usize,
+    pub expiry: time::OffsetDateTime,
+}
+
+impl BundleSummary {
+    fn new(bundle: &metadata::Bundle) -> Self {
+        Self {
+            id: bundle.bundle.id.clone(),
+            status: bundle.metadata.status.clone(),
+            // The blocks are followed only by the break of the indefinite-length bundle array
+            size: bundle
+                .bundle
+                .blocks
+                .values()
+                .map(|block| block.data_start + block.data_len + 1)
+                .max()
+                .unwrap_or_default(),
+            expiry: bundle.expiry(),
+        }
+    }
+}
+
 pub struct Store {
     config: Config,
     metadata_storage: Arc<dyn storage::MetadataStorage>,
     bundle_storage: Arc<dyn storage::BundleStorage>,
+    admission: Option<Arc<std::sync::Mutex<admission::Admission>>>,
+    resilience: Arc<resilience::Resilience>,
+    clock: Arc<dyn utils::clock::Clock>,
+}
+
+// The metadata storage engines compiled into this build
+pub fn metadata_engines() -> Vec<&'static str> {
+    let mut engines = Vec::new();
+    #[cfg(feature = "sqlite-storage")]
+    engines.push(hardy_sqlite_storage::CONFIG_KEY);
+    #[cfg(feature = "mem-storage")]
+    engines.push(metadata_mem::CONFIG_KEY);
+    engines
+}
+
+// The bundle storage engines compiled into this build
+pub fn bundle_engines() -> Vec<&'static str> {
+    let mut engines = vec![bundle_tiered::CONFIG_KEY];
+    #[cfg(feature = "localdisk-storage")]
+    engines.push(hardy_localdisk_storage::CONFIG_KEY);
+    #[cfg(feature = "mem-storage")]
+    engines.push(bundle_mem::CONFIG_KEY);
+    engines
 }
 
 fn init_metadata_storage(
@@ -91,37 +192,81 @@ fn init_bundle_storage(config: &config::Config, _upgrade: bool) -> Arc<dyn stora
         .trace_expect("Invalid 'bundle_storage' value in configuration");
     info!("Using '{engine}' bundle storage engine");
 
-    let config = config.get_table(&engine).unwrap_or_default();
-    match engine.as_str() {
+    new_bundle_storage(config, &engine, DEFAULT)
+}
+
+fn new_bundle_storage(
+    config: &config::Config,
+    engine: &str,
+    default: &str,
+) -> Arc<dyn storage::BundleStorage> {
+    let engine_config = config.get_table(engine).unwrap_or_default();
+    match engine {
         #[cfg(feature = "localdisk-storage")]
-        hardy_localdisk_storage::CONFIG_KEY => hardy_localdisk_storage::Storage::init(&config),
+        hardy_localdisk_storage::CONFIG_KEY => {
+            hardy_localdisk_storage::Storage::init(&engine_config)
+        }
 
         #[cfg(feature = "mem-storage")]
-        bundle_mem::CONFIG_KEY => bundle_mem::Storage::init(&config),
+        bundle_mem::CONFIG_KEY => bundle_mem::Storage::init(&engine_config),
+
+        bundle_tiered::CONFIG_KEY => {
+            let inner = engine_config.get("inner").map_or(default.to_string(), |v| {
+                v.clone()
+                    .into_string()
+                    .trace_expect("Invalid 'inner' value in configuration")
+            });
+            if inner == bundle_tiered::CONFIG_KEY {
+                panic!("Tiered bundle storage cannot wrap itself");
+            }
+            info!("Using '{inner}' bundle storage engine beneath the memory tier");
+
+            bundle_tiered::Storage::init(
+                &engine_config,
+                new_bundle_storage(config, &inner, default),
+            )
+        }
 
         _ => panic!("Unknown bundle storage engine: {engine}"),
     }
 }
 
 impl Store {
-    pub fn new(config: &config::Config, upgrade: bool) -> Arc<Self> {
+    pub fn new(
+        config: &config::Config,
+        upgrade: bool,
+        clock: Arc<dyn utils::clock::Clock>,
+    ) -> Arc<Self> {
         // Init pluggable storage engines
         Arc::new(Self {
             config: Config::new(config),
             metadata_storage: init_metadata_storage(config, upgrade),
             bundle_storage: init_bundle_storage(config, upgrade),
+            admission: admission::Admission::init(config).map(Arc::new),
+            resilience: Arc::new(resilience::Resilience::init(config)),
+            clock,
         })
     }
 
+    pub fn clock(&self) -> &Arc<dyn utils::clock::Clock> {
+        &self.clock
+    }
+
+    // True while storage is failing persistently, see resilience::Resilience
+    pub fn is_degraded(&self) -> bool {
+        self.resilience.is_degraded()
+    }
+
     #[instrument(skip_all)]
     pub async fn start(
-        &self,
+        self: &Arc<Self>,
         dispatcher: Arc<dispatcher::Dispatcher>,
         task_set: &mut tokio::task::JoinSet<()>,
+        progress: Option<tokio::sync::watch::Sender<CheckProgress>>,
         cancel_token: tokio_util::sync::CancellationToken,
     ) {
         info!("Starting store consistency check...");
-        self.bundle_storage_check(dispatcher.clone(), cancel_token.clone())
+        self.bundle_storage_check(dispatcher.clone(), progress, cancel_token.clone())
             .await;
 
         if !cancel_token.is_cancelled() {
@@ -138,10 +283,25 @@ impl Store {
                 let metadata_storage = self.metadata_storage.clone();
                 task_set.spawn(Self::check_waiting(
                     wait_sample_interval,
+                    self.config.max_dispatch_per_wakeup,
                     metadata_storage,
-                    dispatcher,
+                    self.bundle_storage.clone(),
+                    self.resilience.clone(),
+                    self.clock.clone(),
+                    dispatcher.clone(),
                     cancel_token.clone(),
                 ));
+
+                if self.config.scrub_interval != 0 {
+                    info!(
+                        "Scrubbing bundle storage every {} seconds, at up to {} bytes per second",
+                        self.config.scrub_interval, self.config.scrub_rate
+                    );
+                    task_set.spawn(
+                        self.clone()
+                            .scrub_periodically(dispatcher, cancel_token.clone()),
+                    );
+                }
             }
         }
     }
@@ -252,6 +412,7 @@ impl Store {
     async fn bundle_storage_check(
         &self,
         dispatcher: Arc<dispatcher::Dispatcher>,
+        progress: Option<tokio::sync::watch::Sender<CheckProgress>>,
         cancel_token: tokio_util::sync::CancellationToken,
     ) {
         // We're going to spawn a bunch of tasks
@@ -259,24 +420,30 @@ impl Store {
             .map(Into::into)
             .unwrap_or(1)
             + 1;
-        let mut task_set = tokio::task::JoinSet::new();
+        let mut task_set = hardy_async::task::JoinSet::new();
         let semaphore = Arc::new(tokio::sync::Semaphore::new(parallelism));
 
         // Give some feedback
         let timer = tokio::time::sleep(tokio::time::Duration::from_secs(5));
         tokio::pin!(timer);
-        let mut bundles = 0u64;
-        let mut orphans = 0u64;
-        let mut bad = 0u64;
+        let stored_bundles = self.list_stored_bundles(cancel_token.clone()).await;
+        let mut p = CheckProgress {
+            total: stored_bundles.len() as u64,
+            ..Default::default()
+        };
+        let report = |p: &CheckProgress| {
+            if let Some(progress) = &progress {
+                progress.send_replace(*p);
+            }
+        };
+        report(&p);
 
         // For each bundle in the store
-        for (storage_name, file_time) in self.list_stored_bundles(cancel_token.clone()).await {
-            bundles = bundles.saturating_add(1);
-
+        for (storage_name, file_time) in stored_bundles {
             loop {
                 tokio::select! {
                     () = &mut timer => {
-                        info!("Bundle restart in progress, {bundles} bundles processed, {orphans} orphan and {bad} bad bundles found");
+                        info!("Bundle restart in progress, {p}");
                         timer.as_mut().reset(tokio::time::Instant::now() + tokio::time::Duration::from_secs(5));
                     },
                     // Throttle the number of tasks
@@ -285,10 +452,12 @@ impl Store {
                         let permit = permit.trace_expect("Failed to acquire permit");
                         let metadata_storage = self.metadata_storage.clone();
                         let bundle_storage = self.bundle_storage.clone();
+                        let admission = self.admission.clone();
                         let dispatcher = dispatcher.clone();
+                        let clock = self.clock.clone();
 
                         task_set.spawn(async move {
-                            let (o,b) = Self::restart_bundle(metadata_storage, bundle_storage, dispatcher, storage_name, file_time).await;
+                            let (o,b) = Self::restart_bundle(metadata_storage, bundle_storage, admission, dispatcher, clock, storage_name, file_time).await;
                             drop(permit);
                             (o,b)
                         });
@@ -296,8 +465,10 @@ impl Store {
                     }
                     Some(r) = task_set.join_next(), if !task_set.is_empty() => {
                         let (o,b) = r.trace_expect("Task terminated unexpectedly");
-                        orphans = orphans.saturating_add(o);
-                        bad = bad.saturating_add(b);
+                        p.processed = p.processed.saturating_add(1);
+                        p.orphans = p.orphans.saturating_add(o);
+                        p.bad = p.bad.saturating_add(b);
+                        report(&p);
                     },
                     _ = cancel_token.cancelled() => break
                 }
@@ -307,17 +478,21 @@ impl Store {
         // Wait for all sub-tasks to complete
         while let Some(r) = task_set.join_next().await {
             let (o, b) = r.trace_expect("Task terminated unexpectedly");
-            orphans = orphans.saturating_add(o);
-            bad = bad.saturating_add(b);
+            p.processed = p.processed.saturating_add(1);
+            p.orphans = p.orphans.saturating_add(o);
+            p.bad = p.bad.saturating_add(b);
+            report(&p);
         }
-        info!("Bundle restart complete, {bundles} bundles processed, {orphans} orphan and {bad} bad bundles found");
+        info!("Bundle restart complete, {p}");
     }
 
-    #[instrument(skip(metadata_storage, bundle_storage, dispatcher))]
+    #[instrument(skip(metadata_storage, bundle_storage, admission, dispatcher, clock))]
     async fn restart_bundle(
         metadata_storage: Arc<dyn storage::MetadataStorage>,
         bundle_storage: Arc<dyn storage::BundleStorage>,
+        admission: Option<Arc<std::sync::Mutex<admission::Admission>>>,
         dispatcher: Arc<dispatcher::Dispatcher>,
+        clock: Arc<dyn utils::clock::Clock>,
         mut storage_name: Arc<str>,
         file_time: Option<time::OffsetDateTime>,
     ) -> (u64, u64) {
@@ -331,13 +506,14 @@ impl Store {
         };
 
         // Parse the bundle
-        let (bundle, reason, hash, report_unsupported) =
+        let (bundle, reason, hash, report_unsupported, len) =
             match bpv7::ValidBundle::parse(data.as_ref().as_ref(), |_, _| Ok(None)) {
                 Ok(bpv7::ValidBundle::Valid(bundle, report_unsupported)) => (
                     bundle,
                     None,
                     Some(hash(data.as_ref().as_ref())),
                     report_unsupported,
+                    data.as_ref().as_ref().len(),
                 ),
                 Ok(bpv7::ValidBundle::Rewritten(bundle, data, report_unsupported)) => {
                     warn!("Bundle in non-canonical format found: {storage_name}");
@@ -356,7 +532,13 @@ impl Store {
                         ));
 
                     storage_name = new_storage_name;
-                    (bundle, None, Some(hash(&data)), report_unsupported)
+                    (
+                        bundle,
+                        None,
+                        Some(hash(&data)),
+                        report_unsupported,
+                        data.len(),
+                    )
                 }
                 Ok(bpv7::ValidBundle::Invalid(bundle, reason, e)) => {
                     warn!("Invalid bundle found: {storage_name}, {e}");
@@ -365,11 +547,17 @@ impl Store {
                         Some(reason),
                         Some(hash(data.as_ref().as_ref())),
                         false,
+                        data.as_ref().as_ref().len(),
                     )
                 }
                 Err(e) => {
                     // Parse failed badly, no idea who to report to
                     warn!("Junk data found: {storage_name}, {e}");
+                    for (offset, e) in
+                        hardy_cbor::decode::parse_lenient(data.as_ref().as_ref(), 16).2
+                    {
+                        trace!("{storage_name}: CBOR error at offset {offset}: {e}");
+                    }
 
                     // Drop the bundle
                     bundle_storage
@@ -383,6 +571,16 @@ impl Store {
             };
         drop(data);
 
+        // Account for the data before it is dispatched, as dispatch may delete it
+        let restore = |storage_name: &Arc<str>| {
+            if let Some(admission) = &admission {
+                admission
+                    .lock()
+                    .trace_expect("Failed to lock admission mutex")
+                    .restore(storage_name.clone(), len as u64, Priority::of(&bundle));
+            }
+        };
+
         // Check if the metadata_storage knows about this bundle
         let metadata = metadata_storage
             .confirm_exists(&bundle.id)
@@ -412,6 +610,7 @@ impl Store {
                 return (0, 1);
             }
 
+            restore(&storage_name);
             dispatcher
                 .check_bundle(metadata::Bundle { metadata, bundle }, reason)
                 .await
@@ -420,6 +619,7 @@ impl Store {
             return (0, 0);
         }
 
+        restore(&storage_name);
         let mut bundle = metadata::Bundle {
             metadata: metadata::Metadata {
                 storage_name: Some(storage_name),
@@ -432,8 +632,7 @@ impl Store {
 
         // If the bundle isn't valid, it must always be a Tombstone
         if reason.is_some() {
-            bundle.metadata.status =
-                metadata::BundleStatus::Tombstone(time::OffsetDateTime::now_utc())
+            bundle.metadata.status = metadata::BundleStatus::Tombstone(clock.now())
         }
 
         // Send to the dispatcher ingress as it is effectively a new bundle
@@ -448,65 +647,238 @@ impl Store {
     #[instrument(skip_all)]
     async fn check_waiting(
         wait_sample_interval: time::Duration,
+        max_dispatch: usize,
         metadata_storage: Arc<dyn storage::MetadataStorage>,
+        bundle_storage: Arc<dyn storage::BundleStorage>,
+        resilience: Arc<resilience::Resilience>,
+        clock: Arc<dyn utils::clock::Clock>,
         dispatcher: Arc<dispatcher::Dispatcher>,
         cancel_token: tokio_util::sync::CancellationToken,
     ) {
+        /* Bundles woken since the last sleep.  A woken bundle is still waiting in the metadata storage
+         * until the dispatcher gets to it, so must not be woken twice */
+        let mut woken = std::collections::HashSet::new();
         while utils::cancel::cancellable_sleep(wait_sample_interval, &cancel_token).await {
-            // Get all bundles that are ready before now() + self.config.wait_sample_interval
-            let limit = time::OffsetDateTime::now_utc() + wait_sample_interval;
-
-            let (tx, mut rx) = tokio::sync::mpsc::channel::<metadata::Bundle>(16);
-            let dispatcher = dispatcher.clone();
-            let cancel_token = cancel_token.clone();
-
-            let h = tokio::spawn(async move {
-                loop {
-                    tokio::select! {
-                        bundle = rx.recv() => match bundle {
-                            None => break,
-                            Some(bundle) => {
-                                // Double check returned bundles
-                                match bundle.metadata.status {
-                                    metadata::BundleStatus::ForwardAckPending(_, until)
-                                    | metadata::BundleStatus::Waiting(until)
-                                        if until <= limit =>
-                                    {
-                                        dispatcher.dispatch_bundle(bundle).await.trace_expect("Failed to dispatch bundle");
-                                    }
-                                    _ => {}
-                                }
-                            },
-                        },
-                        _ = cancel_token.cancelled() => break,
+            woken.clear();
+            loop {
+                // Get all bundles that are ready before now() + self.config.wait_sample_interval
+                let limit = clock.now() + wait_sample_interval;
+
+                let (tx, mut rx) = tokio::sync::mpsc::channel::<metadata::Bundle>(16);
+                let dispatch = async {
+                    while let Some(bundle) = rx.recv().await {
+                        dispatcher
+                            .dispatch_bundle(bundle)
+                            .await
+                            .trace_expect("Failed to dispatch bundle");
                     }
+                };
+
+                let (dispatched, ()) = tokio::join!(
+                    Self::wake_waiting(
+                        &metadata_storage,
+                        limit,
+                        max_dispatch,
+                        &mut woken,
+                        tx,
+                        &cancel_token
+                    ),
+                    dispatch
+                );
+
+                // A full batch means more bundles are probably ready, so wake them now rather than after another sleep
+                if max_dispatch == 0 || dispatched < max_dispatch || cancel_token.is_cancelled() {
+                    break;
                 }
-            });
+                trace!("Dispatched {dispatched} waiting bundles, looking for more");
+            }
 
-            metadata_storage
-                .get_waiting_bundles(limit, tx)
+            // Probe the metadata storage, noticing when degraded storage has recovered, as ingress is refused until then
+            if let Err(e) = resilience
+                .retry(resilience::Backend::Metadata, "count bundles", || {
+                    metadata_storage.count_by_status()
+                })
                 .await
-                .trace_expect("get_waiting_bundles failed");
+            {
+                warn!("Failed to count bundles: {e}");
+            }
+
+            // Nothing else writes bundle data while ingress is refused, so probe degraded bundle storage with a write too
+            if resilience.is_backend_degraded(resilience::Backend::Bundles) {
+                if let Err(e) = Self::probe_bundle_storage(&bundle_storage, &resilience).await {
+                    warn!("Failed to probe bundle storage: {e}");
+                }
+            }
+        }
+    }
+
+    async fn probe_bundle_storage(
+        bundle_storage: &Arc<dyn storage::BundleStorage>,
+        resilience: &resilience::Resilience,
+    ) -> Result<(), Error> {
+        let storage_name = resilience
+            .retry(resilience::Backend::Bundles, "store probe data", || {
+                bundle_storage.store(&[])
+            })
+            .await?;
+        resilience
+            .retry(resilience::Backend::Bundles, "remove probe data", || {
+                bundle_storage.remove(&storage_name)
+            })
+            .await
+    }
+
+    /* Send the bundles ready before `limit` that are not already in `woken` to `tx`, adding them to
+     * `woken`, and returning how many were sent.  At most `max_dispatch` are sent if not 0, so a long
+     * sleep does not wake every waiting bundle at once: the rest are still waiting in the metadata
+     * storage, and are found by the next batch */
+    async fn wake_waiting(
+        metadata_storage: &Arc<dyn storage::MetadataStorage>,
+        limit: time::OffsetDateTime,
+        max_dispatch: usize,
+        woken: &mut std::collections::HashSet<bpv7::BundleId>,
+        tx: storage::Sender,
+        cancel_token: &tokio_util::sync::CancellationToken,
+    ) -> usize {
+        let (inner_tx, mut rx) = tokio::sync::mpsc::channel::<metadata::Bundle>(16);
+        let forward = async move {
+            let mut dispatched = 0;
+            while let Some(bundle) =
+                hardy_async::channel::recv_or_cancel(&mut rx, cancel_token).await
+            {
+                // Double check returned bundles
+                match bundle.metadata.status {
+                    metadata::BundleStatus::ForwardAckPending(_, until)
+                    | metadata::BundleStatus::Waiting(until)
+                        if until <= limit && woken.insert(bundle.bundle.id.clone()) =>
+                    {
+                        if tx.send(bundle).await.is_err() {
+                            break;
+                        }
+                        dispatched += 1;
+                        if dispatched == max_dispatch {
+                            // Dropping the receiver stops the storage sending any more
+                            break;
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            dispatched
+        };
 
-            h.await.trace_expect("polling task failed")
+        let (r, dispatched) = tokio::join!(
+            metadata_storage.get_waiting_bundles(limit, inner_tx),
+            forward
+        );
+        if let Err(e) = r {
+            // The bundles are still waiting, and will be found by the next cycle
+            error!("Failed to get waiting bundles: {e}");
         }
+        dispatched
     }
 
     #[inline]
     pub async fn load_data(&self, storage_name: &str) -> Result<Option<storage::DataRef>, Error> {
-        self.bundle_storage.load(storage_name).await
+        self.resilience
+            .retry(resilience::Backend::Bundles, "load bundle data", || {
+                self.bundle_storage.load(storage_name)
+            })
+            .await
     }
 
-    #[inline]
-    pub async fn store_data(&self, data: &[u8]) -> Result<(Arc<str>, Arc<[u8]>), Error> {
+    /* Load the data of a bundle, checking it against the hash recorded when it was stored, if configured.
+     * Corrupt data is treated as lost: the bundle is tombstoned, the data removed, and None returned */
+    pub async fn load_bundle_data(
+        &self,
+        bundle: &mut metadata::Bundle,
+    ) -> Result<Option<storage::DataRef>, Error> {
+        let Some(storage_name) = bundle.metadata.storage_name.clone() else {
+            return Ok(None);
+        };
+        let Some(data) = self.load_data(&storage_name).await? else {
+            return Ok(None);
+        };
+
+        if self.config.verify_on_load {
+            if let Some(expected) = &bundle.metadata.hash {
+                if hash(data.as_ref().as_ref()) != *expected {
+                    error!("Bundle data {storage_name} is corrupt, the hash does not match");
+
+                    self.set_status(bundle, metadata::BundleStatus::Tombstone(self.clock.now()))
+                        .await?;
+                    self.delete_data(&storage_name).await?;
+                    return Ok(None);
+                }
+            }
+        }
+        Ok(Some(data))
+    }
+
+    /* Write bundle data to storage, subject to the configured capacity.
+     * Returns None if the bundle is refused, possibly evicting lower priority bundles to admit it */
+    pub async fn store_data(
+        &self,
+        data: &[u8],
+        priority: Priority,
+    ) -> Result<Option<(Arc<str>, Arc<[u8]>)>, Error> {
         // Calculate hash
         let hash = hash(data);
 
+        let Some(admission) = &self.admission else {
+            // Write to bundle storage
+            return self
+                .resilience
+                .retry(resilience::Backend::Bundles, "store bundle data", || {
+                    self.bundle_storage.store(data)
+                })
+                .await
+                .map(|storage_name| Some((storage_name, hash)));
+        };
+
+        let len = data.len() as u64;
+        let Some(victims) = admission
+            .lock()
+            .trace_expect("Failed to lock admission mutex")
+            .reserve(len, priority)
+        else {
+            info!("Bundle storage is full, refusing {priority:?} bundle of {len} bytes");
+            return Ok(None);
+        };
+
+        // The dispatcher reports the evicted bundles as 'Depleted Storage' when it finds their data has gone
+        for storage_name in victims {
+            info!("Evicting bundle data {storage_name} to admit a {priority:?} bundle");
+            self.resilience
+                .retry(resilience::Backend::Bundles, "remove bundle data", || {
+                    self.bundle_storage.remove(&storage_name)
+                })
+                .await?;
+        }
+
         // Write to bundle storage
-        self.bundle_storage
-            .store(data)
+        match self
+            .resilience
+            .retry(resilience::Backend::Bundles, "store bundle data", || {
+                self.bundle_storage.store(data)
+            })
             .await
-            .map(|storage_name| (storage_name, hash))
+        {
+            Ok(storage_name) => {
+                admission
+                    .lock()
+                    .trace_expect("Failed to lock admission mutex")
+                    .commit(storage_name.clone(), len, priority);
+                Ok(Some((storage_name, hash)))
+            }
+            Err(e) => {
+                admission
+                    .lock()
+                    .trace_expect("Failed to lock admission mutex")
+                    .cancel(len);
+                Err(e)
+            }
+        }
     }
 
     #[inline]
@@ -516,11 +888,31 @@ impl Store {
         bundle: &bpv7::Bundle,
     ) -> Result<bool, Error> {
         // Write to metadata store
-        Ok(self
-            .metadata_storage
-            .store(metadata, bundle)
-            .await
-            .trace_expect("Failed to store metadata"))
+        let failed = std::sync::atomic::AtomicBool::new(false);
+        let stored = self
+            .resilience
+            .retry(resilience::Backend::Metadata, "store metadata", || {
+                let failed = &failed;
+                async move {
+                    let r = self.metadata_storage.store(metadata, bundle).await;
+                    if r.is_err() {
+                        failed.store(true, std::sync::atomic::Ordering::Relaxed);
+                    }
+                    r
+                }
+            })
+            .await?;
+
+        if stored || !failed.load(std::sync::atomic::Ordering::Relaxed) {
+            return Ok(stored);
+        }
+
+        /* A failed attempt may have committed before reporting the error, in which case the retry
+         * finds our own metadata rather than a duplicate, so check whose it is */
+        Ok(self.load(&bundle.id).await?.is_some_and(|existing| {
+            existing.metadata.storage_name == metadata.storage_name
+                && existing.metadata.hash == metadata.hash
+        }))
     }
 
     #[inline]
@@ -528,7 +920,11 @@ impl Store {
         &self,
         bundle_id: &bpv7::BundleId,
     ) -> Result<Option<metadata::Bundle>, Error> {
-        self.metadata_storage.load(bundle_id).await
+        self.resilience
+            .retry(resilience::Backend::Metadata, "load metadata", || {
+                self.metadata_storage.load(bundle_id)
+            })
+            .await
     }
 
     #[instrument(skip(self, data))]
@@ -540,7 +936,9 @@ impl Store {
         received_at: Option<time::OffsetDateTime>,
     ) -> Result<Option<metadata::Metadata>, Error> {
         // Write to bundle storage
-        let (storage_name, hash) = self.store_data(data).await?;
+        let Some((storage_name, hash)) = self.store_data(data, Priority::of(bundle)).await? else {
+            return Err(StorageFull.into());
+        };
 
         // Compose metadata
         let metadata = metadata::Metadata {
@@ -555,13 +953,13 @@ impl Store {
             Ok(true) => Ok(Some(metadata)),
             Ok(false) => {
                 // We have a duplicate, remove the duplicate from the bundle store
-                _ = self.bundle_storage.remove(&storage_name).await;
+                _ = self.delete_data(&storage_name).await;
                 Ok(None)
             }
             Err(e) => {
                 // This is just bad, we can't really claim to have stored the bundle,
                 // so just cleanup and get out
-                _ = self.bundle_storage.remove(&storage_name).await;
+                _ = self.delete_data(&storage_name).await;
                 Err(e)
             }
         }
@@ -578,12 +976,97 @@ impl Store {
             .await
     }
 
+    /* Reset matching bundles to Waiting and pass them to `tx` to be dispatched again.
+     * Tombstones are skipped, as the bundle has already gone */
+    #[instrument(skip(self, tx))]
+    pub async fn replay(
+        &self,
+        filter: &bpv7::EidPattern,
+        window: std::ops::Range<time::OffsetDateTime>,
+        tx: tokio::sync::mpsc::Sender<metadata::Bundle>,
+    ) -> Result<usize, Error> {
+        let (inner_tx, mut rx) = tokio::sync::mpsc::channel::<metadata::Bundle>(16);
+        let metadata_storage = self.metadata_storage.clone();
+        let h = tokio::spawn(async move {
+            metadata_storage
+                .poll_received_between(window, inner_tx)
+                .await
+        });
+
+        let mut replayed = 0;
+        while let Some(mut bundle) = rx.recv().await {
+            // Double check returned bundles
+            if matches!(bundle.metadata.status, metadata::BundleStatus::Tombstone(_))
+                || !filter.is_match(&bundle.bundle.destination)
+            {
+                continue;
+            }
+
+            self.set_status(
+                &mut bundle,
+                metadata::BundleStatus::Waiting(self.clock.now()),
+            )
+            .await?;
+
+            if tx.send(bundle).await.is_err() {
+                break;
+            }
+            replayed += 1;
+        }
+        drop(rx);
+
+        h.await.trace_expect("polling task failed")?;
+        Ok(replayed)
+    }
+
+    /* Summarise up to `limit` held bundles whose destination matches `destination`,
+     * for operators investigating why nothing is reaching an endpoint */
+    #[instrument(skip(self))]
+    pub async fn query_bundles(
+        &self,
+        destination: &bpv7::EidPattern,
+        limit: usize,
+    ) -> Result<Vec<BundleSummary>, Error> {
+        let (tx, mut rx) = tokio::sync::mpsc::channel::<metadata::Bundle>(16);
+        let metadata_storage = self.metadata_storage.clone();
+        let pattern = destination.clone();
+        let h =
+            tokio::spawn(async move { metadata_storage.poll_for_destination(pattern, tx).await });
+
+        let mut summaries = Vec::new();
+        while summaries.len() < limit {
+            let Some(bundle) = rx.recv().await else {
+                break;
+            };
+
+            // Double check returned bundles
+            if !matches!(bundle.metadata.status, metadata::BundleStatus::Tombstone(_))
+                && destination.is_match(&bundle.bundle.destination)
+            {
+                summaries.push(BundleSummary::new(&bundle));
+            }
+        }
+        drop(rx);
+
+        h.await.trace_expect("polling task failed")?;
+        Ok(summaries)
+    }
+
+    #[inline]
+    pub async fn bundle_counts(&self) -> Result<metadata::BundleCounts, Error> {
+        self.metadata_storage.count_by_status().await
+    }
+
     #[inline]
     pub async fn check_status(
         &self,
         bundle_id: &bpv7::BundleId,
     ) -> Result<Option<metadata::BundleStatus>, Error> {
-        self.metadata_storage.get_bundle_status(bundle_id).await
+        self.resilience
+            .retry(resilience::Backend::Metadata, "get bundle status", || {
+                self.metadata_storage.get_bundle_status(bundle_id)
+            })
+            .await
     }
 
     #[instrument(skip(self))]
@@ -596,21 +1079,1622 @@ impl Store {
             Ok(())
         } else {
             bundle.metadata.status = status;
-            self.metadata_storage
-                .set_bundle_status(&bundle.bundle.id, &bundle.metadata.status)
+            self.resilience
+                .retry(resilience::Backend::Metadata, "set bundle status", || {
+                    self.metadata_storage
+                        .set_bundle_status(&bundle.bundle.id, &bundle.metadata.status)
+                })
                 .await
         }
     }
 
     #[inline]
     pub async fn delete_data(&self, storage_name: &str) -> Result<(), Error> {
+        if let Some(admission) = &self.admission {
+            admission
+                .lock()
+                .trace_expect("Failed to lock admission mutex")
+                .release(storage_name);
+        }
+
         // Delete the bundle from the bundle store
-        self.bundle_storage.remove(storage_name).await
+        self.resilience
+            .retry(resilience::Backend::Bundles, "remove bundle data", || {
+                self.bundle_storage.remove(storage_name)
+            })
+            .await
     }
 
     #[inline]
     pub async fn delete_metadata(&self, bundle_id: &bpv7::BundleId) -> Result<(), Error> {
-        // Delete the bundle from the bundle store
-        self.metadata_storage.remove(bundle_id).await
+        // Delete the bundle from the metadata store
+        self.resilience
+            .retry(resilience::Backend::Metadata, "remove metadata", || {
+                self.metadata_storage.remove(bundle_id)
+            })
+            .await
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use hardy_bpa_api::async_trait;
+    use std::sync::Mutex;
+
+    // Just enough metadata storage to replay from, that can fail the next writes after committing them
+    #[derive(Default)]
+    pub(crate) struct TestMetadata(
+        pub(crate) Mutex<Vec<metadata::Bundle>>,
+        pub(crate) std::sync::atomic::AtomicU32,
+    );
+
+    #[async_trait]
+    impl storage::MetadataStorage for TestMetadata {
+        async fn load(
+            &self,
+            bundle_id: &bpv7::BundleId,
+        ) -> storage::Result<Option<metadata::Bundle>> {
+            Ok(self
+                .0
+                .lock()
+                .unwrap()
+                .iter()
+                .find(|bundle| &bundle.bundle.id == bundle_id)
+                .cloned())
+        }
+
+        async fn store(
+            &self,
+            metadata: &metadata::Metadata,
+            bundle: &bpv7::Bundle,
+        ) -> storage::Result<bool> {
+            let mut bundles = self.0.lock().unwrap();
+            if bundles.iter().any(|b| b.bundle.id == bundle.id) {
+                return Ok(false);
+            }
+            bundles.push(metadata::Bundle {
+                metadata: metadata.clone(),
+                bundle: bundle.clone(),
+            });
+            if self
+                .1
+                .fetch_update(
+                    std::sync::atomic::Ordering::Relaxed,
+                    std::sync::atomic::Ordering::Relaxed,
+                    |n| n.checked_sub(1),
+                )
+                .is_ok()
+            {
+                return Err("Connection lost".into());
+            }
+            Ok(true)
+        }
+
+        async fn get_bundle_status(
+            &self,
+            bundle_id: &bpv7::BundleId,
+        ) -> storage::Result<Option<metadata::BundleStatus>> {
+            Ok(self
+                .0
+                .lock()
+                .unwrap()
+                .iter()
+                .find(|bundle| &bundle.bundle.id == bundle_id)
+                .map(|bundle| bundle.metadata.status.clone()))
+        }
+
+        async fn set_bundle_status(
+            &self,
+            bundle_id: &bpv7::BundleId,
+            status: &metadata::BundleStatus,
+        ) -> storage::Result<()> {
+            for bundle in self.0.lock().unwrap().iter_mut() {
+                if &bundle.bundle.id == bundle_id {
+                    bundle.metadata.status = status.clone();
+                }
+            }
+            Ok(())
+        }
+
+        async fn remove(&self, bundle_id: &bpv7::BundleId) -> storage::Result<()> {
+            self.0
+                .lock()
+                .unwrap()
+                .retain(|bundle| &bundle.bundle.id != bundle_id);
+            Ok(())
+        }
+
+        async fn confirm_exists(
+            &self,
+            bundle_id: &bpv7::BundleId,
+        ) -> storage::Result<Option<metadata::Metadata>> {
+            Ok(self
+                .0
+                .lock()
+                .unwrap()
+                .iter()
+                .find(|bundle| &bundle.bundle.id == bundle_id)
+                .map(|bundle| bundle.metadata.clone()))
+        }
+
+        async fn get_waiting_bundles(
+            &self,
+            limit: time::OffsetDateTime,
+            tx: storage::Sender,
+        ) -> storage::Result<()> {
+            let bundles = self.0.lock().unwrap().clone();
+            for bundle in bundles {
+                if let metadata::BundleStatus::Waiting(until) = bundle.metadata.status {
+                    if until <= limit && tx.send(bundle).await.is_err() {
+                        break;
+                    }
+                }
+            }
+            Ok(())
+        }
+
+        async fn get_unconfirmed_bundles(&self, _: storage::Sender) -> storage::Result<()> {
+            unimplemented!()
+        }
+
+        async fn poll_for_collection(
+            &self,
+            _: bpv7::Eid,
+            _: storage::Sender,
+        ) -> storage::Result<()> {
+            unimplemented!()
+        }
+
+        async fn poll_received_between(
+            &self,
+            window: std::ops::Range<time::OffsetDateTime>,
+            tx: storage::Sender,
+        ) -> storage::Result<()> {
+            let bundles = self.0.lock().unwrap().clone();
+            for bundle in bundles {
+                if !matches!(bundle.metadata.status, metadata::BundleStatus::Tombstone(_))
+                    && bundle
+                        .metadata
+                        .received_at
+                        .is_some_and(|t| window.contains(&t))
+                {
+                    tx.send(bundle).await?;
+                }
+            }
+            Ok(())
+        }
+
+        async fn poll_for_destination(
+            &self,
+            destination: bpv7::EidPattern,
+            tx: storage::Sender,
+        ) -> storage::Result<()> {
+            let bundles = self.0.lock().unwrap().clone();
+            for bundle in bundles {
+                if !matches!(bundle.metadata.status, metadata::BundleStatus::Tombstone(_))
+                    && destination.is_match(&bundle.bundle.destination)
+                    && tx.send(bundle).await.is_err()
+                {
+                    break;
+                }
+            }
+            Ok(())
+        }
+
+        async fn count_by_status(&self) -> storage::Result<metadata::BundleCounts> {
+            let mut counts = metadata::BundleCounts::default();
+            for bundle in self.0.lock().unwrap().iter() {
+                counts.add(&bundle.metadata.status, 1);
+            }
+            Ok(counts)
+        }
+    }
+
+    struct NoBundles;
+
+    #[async_trait]
+    impl storage::BundleStorage for NoBundles {
+        async fn list(
+            &self,
+            _: tokio::sync::mpsc::Sender<storage::ListResponse>,
+        ) -> storage::Result<()> {
+            unimplemented!()
+        }
+
+        async fn load(&self, _: &str) -> storage::Result<Option<storage::DataRef>> {
+            unimplemented!()
+        }
+
+        async fn store(&self, _: &[u8]) -> storage::Result<Arc<str>> {
+            unimplemented!()
+        }
+
+        async fn remove(&self, _: &str) -> storage::Result<()> {
+            unimplemented!()
+        }
+    }
+
+    // Bundle data keyed by storage name, and the number of bundles ever stored
+    #[derive(Default)]
+    pub(crate) struct TestBundles(
+        pub(crate) Mutex<std::collections::HashMap<String, Vec<u8>>>,
+        pub(crate) std::sync::atomic::AtomicUsize,
+    );
+
+    #[async_trait]
+    impl storage::BundleStorage for TestBundles {
+        async fn list(
+            &self,
+            tx: tokio::sync::mpsc::Sender<storage::ListResponse>,
+        ) -> storage::Result<()> {
+            let names = self.0.lock().unwrap().keys().cloned().collect::<Vec<_>>();
+            for name in names {
+                tx.send((name.into(), None)).await?;
+            }
+            Ok(())
+        }
+
+        async fn load(&self, storage_name: &str) -> storage::Result<Option<storage::DataRef>> {
+            Ok(self
+                .0
+                .lock()
+                .unwrap()
+                .get(storage_name)
+                .map(|data| Arc::new(data.clone()) as storage::DataRef))
+        }
+
+        async fn store(&self, data: &[u8]) -> storage::Result<Arc<str>> {
+            let storage_name = format!(
+                "stored{}",
+                self.1.fetch_add(1, std::sync::atomic::Ordering::Relaxed)
+            );
+            self.0
+                .lock()
+                .unwrap()
+                .insert(storage_name.clone(), data.to_vec());
+            Ok(storage_name.into())
+        }
+
+        async fn remove(&self, storage_name: &str) -> storage::Result<()> {
+            self.0.lock().unwrap().remove(storage_name);
+            Ok(())
+        }
+    }
+
+    // Bundle storage that fails the next `failures` writes
+    #[derive(Default)]
+    struct FlakyBundles {
+        inner: TestBundles,
+        failures: std::sync::atomic::AtomicU32,
+    }
+
+    #[async_trait]
+    impl storage::BundleStorage for FlakyBundles {
+        async fn list(
+            &self,
+            tx: tokio::sync::mpsc::Sender<storage::ListResponse>,
+        ) -> storage::Result<()> {
+            self.inner.list(tx).await
+        }
+
+        async fn load(&self, storage_name: &str) -> storage::Result<Option<storage::DataRef>> {
+            self.inner.load(storage_name).await
+        }
+
+        async fn store(&self, data: &[u8]) -> storage::Result<Arc<str>> {
+            if self
+                .failures
+                .fetch_update(
+                    std::sync::atomic::Ordering::Relaxed,
+                    std::sync::atomic::Ordering::Relaxed,
+                    |n| n.checked_sub(1),
+                )
+                .is_ok()
+            {
+                return Err("Disk on fire".into());
+            }
+            self.inner.store(data).await
+        }
+
+        async fn remove(&self, storage_name: &str) -> storage::Result<()> {
+            self.inner.remove(storage_name).await
+        }
+    }
+
+    pub(crate) fn test_config() -> Config {
+        Config {
+            wait_sample_interval: settings::WAIT_SAMPLE_INTERVAL_SECS,
+            verify_on_load: false,
+            max_dispatch_per_wakeup: 0,
+            scrub_interval: 0,
+            scrub_rate: 0,
+        }
+    }
+
+    // A store over the given storage, with the default configuration, override fields as needed
+    pub(crate) fn test_store(
+        metadata_storage: Arc<dyn storage::MetadataStorage>,
+        bundle_storage: Arc<dyn storage::BundleStorage>,
+    ) -> Store {
+        Store {
+            config: test_config(),
+            metadata_storage,
+            bundle_storage,
+            admission: None,
+            resilience: Default::default(),
+            clock: Arc::new(utils::clock::SystemClock),
+        }
+    }
+
+    fn bundle(seq: u64, destination: &str, status: metadata::BundleStatus) -> metadata::Bundle {
+        metadata::Bundle {
+            bundle: bpv7::Bundle {
+                id: bpv7::BundleId {
+                    source: "ipn:1.1".parse().unwrap(),
+                    timestamp: bpv7::CreationTimestamp {
+                        creation_time: None,
+                        sequence_number: seq,
+                    },
+                    fragment_info: None,
+                },
+                destination: destination.parse().unwrap(),
+                ..Default::default()
+            },
+            metadata: metadata::Metadata {
+                status,
+                received_at: Some(time::OffsetDateTime::now_utc()),
+                ..Default::default()
+            },
+        }
+    }
+
+    #[tokio::test]
+    async fn replay() {
+        let metadata_storage = Arc::new(TestMetadata::default());
+        *metadata_storage.0.lock().unwrap() = vec![
+            bundle(1, "ipn:2.1", metadata::BundleStatus::CollectionPending),
+            bundle(2, "ipn:3.1", metadata::BundleStatus::CollectionPending),
+            bundle(3, "ipn:2.2", metadata::BundleStatus::ForwardPending),
+            bundle(
+                4,
+                "ipn:2.1",
+                metadata::BundleStatus::Tombstone(time::OffsetDateTime::now_utc()),
+            ),
+        ];
+        let store = test_store(metadata_storage.clone(), Arc::new(NoBundles));
+
+        let now = time::OffsetDateTime::now_utc();
+        let (tx, mut rx) = tokio::sync::mpsc::channel(16);
+        let replayed = store
+            .replay(
+                &"ipn:2.*".parse().unwrap(),
+                now - time::Duration::hours(1)..now + time::Duration::hours(1),
+                tx,
+            )
+            .await
+            .unwrap();
+        assert_eq!(replayed, 2);
+
+        let mut dispatched = Vec::new();
+        while let Some(bundle) = rx.recv().await {
+            assert!(matches!(
+                bundle.metadata.status,
+                metadata::BundleStatus::Waiting(_)
+            ));
+            dispatched.push(bundle.bundle.id.timestamp.sequence_number);
+        }
+        dispatched.sort();
+        assert_eq!(dispatched, vec![1, 3]);
+
+        // The storage has been updated too
+        let statuses = metadata_storage
+            .0
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|b| b.metadata.status.clone())
+            .collect::<Vec<_>>();
+        assert!(matches!(
+            statuses[1],
+            metadata::BundleStatus::CollectionPending
+        ));
+        assert!(matches!(statuses[2], metadata::BundleStatus::Waiting(_)));
+        assert!(matches!(statuses[3], metadata::BundleStatus::Tombstone(_)));
+    }
+
+    #[tokio::test]
+    async fn query_bundles() {
+        let metadata_storage = Arc::new(TestMetadata::default());
+        let mut local = bundle(5, "ipn:2.3", metadata::BundleStatus::DispatchPending);
+        local.metadata.received_at = None;
+        *metadata_storage.0.lock().unwrap() = vec![
+            bundle(1, "ipn:2.1", metadata::BundleStatus::CollectionPending),
+            bundle(2, "ipn:3.1", metadata::BundleStatus::CollectionPending),
+            bundle(3, "ipn:2.2", metadata::BundleStatus::ForwardPending),
+            bundle(
+                4,
+                "ipn:2.1",
+                metadata::BundleStatus::Tombstone(time::OffsetDateTime::now_utc()),
+            ),
+            local,
+            bundle(6, "dtn://node/svc", metadata::BundleStatus::DispatchPending),
+        ];
+        let store = test_store(metadata_storage, Arc::new(NoBundles));
+
+        // Tombstones are skipped, but locally originated bundles are included
+        let pattern = "ipn:2.*".parse().unwrap();
+        let summaries = store.query_bundles(&pattern, 10).await.unwrap();
+        assert_eq!(
+            summaries
+                .iter()
+                .map(|s| (s.id.timestamp.sequence_number, s.status.clone()))
+                .collect::<Vec<_>>(),
+            vec![
+                (1, metadata::BundleStatus::CollectionPending),
+                (3, metadata::BundleStatus::ForwardPending),
+                (5, metadata::BundleStatus::DispatchPending),
+            ]
+        );
+
+        let summaries = store
+            .query_bundles(&"dtn://node/**".parse().unwrap(), 10)
+            .await
+            .unwrap();
+        assert_eq!(summaries.len(), 1);
+        assert_eq!(summaries[0].id.timestamp.sequence_number, 6);
+
+        assert_eq!(store.query_bundles(&pattern, 2).await.unwrap().len(), 2);
+        assert!(store
+            .query_bundles(&"ipn:4.*".parse().unwrap(), 10)
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn wakeup_batch() {
+        // Many bundles that all became ready while we slept
+        let now = time::OffsetDateTime::now_utc();
+        let metadata_storage = Arc::new(TestMetadata::default());
+        *metadata_storage.0.lock().unwrap() = (0..50)
+            .map(|seq| {
+                bundle(
+                    seq,
+                    "ipn:2.1",
+                    metadata::BundleStatus::Waiting(now - time::Duration::hours(1)),
+                )
+            })
+            .collect();
+        let metadata_storage: Arc<dyn storage::MetadataStorage> = metadata_storage;
+        let cancel_token = tokio_util::sync::CancellationToken::new();
+
+        async fn wake(
+            metadata_storage: &Arc<dyn storage::MetadataStorage>,
+            now: time::OffsetDateTime,
+            max_dispatch: usize,
+            woken: &mut std::collections::HashSet<bpv7::BundleId>,
+            cancel_token: &tokio_util::sync::CancellationToken,
+        ) -> usize {
+            let (tx, mut rx) = tokio::sync::mpsc::channel(64);
+            let dispatched =
+                Store::wake_waiting(metadata_storage, now, max_dispatch, woken, tx, cancel_token)
+                    .await;
+            let mut received = 0;
+            while rx.recv().await.is_some() {
+                received += 1;
+            }
+            assert_eq!(dispatched, received);
+            received
+        }
+
+        // No more than the batch size is dispatched per batch
+        for (max_dispatch, expected) in [(16, 16), (100, 50), (0, 50)] {
+            let dispatched = wake(
+                &metadata_storage,
+                now,
+                max_dispatch,
+                &mut Default::default(),
+                &cancel_token,
+            )
+            .await;
+            assert_eq!(dispatched, expected);
+        }
+
+        // Successive batches after one sleep wake each bundle once, until none are left
+        let mut woken = Default::default();
+        for expected in [16, 16, 16, 2, 0] {
+            let dispatched = wake(&metadata_storage, now, 16, &mut woken, &cancel_token).await;
+            assert_eq!(dispatched, expected);
+        }
+        assert_eq!(woken.len(), 50);
+    }
+
+    pub(crate) fn new_dispatcher(
+        store: Arc<Store>,
+        task_set: &mut tokio::task::JoinSet<()>,
+        cancel_token: tokio_util::sync::CancellationToken,
+    ) -> Arc<dispatcher::Dispatcher> {
+        new_dispatcher_with_apps(store, task_set, cancel_token).0
+    }
+
+    // The configuration test dispatchers are built from, before any test specific settings
+    pub(crate) fn dispatcher_config(
+    ) -> config::builder::ConfigBuilder<config::builder::DefaultState> {
+        config::Config::builder()
+            .set_default("administrative_endpoint", "ipn:1.0")
+            .unwrap()
+    }
+
+    // A dispatcher, and the registry applications register with
+    pub(crate) fn new_dispatcher_with_apps(
+        store: Arc<Store>,
+        task_set: &mut tokio::task::JoinSet<()>,
+        cancel_token: tokio_util::sync::CancellationToken,
+    ) -> (Arc<dispatcher::Dispatcher>, app_registry::AppRegistry) {
+        let config = dispatcher_config().build().unwrap();
+        let cla_registry = cla_registry::ClaRegistry::new(&config, None);
+        build_dispatcher(store, &config, cla_registry, None, task_set, cancel_token)
+    }
+
+    fn build_dispatcher(
+        store: Arc<Store>,
+        config: &config::Config,
+        cla_registry: cla_registry::ClaRegistry,
+        fib: Option<fib::Fib>,
+        task_set: &mut tokio::task::JoinSet<()>,
+        cancel_token: tokio_util::sync::CancellationToken,
+    ) -> (Arc<dispatcher::Dispatcher>, app_registry::AppRegistry) {
+        let admin_endpoints = utils::admin_endpoints::AdminEndpoints::init(config);
+        let app_registry = app_registry::AppRegistry::new(config, admin_endpoints.clone());
+        let dispatcher = dispatcher::Dispatcher::new(
+            config,
+            admin_endpoints,
+            store,
+            cla_registry,
+            app_registry.clone(),
+            fib,
+            task_set,
+            cancel_token,
+        );
+        (dispatcher, app_registry)
+    }
+
+    // A routing dispatcher, with the applications and CLAs around it
+    pub(crate) struct TestDispatcher {
+        pub(crate) dispatcher: Arc<dispatcher::Dispatcher>,
+        pub(crate) app_registry: app_registry::AppRegistry,
+        pub(crate) fib: Option<fib::Fib>,
+        pub(crate) clas: Vec<Arc<cla_registry::NullCla>>,
+    }
+
+    /* As new_dispatcher_with_apps, but built from `config` with its FIB, and with a NullCla for
+     * each of `neighbours`, accepting bundles up to the given size */
+    pub(crate) async fn new_test_dispatcher(
+        store: Arc<Store>,
+        config: &config::Config,
+        neighbours: &[(&str, Option<u64>)],
+        task_set: &mut tokio::task::JoinSet<()>,
+        cancel_token: tokio_util::sync::CancellationToken,
+    ) -> TestDispatcher {
+        let fib = fib::Fib::new(config);
+        let cla_registry = cla_registry::ClaRegistry::new(config, fib.clone());
+        let mut clas = Vec::new();
+        for (i, (neighbour, max_bundle_size)) in neighbours.iter().enumerate() {
+            let (handle, cla) = cla_registry
+                .register_null_cla(&format!("null{i}"), "Null")
+                .await
+                .unwrap();
+            cla_registry
+                .add_neighbour(hardy_proto::cla::AddNeighbourRequest {
+                    handle,
+                    priority: 0,
+                    neighbour: neighbour.to_string(),
+                    max_bundle_size: *max_bundle_size,
+                })
+                .await
+                .unwrap();
+            clas.push(cla);
+        }
+        let (dispatcher, app_registry) = build_dispatcher(
+            store,
+            config,
+            cla_registry,
+            fib.clone(),
+            task_set,
+            cancel_token,
+        );
+        TestDispatcher {
+            dispatcher,
+            app_registry,
+            fib,
+            clas,
+        }
+    }
+
+    // Register an application for ipn:1.`service`, returning its token
+    pub(crate) async fn register_app(
+        app_registry: &app_registry::AppRegistry,
+        service: u32,
+    ) -> String {
+        use hardy_proto::application::*;
+        app_registry
+            .register(RegisterApplicationRequest {
+                endpoint: Some(register_application_request::Endpoint::IpnServiceNumber(
+                    service,
+                )),
+                ident: format!("app{service}"),
+                grpc_address: None,
+                max_concurrent_notifications: None,
+            })
+            .await
+            .unwrap()
+            .token
+    }
+
+    #[tokio::test]
+    async fn check_progress() {
+        // Some junk, and some valid bundles that have already been tombstoned
+        let metadata_storage = Arc::new(TestMetadata::default());
+        let bundle_storage = Arc::new(TestBundles::default());
+        for i in 0..3 {
+            bundle_storage
+                .0
+                .lock()
+                .unwrap()
+                .insert(format!("junk{i}"), vec![0xFF; 16]);
+        }
+        for i in 0..2 {
+            let (bundle, data) = bpv7::Builder::new()
+                .source("ipn:2.1".parse().unwrap())
+                .destination("ipn:1.1".parse().unwrap())
+                .add_payload_block(vec![i; 8])
+                .build();
+            metadata_storage.0.lock().unwrap().push(metadata::Bundle {
+                bundle,
+                metadata: metadata::Metadata {
+                    status: metadata::BundleStatus::Tombstone(time::OffsetDateTime::now_utc()),
+                    ..Default::default()
+                },
+            });
+            bundle_storage
+                .0
+                .lock()
+                .unwrap()
+                .insert(format!("valid{i}"), data);
+        }
+
+        let store = Arc::new(test_store(metadata_storage, bundle_storage.clone()));
+
+        let mut task_set = tokio::task::JoinSet::new();
+        let cancel_token = tokio_util::sync::CancellationToken::new();
+        let dispatcher = new_dispatcher(store.clone(), &mut task_set, cancel_token.clone());
+
+        let (tx, rx) = tokio::sync::watch::channel(CheckProgress::default());
+        store
+            .bundle_storage_check(dispatcher, Some(tx), cancel_token.clone())
+            .await;
+
+        assert_eq!(
+            *rx.borrow(),
+            CheckProgress {
+                processed: 5,
+                orphans: 0,
+                bad: 5,
+                total: 5,
+            }
+        );
+        assert!(bundle_storage.0.lock().unwrap().is_empty());
+
+        cancel_token.cancel();
+        while task_set.join_next().await.is_some() {}
+    }
+
+    #[tokio::test]
+    async fn corrupt() {
+        let metadata_storage = Arc::new(TestMetadata::default());
+        let bundle_storage = Arc::new(TestBundles::default());
+        let store = Store {
+            config: Config {
+                verify_on_load: true,
+                ..test_config()
+            },
+            ..test_store(metadata_storage.clone(), bundle_storage.clone())
+        };
+
+        let (bundle, data) = bpv7::Builder::new()
+            .source("ipn:2.1".parse().unwrap())
+            .destination("ipn:1.1".parse().unwrap())
+            .add_payload_block(vec![1, 2, 3])
+            .build();
+        let metadata = store
+            .store(
+                &bundle,
+                &data,
+                metadata::BundleStatus::Waiting(time::OffsetDateTime::now_utc()),
+                None,
+            )
+            .await
+            .unwrap()
+            .unwrap();
+        let mut bundle = metadata::Bundle { metadata, bundle };
+        let storage_name = bundle.metadata.storage_name.clone().unwrap();
+
+        // Intact data loads
+        assert!(store.load_bundle_data(&mut bundle).await.unwrap().is_some());
+
+        // Flip a bit
+        *bundle_storage
+            .0
+            .lock()
+            .unwrap()
+            .get_mut(storage_name.as_ref())
+            .unwrap()
+            .last_mut()
+            .unwrap() ^= 1;
+
+        assert!(store.load_bundle_data(&mut bundle).await.unwrap().is_none());
+        assert!(matches!(
+            bundle.metadata.status,
+            metadata::BundleStatus::Tombstone(_)
+        ));
+        assert!(matches!(
+            metadata_storage.0.lock().unwrap()[0].metadata.status,
+            metadata::BundleStatus::Tombstone(_)
+        ));
+        assert!(bundle_storage.0.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn scrub() {
+        let metadata_storage = Arc::new(TestMetadata::default());
+        let bundle_storage = Arc::new(TestBundles::default());
+        let store = Arc::new(test_store(metadata_storage.clone(), bundle_storage.clone()));
+
+        let mut task_set = tokio::task::JoinSet::new();
+        let cancel_token = tokio_util::sync::CancellationToken::new();
+        let dispatcher = new_dispatcher(store.clone(), &mut task_set, cancel_token.clone());
+
+        let mut storage_names = Vec::new();
+        for seq in 0..2 {
+            let (bundle, data) = bpv7::Builder::new()
+                .source(format!("ipn:2.{}", seq + 1).parse().unwrap())
+                .destination("ipn:3.1".parse().unwrap())
+                .add_payload_block(vec![1, 2, 3])
+                .build();
+            let metadata = store
+                .store(
+                    &bundle,
+                    &data,
+                    metadata::BundleStatus::Waiting(time::OffsetDateTime::now_utc()),
+                    Some(time::OffsetDateTime::now_utc()),
+                )
+                .await
+                .unwrap()
+                .unwrap();
+            storage_names.push(metadata.storage_name.unwrap());
+        }
+
+        // Intact data is left alone
+        assert_eq!(store.scrub(&dispatcher, &cancel_token).await.unwrap(), 0);
+
+        // Flip a bit behind the store's back
+        *bundle_storage
+            .0
+            .lock()
+            .unwrap()
+            .get_mut(storage_names[1].as_ref())
+            .unwrap()
+            .last_mut()
+            .unwrap() ^= 1;
+
+        // The corrupt bundle is quarantined and tombstoned, and only found once
+        assert_eq!(store.scrub(&dispatcher, &cancel_token).await.unwrap(), 1);
+        assert_eq!(store.scrub(&dispatcher, &cancel_token).await.unwrap(), 0);
+        assert!(!bundle_storage
+            .0
+            .lock()
+            .unwrap()
+            .contains_key(storage_names[1].as_ref()));
+        assert!(bundle_storage
+            .0
+            .lock()
+            .unwrap()
+            .contains_key(storage_names[0].as_ref()));
+        let statuses = metadata_storage
+            .0
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|b| b.metadata.status.clone())
+            .collect::<Vec<_>>();
+        assert!(matches!(statuses[0], metadata::BundleStatus::Waiting(_)));
+        assert!(matches!(statuses[1], metadata::BundleStatus::Tombstone(_)));
+
+        cancel_token.cancel();
+        while task_set.join_next().await.is_some() {}
+    }
+
+    #[tokio::test]
+    async fn send() {
+        let store = Arc::new(test_store(
+            Arc::new(TestMetadata::default()),
+            Arc::new(TestBundles::default()),
+        ));
+
+        let mut task_set = tokio::task::JoinSet::new();
+        let cancel_token = tokio_util::sync::CancellationToken::new();
+        let (dispatcher, app_registry) =
+            new_dispatcher_with_apps(store, &mut task_set, cancel_token.clone());
+        let token = register_app(&app_registry, 5).await;
+
+        let bundle_id = dispatcher
+            .send(
+                None,
+                "ipn:1.5".parse().unwrap(),
+                vec![1, 2, 3].into(),
+                Some(std::time::Duration::from_secs(60)),
+                None,
+            )
+            .await
+            .unwrap();
+        assert_eq!(bundle_id.source, "ipn:1.0".parse().unwrap());
+
+        // The registered service collects the bundle
+        let mut collected = None;
+        for _ in 0..100 {
+            collected = dispatcher
+                .collect(
+                    "ipn:1.5".parse().unwrap(),
+                    &token,
+                    bundle_id.to_key(),
+                    false,
+                )
+                .await
+                .unwrap();
+            if collected.is_some() {
+                break;
+            }
+            tokio::time::sleep(tokio::time::Duration::from_millis(10)).await;
+        }
+        let collected = collected.expect("Bundle not delivered");
+        assert_eq!(collected.bundle_id, bundle_id.to_key());
+        let bpv7::ValidBundle::Valid(bundle, _) =
+            bpv7::ValidBundle::parse(&collected.data, |_, _| Ok(None)).unwrap()
+        else {
+            panic!("Invalid bundle");
+        };
+        assert_eq!(bundle.id, bundle_id);
+
+        cancel_token.cancel();
+        while task_set.join_next().await.is_some() {}
+    }
+
+    #[tokio::test]
+    async fn events() {
+        let store = Arc::new(test_store(
+            Arc::new(TestMetadata::default()),
+            Arc::new(TestBundles::default()),
+        ));
+
+        let mut task_set = tokio::task::JoinSet::new();
+        let cancel_token = tokio_util::sync::CancellationToken::new();
+        let dispatcher = new_dispatcher(store, &mut task_set, cancel_token.clone());
+        let mut events = dispatcher.subscribe_events();
+
+        let (bundle, data) = bpv7::Builder::new()
+            .source("ipn:2.1".parse().unwrap())
+            .destination("ipn:1.5".parse().unwrap())
+            .lifetime(60_000)
+            .add_payload_block(vec![1, 2, 3])
+            .try_build()
+            .unwrap();
+        dispatcher.receive_bundle(data.into(), None).await.unwrap();
+
+        // Wait for the bundle to reach the local service, and collect it
+        let mut collected = false;
+        for _ in 0..100 {
+            if dispatcher
+                .collect(
+                    "ipn:1.5".parse().unwrap(),
+                    "token",
+                    bundle.id.to_key(),
+                    false,
+                )
+                .await
+                .unwrap()
+                .is_some()
+            {
+                collected = true;
+                break;
+            }
+            tokio::time::sleep(tokio::time::Duration::from_millis(10)).await;
+        }
+        assert!(collected);
+
+        assert_eq!(
+            events.recv().await,
+            Some(dispatcher::DispatchEvent::Received(bundle.id.clone()))
+        );
+        assert_eq!(
+            events.recv().await,
+            Some(dispatcher::DispatchEvent::Delivered(bundle.id))
+        );
+        assert_eq!(events.lagged(), 0);
+
+        cancel_token.cancel();
+        while task_set.join_next().await.is_some() {}
+    }
+
+    #[tokio::test]
+    async fn delivery_ack() {
+        let store = Arc::new(test_store(
+            Arc::new(TestMetadata::default()),
+            Arc::new(TestBundles::default()),
+        ));
+
+        let mut task_set = tokio::task::JoinSet::new();
+        let cancel_token = tokio_util::sync::CancellationToken::new();
+        let (dispatcher, app_registry) =
+            new_dispatcher_with_apps(store.clone(), &mut task_set, cancel_token.clone());
+        let token = register_app(&app_registry, 5).await;
+        let mut events = dispatcher.subscribe_events();
+
+        let (bundle, data) = bpv7::Builder::new()
+            .source("ipn:2.1".parse().unwrap())
+            .destination("ipn:1.5".parse().unwrap())
+            .lifetime(60_000)
+            .add_payload_block(vec![1, 2, 3])
+            .try_build()
+            .unwrap();
+        dispatcher.receive_bundle(data.into(), None).await.unwrap();
+
+        let collect =
+            || dispatcher.collect("ipn:1.5".parse().unwrap(), &token, bundle.id.to_key(), true);
+
+        // Wait for the bundle to reach the local service, and collect it
+        let mut collected = None;
+        for _ in 0..100 {
+            collected = collect().await.unwrap();
+            if collected.is_some() {
+                break;
+            }
+            tokio::time::sleep(tokio::time::Duration::from_millis(10)).await;
+        }
+        assert_eq!(collected.unwrap().data.as_ref(), [1, 2, 3]);
+
+        // The service fails to process it, so it is kept for redelivery
+        assert!(dispatcher
+            .acknowledge(&token, bundle.id.to_key(), false)
+            .await
+            .unwrap());
+        assert_eq!(
+            store
+                .load(&bundle.id)
+                .await
+                .unwrap()
+                .unwrap()
+                .metadata
+                .status,
+            metadata::BundleStatus::CollectionPending
+        );
+        assert_eq!(collect().await.unwrap().unwrap().data.as_ref(), [1, 2, 3]);
+
+        // And succeeds the second time
+        assert!(dispatcher
+            .acknowledge(&token, bundle.id.to_key(), true)
+            .await
+            .unwrap());
+        assert!(matches!(
+            store
+                .load(&bundle.id)
+                .await
+                .unwrap()
+                .unwrap()
+                .metadata
+                .status,
+            metadata::BundleStatus::Tombstone(_)
+        ));
+        assert!(!dispatcher
+            .acknowledge(&token, bundle.id.to_key(), true)
+            .await
+            .unwrap());
+
+        assert_eq!(
+            events.recv().await,
+            Some(dispatcher::DispatchEvent::Received(bundle.id.clone()))
+        );
+        assert_eq!(
+            events.recv().await,
+            Some(dispatcher::DispatchEvent::Delivered(bundle.id))
+        );
+
+        // An application that keeps collecting a bundle without acknowledging it, as if crashing,
+        // is given up on after 'max_delivery_attempts'
+        let (bundle, data) = bpv7::Builder::new()
+            .source("ipn:2.2".parse().unwrap())
+            .destination("ipn:1.5".parse().unwrap())
+            .lifetime(60_000)
+            .add_payload_block(vec![4, 5, 6])
+            .try_build()
+            .unwrap();
+        dispatcher.receive_bundle(data.into(), None).await.unwrap();
+        let collect =
+            || dispatcher.collect("ipn:1.5".parse().unwrap(), &token, bundle.id.to_key(), true);
+        let mut collected = None;
+        for _ in 0..100 {
+            collected = collect().await.unwrap();
+            if collected.is_some() {
+                break;
+            }
+            tokio::time::sleep(tokio::time::Duration::from_millis(10)).await;
+        }
+        assert!(collected.is_some());
+        assert!(collect().await.unwrap().is_some());
+        assert!(collect().await.unwrap().is_some());
+        assert!(collect().await.unwrap().is_none());
+        assert!(matches!(
+            store
+                .load(&bundle.id)
+                .await
+                .unwrap()
+                .unwrap()
+                .metadata
+                .status,
+            metadata::BundleStatus::Tombstone(_)
+        ));
+
+        // Unregistering forgets what the application has collected
+        let (bundle, data) = bpv7::Builder::new()
+            .source("ipn:2.3".parse().unwrap())
+            .destination("ipn:1.5".parse().unwrap())
+            .lifetime(60_000)
+            .add_payload_block(vec![7, 8, 9])
+            .try_build()
+            .unwrap();
+        dispatcher.receive_bundle(data.into(), None).await.unwrap();
+        let mut collected = None;
+        for _ in 0..100 {
+            collected = dispatcher
+                .collect("ipn:1.5".parse().unwrap(), &token, bundle.id.to_key(), true)
+                .await
+                .unwrap();
+            if collected.is_some() {
+                break;
+            }
+            tokio::time::sleep(tokio::time::Duration::from_millis(10)).await;
+        }
+        assert!(collected.is_some());
+        dispatcher
+            .unregister_application(hardy_proto::application::UnregisterApplicationRequest {
+                token: token.clone(),
+            })
+            .await
+            .unwrap();
+        let token = register_app(&app_registry, 5).await;
+        assert!(!dispatcher
+            .acknowledge(&token, bundle.id.to_key(), true)
+            .await
+            .unwrap());
+
+        cancel_token.cancel();
+        while task_set.join_next().await.is_some() {}
+    }
+
+    #[tokio::test]
+    async fn admin_record() {
+        let store = Arc::new(test_store(
+            Arc::new(TestMetadata::default()),
+            Arc::new(TestBundles::default()),
+        ));
+
+        let mut task_set = tokio::task::JoinSet::new();
+        let cancel_token = tokio_util::sync::CancellationToken::new();
+        let dispatcher = new_dispatcher(store.clone(), &mut task_set, cancel_token.clone());
+        let mut events = dispatcher.subscribe_events();
+
+        // A peer reports delivery of a bundle sent by one of our services
+        let report = bpv7::AdministrativeRecord::BundleStatusReport(bpv7::BundleStatusReport {
+            bundle_id: bpv7::BundleId {
+                source: "ipn:1.5".parse().unwrap(),
+                ..Default::default()
+            },
+            delivered: Some(bpv7::StatusAssertion(None)),
+            ..Default::default()
+        });
+        let (bundle, data) = bpv7::Builder::new()
+            .flags(bpv7::BundleFlags {
+                is_admin_record: true,
+                ..Default::default()
+            })
+            .source("ipn:2.0".parse().unwrap())
+            .destination("ipn:1.0".parse().unwrap())
+            .lifetime(60_000)
+            .add_payload_block(hardy_cbor::encode::emit(&report))
+            .try_build()
+            .unwrap();
+        dispatcher.receive_bundle(data.into(), None).await.unwrap();
+
+        assert_eq!(
+            events.recv().await,
+            Some(dispatcher::DispatchEvent::Received(bundle.id.clone()))
+        );
+        assert_eq!(
+            events.recv().await,
+            Some(dispatcher::DispatchEvent::AdminRecord(
+                bundle.id.clone(),
+                bpv7::AdminRecordType::BundleStatusReport
+            ))
+        );
+
+        // It is consumed, not left for a service to collect
+        let mut consumed = false;
+        for _ in 0..100 {
+            if let Some(metadata::BundleStatus::Tombstone(_)) = store
+                .load(&bundle.id)
+                .await
+                .unwrap()
+                .map(|bundle| bundle.metadata.status)
+            {
+                consumed = true;
+                break;
+            }
+            tokio::time::sleep(tokio::time::Duration::from_millis(10)).await;
+        }
+        assert!(consumed);
+
+        cancel_token.cancel();
+        while task_set.join_next().await.is_some() {}
+    }
+
+    #[tokio::test]
+    async fn admission_restart() {
+        // A bundle already held at start-up
+        let build = || {
+            bpv7::Builder::new()
+                .source("ipn:2.1".parse().unwrap())
+                .destination("ipn:1.1".parse().unwrap())
+                .add_payload_block(vec![0; 64])
+                .build()
+        };
+        let (bundle, data) = build();
+        let metadata_storage = Arc::new(TestMetadata::default());
+        metadata_storage.0.lock().unwrap().push(metadata::Bundle {
+            bundle,
+            metadata: metadata::Metadata {
+                status: metadata::BundleStatus::ReassemblyPending,
+                storage_name: Some("held".into()),
+                hash: Some(hash(&data)),
+                ..Default::default()
+            },
+        });
+        let bundle_storage = Arc::new(TestBundles::default());
+        bundle_storage
+            .0
+            .lock()
+            .unwrap()
+            .insert("held".to_string(), data.clone());
+
+        let store = Arc::new(Store {
+            admission: Some(Arc::new(std::sync::Mutex::new(admission::Admission::new(
+                data.len() as u64,
+            )))),
+            ..test_store(metadata_storage, bundle_storage)
+        });
+
+        let mut task_set = tokio::task::JoinSet::new();
+        let cancel_token = tokio_util::sync::CancellationToken::new();
+        let dispatcher = new_dispatcher(store.clone(), &mut task_set, cancel_token.clone());
+        store
+            .bundle_storage_check(dispatcher, None, cancel_token.clone())
+            .await;
+
+        // It is accounted for, so storage is already full
+        let (another, another_data) = build();
+        let e = store
+            .store(
+                &another,
+                &another_data,
+                metadata::BundleStatus::DispatchPending,
+                None,
+            )
+            .await
+            .unwrap_err();
+        assert!(e.is::<StorageFull>());
+
+        cancel_token.cancel();
+        while task_set.join_next().await.is_some() {}
+    }
+
+    #[tokio::test]
+    async fn admission() {
+        let bundle_storage = Arc::new(TestBundles::default());
+        let store = Store {
+            admission: Some(Arc::new(std::sync::Mutex::new(admission::Admission::new(
+                8,
+            )))),
+            ..test_store(Arc::new(TestMetadata::default()), bundle_storage.clone())
+        };
+
+        // Fill the store with best-effort bundles
+        for _ in 0..2 {
+            assert!(store
+                .store_data(&[0; 4], Priority::BestEffort)
+                .await
+                .unwrap()
+                .is_some());
+        }
+
+        // An expedited bundle evicts the oldest best-effort bundle
+        let (storage_name, _) = store
+            .store_data(&[1; 4], Priority::Expedited)
+            .await
+            .unwrap()
+            .unwrap();
+        {
+            let bundles = bundle_storage.0.lock().unwrap();
+            assert!(!bundles.contains_key("stored0"));
+            assert!(bundles.contains_key("stored1"));
+            assert!(bundles.contains_key(storage_name.as_ref()));
+        }
+
+        // But another best-effort bundle is refused
+        assert!(store
+            .store_data(&[2; 4], Priority::BestEffort)
+            .await
+            .unwrap()
+            .is_none());
+        assert_eq!(bundle_storage.0.lock().unwrap().len(), 2);
+
+        // Until there is room again
+        store.delete_data("stored1").await.unwrap();
+        assert!(store
+            .store_data(&[2; 4], Priority::BestEffort)
+            .await
+            .unwrap()
+            .is_some());
+    }
+
+    #[tokio::test]
+    async fn class_of_service() {
+        let build = |class_of_service| {
+            bpv7::Builder::new()
+                .source("ipn:2.1".parse().unwrap())
+                .destination("ipn:3.1".parse().unwrap())
+                .lifetime(60_000)
+                .class_of_service(class_of_service)
+                .add_payload_block(vec![0; 64])
+                .build()
+        };
+        let (bulk, bulk_data) = build(bpv7::ClassOfService::Bulk);
+        let (expedited, expedited_data) = build(bpv7::ClassOfService::Expedited);
+
+        // The class of service survives parsing, and sets the priority
+        for (data, priority) in [
+            (&bulk_data, Priority::Bulk),
+            (&expedited_data, Priority::Expedited),
+        ] {
+            let bpv7::ValidBundle::Valid(bundle, _) =
+                bpv7::ValidBundle::parse(data, |_, _| Ok(None)).unwrap()
+            else {
+                panic!("Invalid bundle");
+            };
+            assert_eq!(Priority::of(&bundle), priority);
+        }
+
+        // Room for one bundle only
+        let bundle_storage = Arc::new(TestBundles::default());
+        let store = Arc::new(Store {
+            admission: Some(Arc::new(std::sync::Mutex::new(admission::Admission::new(
+                bulk_data.len().max(expedited_data.len()) as u64,
+            )))),
+            ..test_store(Arc::new(TestMetadata::default()), bundle_storage.clone())
+        });
+
+        // The expedited bundle evicts the bulk bundle, and is not evicted by another
+        let bulk_metadata = store
+            .store(
+                &bulk,
+                &bulk_data,
+                metadata::BundleStatus::DispatchPending,
+                None,
+            )
+            .await
+            .unwrap()
+            .unwrap();
+        store
+            .store(
+                &expedited,
+                &expedited_data,
+                metadata::BundleStatus::DispatchPending,
+                None,
+            )
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(!bundle_storage
+            .0
+            .lock()
+            .unwrap()
+            .contains_key(bulk_metadata.storage_name.unwrap().as_ref()));
+        let (another_bulk, data) = build(bpv7::ClassOfService::Bulk);
+        assert!(store
+            .store(
+                &another_bulk,
+                &data,
+                metadata::BundleStatus::DispatchPending,
+                None
+            )
+            .await
+            .is_err());
+
+        // It is persisted with the metadata
+        let stored = store.load(&expedited.id).await.unwrap().unwrap();
+        assert_eq!(
+            stored.bundle.class_of_service,
+            Some(bpv7::ClassOfService::Expedited)
+        );
+    }
+
+    #[tokio::test]
+    async fn export_import() {
+        let new_store = |bundle_storage: Arc<dyn storage::BundleStorage>| {
+            test_store(Arc::new(TestMetadata::default()), bundle_storage)
+        };
+
+        let received_at = time::OffsetDateTime::from_unix_timestamp(1_700_000_000).unwrap();
+        let until = received_at + time::Duration::minutes(5);
+        let statuses = [
+            metadata::BundleStatus::DispatchPending,
+            metadata::BundleStatus::CollectionPending,
+            metadata::BundleStatus::Waiting(until),
+            metadata::BundleStatus::ForwardAckPending(3, until),
+            metadata::BundleStatus::Tombstone(received_at),
+        ];
+
+        let store = new_store(Arc::new(TestBundles::default()));
+        let mut bundles = Vec::new();
+        for (i, status) in statuses.iter().enumerate() {
+            let (bundle, data) = bpv7::Builder::new()
+                .source(format!("ipn:1.{}", i + 1).parse().unwrap())
+                .destination("ipn:2.1".parse().unwrap())
+                .add_payload_block(vec![i as u8; 8])
+                .build();
+            store
+                .store(&bundle, &data, status.clone(), Some(received_at))
+                .await
+                .unwrap()
+                .unwrap();
+            bundles.push((bundle.id, data));
+        }
+
+        let mut archive = Vec::new();
+        assert_eq!(store.export(&mut archive).await.unwrap(), 4);
+
+        // Import into a different storage engine, as when migrating a node
+        let imported = new_store(bundle_tiered::Storage::init(
+            &std::collections::HashMap::new(),
+            Arc::new(TestBundles::default()),
+        ));
+        assert_eq!(imported.import(&mut archive.as_slice()).await.unwrap(), 4);
+
+        // A corrupt record length is refused rather than allocated
+        let mut corrupt = u64::MAX.to_be_bytes().to_vec();
+        corrupt.extend_from_slice(&archive[8..]);
+        assert!(imported.import(&mut corrupt.as_slice()).await.is_err());
+
+        // As is an archive that ends part way through a record
+        assert!(imported
+            .import(&mut &archive[..archive.len() - 1])
+            .await
+            .is_err());
+
+        for ((bundle_id, data), status) in bundles.iter().zip(statuses) {
+            let Some(bundle) = imported.load(bundle_id).await.unwrap() else {
+                // Tombstones are not exported
+                assert!(matches!(status, metadata::BundleStatus::Tombstone(_)));
+                continue;
+            };
+
+            // Forwarding acknowledgements cannot survive an import, so wait for the timeout
+            let expected = match status {
+                metadata::BundleStatus::ForwardAckPending(_, until) => {
+                    metadata::BundleStatus::Waiting(until)
+                }
+                status => status,
+            };
+            assert_eq!(bundle.metadata.status, expected);
+            assert_eq!(bundle.metadata.received_at, Some(received_at));
+
+            let stored = imported
+                .load_data(bundle.metadata.storage_name.as_ref().unwrap())
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!(stored.as_ref().as_ref(), data.as_slice());
+        }
+
+        // Importing the same archive again finds only duplicates
+        assert_eq!(imported.import(&mut archive.as_slice()).await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn expiry() {
+        let metadata_storage = Arc::new(TestMetadata::default());
+        let clock = Arc::new(utils::clock::MockClock::new(time::OffsetDateTime::now_utc()));
+        let store = Arc::new(Store {
+            clock: clock.clone(),
+            ..test_store(metadata_storage.clone(), Arc::new(TestBundles::default()))
+        });
+
+        let mut task_set = tokio::task::JoinSet::new();
+        let cancel_token = tokio_util::sync::CancellationToken::new();
+        let dispatcher = new_dispatcher(store, &mut task_set, cancel_token.clone());
+
+        let receive = |seq: u8| {
+            let (bundle, data) = bpv7::Builder::new()
+                .source("ipn:2.1".parse().unwrap())
+                .destination("ipn:1.1".parse().unwrap())
+                .lifetime(60_000)
+                .add_payload_block(vec![seq])
+                .build();
+            let dispatcher = dispatcher.clone();
+            async move {
+                dispatcher.receive_bundle(data.into(), None).await.unwrap();
+                bundle.id
+            }
+        };
+        let tombstoned = |bundle_id: &bpv7::BundleId| {
+            metadata_storage
+                .0
+                .lock()
+                .unwrap()
+                .iter()
+                .find(|b| &b.bundle.id == bundle_id)
+                .is_some_and(|b| matches!(b.metadata.status, metadata::BundleStatus::Tombstone(_)))
+        };
+
+        // A bundle within its lifetime is kept
+        let bundle_id = receive(1).await;
+        assert!(!tombstoned(&bundle_id));
+
+        // The same lifetime has expired once the clock has moved on, without waiting for it
+        clock.advance(time::Duration::minutes(2));
+        let bundle_id = receive(2).await;
+        assert!(tombstoned(&bundle_id));
+
+        cancel_token.cancel();
+        while task_set.join_next().await.is_some() {}
+    }
+
+    #[tokio::test]
+    async fn flaky_storage() {
+        let bundle_storage = Arc::new(FlakyBundles::default());
+        let store = Arc::new(Store {
+            resilience: Arc::new(resilience::Resilience::new(
+                2,
+                std::time::Duration::from_millis(1),
+            )),
+            ..test_store(Arc::new(TestMetadata::default()), bundle_storage.clone())
+        });
+
+        let mut task_set = tokio::task::JoinSet::new();
+        let cancel_token = tokio_util::sync::CancellationToken::new();
+        let dispatcher = new_dispatcher(store.clone(), &mut task_set, cancel_token.clone());
+
+        let receive = |seq: u8| {
+            let (_, data) = bpv7::Builder::new()
+                .source("ipn:2.1".parse().unwrap())
+                .destination("ipn:3.1".parse().unwrap())
+                .add_payload_block(vec![seq])
+                .build();
+            let dispatcher = dispatcher.clone();
+            async move { dispatcher.receive_bundle(data.into(), None).await }
+        };
+
+        let stored = || {
+            bundle_storage
+                .inner
+                .1
+                .load(std::sync::atomic::Ordering::Relaxed)
+        };
+
+        // Transient failures are retried, and the bundle is stored
+        bundle_storage
+            .failures
+            .store(2, std::sync::atomic::Ordering::Relaxed);
+        receive(1).await.unwrap();
+        assert!(!store.is_degraded());
+        assert_eq!(stored(), 1);
+
+        // Persistent failures degrade the store, and further bundles are refused untried
+        bundle_storage
+            .failures
+            .store(4, std::sync::atomic::Ordering::Relaxed);
+        assert!(receive(2).await.is_err());
+        assert!(store.is_degraded());
+        assert!(receive(3).await.is_err_and(|e| e.is::<StorageDegraded>()));
+        assert_eq!(
+            bundle_storage
+                .failures
+                .load(std::sync::atomic::Ordering::Relaxed),
+            1
+        );
+
+        // Healthy metadata storage does not hide the failing bundle storage
+        store
+            .check_status(
+                &bundle(1, "ipn:3.1", metadata::BundleStatus::DispatchPending)
+                    .bundle
+                    .id,
+            )
+            .await
+            .unwrap();
+        assert!(store.is_degraded());
+
+        // Storage recovers once an operation succeeds again
+        store
+            .store_data(b"probe", Priority::default())
+            .await
+            .unwrap();
+        assert!(!store.is_degraded());
+        receive(4).await.unwrap();
+        assert_eq!(stored(), 3);
+
+        cancel_token.cancel();
+        while task_set.join_next().await.is_some() {}
+    }
+
+    #[tokio::test]
+    async fn store_metadata_retry() {
+        let metadata_storage = Arc::new(TestMetadata::default());
+        let store = Store {
+            resilience: Arc::new(resilience::Resilience::new(
+                2,
+                std::time::Duration::from_millis(1),
+            )),
+            ..test_store(metadata_storage.clone(), Arc::new(TestBundles::default()))
+        };
+
+        let (bundle, data) = bpv7::Builder::new()
+            .source("ipn:2.1".parse().unwrap())
+            .destination("ipn:3.1".parse().unwrap())
+            .add_payload_block(vec![1])
+            .build();
+
+        // The first write commits but reports an error, so the retry finds our own metadata, not a duplicate
+        metadata_storage
+            .1
+            .store(1, std::sync::atomic::Ordering::Relaxed);
+        let metadata = store
+            .store(
+                &bundle,
+                &data,
+                metadata::BundleStatus::DispatchPending,
+                None,
+            )
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(!store.is_degraded());
+        assert_eq!(metadata_storage.0.lock().unwrap().len(), 1);
+        assert!(store
+            .load_data(metadata.storage_name.as_ref().unwrap())
+            .await
+            .unwrap()
+            .is_some());
+
+        // A real duplicate is still refused
+        assert!(store
+            .store(
+                &bundle,
+                &data,
+                metadata::BundleStatus::DispatchPending,
+                None
+            )
+            .await
+            .unwrap()
+            .is_none());
+        assert_eq!(metadata_storage.0.lock().unwrap().len(), 1);
     }
 }
@@ -0,0 +1,124 @@
+use super::*;
+use std::sync::atomic::{AtomicBool, Ordering};
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+#[error("Storage is degraded")]
+pub struct StorageDegraded;
+
+const DEFAULT_RETRIES: u32 = 3;
+const DEFAULT_RETRY_BACKOFF_MS: u32 = 100;
+const MAX_RETRY_BACKOFF: std::time::Duration = std::time::Duration::from_secs(5);
+
+// The storage backends, whose health is tracked separately
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Backend {
+    Metadata,
+    Bundles,
+}
+
+impl std::fmt::Display for Backend {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Backend::Metadata => write!(f, "Metadata storage"),
+            Backend::Bundles => write!(f, "Bundle storage"),
+        }
+    }
+}
+
+/* Retries failed storage operations with exponential backoff, as most failures of a flaky disk
+ * or database are transient.  If an operation still fails once the retries are exhausted,
+ * its backend is marked degraded until an operation on the same backend next succeeds, and the
+ * error is returned to the caller rather than panicking.  The backends are tracked separately,
+ * so a healthy metadata store does not hide a failing bundle store, or vice versa */
+pub struct Resilience {
+    retries: u32,
+    backoff: std::time::Duration,
+    metadata_degraded: AtomicBool,
+    bundles_degraded: AtomicBool,
+}
+
+impl Default for Resilience {
+    fn default() -> Self {
+        Self::new(
+            DEFAULT_RETRIES,
+            std::time::Duration::from_millis(DEFAULT_RETRY_BACKOFF_MS.into()),
+        )
+    }
+}
+
+impl Resilience {
+    pub fn init(config: &config::Config) -> Self {
+        let retries = settings::get_with_default(config, "storage_retries", DEFAULT_RETRIES)
+            .trace_expect("Invalid 'storage_retries' value in configuration");
+        let backoff =
+            settings::get_with_default(config, "storage_retry_backoff", DEFAULT_RETRY_BACKOFF_MS)
+                .trace_expect("Invalid 'storage_retry_backoff' value in configuration");
+
+        Self::new(retries, std::time::Duration::from_millis(backoff.into()))
+    }
+
+    pub fn new(retries: u32, backoff: std::time::Duration) -> Self {
+        Self {
+            retries,
+            backoff,
+            metadata_degraded: AtomicBool::new(false),
+            bundles_degraded: AtomicBool::new(false),
+        }
+    }
+
+    fn degraded(&self, backend: Backend) -> &AtomicBool {
+        match backend {
+            Backend::Metadata => &self.metadata_degraded,
+            Backend::Bundles => &self.bundles_degraded,
+        }
+    }
+
+    // True while either backend is degraded
+    pub fn is_degraded(&self) -> bool {
+        self.is_backend_degraded(Backend::Metadata) || self.is_backend_degraded(Backend::Bundles)
+    }
+
+    pub fn is_backend_degraded(&self, backend: Backend) -> bool {
+        self.degraded(backend).load(Ordering::Relaxed)
+    }
+
+    pub async fn retry<T, F, Fut>(
+        &self,
+        backend: Backend,
+        operation: &str,
+        mut f: F,
+    ) -> Result<T, Error>
+    where
+        F: FnMut() -> Fut,
+        Fut: std::future::Future<Output = Result<T, Error>>,
+    {
+        let mut backoff = self.backoff;
+        let mut attempt = 0;
+        loop {
+            match f().await {
+                Ok(r) => {
+                    if self.degraded(backend).swap(false, Ordering::Relaxed) {
+                        info!("{backend} has recovered");
+                    }
+                    return Ok(r);
+                }
+                Err(e) if attempt < self.retries => {
+                    attempt += 1;
+                    warn!(
+                        "Failed to {operation}, retry {attempt} of {} in {backoff:?}: {e}",
+                        self.retries
+                    );
+                    tokio::time::sleep(backoff).await;
+                    backoff = (backoff * 2).min(MAX_RETRY_BACKOFF);
+                }
+                Err(e) => {
+                    if !self.degraded(backend).swap(true, Ordering::Relaxed) {
+                        error!("Failed to {operation}, {backend} is degraded: {e}");
+                    }
+                    return Err(e);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,119 @@
+use super::*;
+
+// Bytes per second read while scrubbing, so a scan does not starve the BPA of IO
+pub const DEFAULT_SCRUB_RATE: u64 = 4 * 1024 * 1024;
+
+impl Store {
+    // Scan the store every `scrub_interval` seconds, until cancelled
+    pub(super) async fn scrub_periodically(
+        self: Arc<Self>,
+        dispatcher: Arc<dispatcher::Dispatcher>,
+        cancel_token: tokio_util::sync::CancellationToken,
+    ) {
+        let interval = time::Duration::seconds(self.config.scrub_interval as i64);
+        while utils::cancel::cancellable_sleep(interval, &cancel_token).await {
+            match self.scrub(&dispatcher, &cancel_token).await {
+                Ok(0) => {}
+                Ok(quarantined) => warn!("Scrubber quarantined {quarantined} corrupt bundles"),
+                Err(e) => error!("Failed to scrub bundle storage: {e}"),
+            }
+        }
+    }
+
+    /* A single pass over the held bundles, re-reading their data and checking it against the hash
+     * recorded in the metadata, so bit-rot is found before the bundle is next needed.
+     * Corrupt data is quarantined and the bundle reported as deleted, as if it had been lost.
+     * Returns the number of bundles quarantined */
+    pub async fn scrub(
+        &self,
+        dispatcher: &dispatcher::Dispatcher,
+        cancel_token: &tokio_util::sync::CancellationToken,
+    ) -> Result<usize, Error> {
+        let (tx, mut rx) = tokio::sync::mpsc::channel::<metadata::Bundle>(16);
+        let metadata_storage = self.metadata_storage.clone();
+        let h = tokio::spawn(async move {
+            metadata_storage
+                .poll_received_between(
+                    time::OffsetDateTime::UNIX_EPOCH..time::PrimitiveDateTime::MAX.assume_utc(),
+                    tx,
+                )
+                .await
+        });
+
+        let mut quarantined = 0;
+        while let Some(mut bundle) =
+            hardy_async::channel::recv_or_cancel(&mut rx, cancel_token).await
+        {
+            // Bundles stored without a hash cannot be checked
+            let (Some(storage_name), Some(expected)) = (
+                bundle.metadata.storage_name.clone(),
+                bundle.metadata.hash.clone(),
+            ) else {
+                continue;
+            };
+            let Some(data) = self.load_data(&storage_name).await? else {
+                continue;
+            };
+            let data = data.as_ref().as_ref();
+            let intact = hash(data) == expected;
+
+            // Throttle the IO
+            if self.config.scrub_rate != 0
+                && !utils::cancel::cancellable_sleep(
+                    time::Duration::seconds_f64(data.len() as f64 / self.config.scrub_rate as f64),
+                    cancel_token,
+                )
+                .await
+            {
+                break;
+            }
+
+            // The bundle may have gone while we were reading it
+            if intact
+                || matches!(
+                    self.check_status(&bundle.bundle.id).await?,
+                    None | Some(metadata::BundleStatus::Tombstone(_))
+                )
+            {
+                continue;
+            }
+
+            error!("Bundle data {storage_name} does not match its recorded hash");
+            self.quarantine_data(&storage_name).await?;
+            dispatcher
+                .report_bundle_deletion(&bundle, bpv7::StatusReportReasonCode::DepletedStorage)
+                .await?;
+            self.set_status(
+                &mut bundle,
+                metadata::BundleStatus::Tombstone(self.clock.now()),
+            )
+            .await?;
+            quarantined += 1;
+        }
+        drop(rx);
+
+        // Stopping early closes the channel, which fails the poll
+        let r = h.await.trace_expect("polling task failed");
+        if !cancel_token.is_cancelled() {
+            r?;
+        }
+        Ok(quarantined)
+    }
+
+    async fn quarantine_data(&self, storage_name: &str) -> Result<(), Error> {
+        if let Some(admission) = &self.admission {
+            admission
+                .lock()
+                .trace_expect("Failed to lock admission mutex")
+                .release(storage_name);
+        }
+
+        self.resilience
+            .retry(
+                resilience::Backend::Bundles,
+                "quarantine bundle data",
+                || self.bundle_storage.quarantine(storage_name),
+            )
+            .await
+    }
+}
@@ -52,16 +52,27 @@ impl std::fmt::Display for DtnNodeId {
     }
 }
 
+/* Which node id to use as the source of administrative bundles, when both an ipn and a dtn node id are configured */
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum NodeIdSelector {
+    #[default]
+    MatchScheme,
+    PreferIpn,
+    PreferDtn,
+}
+
 #[derive(Clone)]
 pub struct AdminEndpoints {
     pub ipn: Option<IpnNodeId>,
     pub dtn: Option<DtnNodeId>,
+    pub selector: NodeIdSelector,
 }
 
 impl AdminEndpoints {
     pub fn init(config: &config::Config) -> Self {
         // Load NodeId from config
-        let admin_endpoints = init_from_value(
+        let mut admin_endpoints = init_from_value(
             config
                 .get::<config::Value>("administrative_endpoint")
                 .trace_expect(
@@ -70,6 +81,10 @@ impl AdminEndpoints {
         )
         .trace_expect("Invalid 'administrative_endpoint' value in configuration");
 
+        admin_endpoints.selector =
+            settings::get_with_default(config, "node_id_selection", NodeIdSelector::default())
+                .trace_expect("Invalid 'node_id_selection' value in configuration");
+
         match (&admin_endpoints.ipn, &admin_endpoints.dtn) {
             (None, None) => unreachable!(),
             (None, Some(node_id)) => info!("Administrative Endpoint: {node_id}"),
@@ -96,17 +111,22 @@ impl AdminEndpoints {
                 },
                 _ => node_id.to_eid(0),
             },
-            (Some(ipn_node_id), Some(dtn_node_id)) => match destination {
-                Eid::LocalNode { .. } => Eid::LocalNode { service_number: 0 },
-                Eid::LegacyIpn { .. } => Eid::LegacyIpn {
+            (Some(ipn_node_id), Some(dtn_node_id)) => match (destination, self.selector) {
+                (Eid::LocalNode { .. }, _) => Eid::LocalNode { service_number: 0 },
+                (
+                    Eid::LegacyIpn { .. },
+                    NodeIdSelector::MatchScheme | NodeIdSelector::PreferIpn,
+                ) => Eid::LegacyIpn {
                     allocator_id: ipn_node_id.allocator_id,
                     node_number: ipn_node_id.node_number,
                     service_number: 0,
                 },
-                Eid::Dtn { .. } => Eid::Dtn {
-                    node_name: dtn_node_id.node_name.clone(),
-                    demux: [].into(),
-                },
+                (Eid::Dtn { .. }, NodeIdSelector::MatchScheme) | (_, NodeIdSelector::PreferDtn) => {
+                    Eid::Dtn {
+                        node_name: dtn_node_id.node_name.clone(),
+                        demux: [].into(),
+                    }
+                }
                 _ => ipn_node_id.to_eid(0),
             },
             _ => unreachable!(),
@@ -140,36 +160,12 @@ impl AdminEndpoints {
     }
 
     pub fn is_admin_endpoint(&self, eid: &Eid) -> bool {
-        match eid {
-            Eid::LocalNode { service_number } => *service_number == 0,
-            Eid::LegacyIpn {
-                allocator_id,
-                node_number,
-                service_number,
-            }
-            | Eid::Ipn {
-                allocator_id,
-                node_number,
-                service_number,
-            } => match &self.ipn {
-                Some(node_id) => {
-                    node_id.allocator_id == *allocator_id
-                        && node_id.node_number == *node_number
-                        && *service_number == 0
-                }
-                _ => false,
-            },
-            Eid::Dtn { node_name, demux } => match &self.dtn {
-                Some(node_id) => node_id.node_name == *node_name && demux.is_empty(),
-                _ => false,
-            },
-            _ => false,
-        }
+        eid.is_administrative() && self.is_local_service(eid)
     }
 }
 
 #[derive(Error, Debug)]
-enum Error {
+pub enum Error {
     #[error("Value must be a string or array of strings")]
     InvalidValue,
 
@@ -201,7 +197,7 @@ enum Error {
     Config(#[from] config::ConfigError),
 }
 
-fn init_from_value(v: config::Value) -> Result<AdminEndpoints, Error> {
+pub(super) fn init_from_value(v: config::Value) -> Result<AdminEndpoints, Error> {
     match v.kind {
         config::ValueKind::String(s) => init_from_string(s),
         config::ValueKind::Array(v) => init_from_array(v),
@@ -232,6 +228,7 @@ fn init_from_string(s: String) -> Result<AdminEndpoints, Error> {
                         node_number,
                     }),
                     dtn: None,
+                    selector: NodeIdSelector::default(),
                 })
             }
         }
@@ -242,6 +239,7 @@ fn init_from_string(s: String) -> Result<AdminEndpoints, Error> {
                 Ok(AdminEndpoints {
                     dtn: Some(DtnNodeId { node_name }),
                     ipn: None,
+                    selector: NodeIdSelector::default(),
                 })
             }
         }
@@ -253,6 +251,7 @@ fn init_from_array(t: Vec<config::Value>) -> Result<AdminEndpoints, Error> {
     let mut admin_endpoints = AdminEndpoints {
         ipn: None,
         dtn: None,
+        selector: NodeIdSelector::default(),
     };
     for v in t {
         let n = init_from_value(v)?;
@@ -339,4 +338,27 @@ mod tests {
         /*
         #administrative_endpoint = [ "ipn:[A.]N.0", "dtn://node-name/"]*/
     }
+
+    #[test]
+    fn selection() {
+        let mut a = init_from_value(fake_config(vec!["ipn:1.0", "dtn://node-name/"])).unwrap();
+        let ipn: Eid = "ipn:1.0".parse().unwrap();
+        let dtn: Eid = "dtn://node-name/".parse().unwrap();
+
+        assert_eq!(a.selector, NodeIdSelector::MatchScheme);
+        assert_eq!(a.get_admin_endpoint(&"ipn:2.1".parse().unwrap()), ipn);
+        assert_eq!(
+            a.get_admin_endpoint(&"dtn://other-node/svc".parse().unwrap()),
+            dtn
+        );
+
+        a.selector = NodeIdSelector::PreferDtn;
+        assert_eq!(a.get_admin_endpoint(&"ipn:2.1".parse().unwrap()), dtn);
+
+        a.selector = NodeIdSelector::PreferIpn;
+        assert_eq!(
+            a.get_admin_endpoint(&"dtn://other-node/svc".parse().unwrap()),
+            ipn
+        );
+    }
 }
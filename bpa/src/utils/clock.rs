@@ -0,0 +1,35 @@
+/* The source of the current time for bundle expiry and age, waiting bundles and tombstones.
+ * The BPA runs on the system clock, tests can substitute a clock they control */
+pub trait Clock: Send + Sync {
+    fn now(&self) -> time::OffsetDateTime;
+}
+
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> time::OffsetDateTime {
+        time::OffsetDateTime::now_utc()
+    }
+}
+
+// A clock that only moves when it is told to
+#[cfg(test)]
+pub struct MockClock(std::sync::Mutex<time::OffsetDateTime>);
+
+#[cfg(test)]
+impl MockClock {
+    pub fn new(now: time::OffsetDateTime) -> Self {
+        Self(std::sync::Mutex::new(now))
+    }
+
+    pub fn advance(&self, duration: time::Duration) {
+        *self.0.lock().unwrap() += duration;
+    }
+}
+
+#[cfg(test)]
+impl Clock for MockClock {
+    fn now(&self) -> time::OffsetDateTime {
+        *self.0.lock().unwrap()
+    }
+}
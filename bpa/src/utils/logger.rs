@@ -1,15 +1,40 @@
 use super::*;
+use std::sync::OnceLock;
+use tracing_subscriber::{filter::LevelFilter, prelude::*, reload, Registry};
 
-pub fn init(config: &config::Config) {
-    let log_level = settings::get_with_default::<String, _>(config, "log_level", "info")
+static RELOAD_HANDLE: OnceLock<reload::Handle<LevelFilter, Registry>> = OnceLock::new();
+
+fn log_level(config: &config::Config) -> LevelFilter {
+    settings::get_with_default::<String, _>(config, "log_level", "info")
         .expect("Invalid 'log_level' value in configuration")
-        .parse::<tracing_subscriber::filter::LevelFilter>()
-        .expect("Invalid log level");
+        .parse::<LevelFilter>()
+        .expect("Invalid log level")
+}
+
+pub fn init(config: &config::Config) {
+    let log_level = log_level(config);
+    let (filter, handle) = reload::Layer::new(log_level);
 
-    tracing_subscriber::fmt()
-        .with_max_level(log_level)
-        .with_target(
-            log_level > tracing_subscriber::filter::LevelFilter::from_level(tracing::Level::INFO),
+    tracing_subscriber::registry()
+        .with(filter)
+        .with(
+            tracing_subscriber::fmt::layer()
+                .with_target(log_level > LevelFilter::from_level(tracing::Level::INFO)),
         )
         .init();
+
+    _ = RELOAD_HANDLE.set(handle);
+}
+
+pub fn reload(config: &config::Config) {
+    let log_level = log_level(config);
+    if let Some(handle) = RELOAD_HANDLE.get() {
+        if handle.clone_current() == Some(log_level) {
+            return;
+        }
+        match handle.modify(|filter| *filter = log_level) {
+            Ok(()) => info!("Log level changed to {log_level}"),
+            Err(e) => error!("Failed to change log level: {e}"),
+        }
+    }
 }
@@ -3,5 +3,7 @@ use super::*;
 pub mod admin_endpoints;
 pub mod built_info;
 pub mod cancel;
+pub mod clock;
 pub mod logger;
 pub mod settings;
+pub mod validate;
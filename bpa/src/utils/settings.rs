@@ -1,5 +1,6 @@
 use super::*;
 use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
 
 pub const WAIT_SAMPLE_INTERVAL_SECS: u64 = 60;
 
@@ -12,10 +13,85 @@ fn options() -> getopts::Options {
             "upgrade-store",
             "upgrade the bundle store to the current format",
         )
-        .optopt("c", "config", "use a custom configuration file", "FILE");
+        .optopt("c", "config", "use a custom configuration file", "FILE")
+        .optopt(
+            "",
+            "export-store",
+            "write every stored bundle to an archive, then exit",
+            "FILE",
+        )
+        .optopt(
+            "",
+            "import-store",
+            "store every bundle in an archive, then exit",
+            "FILE",
+        )
+        .optopt(
+            "",
+            "replay",
+            "re-dispatch the stored bundles for destinations matching PATTERN, once started",
+            "PATTERN",
+        )
+        .optopt(
+            "",
+            "replay-since",
+            "only replay bundles received since TIME (RFC3339), defaults to a day before --replay-until",
+            "TIME",
+        )
+        .optopt(
+            "",
+            "replay-until",
+            "only replay bundles received until TIME (RFC3339), defaults to now",
+            "TIME",
+        );
     opts
 }
 
+/* A one-shot operation requested on the command line.  Store commands are run instead of starting
+ * the BPA, a replay is run once the BPA has started */
+pub enum Command {
+    ExportStore(PathBuf),
+    ImportStore(PathBuf),
+    Replay(Replay),
+}
+
+// Re-dispatch the stored bundles for `destinations` received within `window`
+pub struct Replay {
+    pub destinations: bpv7::EidPattern,
+    pub window: std::ops::Range<time::OffsetDateTime>,
+}
+
+impl Replay {
+    fn parse(
+        destinations: &str,
+        since: Option<String>,
+        until: Option<String>,
+    ) -> Result<Self, String> {
+        let destinations = destinations
+            .parse()
+            .map_err(|e| format!("Invalid --replay pattern '{destinations}': {e}"))?;
+        let parse_time = |flag: &str, s: String| {
+            time::OffsetDateTime::parse(&s, &time::format_description::well_known::Rfc3339)
+                .map_err(|e| format!("Invalid --{flag} time '{s}': {e}"))
+        };
+        let until = until
+            .map(|s| parse_time("replay-until", s))
+            .transpose()?
+            .unwrap_or_else(time::OffsetDateTime::now_utc);
+        let since = since
+            .map(|s| parse_time("replay-since", s))
+            .transpose()?
+            .unwrap_or(until - time::Duration::days(1));
+        if since > until {
+            return Err("--replay-since must not be after --replay-until".to_string());
+        }
+        Ok(Self {
+            destinations,
+            window: since..until,
+        })
+    }
+}
+
 pub fn config_dir() -> PathBuf {
     directories::ProjectDirs::from("dtn", "Hardy", built_info::PKG_NAME).map_or_else(
         || {
@@ -54,7 +130,7 @@ pub fn get_with_default<'de, T: serde::Deserialize<'de>, D: Into<T>>(
     }
 }
 
-pub fn init() -> Option<(config::Config, bool, String)> {
+pub fn init() -> Option<(config::Config, bool, Option<Command>, String)> {
     // Parse cmdline
     let opts = options();
     let args: Vec<String> = std::env::args().collect();
@@ -78,37 +154,158 @@ pub fn init() -> Option<(config::Config, bool, String)> {
         return None;
     }
 
-    let mut b = config::Config::builder();
+    let command = match (
+        flags.opt_str("export-store"),
+        flags.opt_str("import-store"),
+        flags.opt_str("replay"),
+    ) {
+        (Some(path), None, None) => Some(Command::ExportStore(path.into())),
+        (None, Some(path), None) => Some(Command::ImportStore(path.into())),
+        (None, None, Some(destinations)) => match Replay::parse(
+            &destinations,
+            flags.opt_str("replay-since"),
+            flags.opt_str("replay-until"),
+        ) {
+            Ok(replay) => Some(Command::Replay(replay)),
+            Err(e) => {
+                eprintln!("{e}");
+                return None;
+            }
+        },
+        (None, None, None) => None,
+        _ => {
+            eprintln!("Only one of --export-store, --import-store and --replay can be used");
+            return None;
+        }
+    };
+    if !matches!(command, Some(Command::Replay(_)))
+        && (flags.opt_present("replay-since") || flags.opt_present("replay-until"))
+    {
+        eprintln!("--replay-since and --replay-until can only be used with --replay");
+        return None;
+    }
 
     // Add config file
     let config_source: String;
-    if let Some(source) = flags.opt_str("config") {
+    let config_file = if let Some(source) = flags.opt_str("config") {
         config_source =
             format!("Using base configuration file '{source}' specified on command line");
-        b = b.add_source(config::File::with_name(&source).format(config::FileFormat::Toml))
+        ConfigFile::Required(source)
     } else if let Ok(source) = std::env::var("HARDY_BPA_CONFIG_FILE") {
         config_source = format!("Using base configuration file '{source}' specified by HARDY_BPA_CONFIG_FILE environment variable");
-        b = b.add_source(config::File::with_name(&source).format(config::FileFormat::Toml))
+        ConfigFile::Required(source)
     } else {
         let path = config_dir().join(format!("{}.config", built_info::PKG_NAME));
         config_source = format!(
             "Using optional base configuration file '{}'",
             path.display()
         );
-        b = b.add_source(
-            config::File::from(path)
+        ConfigFile::Optional(path)
+    };
+
+    // And parse...
+    let config = build(&config_file).expect("Failed to build configuration");
+    _ = CONFIG_FILE.set(config_file);
+    Some((config, flags.opt_present("u"), command, config_source))
+}
+
+enum ConfigFile {
+    Required(String),
+    Optional(PathBuf),
+}
+
+// Remember where the configuration came from, so it can be re-read
+static CONFIG_FILE: OnceLock<ConfigFile> = OnceLock::new();
+
+fn build(config_file: &ConfigFile) -> Result<config::Config, config::ConfigError> {
+    let mut b = config::Config::builder();
+    b = match config_file {
+        ConfigFile::Required(source) => {
+            b.add_source(config::File::with_name(source).format(config::FileFormat::Toml))
+        }
+        ConfigFile::Optional(path) => b.add_source(
+            config::File::from(path.as_path())
                 .required(false)
                 .format(config::FileFormat::Toml),
-        )
-    }
+        ),
+    };
 
     // Pull in environment vars
-    b = b.add_source(config::Environment::with_prefix("HARDY_BPA"));
+    b.add_source(config::Environment::with_prefix("HARDY_BPA"))
+        .build()
+}
 
-    // And parse...
-    Some((
-        b.build().expect("Failed to build configuration"),
-        flags.opt_present("u"),
-        config_source,
-    ))
+pub fn reload() -> Result<config::Config, config::ConfigError> {
+    build(
+        CONFIG_FILE
+            .get()
+            .expect("Configuration has not been loaded"),
+    )
+}
+
+// Re-read the configuration on SIGHUP, passing it to `f`
+#[cfg(unix)]
+pub fn listen_for_reload(
+    f: impl Fn(config::Config) + Send + 'static,
+    task_set: &mut tokio::task::JoinSet<()>,
+    cancel_token: tokio_util::sync::CancellationToken,
+) {
+    let mut hup_handler = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup())
+        .trace_expect("Failed to register signal handlers");
+
+    task_set.spawn(async move {
+        loop {
+            tokio::select! {
+                _ = hup_handler.recv() => {
+                    info!("Received hangup signal, reloading configuration...");
+                    // An invalid configuration is refused whole, rather than panicking part way through applying it
+                    match reload() {
+                        Ok(config) => match validate::validate(&config) {
+                            Ok(()) => f(config),
+                            Err(errors) => {
+                                for e in errors {
+                                    error!("{e}");
+                                }
+                                error!("Invalid configuration, keeping the current configuration");
+                            }
+                        },
+                        Err(e) => error!("Failed to reload configuration: {e}"),
+                    }
+                }
+                _ = cancel_token.cancelled() => break
+            }
+        }
+    });
+}
+
+#[cfg(not(unix))]
+pub fn listen_for_reload(
+    _f: impl Fn(config::Config) + Send + 'static,
+    _task_set: &mut tokio::task::JoinSet<()>,
+    _cancel_token: tokio_util::sync::CancellationToken,
+) {
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn replay() {
+        let replay =
+            Replay::parse("ipn:2.*", None, Some("2024-01-02T00:00:00Z".to_string())).unwrap();
+        assert_eq!(
+            replay.window.end - replay.window.start,
+            time::Duration::days(1)
+        );
+
+        assert!(Replay::parse("ipn:[-", None, None).is_err());
+        assert!(Replay::parse("ipn:2.*", Some("yesterday".to_string()), None).is_err());
+        assert!(Replay::parse(
+            "ipn:2.*",
+            Some("2024-01-03T00:00:00Z".to_string()),
+            Some("2024-01-02T00:00:00Z".to_string())
+        )
+        .is_err());
+    }
 }
@@ -0,0 +1,462 @@
+use super::*;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum ConfigError {
+    #[error("Missing '{0}' value in configuration")]
+    Missing(&'static str),
+
+    #[error("Invalid '{key}' value in configuration: {reason}")]
+    Invalid { key: String, reason: String },
+
+    #[error("Invalid 'administrative_endpoint' value in configuration: {0}")]
+    AdminEndpoint(#[from] admin_endpoints::Error),
+
+    #[error("Unknown {key} engine '{engine}', this build supports {known:?}")]
+    UnknownEngine {
+        key: &'static str,
+        engine: String,
+        known: Vec<&'static str>,
+    },
+
+    #[error("'{0}' must be greater than zero")]
+    NotPositive(&'static str),
+
+    #[error("Invalid EID pattern '{pattern}' in '{key}': {error}")]
+    Pattern {
+        key: &'static str,
+        pattern: String,
+        error: bpv7::EidPatternError,
+    },
+
+    #[error("Failed to read static routes file '{file}': {reason}")]
+    StaticRoutesFile { file: String, reason: String },
+
+    #[error("Invalid static route at line {line} of '{file}': {reason}")]
+    StaticRoute {
+        file: String,
+        line: usize,
+        reason: String,
+    },
+}
+
+// A year is far longer than any CLA should take to acknowledge a bundle
+const MAX_FORWARD_ACK_TIMEOUT_SECS: u64 = 365 * 24 * 60 * 60;
+
+/* Check the configuration before anything is started, collecting every problem found
+ * so an operator can fix them all at once, rather than one panic at a time */
+pub fn validate(config: &config::Config) -> Result<(), Vec<ConfigError>> {
+    let mut errors = Vec::new();
+
+    match config.get::<config::Value>("administrative_endpoint") {
+        Err(config::ConfigError::NotFound(_)) => {
+            errors.push(ConfigError::Missing("administrative_endpoint"))
+        }
+        Err(e) => errors.push(invalid("administrative_endpoint", e)),
+        Ok(v) => {
+            if let Err(e) = admin_endpoints::init_from_value(v) {
+                errors.push(e.into());
+            }
+        }
+    }
+
+    if let Err(e) = settings::get_with_default(
+        config,
+        "node_id_selection",
+        admin_endpoints::NodeIdSelector::default(),
+    ) {
+        errors.push(invalid("node_id_selection", e));
+    }
+
+    check_engine(
+        config,
+        "metadata_storage",
+        store::metadata_engines(),
+        &mut errors,
+    );
+    if check_engine(
+        config,
+        "bundle_storage",
+        store::bundle_engines(),
+        &mut errors,
+    )
+    .as_deref()
+        == Some("tiered")
+    {
+        // The tiered engine cannot wrap itself
+        let known = store::bundle_engines()
+            .into_iter()
+            .filter(|engine| *engine != "tiered")
+            .collect();
+        check_engine(config, "tiered.inner", known, &mut errors);
+    }
+
+    match settings::get_with_default::<u64, _>(
+        config,
+        "wait_sample_interval",
+        settings::WAIT_SAMPLE_INTERVAL_SECS,
+    ) {
+        Err(e) => errors.push(invalid("wait_sample_interval", e)),
+        Ok(0) => errors.push(ConfigError::NotPositive("wait_sample_interval")),
+        Ok(v) if v > i64::MAX as u64 => errors.push(ConfigError::Invalid {
+            key: "wait_sample_interval".to_string(),
+            reason: "value is too large".to_string(),
+        }),
+        Ok(_) => {}
+    }
+
+    for key in [
+        "status_reports",
+        "trace_propagation",
+        "reflect_loop_prevention",
+        "accept_source_routes",
+        "forwarding",
+        "verify_on_load",
+    ] {
+        if let Err(e) = settings::get_with_default::<bool, _>(config, key, false) {
+            errors.push(invalid(key, e));
+        }
+    }
+
+    for key in [
+        "max_forwarding_delay",
+        "max_dispatch_per_wakeup",
+        "max_concurrent_notifications",
+        "max_in_flight_per_peer",
+        "max_ingress_queue",
+        "status_report_limit",
+        "max_delivery_attempts",
+        "storage_retries",
+        "storage_retry_backoff",
+    ] {
+        if let Err(e) = settings::get_with_default::<u32, _>(config, key, 0u32) {
+            errors.push(invalid(key, e));
+        }
+    }
+
+    match settings::get_with_default::<u64, _>(config, "scrub_interval", 0u64) {
+        Err(e) => errors.push(invalid("scrub_interval", e)),
+        Ok(v) if v > i64::MAX as u64 => errors.push(ConfigError::Invalid {
+            key: "scrub_interval".to_string(),
+            reason: "value is too large".to_string(),
+        }),
+        Ok(_) => {}
+    }
+
+    if let Err(e) = settings::get_with_default::<u64, _>(config, "scrub_rate", 0u64) {
+        errors.push(invalid("scrub_rate", e));
+    }
+
+    match settings::get_with_default::<u64, _>(
+        config,
+        "forward_ack_timeout",
+        dispatcher::FORWARD_ACK_TIMEOUT_SECS,
+    ) {
+        Err(e) => errors.push(invalid("forward_ack_timeout", e)),
+        Ok(0) => errors.push(ConfigError::NotPositive("forward_ack_timeout")),
+        Ok(t) if t > MAX_FORWARD_ACK_TIMEOUT_SECS => errors.push(ConfigError::Invalid {
+            key: "forward_ack_timeout".to_string(),
+            reason: format!("must be at most {MAX_FORWARD_ACK_TIMEOUT_SECS} seconds"),
+        }),
+        Ok(_) => {}
+    }
+
+    match settings::get_with_default::<u64, _>(
+        config,
+        "status_report_window",
+        dispatcher::STATUS_REPORT_WINDOW_SECS,
+    ) {
+        Err(e) => errors.push(invalid("status_report_window", e)),
+        Ok(0) => errors.push(ConfigError::NotPositive("status_report_window")),
+        Ok(_) => {}
+    }
+
+    if let Err(e) = settings::get_with_default::<u64, _>(config, "storage_capacity", 0u64) {
+        errors.push(invalid("storage_capacity", e));
+    }
+
+    match settings::get_with_default::<u8, _>(config, "storage_early_drop", 0u8) {
+        Err(e) => errors.push(invalid("storage_early_drop", e)),
+        Ok(p) if p > 100 => errors.push(invalid(
+            "storage_early_drop",
+            "must be a percentage between 0 and 100",
+        )),
+        Ok(_) => {}
+    }
+
+    if let Err(e) = settings::get_with_default(
+        config,
+        "unsupported_blocks",
+        dispatcher::UnsupportedBlocks::default(),
+    ) {
+        errors.push(invalid("unsupported_blocks", e));
+    }
+
+    if let Err(e) = settings::get_with_default(
+        config,
+        "local_delivery",
+        dispatcher::LocalDelivery::default(),
+    ) {
+        errors.push(invalid("local_delivery", e));
+    }
+
+    if let Err(e) =
+        settings::get_with_default(config, "admin_records", dispatcher::AdminRecords::default())
+    {
+        errors.push(invalid("admin_records", e));
+    }
+
+    if let Err(e) = settings::get_with_default(
+        config,
+        "spoofed_sources",
+        dispatcher::SpoofedSources::default(),
+    ) {
+        errors.push(invalid("spoofed_sources", e));
+    }
+
+    if let Err(e) = settings::get_with_default(
+        config,
+        "future_bundles",
+        dispatcher::FutureBundles::default(),
+    ) {
+        errors.push(invalid("future_bundles", e));
+    }
+
+    if let Err(e) = settings::get_with_default::<u64, _>(config, "clock_skew_tolerance", 0) {
+        errors.push(invalid("clock_skew_tolerance", e));
+    }
+
+    if let Err(e) = settings::get_with_default::<u64, _>(config, "dedup_filter_window", 0) {
+        errors.push(invalid("dedup_filter_window", e));
+    }
+
+    if let Err(e) = settings::get_with_default::<usize, _>(config, "dedup_filter_size", 0usize) {
+        errors.push(invalid("dedup_filter_size", e));
+    }
+
+    match settings::get_with_default::<String, _>(config, "log_level", "info") {
+        Err(e) => errors.push(invalid("log_level", e)),
+        Ok(level) => {
+            if let Err(e) = level.parse::<tracing_subscriber::filter::LevelFilter>() {
+                errors.push(invalid("log_level", e));
+            }
+        }
+    }
+
+    match config.get::<std::collections::HashMap<String, String>>("ingress_sources") {
+        Err(config::ConfigError::NotFound(_)) => {}
+        Err(e) => errors.push(invalid("ingress_sources", e)),
+        Ok(sources) => {
+            for (_, pattern) in sources {
+                if let Err(error) = pattern.parse::<bpv7::EidPattern>() {
+                    errors.push(ConfigError::Pattern {
+                        key: "ingress_sources",
+                        pattern,
+                        error,
+                    });
+                }
+            }
+        }
+    }
+
+    if let Err(e) = config.get::<std::collections::HashMap<String, u64>>("egress_rates") {
+        if !matches!(e, config::ConfigError::NotFound(_)) {
+            errors.push(invalid("egress_rates", e));
+        }
+    }
+
+    // ipn_2_element may also be an empty table, which is ignored
+    if let Ok(patterns) = config.get::<Vec<String>>("ipn_2_element") {
+        for pattern in patterns {
+            if let Err(error) = pattern.parse::<bpv7::EidPattern>() {
+                errors.push(ConfigError::Pattern {
+                    key: "ipn_2_element",
+                    pattern,
+                    error,
+                });
+            }
+        }
+    }
+
+    static_routes::validate(config, &mut errors);
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+fn invalid(key: &str, reason: impl std::fmt::Display) -> ConfigError {
+    ConfigError::Invalid {
+        key: key.to_string(),
+        reason: reason.to_string(),
+    }
+}
+
+fn check_engine(
+    config: &config::Config,
+    key: &'static str,
+    known: Vec<&'static str>,
+    errors: &mut Vec<ConfigError>,
+) -> Option<String> {
+    match config.get::<String>(key) {
+        Err(config::ConfigError::NotFound(_)) => None,
+        Err(e) => {
+            errors.push(invalid(key, e));
+            None
+        }
+        Ok(engine) if !known.contains(&engine.as_str()) => {
+            errors.push(ConfigError::UnknownEngine { key, engine, known });
+            None
+        }
+        Ok(engine) => Some(engine),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn build_config(values: &[(&str, config::Value)]) -> config::Config {
+        values
+            .iter()
+            .fold(config::Config::builder(), |b, (k, v)| {
+                b.set_override(*k, v.clone()).unwrap()
+            })
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn valid() {
+        validate(&build_config(&[
+            ("administrative_endpoint", "ipn:1.0".into()),
+            ("wait_sample_interval", 30.into()),
+        ]))
+        .unwrap();
+    }
+
+    #[test]
+    fn missing_node_id() {
+        let errors = validate(&build_config(&[])).unwrap_err();
+        assert!(matches!(
+            errors[..],
+            [ConfigError::Missing("administrative_endpoint")]
+        ));
+
+        let errors = validate(&build_config(&[(
+            "administrative_endpoint",
+            Vec::<String>::new().into(),
+        )]))
+        .unwrap_err();
+        assert!(matches!(
+            errors[..],
+            [ConfigError::AdminEndpoint(
+                admin_endpoints::Error::NoEndpoints
+            )]
+        ));
+    }
+
+    #[test]
+    fn all_reported() {
+        let errors = validate(&build_config(&[
+            ("administrative_endpoint", "ipn:1.1".into()),
+            ("metadata_storage", "carrier-pigeon".into()),
+            ("wait_sample_interval", 0.into()),
+            ("status_reports", "sometimes".into()),
+            ("ipn_2_element", vec!["ipn:1.*", "ipn:[-"].into()),
+        ]))
+        .unwrap_err();
+
+        assert_eq!(errors.len(), 5, "{errors:?}");
+        assert!(matches!(
+            errors[0],
+            ConfigError::AdminEndpoint(admin_endpoints::Error::IpnNonZeroServiceNumber)
+        ));
+        assert!(matches!(
+            &errors[1],
+            ConfigError::UnknownEngine { key: "metadata_storage", engine, .. } if engine == "carrier-pigeon"
+        ));
+        assert!(matches!(
+            errors[2],
+            ConfigError::NotPositive("wait_sample_interval")
+        ));
+        assert!(matches!(&errors[3], ConfigError::Invalid { key, .. } if key == "status_reports"));
+        assert!(matches!(
+            &errors[4],
+            ConfigError::Pattern { key: "ipn_2_element", pattern, .. } if pattern == "ipn:[-"
+        ));
+    }
+
+    #[test]
+    fn log_level() {
+        let errors = validate(&build_config(&[
+            ("administrative_endpoint", "ipn:1.0".into()),
+            ("log_level", "chatty".into()),
+        ]))
+        .unwrap_err();
+        assert!(matches!(&errors[..], [ConfigError::Invalid { key, .. }] if key == "log_level"));
+    }
+
+    #[test]
+    fn forward_ack_timeout() {
+        let errors = validate(&build_config(&[
+            ("administrative_endpoint", "ipn:1.0".into()),
+            ("forward_ack_timeout", config::Value::from(u64::MAX)),
+        ]))
+        .unwrap_err();
+        assert_eq!(errors.len(), 1, "{errors:?}");
+        assert!(
+            matches!(&errors[0], ConfigError::Invalid { key, .. } if key == "forward_ack_timeout")
+        );
+
+        validate(&build_config(&[
+            ("administrative_endpoint", "ipn:1.0".into()),
+            (
+                "forward_ack_timeout",
+                config::Value::from(MAX_FORWARD_ACK_TIMEOUT_SECS),
+            ),
+        ]))
+        .unwrap();
+    }
+
+    #[test]
+    fn static_routes() {
+        let path =
+            std::env::temp_dir().join(format!("hardy-bpa-validate-{}.routes", std::process::id()));
+        std::fs::write(
+            &path,
+            "# Comment\nipn:2.*.* via ipn:3.1.0\nipn:4.*.* teleport\n\nipn:5.*.* drop\nipn:6.*.* source-route ipn:3.1.0,ipn:4.1.0\nipn:7.*.* source-route ipn:3.1.0,\n",
+        )
+        .unwrap();
+
+        let errors = validate(&build_config(&[
+            ("administrative_endpoint", "ipn:1.0".into()),
+            (
+                "static_routes.routes_file",
+                path.to_string_lossy().as_ref().into(),
+            ),
+        ]))
+        .unwrap_err();
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(matches!(
+            errors[..],
+            [
+                ConfigError::StaticRoute { line: 3, .. },
+                ConfigError::StaticRoute { line: 7, .. }
+            ]
+        ));
+
+        let errors = validate(&build_config(&[
+            ("administrative_endpoint", "ipn:1.0".into()),
+            (
+                "static_routes.routes_file",
+                path.to_string_lossy().as_ref().into(),
+            ),
+        ]))
+        .unwrap_err();
+        assert!(matches!(errors[..], [ConfigError::StaticRoutesFile { .. }]));
+    }
+}
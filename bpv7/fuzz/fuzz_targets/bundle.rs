@@ -29,10 +29,9 @@ fn get_keys(
 }
 
 fuzz_target!(|data: &[u8]| {
-    if let Ok(ValidBundle::Rewritten(_, data, _)) = ValidBundle::parse(data, get_keys) {
-        let Ok(ValidBundle::Valid(..)) = ValidBundle::parse(&data, get_keys) else {
-            panic!("Rewrite borked");
-        };
+    match roundtrip_check(data, get_keys) {
+        Ok(()) | Err(RoundtripError::InvalidBundle(_)) => {}
+        Err(e) => panic!("Rewrite borked: {e}"),
     }
 });
 
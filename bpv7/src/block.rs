@@ -19,6 +19,28 @@ impl Block {
             ..self.data_start + self.payload_offset + self.payload_len]
     }
 
+    /* The canonical CBOR byte string of the block-type-specific data, as covered by BIB integrity
+     * protection.  The primary block is not a byte string, so its encoding is wrapped in one */
+    pub fn canonical_bytes(&self, source_data: &[u8]) -> Result<Box<[u8]>, Error> {
+        let payload = self.payload(source_data);
+        if let BlockType::Primary = self.block_type {
+            return Ok(cbor::encode::emit(payload).into());
+        }
+        cbor::decode::parse_value(payload, |value, s, tags| match value {
+            cbor::decode::Value::Bytes(_) if s && tags.is_empty() => Ok(payload.into()),
+            cbor::decode::Value::Bytes(data) => Ok(cbor::encode::emit(data).into()),
+            cbor::decode::Value::ByteStream(data) => {
+                Ok(cbor::encode::emit(data.concat().as_slice()).into())
+            }
+            value => Err(cbor::decode::Error::IncorrectType(
+                "Byte String".to_string(),
+                value.type_name(!tags.is_empty()),
+            )),
+        })
+        .map(|(data, _)| data)
+        .map_field_err("block-type-specific data")
+    }
+
     fn emit_inner(
         &mut self,
         block_number: u64,
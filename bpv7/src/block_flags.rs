@@ -1,6 +1,6 @@
 use super::*;
 
-#[derive(Default, Debug, Clone)]
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
 pub struct BlockFlags {
     pub must_replicate: bool,
     pub report_on_failure: bool,
@@ -9,9 +9,25 @@ pub enum BlockType {
     HopCount,
     BlockIntegrity,
     BlockSecurity,
+    ClassOfService,
     Unrecognised(u64),
 }
 
+impl BlockType {
+    /* The block types assigned by RFC 9171 and RFC 9172, and Class of Service from the private or
+     * experimental range 192 to 255, with their codes.  Every other code converts to Unrecognised */
+    pub const REGISTRY: [(BlockType, u64); 8] = [
+        (BlockType::Primary, 0),
+        (BlockType::Payload, 1),
+        (BlockType::PreviousNode, 6),
+        (BlockType::BundleAge, 7),
+        (BlockType::HopCount, 10),
+        (BlockType::BlockIntegrity, 11),
+        (BlockType::BlockSecurity, 12),
+        (BlockType::ClassOfService, 194),
+    ];
+}
+
 impl std::fmt::Display for BlockType {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -22,6 +38,7 @@ impl std::fmt::Display for BlockType {
             BlockType::HopCount => write!(f, "Hop Count"),
             BlockType::BlockIntegrity => write!(f, "Bundle Integrity"),
             BlockType::BlockSecurity => write!(f, "Bundle Security"),
+            BlockType::ClassOfService => write!(f, "Class of Service"),
             BlockType::Unrecognised(v) if *v >= 192 && *v <= 255 => {
                 write!(f, "Private/Experimental type {v}")
             }
@@ -40,6 +57,7 @@ impl From<BlockType> for u64 {
             BlockType::HopCount => 10,
             BlockType::BlockIntegrity => 11,
             BlockType::BlockSecurity => 12,
+            BlockType::ClassOfService => 194,
             BlockType::Unrecognised(v) => v,
         }
     }
@@ -55,6 +73,7 @@ impl From<u64> for BlockType {
             10 => BlockType::HopCount,
             11 => BlockType::BlockIntegrity,
             12 => BlockType::BlockSecurity,
+            194 => BlockType::ClassOfService,
             value => BlockType::Unrecognised(value),
         }
     }
@@ -74,3 +93,25 @@ impl cbor::decode::FromCbor for BlockType {
             .map(|o| o.map(|(value, shortest, len)| (value.into(), shortest, len)))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn registry() {
+        for (block_type, code) in BlockType::REGISTRY {
+            assert_eq!(u64::from(block_type), code);
+            assert_eq!(BlockType::from(code), block_type);
+            assert_eq!(
+                cbor::decode::parse::<BlockType>(&cbor::encode::emit(block_type)).unwrap(),
+                block_type
+            );
+        }
+
+        for code in [2, 5, 13, 192, 255, 256] {
+            assert_eq!(BlockType::from(code), BlockType::Unrecognised(code));
+            assert_eq!(u64::from(BlockType::Unrecognised(code)), code);
+        }
+    }
+}
@@ -92,6 +92,64 @@ impl OperationSet {
     pub fn is_unsupported(&self) -> bool {
         self.operations.values().next().unwrap().is_unsupported()
     }
+
+    /* Originate a BCB encrypting the block `target_number`, returning it along with the ciphertext.
+     * AES-GCM must never reuse an IV under the same key, so unlike BIBs each BCB has a single
+     * target, and each target needs its own BCB with a fresh IV */
+    #[allow(clippy::too_many_arguments)]
+    pub fn encrypt_aes_gcm(
+        source: Eid,
+        parameters: bcb_aes_gcm::Parameters,
+        key: &KeyMaterial,
+        target_number: u64,
+        bcb_block: &block::Block,
+        bcb_block_number: u64,
+        bundle: &Bundle,
+        bundle_data: &[u8],
+    ) -> Result<(Self, Box<[u8]>), Error> {
+        if source.is_null() || matches!(source, Eid::LocalNode { .. }) {
+            return Err(Error::InvalidSecuritySource);
+        }
+
+        let Some(target) = bundle.blocks.get(&target_number) else {
+            return Err(Error::MissingSecurityTarget);
+        };
+
+        // We do not encrypt BIBs, which would require sharing all their targets
+        if target_number == 0
+            || target.bcb.is_some()
+            || matches!(
+                target.block_type,
+                BlockType::BlockIntegrity | BlockType::BlockSecurity
+            )
+        {
+            return Err(Error::InvalidBCBTarget);
+        }
+
+        let mut op = bcb_aes_gcm::Operation::new(Rc::new(parameters));
+        let ciphertext = op.encrypt(
+            Some(key),
+            OperationArgs {
+                bpsec_source: &source,
+                target,
+                target_number,
+                source: bcb_block,
+                source_number: bcb_block_number,
+                bundle,
+                primary_block: None,
+                bundle_data,
+            },
+            None,
+        )?;
+
+        Ok((
+            Self {
+                source,
+                operations: HashMap::from([(target_number, Operation::AES_GCM(op))]),
+            },
+            ciphertext,
+        ))
+    }
 }
 
 impl cbor::encode::ToCbor for OperationSet {
@@ -187,6 +187,15 @@ pub struct Operation {
 }
 
 impl Operation {
+    /* For originating a BCB.  If `parameters.key` holds a CEK wrapped with
+     * key::KeyAlgorithm::wrap(), the key material passed to encrypt() is the KEK */
+    pub fn new(parameters: Rc<Parameters>) -> Self {
+        Self {
+            parameters,
+            results: Results(Box::default()),
+        }
+    }
+
     pub fn is_unsupported(&self) -> bool {
         matches!(self.parameters.variant, AesVariant::Unrecognised(_))
     }
@@ -214,7 +223,7 @@ impl Operation {
                 aad,
                 data,
             ),
-            AesVariant::Unrecognised(_) => unreachable!(),
+            AesVariant::Unrecognised(v) => Err(Error::UnsupportedAesVariant(v)),
         }
     }
 
@@ -232,7 +241,10 @@ impl Operation {
             });
         };
         let key = rfc9173::unwrap_key(args.bpsec_source, key, &self.parameters.key)?;
-        let (data, aad) = self.build_data(&args, payload_data)?;
+        let (mut data, aad) = self.build_data(&args, payload_data)?;
+
+        // Append authentication tag
+        data.extend_from_slice(&self.results.0);
 
         match self.parameters.variant {
             AesVariant::A128GCM => self.decrypt_inner(
@@ -293,7 +305,7 @@ impl Operation {
         }
         let aad = encoder.build();
 
-        let mut data = if let Some(payload_data) = payload_data {
+        let data = if let Some(payload_data) = payload_data {
             payload_data.into()
         } else {
             cbor::decode::parse_value(args.target.payload(args.bundle_data), |value, _, _| {
@@ -311,10 +323,6 @@ impl Operation {
             })
             .map(|v| v.0)?
         };
-
-        // Append authentication tag
-        data.extend_from_slice(&self.results.0);
-
         Ok((data, aad))
     }
 
@@ -336,20 +344,21 @@ impl Operation {
     }
 
     fn encrypt_inner(
-        &self,
+        &mut self,
         cipher: &mut impl aes_gcm::aead::AeadInPlace,
         aad: Vec<u8>,
         mut data: Vec<u8>,
     ) -> Result<Box<[u8]>, Error> {
-        // Encrypt in-place, this results in a single data copy
-        cipher
-            .encrypt_in_place(self.parameters.iv.as_ref().into(), &aad, &mut data)
-            .map(|_| data.into())
-            .map_err(|_| bpsec::Error::EncryptionFailed)
+        // Encrypt in-place, this results in a single data copy, the tag becomes the result
+        let tag = cipher
+            .encrypt_in_place_detached(self.parameters.iv.as_ref().into(), &aad, &mut data)
+            .map_err(|_| bpsec::Error::EncryptionFailed)?;
+        self.results = Results(tag.as_slice().into());
+        Ok(data.into())
     }
 
     pub fn emit_context(&self, encoder: &mut cbor::encode::Encoder, source: &Eid) {
-        encoder.emit(Context::BIB_HMAC_SHA2);
+        encoder.emit(Context::BCB_AES_GCM);
         encoder.emit(1);
         encoder.emit(source);
         encoder.emit(self.parameters.as_ref());
@@ -392,3 +401,83 @@ pub fn parse(
     }
     Ok((asb.source, operations))
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // RFC 9173 Appendix A.2, with the creation timestamp tweaked to be valid, and a CRC added
+    const APPENDIX_A_2: [u8; 162] = hex_literal::hex!(
+        "9f89070001820282010282028202018202820201820118281a000f424042e4fe850c0201
+        0058508101020182028202018482014c5477656c7665313231323132820201820358
+        1869c411276fecddc4780df42c8a2af89296fabf34d7fae7008204008181820150ef
+        a4b5ac0108e3816c5606479801bc04850101000058233a09c1e63fe23a7f66a59c73
+        03837241e070b02619fc59c5214a22f08cd70795e73e9aff"
+    );
+    const PLAINTEXT: &[u8] = b"Ready to generate a 32-byte payload";
+
+    #[test]
+    fn wrapped_key() {
+        let ValidBundle::Valid(bundle, _) =
+            ValidBundle::parse(&APPENDIX_A_2, |_, _| Ok(None)).unwrap()
+        else {
+            panic!("Invalid bundle");
+        };
+        let source = "ipn:2.1".parse().unwrap();
+        let args = || bcb::OperationArgs {
+            bpsec_source: &source,
+            target: bundle.blocks.get(&1).unwrap(),
+            target_number: 1,
+            source: bundle.blocks.get(&2).unwrap(),
+            source_number: 2,
+            bundle: &bundle,
+            primary_block: None,
+            bundle_data: &APPENDIX_A_2,
+        };
+
+        // Wrap the CEK with the KEK, and encrypt with the KEK alone
+        let kek =
+            KeyMaterial::SymmetricKey(hex_literal::hex!("6162636465666768696a6b6c6d6e6f70").into());
+        let parameters = Rc::new(Parameters {
+            iv: hex_literal::hex!("5477656c7665313231323132").into(),
+            variant: AesVariant::A128GCM,
+            key: key::KeyAlgorithm::A128KW
+                .wrap(&kek, &hex_literal::hex!("71776572747975696f70617364666768"))
+                .unwrap(),
+            flags: rfc9173::ScopeFlags {
+                include_primary_block: false,
+                include_target_header: false,
+                include_security_header: false,
+                unrecognised: 0,
+            },
+        });
+        let mut op = Operation::new(parameters);
+        let ciphertext = op.encrypt(Some(&kek), args(), Some(PLAINTEXT)).unwrap();
+        assert_eq!(
+            ciphertext.as_ref(),
+            hex_literal::hex!(
+                "3a09c1e63fe23a7f66a59c7303837241e070b02619fc59c5214a22f08cd70795e73e9a"
+            )
+        );
+        assert_eq!(
+            op.results.0.as_ref(),
+            hex_literal::hex!("efa4b5ac0108e3816c5606479801bc04")
+        );
+
+        // The acceptor unwraps the CEK with the same KEK to decrypt
+        assert_eq!(
+            op.decrypt(Some(&kek), args(), Some(&ciphertext))
+                .unwrap()
+                .plaintext
+                .as_deref(),
+            Some(PLAINTEXT)
+        );
+        assert!(op
+            .decrypt(
+                Some(&KeyMaterial::SymmetricKey([0; 16].into())),
+                args(),
+                Some(&ciphertext)
+            )
+            .is_err());
+    }
+}
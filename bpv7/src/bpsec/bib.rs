@@ -51,6 +51,13 @@ impl Operation {
         }
     }
 
+    pub fn canonical_bytes(&self, args: OperationArgs) -> Result<Box<[u8]>, Error> {
+        match self {
+            Self::HMAC_SHA2(o) => o.canonical_bytes(&args, None),
+            Self::Unrecognised(v, _) => Err(Error::UnrecognisedContext(*v)),
+        }
+    }
+
     pub fn verify(
         &self,
         key: Option<&KeyMaterial>,
@@ -90,6 +97,67 @@ impl OperationSet {
     pub fn is_unsupported(&self) -> bool {
         self.operations.values().next().unwrap().is_unsupported()
     }
+
+    /* Originate a BIB covering every block in `targets`.  RFC 9173 requires all the
+     * targets of one security block to share a single set of parameters, and thus scope,
+     * so this mirrors the verification path, signing each target in turn */
+    #[allow(clippy::too_many_arguments)]
+    pub fn sign_hmac_sha2(
+        source: Eid,
+        parameters: bib_hmac_sha2::Parameters,
+        key: &KeyMaterial,
+        targets: &[u64],
+        bib_block: &block::Block,
+        bib_block_number: u64,
+        bundle: &Bundle,
+        bundle_data: &[u8],
+    ) -> Result<Self, Error> {
+        if targets.is_empty() {
+            return Err(Error::NoTargets);
+        }
+        if source.is_null() || matches!(source, Eid::LocalNode { .. }) {
+            return Err(Error::InvalidSecuritySource);
+        }
+
+        let parameters = Rc::new(parameters);
+        let mut operations = HashMap::new();
+        for target_number in targets {
+            let Some(target) = bundle.blocks.get(target_number) else {
+                return Err(Error::MissingSecurityTarget);
+            };
+
+            // BIBs must not target BPSec blocks, and we cannot sign plaintext we cannot see
+            if target.bcb.is_some()
+                || matches!(
+                    target.block_type,
+                    BlockType::BlockIntegrity | BlockType::BlockSecurity
+                )
+            {
+                return Err(Error::InvalidBIBTarget);
+            }
+
+            let mut op = Operation::HMAC_SHA2(bib_hmac_sha2::Operation::new(parameters.clone()));
+            op.sign(
+                Some(key),
+                OperationArgs {
+                    bpsec_source: &source,
+                    target,
+                    target_number: *target_number,
+                    source: bib_block,
+                    source_number: bib_block_number,
+                    bundle,
+                    primary_block: None,
+                    bundle_data,
+                },
+                None,
+            )?;
+
+            if operations.insert(*target_number, op).is_some() {
+                return Err(Error::DuplicateOpTarget);
+            }
+        }
+        Ok(Self { source, operations })
+    }
 }
 
 impl cbor::encode::ToCbor for OperationSet {
@@ -155,3 +223,212 @@ impl cbor::decode::FromCbor for OperationSet {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const KEY: [u8; 16] = hex_literal::hex!("1a2b1a2b1a2b1a2b1a2b1a2b1a2b1a2b");
+
+    fn key() -> KeyMaterial {
+        KeyMaterial::SymmetricKey(KEY.into())
+    }
+
+    fn lookup(source: &Eid, context: Context) -> Result<Option<KeyMaterial>, bpsec::Error> {
+        Ok((context == Context::BIB_HMAC_SHA2 && source == &"ipn:2.1".parse().unwrap()).then(key))
+    }
+
+    fn build() -> (Bundle, Vec<u8>) {
+        let (_, data) = Builder::new()
+            .source("ipn:2.1".parse().unwrap())
+            .destination("ipn:3.1".parse().unwrap())
+            .crc_type(CrcType::CRC32_CASTAGNOLI)
+            .add_extension_block(BlockType::BundleAge)
+            .data(cbor::encode::emit(300u64))
+            .build()
+            .add_payload_block(b"Ready to generate a 32-byte payload".to_vec())
+            .build();
+        match ValidBundle::parse(&data, lookup).unwrap() {
+            ValidBundle::Valid(bundle, _) => (bundle, data),
+            _ => panic!("Invalid bundle"),
+        }
+    }
+
+    #[test]
+    fn multiple_targets() {
+        let (bundle, data) = build();
+        let age_number = *bundle
+            .blocks
+            .iter()
+            .find(|(_, block)| block.block_type == BlockType::BundleAge)
+            .unwrap()
+            .0;
+
+        // One BIB covering both the payload and a metadata block
+        let data = Editor::new(&bundle, &data)
+            .sign_hmac_sha2("ipn:2.1".parse().unwrap(), &key(), &[1, age_number])
+            .unwrap()
+            .build();
+        let ValidBundle::Valid(bundle, _) = ValidBundle::parse(&data, lookup).unwrap() else {
+            panic!("Failed to verify multi-target BIB");
+        };
+
+        let bib_number = *bundle
+            .blocks
+            .iter()
+            .find(|(_, block)| block.block_type == BlockType::BlockIntegrity)
+            .unwrap()
+            .0;
+        let (bib_block, bib, _) = bundle
+            .parse_payload::<OperationSet>(&bib_number, None, &data)
+            .unwrap();
+        let mut targets = bib.operations.keys().copied().collect::<Vec<_>>();
+        targets.sort();
+        assert_eq!(targets, [1, age_number]);
+
+        // Both targets verify under the shared default scope, which includes the primary block
+        for (target_number, op) in &bib.operations {
+            let r = op
+                .verify(
+                    Some(&key()),
+                    OperationArgs {
+                        bpsec_source: &bib.source,
+                        target: bundle.blocks.get(target_number).unwrap(),
+                        target_number: *target_number,
+                        source: bib_block,
+                        source_number: bib_number,
+                        bundle: &bundle,
+                        primary_block: None,
+                        bundle_data: &data,
+                    },
+                    None,
+                )
+                .unwrap();
+            assert!(r.protects_primary_block && r.can_sign);
+        }
+
+        // And the wrong key fails them
+        assert!(matches!(
+            ValidBundle::parse(&data, |_, _| Ok(Some(KeyMaterial::SymmetricKey(
+                [0u8; 16].into()
+            ))))
+            .unwrap(),
+            ValidBundle::Invalid(..)
+        ));
+    }
+
+    #[test]
+    fn invalid_targets() {
+        let (bundle, data) = build();
+        let sign = |source: &str, targets: &[u64]| {
+            Editor::new(&bundle, &data)
+                .sign_hmac_sha2(source.parse().unwrap(), &key(), targets)
+                .map(|_| ())
+        };
+
+        assert!(matches!(
+            sign("ipn:2.1", &[]),
+            Err(crate::Error::InvalidBPSec(Error::NoTargets))
+        ));
+        assert!(matches!(
+            sign("ipn:2.1", &[1, 9]),
+            Err(crate::Error::InvalidBPSec(Error::MissingSecurityTarget))
+        ));
+        assert!(matches!(
+            sign("dtn:none", &[1]),
+            Err(crate::Error::InvalidBPSec(Error::InvalidSecuritySource))
+        ));
+    }
+
+    #[test]
+    fn sha_variants() {
+        let (bundle, data) = build();
+        for (variant, len) in [
+            (bib_hmac_sha2::ShaVariant::HMAC_256_256, 32),
+            (bib_hmac_sha2::ShaVariant::HMAC_384_384, 48),
+            (bib_hmac_sha2::ShaVariant::HMAC_512_512, 64),
+        ] {
+            let data = Editor::new(&bundle, &data)
+                .sign_hmac_sha2_variant("ipn:2.1".parse().unwrap(), variant, &key(), &[1])
+                .unwrap()
+                .build();
+            let ValidBundle::Valid(bundle, _) = ValidBundle::parse(&data, lookup).unwrap() else {
+                panic!("Failed to verify {variant:?}");
+            };
+
+            let bib_number = *bundle
+                .blocks
+                .iter()
+                .find(|(_, block)| block.block_type == BlockType::BlockIntegrity)
+                .unwrap()
+                .0;
+            let (_, bib, _) = bundle
+                .parse_payload::<OperationSet>(&bib_number, None, &data)
+                .unwrap();
+            let Some(Operation::HMAC_SHA2(op)) = bib.operations.get(&1) else {
+                panic!("Missing HMAC-SHA2 operation");
+            };
+            assert_eq!(op.parameters.variant, variant);
+            assert_eq!(op.results.0.len(), len);
+
+            // And the wrong key fails them
+            assert!(matches!(
+                ValidBundle::parse(&data, |_, _| Ok(Some(KeyMaterial::SymmetricKey(
+                    [0u8; 16].into()
+                ))))
+                .unwrap(),
+                ValidBundle::Invalid(..)
+            ));
+        }
+
+        // Unsupported variants are refused, rather than signed with a guess
+        assert!(matches!(
+            Editor::new(&bundle, &data).sign_hmac_sha2_variant(
+                "ipn:2.1".parse().unwrap(),
+                bib_hmac_sha2::ShaVariant::Unrecognised(99),
+                &key(),
+                &[1]
+            ),
+            Err(crate::Error::InvalidBPSec(Error::UnsupportedShaVariant(99)))
+        ));
+    }
+
+    #[test]
+    fn conflicting_operations() {
+        let (bundle, data) = build();
+        let conflicting = |data: &[u8]| {
+            let ValidBundle::Invalid(_, StatusReportReasonCode::ConflictingSecurityOperation, e) =
+                ValidBundle::parse(data, lookup).unwrap()
+            else {
+                panic!("Conflicting BIBs accepted");
+            };
+            assert!(matches!(
+                e.downcast_ref::<crate::Error>(),
+                Some(crate::Error::InvalidBPSec(Error::DuplicateOpTarget))
+            ));
+        };
+
+        // Two BIBs that both verify, targeting the payload
+        conflicting(
+            &Editor::new(&bundle, &data)
+                .sign_hmac_sha2("ipn:2.1".parse().unwrap(), &key(), &[1])
+                .unwrap()
+                .sign_hmac_sha2("ipn:2.1".parse().unwrap(), &key(), &[1])
+                .unwrap()
+                .build(),
+        );
+
+        // An unrecognised context that would otherwise be dropped still conflicts
+        let unrecognised = hex_literal::hex!("8101 18C8 00 82028202 01 8181820141 00");
+        conflicting(
+            &Editor::new(&bundle, &data)
+                .sign_hmac_sha2("ipn:2.1".parse().unwrap(), &key(), &[1])
+                .unwrap()
+                .add_extension_block(BlockType::BlockIntegrity)
+                .delete_block_on_failure(true)
+                .data(unrecognised.to_vec())
+                .build()
+                .build(),
+        );
+    }
+}
@@ -118,7 +118,7 @@ impl cbor::encode::ToCbor for &Parameters {
 }
 
 #[derive(Debug)]
-pub struct Results(Box<[u8]>);
+pub struct Results(pub(super) Box<[u8]>);
 
 impl Results {
     fn from_cbor(
@@ -158,22 +158,29 @@ impl cbor::encode::ToCbor for &Results {
     }
 }
 
-fn emit_data(mac: &mut impl hmac::Mac, data: &[u8]) {
+fn emit_data(update: &mut impl FnMut(&[u8]), data: &[u8]) {
     let mut header = cbor::encode::emit(data.len());
     if let Some(m) = header.first_mut() {
         *m |= 2 << 5;
     }
-    mac.update(&header);
-    mac.update(data);
+    update(&header);
+    update(data);
 }
 
 #[derive(Debug)]
 pub struct Operation {
-    parameters: Rc<Parameters>,
-    results: Results,
+    pub(super) parameters: Rc<Parameters>,
+    pub(super) results: Results,
 }
 
 impl Operation {
+    pub fn new(parameters: Rc<Parameters>) -> Self {
+        Self {
+            parameters,
+            results: Results(Box::default()),
+        }
+    }
+
     pub fn is_unsupported(&self) -> bool {
         matches!(self.parameters.variant, ShaVariant::Unrecognised(_))
     }
@@ -220,7 +227,7 @@ impl Operation {
                 .into_bytes()
                 .as_slice()
                 .into(),
-            ShaVariant::Unrecognised(_) => unreachable!(),
+            ShaVariant::Unrecognised(v) => return Err(Error::UnsupportedShaVariant(v)),
         };
         Ok(())
     }
@@ -242,49 +249,49 @@ impl Operation {
 
         let can_sign = match self.parameters.variant {
             ShaVariant::HMAC_256_256 => {
-                if self
-                    .calculate_hmac(
+                if !bpsec::verify_mac(
+                    self.calculate_hmac(
                         hmac::Hmac::<sha2::Sha256>::new_from_slice(&key)
                             .map_field_err("SHA-256 key")?,
                         &args,
                         payload_data,
                     )?
                     .into_bytes()
-                    .as_slice()
-                    != self.results.0.as_ref()
-                {
+                    .as_slice(),
+                    self.results.0.as_ref(),
+                ) {
                     return Err(bpsec::Error::IntegrityCheckFailed);
                 }
                 true
             }
             ShaVariant::HMAC_384_384 => {
-                if self
-                    .calculate_hmac(
+                if !bpsec::verify_mac(
+                    self.calculate_hmac(
                         hmac::Hmac::<sha2::Sha384>::new_from_slice(&key)
                             .map_field_err("SHA-384 key")?,
                         &args,
                         payload_data,
                     )?
                     .into_bytes()
-                    .as_slice()
-                    != self.results.0.as_ref()
-                {
+                    .as_slice(),
+                    self.results.0.as_ref(),
+                ) {
                     return Err(bpsec::Error::IntegrityCheckFailed);
                 }
                 true
             }
             ShaVariant::HMAC_512_512 => {
-                if self
-                    .calculate_hmac(
+                if !bpsec::verify_mac(
+                    self.calculate_hmac(
                         hmac::Hmac::<sha2::Sha512>::new_from_slice(&key)
                             .map_field_err("SHA-512 key")?,
                         &args,
                         payload_data,
                     )?
                     .into_bytes()
-                    .as_slice()
-                    != self.results.0.as_ref()
-                {
+                    .as_slice(),
+                    self.results.0.as_ref(),
+                ) {
                     return Err(bpsec::Error::IntegrityCheckFailed);
                 }
                 true
@@ -308,8 +315,29 @@ impl Operation {
     where
         M: hmac::Mac,
     {
+        self.emit_ippt(args, payload_data, |data| mac.update(data))?;
+        Ok(mac.finalize())
+    }
+
+    // The Integrity-Protected Plaintext the MAC is calculated over, as RFC 9173 Section 3.7
+    pub fn canonical_bytes(
+        &self,
+        args: &bib::OperationArgs,
+        payload_data: Option<&[u8]>,
+    ) -> Result<Box<[u8]>, Error> {
+        let mut ippt = Vec::new();
+        self.emit_ippt(args, payload_data, |data| ippt.extend_from_slice(data))?;
+        Ok(ippt.into())
+    }
+
+    fn emit_ippt(
+        &self,
+        args: &bib::OperationArgs,
+        payload_data: Option<&[u8]>,
+        mut update: impl FnMut(&[u8]),
+    ) -> Result<(), Error> {
         // Build IPT
-        mac.update(&cbor::encode::emit(&rfc9173::ScopeFlags {
+        update(&cbor::encode::emit(&rfc9173::ScopeFlags {
             include_primary_block: self.parameters.flags.include_primary_block,
             include_target_header: self.parameters.flags.include_target_header,
             include_security_header: self.parameters.flags.include_security_header,
@@ -319,9 +347,9 @@ impl Operation {
         if !matches!(args.target.block_type, BlockType::Primary) {
             if self.parameters.flags.include_primary_block {
                 if let Some(p) = args.primary_block {
-                    mac.update(p);
+                    update(p);
                 } else {
-                    mac.update(
+                    update(
                         args.bundle
                             .blocks
                             .get(&0)
@@ -336,7 +364,7 @@ impl Operation {
                 encoder.emit(args.target.block_type);
                 encoder.emit(args.target_number);
                 encoder.emit(&args.target.flags);
-                mac.update(&encoder.build());
+                update(&encoder.build());
             }
         }
 
@@ -345,15 +373,15 @@ impl Operation {
             encoder.emit(args.source.block_type);
             encoder.emit(args.source_number);
             encoder.emit(&args.source.flags);
-            mac.update(&encoder.build());
+            update(&encoder.build());
         }
 
         if matches!(args.target.block_type, BlockType::Primary) {
             if let Some(p) = args.primary_block {
-                emit_data(&mut mac, p);
+                emit_data(&mut update, p);
             } else {
                 emit_data(
-                    &mut mac,
+                    &mut update,
                     args.bundle
                         .blocks
                         .get(&0)
@@ -362,7 +390,7 @@ impl Operation {
                 );
             }
         } else if let Some(payload_data) = payload_data {
-            emit_data(&mut mac, payload_data);
+            emit_data(&mut update, payload_data);
         } else {
             let payload_data = args.target.payload(args.bundle_data);
             cbor::decode::parse_value(payload_data, |value, s, tags| {
@@ -377,24 +405,23 @@ impl Operation {
                         if let Some(m) = header.first_mut() {
                             *m |= 2 << 5;
                         }
-                        mac.update(&header);
+                        update(&header);
                         for d in data {
-                            mac.update(d);
+                            update(d);
                         }
                     }
                     cbor::decode::Value::Bytes(_) if s && tags.is_empty() => {
-                        mac.update(payload_data);
+                        update(payload_data);
                     }
                     cbor::decode::Value::Bytes(data) => {
-                        emit_data(&mut mac, data);
+                        emit_data(&mut update, data);
                     }
                     _ => unreachable!(),
                 }
                 Ok::<_, bpsec::Error>(())
             })?;
         }
-
-        Ok(mac.finalize())
+        Ok(())
     }
 
     pub fn emit_context(&self, encoder: &mut cbor::encode::Encoder, source: &Eid) {
@@ -65,9 +65,21 @@ pub enum Error {
     #[error("Integrity check failed")]
     IntegrityCheckFailed,
 
+    #[error("Unsupported HMAC-SHA2 variant {0}")]
+    UnsupportedShaVariant(u64),
+
+    #[error("Unsupported AES variant {0}")]
+    UnsupportedAesVariant(u64),
+
     #[error("No key material for security operation source {0}")]
     NoKey(Eid),
 
+    #[error("Key material is not a symmetric key")]
+    InvalidKeyMaterial,
+
+    #[error("Block {0} is not the target of a BCB")]
+    NotEncrypted(u64),
+
     #[error("Failed to parse {field}: {source}")]
     InvalidField {
         field: &'static str,
@@ -0,0 +1,44 @@
+use super::*;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SecurityBlockInfo {
+    pub block_number: u64,
+    pub kind: BlockType,
+    pub context: Context,
+    pub source: Eid,
+    pub targets: Vec<u64>,
+}
+
+impl Bundle {
+    /* List the BIBs and BCBs of a parsed bundle, with their security source and target block
+     * numbers, in block number order.  Nothing is verified or decrypted, so a BIB that is itself
+     * the target of a BCB cannot be read and is not listed, although the BCB lists it as a target */
+    pub fn security_blocks(&self, source_data: &[u8]) -> Vec<SecurityBlockInfo> {
+        let mut blocks = self
+            .blocks
+            .iter()
+            .filter(|(_, block)| {
+                matches!(
+                    block.block_type,
+                    BlockType::BlockIntegrity | BlockType::BlockSecurity
+                ) && block.bcb.is_none()
+            })
+            .filter_map(|(block_number, block)| {
+                let (_, asb, _) = self
+                    .parse_payload::<parse::AbstractSyntaxBlock>(block_number, None, source_data)
+                    .ok()?;
+                let mut targets = asb.results.into_keys().collect::<Vec<_>>();
+                targets.sort_unstable();
+                Some(SecurityBlockInfo {
+                    block_number: *block_number,
+                    kind: block.block_type,
+                    context: asb.context,
+                    source: asb.source,
+                    targets,
+                })
+            })
+            .collect::<Vec<_>>();
+        blocks.sort_unstable_by_key(|info| info.block_number);
+        blocks
+    }
+}
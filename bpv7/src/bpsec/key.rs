@@ -0,0 +1,80 @@
+use super::*;
+
+/* How the content encryption key (CEK) of a BCB reaches the security acceptor.
+ * Either the key material held for the security source is used directly as the CEK,
+ * or it is a key encryption key (KEK) and the CEK travels in the block, wrapped per RFC 3394 */
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum KeyAlgorithm {
+    #[default]
+    Direct,
+    A128KW,
+    A256KW,
+}
+
+impl KeyAlgorithm {
+    /* Wrap `cek` with `kek`, returning the 'Wrapped Key' security context parameter,
+     * or None if the CEK is used directly */
+    pub fn wrap(&self, kek: &KeyMaterial, cek: &[u8]) -> Result<Option<Box<[u8]>>, Error> {
+        let KeyMaterial::SymmetricKey(kek) = kek else {
+            return Err(Error::InvalidKeyMaterial);
+        };
+
+        match self {
+            Self::Direct => Ok(None),
+            Self::A128KW => aes_kw::KekAes128::try_from(kek.as_ref())
+                .and_then(|kek| kek.wrap_vec(cek))
+                .map(|v| Some(v.into())),
+            Self::A256KW => aes_kw::KekAes256::try_from(kek.as_ref())
+                .and_then(|kek| kek.wrap_vec(cek))
+                .map(|v| Some(v.into())),
+        }
+        .map_field_err("key encryption key")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn rfc9173_appendix_a_2_wrapped_key() {
+        assert_eq!(
+            KeyAlgorithm::A128KW
+                .wrap(
+                    &KeyMaterial::SymmetricKey(
+                        hex_literal::hex!("6162636465666768696a6b6c6d6e6f70").into()
+                    ),
+                    &hex_literal::hex!("71776572747975696f70617364666768"),
+                )
+                .unwrap()
+                .as_deref(),
+            Some(hex_literal::hex!("69c411276fecddc4780df42c8a2af89296fabf34d7fae700").as_ref())
+        );
+    }
+
+    #[test]
+    fn wrap() {
+        let cek = hex_literal::hex!(
+            "71776572747975696f70617364666768
+            71776572747975696f70617364666768"
+        );
+        let kek = KeyMaterial::SymmetricKey([0x5a; 32].into());
+
+        let wrapped = KeyAlgorithm::A256KW.wrap(&kek, &cek).unwrap();
+        assert_eq!(wrapped.as_ref().map(|w| w.len()), Some(cek.len() + 8));
+        assert_eq!(
+            rfc9173::unwrap_key(&Eid::Null, &kek, &wrapped)
+                .unwrap()
+                .as_ref(),
+            cek.as_ref()
+        );
+
+        // Direct mode carries no wrapped key, and the KEK must suit the algorithm
+        assert!(KeyAlgorithm::Direct.wrap(&kek, &cek).unwrap().is_none());
+        assert!(KeyAlgorithm::A128KW.wrap(&kek, &cek).is_err());
+        assert!(KeyAlgorithm::A256KW
+            .wrap(&KeyMaterial::PrivateKey, &cek)
+            .is_err());
+    }
+}
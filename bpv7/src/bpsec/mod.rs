@@ -1,18 +1,22 @@
 use super::*;
 use std::{collections::HashMap, ops::Range, rc::Rc};
+use subtle::ConstantTimeEq;
 use zeroize::{Zeroize, ZeroizeOnDrop, Zeroizing};
 
 pub mod bcb;
-mod bcb_aes_gcm;
+pub mod bcb_aes_gcm;
 pub mod bib;
-mod bib_hmac_sha2;
+pub mod bib_hmac_sha2;
 mod error;
+mod info;
+pub mod key;
 mod parse;
 mod rfc9173;
 
 use error::CaptureFieldErr;
 
 pub use error::Error;
+pub use info::SecurityBlockInfo;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 #[allow(clippy::upper_case_acronyms)]
@@ -68,3 +72,24 @@ pub enum KeyMaterial {
     SymmetricKey(Box<[u8]>),
     PrivateKey,
 }
+
+/* Compare two MACs or authentication tags in constant time.  The time taken depends only
+ * on the lengths of the inputs, so a failed comparison does not leak how many bytes matched */
+pub fn verify_mac(expected: &[u8], actual: &[u8]) -> bool {
+    expected.ct_eq(actual).into()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn constant_time_compare() {
+        assert!(verify_mac(&[1, 2, 3, 4], &[1, 2, 3, 4]));
+        assert!(verify_mac(&[], &[]));
+        assert!(!verify_mac(&[1, 2, 3, 4], &[1, 2, 3, 5]));
+        assert!(!verify_mac(&[1, 2, 3, 4], &[0, 2, 3, 4]));
+        assert!(!verify_mac(&[1, 2, 3, 4], &[1, 2, 3]));
+        assert!(!verify_mac(&[1, 2, 3], &[1, 2, 3, 4]));
+    }
+}
@@ -97,8 +97,12 @@ pub fn unwrap_key(
 mod test {
     use super::*;
 
-    fn do_test(data: &[u8], keys: &[(EidPattern, Context, Box<[u8]>)]) {
-        match ValidBundle::parse(data, |source, context| {
+    fn do_test(
+        data: &[u8],
+        keys: &[(EidPattern, Context, Box<[u8]>)],
+        expected: &[(u64, BlockType, &str, &[u64])],
+    ) {
+        let bundle = match ValidBundle::parse(data, |source, context| {
             for (eid, c2, key) in keys {
                 if &context == c2 && eid.is_match(source) {
                     return Ok(Some(KeyMaterial::SymmetricKey(key.clone())));
@@ -108,31 +112,96 @@ mod test {
         })
         .expect("Failed to parse")
         {
-            ValidBundle::Valid(..) => {}
+            ValidBundle::Valid(bundle, _) => bundle,
             ValidBundle::Rewritten(..) => panic!("Non-canonical bundle"),
             ValidBundle::Invalid(_, _, e) => panic!("Invalid bundle: {e}"),
+        };
+
+        let blocks = bundle.security_blocks(data);
+        assert_eq!(blocks.len(), expected.len());
+        for (info, (block_number, kind, source, targets)) in blocks.iter().zip(expected) {
+            assert_eq!(info.block_number, *block_number);
+            assert_eq!(info.kind, *kind);
+            assert_eq!(info.source, source.parse().unwrap());
+            assert_eq!(info.targets, *targets);
         }
     }
 
+    // Note: I've tweaked the creation timestamp to be valid, and added a CRC
+    const APPENDIX_A_1: [u8; 168] = hex_literal::hex!(
+        "9f89070001820282010282028202018202820201820118281a000f424042e4fe850b0200
+        005856810101018202820201828201078203008181820158403bdc69b3a34a2b5d3a
+        8554368bd1e808f606219d2a10a846eae3886ae4ecc83c4ee550fdfb1cc636b904e2
+        f1a73e303dcd4b6ccece003e95e8164dcc89a156e185010100005823526561647920
+        746f2067656e657261746520612033322d62797465207061796c6f6164ff"
+    );
+
     #[test]
     fn rfc9173_appendix_a_1() {
         do_test(
-            // Note: I've tweaked the creation timestamp to be valid, and added a CRC
-            &hex_literal::hex!(
-                "9f89070001820282010282028202018202820201820118281a000f424042e4fe850b0200
-                005856810101018202820201828201078203008181820158403bdc69b3a34a2b5d3a
-                8554368bd1e808f606219d2a10a846eae3886ae4ecc83c4ee550fdfb1cc636b904e2
-                f1a73e303dcd4b6ccece003e95e8164dcc89a156e185010100005823526561647920
-                746f2067656e657261746520612033322d62797465207061796c6f6164ff"
-            ),
+            &APPENDIX_A_1,
             &[(
                 "ipn:2.1".parse().unwrap(),
                 Context::BIB_HMAC_SHA2,
                 hex_literal::hex!("1a2b1a2b1a2b1a2b1a2b1a2b1a2b1a2b").into(),
             )],
+            &[(2, BlockType::BlockIntegrity, "ipn:2.1", &[1])],
         )
     }
 
+    #[test]
+    fn rfc9173_appendix_a_1_canonical_bytes() {
+        use hmac::Mac;
+
+        let ValidBundle::Valid(bundle, _) =
+            ValidBundle::parse(&APPENDIX_A_1, |_, _| Ok(None)).unwrap()
+        else {
+            panic!("Invalid bundle");
+        };
+
+        // The BIB has no scope flags set, so only covers the payload
+        let ippt = bundle.canonical_bytes(&APPENDIX_A_1, 2, 1).unwrap();
+        let payload = bundle
+            .blocks
+            .get(&1)
+            .unwrap()
+            .canonical_bytes(&APPENDIX_A_1)
+            .unwrap();
+        assert_eq!(ippt[0], 0);
+        assert_eq!(&ippt[1..], payload.as_ref());
+
+        // An external signer produces the same MAC as the vector
+        let mut mac = hmac::Hmac::<sha2::Sha512>::new_from_slice(&hex_literal::hex!(
+            "1a2b1a2b1a2b1a2b1a2b1a2b1a2b1a2b"
+        ))
+        .unwrap();
+        mac.update(&ippt);
+        assert_eq!(
+            mac.finalize().into_bytes().as_slice(),
+            hex_literal::hex!(
+                "3bdc69b3a34a2b5d3a8554368bd1e808f606219d2a10a846eae3886ae4ecc83c
+                4ee550fdfb1cc636b904e2f1a73e303dcd4b6ccece003e95e8164dcc89a156e1"
+            )
+        );
+
+        // Only BIBs and their targets can be asked for
+        assert!(bundle.canonical_bytes(&APPENDIX_A_1, 1, 1).is_err());
+        assert!(bundle.canonical_bytes(&APPENDIX_A_1, 2, 0).is_err());
+
+        // Block data that is not a byte string is an error, not a panic
+        let block = Block {
+            block_type: BlockType::Unrecognised(200),
+            flags: BlockFlags::default(),
+            crc_type: CrcType::None,
+            data_start: 0,
+            data_len: 1,
+            payload_offset: 0,
+            payload_len: 1,
+            bcb: None,
+        };
+        assert!(block.canonical_bytes(&[0x01]).is_err());
+    }
+
     #[test]
     fn rfc9173_appendix_a_2() {
         do_test(
@@ -149,6 +218,7 @@ mod test {
                 Context::BCB_AES_GCM,
                 hex_literal::hex!("6162636465666768696a6b6c6d6e6f70").into(),
             )],
+            &[(2, BlockType::BlockSecurity, "ipn:2.1", &[1])],
         )
     }
 
@@ -177,6 +247,10 @@ mod test {
                     hex_literal::hex!("71776572747975696f70617364666768").into(),
                 ),
             ],
+            &[
+                (3, BlockType::BlockIntegrity, "ipn:3.0", &[0, 2]),
+                (4, BlockType::BlockSecurity, "ipn:2.1", &[1]),
+            ],
         )
     }
 
@@ -209,6 +283,8 @@ mod test {
                     .into(),
                 ),
             ],
+            // The BIB is encrypted, so only the BCB can be listed
+            &[(2, BlockType::BlockSecurity, "ipn:2.1", &[1, 3])],
         )
     }
 }
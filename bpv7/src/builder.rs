@@ -7,12 +7,16 @@ const DEFAULT_LIFETIME: u64 = time::Duration::new(24 * 60 * 60, 0).whole_millise
 pub struct Builder {
     bundle_flags: BundleFlags,
     crc_type: CrcType,
+    primary_crc_type: Option<CrcType>,
     source: Eid,
     destination: Eid,
     report_to: Option<Eid>,
     lifetime: u64,
+    expiry: Option<time::OffsetDateTime>,
+    class_of_service: Option<ClassOfService>,
     payload: BlockTemplate,
     extensions: Vec<BlockTemplate>,
+    block_order: Vec<u64>,
 }
 
 impl Default for Builder {
@@ -20,16 +24,20 @@ impl Default for Builder {
         Self {
             bundle_flags: BundleFlags::default(),
             crc_type: DEFAULT_CRC_TYPE,
+            primary_crc_type: None,
             source: Eid::default(),
             destination: Eid::default(),
             report_to: None,
             lifetime: DEFAULT_LIFETIME,
+            expiry: None,
+            class_of_service: None,
             payload: BlockTemplate::new(
                 BlockType::Payload,
                 BlockFlags::default(),
                 DEFAULT_CRC_TYPE,
             ),
             extensions: Vec::new(),
+            block_order: Vec::new(),
         }
     }
 }
@@ -44,11 +52,20 @@ impl Builder {
         self
     }
 
+    /* The CRC type of the primary block, and of blocks added after this call
+     * that do not set their own */
     pub fn crc_type(mut self, crc_type: CrcType) -> Self {
         self.crc_type = crc_type;
         self
     }
 
+    /* The CRC type of the primary block alone, whatever crc_type() is set to.
+     * A primary block without a CRC must be protected by a BIB */
+    pub fn with_primary_crc(mut self, crc_type: CrcType) -> Self {
+        self.primary_crc_type = Some(crc_type);
+        self
+    }
+
     pub fn source(mut self, source: Eid) -> Self {
         self.source = source;
         self
@@ -66,6 +83,30 @@ impl Builder {
 
     pub fn lifetime(mut self, lifetime: u64) -> Self {
         self.lifetime = lifetime;
+        self.expiry = None;
+        self
+    }
+
+    /* Set the lifetime so the bundle expires at `expiry`, rather than after a duration.
+     * The lifetime is measured from the creation time the bundle is given when it is built */
+    pub fn with_expiry(mut self, expiry: time::OffsetDateTime) -> Self {
+        self.expiry = Some(expiry);
+        self
+    }
+
+    /* Add a Class of Service block, replicated in every fragment.
+     * It is added after any other extension blocks, whenever this is called */
+    pub fn class_of_service(mut self, class_of_service: ClassOfService) -> Self {
+        self.class_of_service = Some(class_of_service);
+        self
+    }
+
+    /* Emit the extension blocks with the block numbers in `order` first, in that order, rather than
+     * in ascending block number order, to build valid but unusual bundles for interop testing.
+     * Extension blocks are numbered from 2 in the order they are added.  The primary block is
+     * always emitted first, and the payload block last */
+    pub fn block_order(mut self, order: &[u64]) -> Self {
+        self.block_order = order.to_vec();
         self
     }
 
@@ -73,13 +114,68 @@ impl Builder {
         BlockBuilder::new(self, block_type)
     }
 
+    /* Add a block of any type, including types unknown to this crate, with caller-supplied block-type-specific data */
+    pub fn add_raw_block(
+        self,
+        block_type: u64,
+        flags: BlockFlags,
+        crc_type: CrcType,
+        data: &[u8],
+    ) -> Self {
+        let mut template = BlockTemplate::new(block_type.into(), flags, crc_type);
+        template.data(data.to_vec());
+        BlockBuilder {
+            builder: self,
+            template,
+        }
+        .build()
+    }
+
     pub fn add_payload_block(self, data: Vec<u8>) -> Self {
         self.add_extension_block(BlockType::Payload)
             .data(data)
             .build()
     }
 
-    pub fn build(mut self) -> (Bundle, Vec<u8>) {
+    /* Control and keepalive bundles may carry no application data, and RFC 9171 permits a
+     * zero-length payload.  This is also what is built if no payload block is added */
+    pub fn add_empty_payload_block(self) -> Self {
+        self.add_payload_block(Vec::new())
+    }
+
+    /* As build(), but refuse to build a bundle requesting status reports
+     * that could never be usefully delivered, as the report-to EID is null or a group,
+     * or a bundle that would expire before it was created */
+    pub fn try_build(self) -> Result<(Bundle, Vec<u8>), Error> {
+        bundle::check_report_to(
+            &self.bundle_flags,
+            self.report_to.as_ref().unwrap_or(&self.source),
+        )?;
+
+        let timestamp = CreationTimestamp::now();
+        if self.lifetime_from(&timestamp).is_none() {
+            return Err(Error::ExpiryBeforeCreation);
+        }
+        Ok(self.build_with_timestamp(timestamp))
+    }
+
+    // A bundle built with an expiry before its creation time has a lifetime of 0
+    pub fn build(self) -> (Bundle, Vec<u8>) {
+        self.build_with_timestamp(CreationTimestamp::now())
+    }
+
+    fn lifetime_from(&self, timestamp: &CreationTimestamp) -> Option<u64> {
+        let Some(expiry) = self.expiry else {
+            return Some(self.lifetime);
+        };
+        DtnTime::try_from(expiry)
+            .ok()?
+            .millisecs()
+            .checked_sub(timestamp.creation_time?.millisecs())
+    }
+
+    fn build_with_timestamp(mut self, timestamp: CreationTimestamp) -> (Bundle, Vec<u8>) {
+        let lifetime = self.lifetime_from(&timestamp).unwrap_or(0);
         let mut bundle = Bundle {
             report_to: if let Some(report_to) = &mut self.report_to {
                 std::mem::take(report_to)
@@ -88,25 +184,48 @@ impl Builder {
             },
             id: BundleId {
                 source: std::mem::take(&mut self.source),
-                timestamp: CreationTimestamp::now(),
+                timestamp,
                 ..Default::default()
             },
             flags: self.bundle_flags.clone(),
-            crc_type: self.crc_type,
+            crc_type: self.primary_crc_type.unwrap_or(self.crc_type),
             destination: std::mem::take(&mut self.destination),
-            lifetime: self.lifetime,
+            lifetime,
+            class_of_service: self.class_of_service,
             ..Default::default()
         };
 
+        if let Some(class_of_service) = self.class_of_service {
+            let mut template = BlockTemplate::new(
+                BlockType::ClassOfService,
+                BlockFlags::default(),
+                self.crc_type,
+            );
+            template.must_replicate(true);
+            template.data(cbor::encode::emit(class_of_service));
+            self.extensions.push(template);
+        }
+
+        let mut extensions = std::mem::take(&mut self.extensions)
+            .into_iter()
+            .zip(2u64..)
+            .collect::<Vec<_>>();
+        extensions.sort_by_key(|(_, block_number)| {
+            self.block_order
+                .iter()
+                .position(|n| n == block_number)
+                .unwrap_or(usize::MAX)
+        });
+
         let data = cbor::encode::emit_array(None, |a| {
             // Emit primary block
             bundle.emit_primary_block(a);
 
             // Emit extension blocks
-            for (block_number, block) in self.extensions.into_iter().enumerate() {
+            for (block, block_number) in extensions {
                 bundle
                     .blocks
-                    .insert(block_number as u64, block.build(block_number as u64 + 2, a));
+                    .insert(block_number, block.build(block_number, a));
             }
 
             // Emit payload
@@ -194,6 +313,10 @@ impl BlockTemplate {
         self.block_type
     }
 
+    pub fn flags(&mut self, flags: BlockFlags) {
+        self.flags = flags;
+    }
+
     pub fn must_replicate(&mut self, must_replicate: bool) {
         self.flags.must_replicate = must_replicate;
     }
@@ -219,6 +342,20 @@ impl BlockTemplate {
         self.data = data;
     }
 
+    /* Would this template emit exactly the same block as `block`, so the original bytes can be reused? */
+    pub fn is_unchanged(&self, block: &Block, source_data: &[u8]) -> bool {
+        self.block_type == block.block_type
+            && self.flags == block.flags
+            && self.crc_type == block.crc_type
+            && cbor::decode::parse_value(block.payload(source_data), |value, _, _| {
+                Ok::<_, cbor::decode::Error>(match value {
+                    cbor::decode::Value::Bytes(data) => data == self.data.as_slice(),
+                    _ => false,
+                })
+            })
+            .is_ok_and(|(unchanged, _)| unchanged)
+    }
+
     pub fn build(self, block_number: u64, array: &mut cbor::encode::Array) -> Block {
         let mut block = Block {
             block_type: self.block_type,
@@ -243,3 +380,343 @@ fn test() {
         .report_to("ipn:3.0".parse().unwrap())
         .build();
 }
+
+#[test]
+fn raw_block() {
+    fn parse(data: &[u8]) -> (Bundle, bool) {
+        let ValidBundle::Valid(bundle, report_unsupported) =
+            ValidBundle::parse(data, |_, _| Ok(None)).unwrap()
+        else {
+            panic!("Invalid bundle");
+        };
+        (bundle, report_unsupported)
+    }
+
+    fn check(bundle: &Bundle, data: &[u8]) {
+        let block = bundle
+            .blocks
+            .values()
+            .find(|block| block.block_type == BlockType::Unrecognised(200))
+            .expect("Missing raw block");
+        assert!(block.flags.must_replicate);
+        assert!(block.flags.report_on_failure);
+        assert!(!block.flags.delete_bundle_on_failure);
+        assert_eq!(block.crc_type, CrcType::CRC16_X25);
+        cbor::decode::parse_value(block.payload(data), |v, _, _| match v {
+            cbor::decode::Value::Bytes(v) => {
+                assert_eq!(v, &[0x82, 0x01, 0x02]);
+                Ok::<_, cbor::decode::Error>(())
+            }
+            _ => panic!("Block data is not a byte string"),
+        })
+        .unwrap();
+    }
+
+    let (_, data) = Builder::new()
+        .source("ipn:1.1".parse().unwrap())
+        .destination("ipn:2.1".parse().unwrap())
+        .add_raw_block(
+            200,
+            BlockFlags {
+                must_replicate: true,
+                report_on_failure: true,
+                ..Default::default()
+            },
+            CrcType::CRC16_X25,
+            &[0x82, 0x01, 0x02],
+        )
+        .add_payload_block(Vec::new())
+        .build();
+
+    let (bundle, report_unsupported) = parse(&data);
+    assert!(report_unsupported);
+    check(&bundle, &data);
+
+    // And survives a rebuild
+    let data = Editor::new(&bundle, &data)
+        .previous_node(&"ipn:3.0".parse().unwrap())
+        .build();
+    let (bundle, _) = parse(&data);
+    check(&bundle, &data);
+}
+
+#[test]
+fn null_report_to() {
+    let builder = || {
+        Builder::new()
+            .flags(BundleFlags {
+                delete_report_requested: true,
+                ..Default::default()
+            })
+            .source("ipn:1.1".parse().unwrap())
+            .destination("ipn:2.1".parse().unwrap())
+            .add_payload_block(Vec::new())
+    };
+
+    assert!(matches!(
+        builder().report_to(Eid::Null).try_build(),
+        Err(Error::NullReportTo)
+    ));
+
+    // Reports to a group would multiply
+    assert!(matches!(
+        builder()
+            .report_to("dtn://group/~all".parse().unwrap())
+            .try_build(),
+        Err(Error::NonSingletonReportTo(_))
+    ));
+
+    // Unset report-to falls back to the source
+    assert!(builder().try_build().is_ok());
+    assert!(matches!(
+        builder().source(Eid::Null).try_build(),
+        Err(Error::NullReportTo)
+    ));
+
+    // No reports, no problem
+    assert!(Builder::new()
+        .destination("ipn:2.1".parse().unwrap())
+        .add_payload_block(Vec::new())
+        .try_build()
+        .is_ok());
+}
+
+#[test]
+fn empty_payload() {
+    let check = |data: &[u8]| {
+        let ValidBundle::Valid(bundle, _) = ValidBundle::parse(data, |_, _| Ok(None)).unwrap()
+        else {
+            panic!("Invalid bundle");
+        };
+        match bundle.block_payload(1, data, |_, _| Ok(None)).unwrap() {
+            Some(Payload::Range(range)) => assert!(range.is_empty()),
+            _ => panic!("Missing payload"),
+        }
+        assert!(roundtrip_check(data, |_, _| Ok(None)).is_ok());
+    };
+
+    let builder = || {
+        Builder::new()
+            .source("ipn:1.1".parse().unwrap())
+            .destination("ipn:2.1".parse().unwrap())
+    };
+
+    let (bundle, data) = builder().add_empty_payload_block().build();
+    check(&data);
+
+    // No payload block at all builds the same blocks, the creation timestamps may differ
+    let (implicit_bundle, implicit) = builder().build();
+    check(&implicit);
+    assert_eq!(bundle.blocks.len(), implicit_bundle.blocks.len());
+    assert_eq!(
+        bundle.blocks.get(&1).map(|b| b.data_len),
+        implicit_bundle.blocks.get(&1).map(|b| b.data_len)
+    );
+}
+
+#[test]
+fn block_order() {
+    // The order extension blocks were emitted in, by their position in the data
+    fn emitted(bundle: &Bundle) -> Vec<u64> {
+        let mut blocks = bundle
+            .blocks
+            .iter()
+            .filter(|(block_number, _)| **block_number > 1)
+            .collect::<Vec<_>>();
+        blocks.sort_by_key(|(_, block)| block.data_start);
+        blocks.into_iter().map(|(n, _)| *n).collect()
+    }
+
+    let builder = || {
+        Builder::new()
+            .source("ipn:1.1".parse().unwrap())
+            .destination("ipn:2.1".parse().unwrap())
+            .add_extension_block(BlockType::HopCount)
+            .data(cbor::encode::emit(&HopInfo { limit: 5, count: 0 }))
+            .build()
+            .add_extension_block(BlockType::BundleAge)
+            .data(cbor::encode::emit(0u64))
+            .build()
+            .add_extension_block(BlockType::PreviousNode)
+            .data(cbor::encode::emit(&"ipn:3.0".parse::<Eid>().unwrap()))
+            .build()
+    };
+
+    // RFC 9171 does not constrain the order of extension blocks, so any order is valid
+    let (bundle, data) = builder()
+        .block_order(&[4, 2])
+        .add_payload_block(vec![1, 2, 3])
+        .build();
+    assert_eq!(emitted(&bundle), [4, 2, 3]);
+    let ValidBundle::Valid(bundle, _) = ValidBundle::parse(&data, |_, _| Ok(None)).unwrap() else {
+        panic!("Out of order bundle not valid");
+    };
+    assert_eq!(emitted(&bundle), [4, 2, 3]);
+
+    // But a rewritten bundle has its blocks restored to block number order
+    let (_, data) = builder()
+        .add_raw_block(
+            200,
+            BlockFlags {
+                delete_block_on_failure: true,
+                ..Default::default()
+            },
+            CrcType::CRC32_CASTAGNOLI,
+            &[],
+        )
+        .block_order(&[5, 4, 3, 2])
+        .add_payload_block(vec![1, 2, 3])
+        .build();
+    let ValidBundle::Rewritten(bundle, data, _) =
+        ValidBundle::parse(&data, |_, _| Ok(None)).unwrap()
+    else {
+        panic!("Bundle with a discardable block not rewritten");
+    };
+    assert_eq!(emitted(&bundle), [2, 3, 4]);
+    assert!(bundle
+        .hop_count
+        .is_some_and(|hop_info| hop_info.limit == 5 && hop_info.count == 0));
+    assert_eq!(bundle.age, Some(0));
+    assert_eq!(bundle.previous_node, Some("ipn:3.0".parse().unwrap()));
+    let ValidBundle::Valid(bundle, _) = ValidBundle::parse(&data, |_, _| Ok(None)).unwrap() else {
+        panic!("Rewritten bundle not valid");
+    };
+    assert_eq!(emitted(&bundle), [2, 3, 4]);
+}
+
+#[test]
+fn primary_crc() {
+    for crc_type in [CrcType::CRC16_X25, CrcType::CRC32_CASTAGNOLI] {
+        let (_, data) = Builder::new()
+            .source("ipn:1.1".parse().unwrap())
+            .destination("ipn:2.1".parse().unwrap())
+            .with_primary_crc(crc_type)
+            .crc_type(CrcType::None)
+            .add_payload_block(b"Hello".to_vec())
+            .build();
+
+        // Parsing checks the CRC
+        let ValidBundle::Valid(bundle, _) = ValidBundle::parse(&data, |_, _| Ok(None)).unwrap()
+        else {
+            panic!("Invalid bundle");
+        };
+        assert_eq!(bundle.crc_type, crc_type);
+        assert_eq!(bundle.blocks[&0].crc_type, crc_type);
+        assert_eq!(bundle.blocks[&1].crc_type, CrcType::None);
+
+        // Corrupt the CRC value, the end of the primary block
+        let mut data = data;
+        let primary = &bundle.blocks[&0];
+        data[primary.data_start + primary.data_len - 1] ^= 0xFF;
+        assert!(!matches!(
+            ValidBundle::parse(&data, |_, _| Ok(None)),
+            Ok(ValidBundle::Valid(..))
+        ));
+    }
+
+    // Without a CRC or a BIB the primary block is unprotected
+    let (bundle, data) = Builder::new()
+        .source("ipn:1.1".parse().unwrap())
+        .destination("ipn:2.1".parse().unwrap())
+        .with_primary_crc(CrcType::None)
+        .add_payload_block(b"Hello".to_vec())
+        .build();
+    assert_eq!(bundle.crc_type, CrcType::None);
+    assert!(matches!(
+        ValidBundle::parse(&data, |_, _| Ok(None)).unwrap(),
+        ValidBundle::Invalid(_, _, e) if matches!(e.downcast_ref::<Error>(), Some(Error::MissingIntegrityCheck))
+    ));
+}
+
+#[test]
+fn expiry() {
+    let builder = || {
+        Builder::new()
+            .source("ipn:1.1".parse().unwrap())
+            .destination("ipn:2.1".parse().unwrap())
+            .add_payload_block(Vec::new())
+    };
+
+    let expiry = time::OffsetDateTime::now_utc() + time::Duration::hours(1);
+    let (bundle, data) = builder().with_expiry(expiry).try_build().unwrap();
+    let creation_time = bundle.id.timestamp.creation_time.unwrap();
+    assert_eq!(
+        bundle.lifetime,
+        DtnTime::try_from(expiry).unwrap().millisecs() - creation_time.millisecs()
+    );
+    assert!(bundle.lifetime <= 60 * 60 * 1000);
+    let ValidBundle::Valid(parsed, _) = ValidBundle::parse(&data, |_, _| Ok(None)).unwrap() else {
+        panic!("Invalid bundle")
+    };
+    assert_eq!(parsed.lifetime, bundle.lifetime);
+
+    // The last of lifetime() and with_expiry() wins
+    let (bundle, _) = builder().with_expiry(expiry).lifetime(1000).build();
+    assert_eq!(bundle.lifetime, 1000);
+
+    let expired = time::OffsetDateTime::now_utc() - time::Duration::seconds(1);
+    assert!(matches!(
+        builder().with_expiry(expired).try_build(),
+        Err(Error::ExpiryBeforeCreation)
+    ));
+    assert_eq!(builder().with_expiry(expired).build().0.lifetime, 0);
+}
+
+#[test]
+fn class_of_service() {
+    let builder = || {
+        Builder::new()
+            .source("ipn:1.1".parse().unwrap())
+            .destination("ipn:2.1".parse().unwrap())
+            .add_payload_block(vec![1, 2, 3])
+    };
+
+    let (bundle, data) = builder()
+        .class_of_service(ClassOfService::Expedited)
+        .build();
+    assert_eq!(bundle.class_of_service, Some(ClassOfService::Expedited));
+    let ValidBundle::Valid(bundle, _) = ValidBundle::parse(&data, |_, _| Ok(None)).unwrap() else {
+        panic!("Invalid bundle")
+    };
+    assert_eq!(bundle.class_of_service, Some(ClassOfService::Expedited));
+    assert!(bundle
+        .blocks
+        .values()
+        .any(|block| block.block_type == BlockType::ClassOfService && block.flags.must_replicate));
+
+    let (_, data) = builder().build();
+    let ValidBundle::Valid(bundle, _) = ValidBundle::parse(&data, |_, _| Ok(None)).unwrap() else {
+        panic!("Invalid bundle")
+    };
+    assert_eq!(bundle.class_of_service, None);
+
+    /* Another use of the experimental block type, or an unknown class of service, is not silently
+     * treated as a class of service, and the bundle is left as it is */
+    for (bundle, data) in [
+        builder()
+            .add_extension_block(BlockType::ClassOfService)
+            .data(cbor::encode::emit(3u64))
+            .build()
+            .build(),
+        builder()
+            .add_extension_block(BlockType::ClassOfService)
+            .data(vec![0x40])
+            .build()
+            .build(),
+        builder()
+            .add_extension_block(BlockType::ClassOfService)
+            .data(cbor::encode::emit(ClassOfService::Bulk))
+            .build()
+            .add_extension_block(BlockType::ClassOfService)
+            .data(cbor::encode::emit(ClassOfService::Expedited))
+            .build()
+            .build(),
+    ] {
+        let ValidBundle::Valid(parsed, _) = ValidBundle::parse(&data, |_, _| Ok(None)).unwrap()
+        else {
+            panic!("Invalid bundle")
+        };
+        assert_eq!(parsed.class_of_service, None);
+        assert_eq!(parsed.blocks.len(), bundle.blocks.len());
+    }
+}
@@ -59,12 +59,52 @@ pub struct Bundle {
     pub previous_node: Option<Eid>,
     pub age: Option<u64>,
     pub hop_count: Option<HopInfo>,
+    pub class_of_service: Option<ClassOfService>,
 
     // The extension blocks
     pub blocks: std::collections::HashMap<u64, Block>,
 }
 
 impl Bundle {
+    /* Can the status reports requested by this bundle be sent anywhere useful.
+     * A null report-to cannot receive them, and reporting to a group multiplies every report */
+    pub fn check_report_to(&self) -> Result<(), Error> {
+        check_report_to(&self.flags, &self.report_to)
+    }
+
+    /* A bundle is looping if it has been handed back to us by our neighbour,
+     * or if it has been forwarded so many times it has exhausted its hop limit */
+    pub fn detect_loop(&self, our_node_ids: &[Eid]) -> bool {
+        if let Some(previous_node) = &self.previous_node {
+            if our_node_ids
+                .iter()
+                .any(|node_id| is_same_node(node_id, previous_node))
+            {
+                return true;
+            }
+        }
+        self.hop_count
+            .as_ref()
+            .is_some_and(|hop_info| hop_info.count > hop_info.limit)
+    }
+
+    /* Iterate the blocks in canonical order, as RFC 9171 lays them out: the primary block first,
+     * then the extension blocks by block number, and the payload block last */
+    pub fn blocks_in_order(&self) -> impl Iterator<Item = (&u64, &Block)> {
+        let mut extension_blocks = self
+            .blocks
+            .iter()
+            .filter(|(block_number, _)| **block_number > 1)
+            .collect::<Vec<_>>();
+        extension_blocks.sort_unstable_by_key(|(block_number, _)| **block_number);
+
+        self.blocks
+            .get_key_value(&0)
+            .into_iter()
+            .chain(extension_blocks)
+            .chain(self.blocks.get_key_value(&1))
+    }
+
     pub fn emit_primary_block(&mut self, array: &mut cbor::encode::Array) {
         let data_start = array.offset();
         let data = primary_block::PrimaryBlock::emit(self);
@@ -91,7 +131,44 @@ impl Bundle {
         );
     }
 
-    fn parse_payload<T>(
+    /* The exact bytes that the BIB `bib_number` protects the block `target_number` with, respecting
+     * the scope flags of the BIB, so an external signer can calculate the MAC without the key.
+     * A BIB that is the target of a BCB must be decrypted first, so is not supported */
+    pub fn canonical_bytes(
+        &self,
+        source_data: &[u8],
+        bib_number: u64,
+        target_number: u64,
+    ) -> Result<Box<[u8]>, Error> {
+        if !self.blocks.get(&bib_number).is_some_and(|block| {
+            block.block_type == BlockType::BlockIntegrity && block.bcb.is_none()
+        }) {
+            return Err(Error::Unsupported(bib_number));
+        }
+
+        let (bib_block, bib, _) =
+            self.parse_payload::<bpsec::bib::OperationSet>(&bib_number, None, source_data)?;
+        let (Some(op), Some(target)) = (
+            bib.operations.get(&target_number),
+            self.blocks.get(&target_number),
+        ) else {
+            return Err(bpsec::Error::MissingSecurityTarget.into());
+        };
+
+        op.canonical_bytes(bpsec::bib::OperationArgs {
+            bpsec_source: &bib.source,
+            target,
+            target_number,
+            source: bib_block,
+            source_number: bib_number,
+            bundle: self,
+            primary_block: None,
+            bundle_data: source_data,
+        })
+        .map_err(Into::into)
+    }
+
+    pub(crate) fn parse_payload<T>(
         &self,
         block_number: &u64,
         decrypted_data: Option<&(Box<[u8]>, bool)>,
@@ -135,7 +212,7 @@ impl Bundle {
 
     /* Refactoring this huge function into parts doesn't really help readability,
      * and seems to drive the borrow checker insane */
-    #[allow(clippy::type_complexity)]
+    #[allow(clippy::type_complexity, clippy::too_many_arguments)]
     fn parse_blocks(
         &mut self,
         canonical_bundle: bool,
@@ -143,15 +220,20 @@ impl Bundle {
         blocks: &mut cbor::decode::Array,
         mut offset: usize,
         source_data: &[u8],
+        limits: &ParseLimits,
         keys: &mut impl KeyCache,
+        scratch: &mut ParseScratch,
     ) -> Result<(Option<Box<[u8]>>, bool), Error> {
         let mut last_block_number = 0;
         let mut noncanonical_blocks: HashMap<u64, bool> = HashMap::new();
-        let mut blocks_to_check = HashMap::new();
+        let blocks_to_check = &mut scratch.blocks_to_check;
+        blocks_to_check.clear();
         let mut blocks_to_remove = HashSet::new();
         let mut report_unsupported = false;
+        let mut unsupported = None;
         let mut bcbs_to_check = Vec::new();
         let mut bibs_to_check = HashSet::new();
+        let mut ambiguous_class_of_service = false;
 
         // Parse the blocks and build a map
         while let Some((mut block, canonical, block_len)) =
@@ -178,6 +260,16 @@ impl Bundle {
                         return Err(Error::DuplicateBlocks(block.block.block_type));
                     }
                 }
+                BlockType::ClassOfService => {
+                    /* The type is from the experimental range, so another implementation may use it
+                     * differently.  If there is more than one, none of them is a class of service */
+                    if blocks_to_check
+                        .insert(block.block.block_type, block.number)
+                        .is_some()
+                    {
+                        ambiguous_class_of_service = true;
+                    }
+                }
                 BlockType::BlockIntegrity => {
                     bibs_to_check.insert(block.number);
                 }
@@ -186,7 +278,8 @@ impl Bundle {
                 }
                 BlockType::Unrecognised(_) => {
                     if block.block.flags.delete_bundle_on_failure {
-                        return Err(Error::Unsupported(block.number));
+                        // Finish parsing first, so the bundle can be reported
+                        unsupported.get_or_insert(block.number);
                     }
 
                     if block.block.flags.report_on_failure {
@@ -204,11 +297,18 @@ impl Bundle {
             if self.blocks.insert(block.number, block.block).is_some() {
                 return Err(Error::DuplicateBlockNumber(block.number));
             }
+            if self.blocks.len() > limits.max_blocks {
+                return Err(Error::TooManyBlocks(limits.max_blocks));
+            }
 
             last_block_number = block.number;
             offset += block_len;
         }
 
+        if let Some(block_number) = unsupported {
+            return Err(Error::Unsupported(block_number));
+        }
+
         // Check the last block is the payload
         if let Some(payload_block_number) = blocks_to_check.remove(&BlockType::Payload) {
             if payload_block_number != last_block_number {
@@ -249,6 +349,16 @@ impl Bundle {
                 return Err(bpsec::Error::BCBDeleteFlag.into());
             }
 
+            // Claim the targets first, so conflicting operations are rejected whatever the order
+            for target_number in bcb.operations.keys() {
+                if bcb_targets
+                    .insert(*target_number, bcb_block_number)
+                    .is_some()
+                {
+                    return Err(bpsec::Error::DuplicateOpTarget.into());
+                }
+            }
+
             if bcb.is_unsupported() {
                 if bcb_block.flags.delete_bundle_on_failure {
                     return Err(Error::Unsupported(bcb_block_number));
@@ -266,12 +376,6 @@ impl Bundle {
             // Decrypt targets
             let mut targets_to_drop = HashSet::new();
             for (target_number, op) in &bcb.operations {
-                if bcb_targets
-                    .insert(*target_number, bcb_block_number)
-                    .is_some()
-                {
-                    return Err(bpsec::Error::DuplicateOpTarget.into());
-                }
                 let Some(target_block) = self.blocks.get(target_number) else {
                     return Err(bpsec::Error::MissingSecurityTarget.into());
                 };
@@ -350,7 +454,7 @@ impl Bundle {
         }
 
         // Now parse all the non-BIBs we need to check
-        for (block_type, block_number) in blocks_to_check {
+        for (block_type, block_number) in blocks_to_check.drain() {
             if !match block_type {
                 BlockType::PreviousNode => {
                     let (_, v, s) = self
@@ -385,6 +489,20 @@ impl Bundle {
                     self.hop_count = Some(v);
                     s
                 }
+                BlockType::ClassOfService if !ambiguous_class_of_service => {
+                    // Leave a block we cannot decode untouched, as if it were unrecognised
+                    match self.parse_payload(
+                        &block_number,
+                        decrypted_data.get(&block_number),
+                        source_data,
+                    ) {
+                        Ok((_, v, s)) => {
+                            self.class_of_service = Some(v);
+                            s
+                        }
+                        Err(_) => true,
+                    }
+                }
                 _ => true,
             } {
                 noncanonical_blocks.insert(block_number, true);
@@ -408,6 +526,13 @@ impl Bundle {
                 )
                 .map_field_err("BPSec integrity extension block")?;
 
+            // Even a BIB we are about to discard must not conflict with another
+            for target_number in bib.operations.keys() {
+                if !bib_targets.insert(*target_number) {
+                    return Err(bpsec::Error::DuplicateOpTarget.into());
+                }
+            }
+
             if bib.is_unsupported() {
                 if bib_block.flags.delete_bundle_on_failure {
                     return Err(Error::Unsupported(bib_block_number));
@@ -429,9 +554,6 @@ impl Bundle {
 
             // Check targets
             for (target_number, op) in &bib.operations {
-                if !bib_targets.insert(*target_number) {
-                    return Err(bpsec::Error::DuplicateOpTarget.into());
-                }
                 let Some(target_block) = self.blocks.get(target_number) else {
                     return Err(bpsec::Error::MissingSecurityTarget.into());
                 };
@@ -560,6 +682,13 @@ impl Bundle {
                         );
                         false
                     }
+                    BlockType::ClassOfService => {
+                        new_payloads.insert(
+                            *block_number,
+                            cbor::encode::emit(self.class_of_service.unwrap()).into(),
+                        );
+                        false
+                    }
                     BlockType::BlockIntegrity | BlockType::BlockSecurity => {
                         /* ignore for now  */
                         true
@@ -636,25 +765,30 @@ impl Bundle {
             // Stash payload block for last
             let mut payload_block = self.blocks.remove(&1).unwrap();
 
-            // Emit blocks
-            self.blocks.retain(|block_number, block| {
-                if *block_number == 0 {
-                    return true;
-                }
-                if blocks_to_remove.contains(block_number) {
-                    return false;
+            // Emit extension blocks in block number order
+            let mut block_numbers = self
+                .blocks
+                .keys()
+                .filter(|block_number| **block_number > 1)
+                .copied()
+                .collect::<Vec<_>>();
+            block_numbers.sort_unstable();
+            for block_number in block_numbers {
+                if blocks_to_remove.contains(&block_number) {
+                    self.blocks.remove(&block_number);
+                    continue;
                 }
 
-                if let Some(data) = new_payloads.remove(block_number) {
-                    block.emit(*block_number, &data, a);
-                } else if noncanonical_blocks.remove(block_number).is_some() {
-                    block.rewrite(*block_number, a, source_data);
+                let block = self.blocks.get_mut(&block_number).unwrap();
+                if let Some(data) = new_payloads.remove(&block_number) {
+                    block.emit(block_number, &data, a);
+                } else if noncanonical_blocks.remove(&block_number).is_some() {
+                    block.rewrite(block_number, a, source_data);
                 } else {
                     // Copy canonical blocks verbatim
                     block.write(source_data, a);
                 }
-                true
-            });
+            }
 
             // Emit payload block
             if noncanonical_blocks.remove(&1).is_some() {
@@ -668,6 +802,98 @@ impl Bundle {
     }
 }
 
+pub(crate) fn check_report_to(flags: &BundleFlags, report_to: &Eid) -> Result<(), Error> {
+    if !flags.requests_reports() {
+        Ok(())
+    } else if report_to.is_null() {
+        Err(Error::NullReportTo)
+    } else if !report_to.is_singleton() {
+        Err(Error::NonSingletonReportTo(report_to.clone()))
+    } else {
+        Ok(())
+    }
+}
+
+fn is_same_node(node_id: &Eid, eid: &Eid) -> bool {
+    match (node_id, eid) {
+        (
+            Eid::Ipn {
+                allocator_id: a1,
+                node_number: n1,
+                ..
+            }
+            | Eid::LegacyIpn {
+                allocator_id: a1,
+                node_number: n1,
+                ..
+            },
+            Eid::Ipn {
+                allocator_id: a2,
+                node_number: n2,
+                ..
+            }
+            | Eid::LegacyIpn {
+                allocator_id: a2,
+                node_number: n2,
+                ..
+            },
+        ) => a1 == a2 && n1 == n2,
+        (Eid::Dtn { node_name: n1, .. }, Eid::Dtn { node_name: n2, .. }) => n1 == n2,
+        _ => node_id == eid,
+    }
+}
+
+/* What the parser does with a bundle requesting status reports to a null or non-singleton
+ * report-to EID, see Bundle::check_report_to() */
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ReportToPolicy {
+    // The bundle is valid, and it is up to the caller whether to report on it
+    #[default]
+    Accept,
+    // The bundle is invalid
+    Reject,
+}
+
+/* Caps on the shape of a bundle, so a hostile peer cannot make the parser, and everything
+ * downstream of it, do unbounded work.  The primary block counts towards max_blocks */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ParseLimits {
+    pub max_blocks: usize,
+    pub report_to: ReportToPolicy,
+}
+
+impl Default for ParseLimits {
+    fn default() -> Self {
+        Self {
+            max_blocks: 256,
+            report_to: ReportToPolicy::default(),
+        }
+    }
+}
+
+/* Allocations reused across parses, for callers parsing many bundles in a row, such as
+ * when recovering a store.  Hand each bundle back with recycle() once finished with it,
+ * and the next parse reuses its block map */
+#[derive(Debug, Default)]
+pub struct ParseScratch {
+    blocks: HashMap<u64, Block>,
+    blocks_to_check: HashMap<BlockType, u64>,
+}
+
+impl ParseScratch {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn recycle(&mut self, bundle: Bundle) {
+        // Keep whichever block map has grown the largest
+        if bundle.blocks.capacity() > self.blocks.capacity() {
+            self.blocks = bundle.blocks;
+            self.blocks.clear();
+        }
+    }
+}
+
 // For parsing a bundle plus 'minimal viability'
 #[derive(Debug)]
 pub enum ValidBundle {
@@ -684,6 +910,24 @@ impl ValidBundle {
     pub fn parse(
         data: &[u8],
         f: impl FnMut(&Eid, bpsec::Context) -> Result<Option<bpsec::KeyMaterial>, bpsec::Error>,
+    ) -> Result<Self, Error> {
+        Self::parse_with_limits(data, &ParseLimits::default(), f)
+    }
+
+    pub fn parse_with_limits(
+        data: &[u8],
+        limits: &ParseLimits,
+        f: impl FnMut(&Eid, bpsec::Context) -> Result<Option<bpsec::KeyMaterial>, bpsec::Error>,
+    ) -> Result<Self, Error> {
+        Self::parse_with_scratch(data, limits, &mut ParseScratch::default(), f)
+    }
+
+    // As parse_with_limits(), reusing the allocations in `scratch`
+    pub fn parse_with_scratch(
+        data: &[u8],
+        limits: &ParseLimits,
+        scratch: &mut ParseScratch,
+        f: impl FnMut(&Eid, bpsec::Context) -> Result<Option<bpsec::KeyMaterial>, bpsec::Error>,
     ) -> Result<Self, Error> {
         let mut keys = KeyCacheImpl::new(f);
         cbor::decode::parse_array(data, |blocks, mut canonical, tags| {
@@ -719,6 +963,7 @@ impl ValidBundle {
             }
 
             // Add a block 0
+            bundle.blocks = std::mem::take(&mut scratch.blocks);
             bundle.blocks.insert(
                 0,
                 Block {
@@ -739,14 +984,25 @@ impl ValidBundle {
             );
 
             // And now parse the blocks
-            match bundle.parse_blocks(
+            let r = bundle.parse_blocks(
                 canonical,
                 canonical_primary_block,
                 blocks,
                 block_start + block_len,
                 data,
+                limits,
                 &mut keys,
-            ) {
+                scratch,
+            );
+
+            // Only checked once the whole bundle has been parsed
+            let r = r.and_then(|r| {
+                if limits.report_to == ReportToPolicy::Reject {
+                    bundle.check_report_to()?;
+                }
+                Ok(r)
+            });
+            match r {
                 Ok((None, report_unsupported)) => Ok(Self::Valid(bundle, report_unsupported)),
                 Ok((Some(new_data), report_unsupported)) => {
                     Ok(Self::Rewritten(bundle, new_data, report_unsupported))
@@ -756,6 +1012,19 @@ impl ValidBundle {
                     StatusReportReasonCode::BlockUnsupported,
                     Error::Unsupported(n).into(),
                 )),
+                Err(e @ Error::InvalidBPSec(bpsec::Error::DuplicateOpTarget)) => Ok(Self::Invalid(
+                    bundle,
+                    StatusReportReasonCode::ConflictingSecurityOperation,
+                    e.into(),
+                )),
+                // The blocks are fine, there is just nowhere sensible to send reports
+                Err(e @ (Error::NullReportTo | Error::NonSingletonReportTo(_))) => {
+                    Ok(Self::Invalid(
+                        bundle,
+                        StatusReportReasonCode::NoAdditionalInformation,
+                        e.into(),
+                    ))
+                }
                 Err(e) => Ok(Self::Invalid(
                     bundle,
                     StatusReportReasonCode::BlockUnintelligible,
@@ -766,3 +1035,299 @@ impl ValidBundle {
         .map(|v| v.0)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(data: &[u8]) -> Bundle {
+        let ValidBundle::Valid(bundle, _) = ValidBundle::parse(data, |_, _| Ok(None)).unwrap()
+        else {
+            panic!("Invalid bundle");
+        };
+        bundle
+    }
+
+    fn build(previous_node: &str, hop_info: HopInfo) -> Bundle {
+        let (_, data) = Builder::new()
+            .source("ipn:1.1".parse().unwrap())
+            .destination("ipn:2.1".parse().unwrap())
+            .add_extension_block(BlockType::PreviousNode)
+            .data(cbor::encode::emit(&previous_node.parse::<Eid>().unwrap()))
+            .build()
+            .add_extension_block(BlockType::HopCount)
+            .data(cbor::encode::emit(&hop_info))
+            .build()
+            .add_payload_block(Vec::new())
+            .build();
+        parse(&data)
+    }
+
+    #[test]
+    fn detect_loop() {
+        let our_node_ids = ["ipn:3.0".parse().unwrap(), "dtn://node/".parse().unwrap()];
+        let hop_info = HopInfo {
+            limit: 10,
+            count: 1,
+        };
+
+        assert!(build("ipn:3.0", hop_info.clone()).detect_loop(&our_node_ids));
+        assert!(build("ipn:3.7", hop_info.clone()).detect_loop(&our_node_ids));
+        assert!(build("dtn://node/svc", hop_info.clone()).detect_loop(&our_node_ids));
+        assert!(!build("ipn:4.0", hop_info.clone()).detect_loop(&our_node_ids));
+        assert!(!build("dtn://other/", hop_info).detect_loop(&our_node_ids));
+
+        // The last hop may be used, but not exceeded
+        assert!(!build(
+            "ipn:4.0",
+            HopInfo {
+                limit: 10,
+                count: 10
+            }
+        )
+        .detect_loop(&our_node_ids));
+        assert!(build(
+            "ipn:4.0",
+            HopInfo {
+                limit: 10,
+                count: 11
+            }
+        )
+        .detect_loop(&our_node_ids));
+    }
+
+    #[test]
+    fn set_previous_node() {
+        let (_, data) = Builder::new()
+            .source("ipn:1.1".parse().unwrap())
+            .destination("ipn:2.1".parse().unwrap())
+            .add_payload_block(Vec::new())
+            .build();
+        let bundle = parse(&data);
+        assert!(bundle.previous_node.is_none());
+
+        let node_id: Eid = "ipn:3.0".parse().unwrap();
+        let data = Editor::new(&bundle, &data).previous_node(&node_id).build();
+        let bundle = parse(&data);
+        assert_eq!(bundle.previous_node, Some(node_id.clone()));
+        assert!(bundle.detect_loop(&[node_id]));
+    }
+
+    #[test]
+    fn unsupported_block_deletes_bundle() {
+        let (_, data) = Builder::new()
+            .source("ipn:1.1".parse().unwrap())
+            .destination("ipn:2.1".parse().unwrap())
+            .add_extension_block(BlockType::Unrecognised(200))
+            .delete_bundle_on_failure(true)
+            .data(vec![0x40])
+            .build()
+            .add_payload_block(b"Hello".to_vec())
+            .build();
+        assert!(matches!(
+            ValidBundle::parse(&data, |_, _| Ok(None)).unwrap(),
+            ValidBundle::Invalid(_, StatusReportReasonCode::BlockUnsupported, _)
+        ));
+    }
+
+    #[test]
+    fn too_many_blocks() {
+        let mut builder = Builder::new()
+            .source("ipn:1.1".parse().unwrap())
+            .destination("ipn:2.1".parse().unwrap());
+        for _ in 0..5 {
+            builder = builder
+                .add_extension_block(BlockType::Unrecognised(200))
+                .data(vec![0x40])
+                .build();
+        }
+        let (_, data) = builder.add_payload_block(b"Hello".to_vec()).build();
+
+        // Primary, payload and 5 extension blocks
+        assert!(matches!(
+            ValidBundle::parse_with_limits(
+                &data,
+                &ParseLimits {
+                    max_blocks: 7,
+                    ..Default::default()
+                },
+                |_, _| Ok(None)
+            )
+            .unwrap(),
+            ValidBundle::Valid(..)
+        ));
+
+        let ValidBundle::Invalid(_, StatusReportReasonCode::BlockUnintelligible, e) =
+            ValidBundle::parse_with_limits(
+                &data,
+                &ParseLimits {
+                    max_blocks: 6,
+                    ..Default::default()
+                },
+                |_, _| Ok(None),
+            )
+            .unwrap()
+        else {
+            panic!("Bundle with too many blocks parsed");
+        };
+        assert!(matches!(
+            e.downcast_ref::<Error>(),
+            Some(Error::TooManyBlocks(6))
+        ));
+    }
+
+    #[test]
+    fn report_to() {
+        let build = |report_to: &str| {
+            Builder::new()
+                .flags(BundleFlags {
+                    delivery_report_requested: true,
+                    ..Default::default()
+                })
+                .source("ipn:1.1".parse().unwrap())
+                .destination("ipn:2.1".parse().unwrap())
+                .report_to(report_to.parse().unwrap())
+                .add_payload_block(b"Hello".to_vec())
+                .build()
+                .1
+        };
+        let reject = ParseLimits {
+            report_to: ReportToPolicy::Reject,
+            ..Default::default()
+        };
+
+        // Accepted by default, but flagged
+        for report_to in ["dtn:none", "dtn://group/~all"] {
+            let data = build(report_to);
+            let ValidBundle::Valid(bundle, _) = ValidBundle::parse(&data, |_, _| Ok(None)).unwrap()
+            else {
+                panic!("Bundle with {report_to} report-to not accepted");
+            };
+            assert!(bundle.check_report_to().is_err());
+
+            let ValidBundle::Invalid(_, StatusReportReasonCode::NoAdditionalInformation, e) =
+                ValidBundle::parse_with_limits(&data, &reject, |_, _| Ok(None)).unwrap()
+            else {
+                panic!("Bundle with {report_to} report-to not rejected");
+            };
+            assert!(matches!(
+                e.downcast_ref::<Error>(),
+                Some(Error::NullReportTo | Error::NonSingletonReportTo(_))
+            ));
+        }
+
+        let data = build("ipn:3.0");
+        let ValidBundle::Valid(bundle, _) =
+            ValidBundle::parse_with_limits(&data, &reject, |_, _| Ok(None)).unwrap()
+        else {
+            panic!("Bundle with singleton report-to rejected");
+        };
+        assert!(bundle.check_report_to().is_ok());
+    }
+
+    #[test]
+    fn scratch() {
+        // A mix of shapes, so the recycled block map is reused for bundles of different sizes
+        let bundles = [
+            Builder::new()
+                .source("ipn:1.1".parse().unwrap())
+                .destination("ipn:2.1".parse().unwrap())
+                .add_payload_block(b"Hello".to_vec())
+                .build()
+                .1,
+            Builder::new()
+                .source("dtn://node/service".parse().unwrap())
+                .destination("ipn:2.1".parse().unwrap())
+                .add_extension_block(BlockType::HopCount)
+                .data(cbor::encode::emit(&HopInfo {
+                    limit: 30,
+                    count: 0,
+                }))
+                .build()
+                .add_extension_block(BlockType::Unrecognised(200))
+                .data(vec![0x40])
+                .build()
+                .class_of_service(ClassOfService::Expedited)
+                .add_payload_block(b"World".to_vec())
+                .build()
+                .1,
+        ];
+        let summary = |bundle: &Bundle| {
+            let blocks = bundle
+                .blocks
+                .iter()
+                .map(|(n, b)| (*n, format!("{b:?}")))
+                .collect::<std::collections::BTreeMap<_, _>>();
+            format!(
+                "{:?} {:?} {:?} {blocks:?}",
+                bundle.id, bundle.hop_count, bundle.class_of_service
+            )
+        };
+        let expected = bundles
+            .iter()
+            .map(|data| summary(&parse(data)))
+            .collect::<Vec<_>>();
+
+        let mut scratch = ParseScratch::new();
+        for i in 0..10_000 {
+            let data = &bundles[i % bundles.len()];
+            let ValidBundle::Valid(bundle, _) = ValidBundle::parse_with_scratch(
+                data,
+                &ParseLimits::default(),
+                &mut scratch,
+                |_, _| Ok(None),
+            )
+            .unwrap() else {
+                panic!("Bundle failed to parse with a reused scratch");
+            };
+            assert_eq!(summary(&bundle), expected[i % bundles.len()]);
+            scratch.recycle(bundle);
+        }
+    }
+
+    #[test]
+    fn blocks_in_order() {
+        let (_, data) = Builder::new()
+            .source("ipn:1.1".parse().unwrap())
+            .destination("ipn:2.1".parse().unwrap())
+            .add_extension_block(BlockType::PreviousNode)
+            .data(cbor::encode::emit(&"ipn:3.0".parse::<Eid>().unwrap()))
+            .build()
+            .add_extension_block(BlockType::HopCount)
+            .data(cbor::encode::emit(&HopInfo {
+                limit: 10,
+                count: 1,
+            }))
+            .build()
+            .add_extension_block(BlockType::BundleAge)
+            .data(cbor::encode::emit(300u64))
+            .build()
+            .add_payload_block(b"Hello".to_vec())
+            .build();
+        let bundle = parse(&data);
+
+        let block_numbers = bundle
+            .blocks_in_order()
+            .map(|(block_number, _)| *block_number)
+            .collect::<Vec<_>>();
+        let mut extension_blocks = bundle
+            .blocks
+            .keys()
+            .copied()
+            .filter(|block_number| *block_number > 1)
+            .collect::<Vec<_>>();
+        extension_blocks.sort();
+        assert_eq!(extension_blocks.len(), 3);
+        assert_eq!(block_numbers.first(), Some(&0));
+        assert_eq!(&block_numbers[1..4], extension_blocks.as_slice());
+        assert_eq!(block_numbers.last(), Some(&1));
+
+        assert!(matches!(
+            bundle
+                .blocks_in_order()
+                .map(|(_, block)| block.block_type)
+                .collect::<Vec<_>>()[..],
+            [BlockType::Primary, _, _, _, BlockType::Payload]
+        ));
+    }
+}
@@ -14,6 +14,16 @@ pub struct BundleFlags {
     pub unrecognised: u64,
 }
 
+impl BundleFlags {
+    // Are any status reports requested
+    pub fn requests_reports(&self) -> bool {
+        self.receipt_report_requested
+            || self.forward_report_requested
+            || self.delivery_report_requested
+            || self.delete_report_requested
+    }
+}
+
 impl From<u64> for BundleFlags {
     fn from(value: u64) -> Self {
         let mut flags = Self {
@@ -49,6 +49,15 @@ impl<T, E: Into<Box<dyn std::error::Error + Send + Sync>>> CaptureFieldErr<T>
 }
 
 impl BundleId {
+    /* The id of a fragment of this bundle, `offset` and `total_len` are relative to
+     * the whole application data unit, so every fragment shares the source and timestamp */
+    pub fn fragment(&self, offset: u64, total_len: u64) -> Self {
+        Self {
+            fragment_info: Some(FragmentInfo { offset, total_len }),
+            ..self.clone()
+        }
+    }
+
     pub fn from_key(k: &str) -> Result<Self, Error> {
         cbor::decode::parse_array(&BASE64_STANDARD_NO_PAD.decode(k)?, |array, _, _| {
             let s = Self {
@@ -73,6 +82,7 @@ impl BundleId {
         })
         .map(|v| v.0)
     }
+
     pub fn to_key(&self) -> String {
         BASE64_STANDARD_NO_PAD.encode(if let Some(fragment_info) = &self.fragment_info {
             cbor::encode::emit_array(Some(4), |array| {
@@ -89,3 +99,37 @@ impl BundleId {
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fragment() {
+        let parent = BundleId {
+            source: "ipn:1.1".parse().unwrap(),
+            timestamp: CreationTimestamp {
+                creation_time: None,
+                sequence_number: 7,
+            },
+            fragment_info: None,
+        };
+
+        let first = parent.fragment(0, 100);
+        let second = parent.fragment(50, 100);
+        assert_ne!(first, second);
+        assert_eq!(first.source, parent.source);
+        assert_eq!(first.timestamp, parent.timestamp);
+        assert_eq!(
+            BundleId {
+                fragment_info: None,
+                ..second.clone()
+            },
+            parent
+        );
+
+        for id in [parent, first, second] {
+            assert_eq!(BundleId::from_key(&id.to_key()).unwrap(), id);
+        }
+    }
+}
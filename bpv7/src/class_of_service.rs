@@ -0,0 +1,52 @@
+use super::*;
+
+/* The relative importance of a bundle, carried in a Class of Service extension block.
+ * A bundle without the block is Normal */
+#[derive(Default, Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ClassOfService {
+    Bulk,
+    #[default]
+    Normal,
+    Expedited,
+}
+
+impl From<ClassOfService> for u64 {
+    fn from(value: ClassOfService) -> Self {
+        match value {
+            ClassOfService::Bulk => 0,
+            ClassOfService::Normal => 1,
+            ClassOfService::Expedited => 2,
+        }
+    }
+}
+
+impl TryFrom<u64> for ClassOfService {
+    type Error = Error;
+
+    fn try_from(value: u64) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(ClassOfService::Bulk),
+            1 => Ok(ClassOfService::Normal),
+            2 => Ok(ClassOfService::Expedited),
+            value => Err(Error::InvalidClassOfService(value)),
+        }
+    }
+}
+
+impl cbor::encode::ToCbor for ClassOfService {
+    fn to_cbor(self, encoder: &mut cbor::encode::Encoder) {
+        encoder.emit(u64::from(self))
+    }
+}
+
+impl cbor::decode::FromCbor for ClassOfService {
+    type Error = Error;
+
+    fn try_from_cbor(data: &[u8]) -> Result<Option<(Self, bool, usize)>, Self::Error> {
+        if let Some((v, shortest, len)) = cbor::decode::try_parse::<(u64, bool, usize)>(data)? {
+            Ok(Some((v.try_into()?, shortest, len)))
+        } else {
+            Ok(None)
+        }
+    }
+}
@@ -26,7 +26,7 @@ pub enum Error {
 }
 
 #[allow(non_camel_case_types)]
-#[derive(Default, Debug, Copy, Clone)]
+#[derive(Default, Debug, Copy, Clone, PartialEq, Eq)]
 pub enum CrcType {
     #[default]
     None,
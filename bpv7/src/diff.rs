@@ -0,0 +1,187 @@
+use super::*;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BlockChange {
+    BlockType(BlockType, BlockType),
+    Flags(BlockFlags, BlockFlags),
+    CrcType(CrcType, CrcType),
+    Crc,
+    Data,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BlockDiff {
+    Added(u64, BlockType),
+    Removed(u64, BlockType),
+    Changed(u64, Vec<BlockChange>),
+}
+
+impl BlockDiff {
+    pub fn block_number(&self) -> u64 {
+        match self {
+            Self::Added(block_number, _)
+            | Self::Removed(block_number, _)
+            | Self::Changed(block_number, _) => *block_number,
+        }
+    }
+}
+
+/* Compare two encodings of a bundle block-by-block, to help work out what a
+ * misbehaving node has altered.  The results are ordered by block number */
+pub fn diff(a: &[u8], b: &[u8]) -> Result<Vec<BlockDiff>, Error> {
+    let (a_bundle, a_data) = parse(a)?;
+    let (b_bundle, b_data) = parse(b)?;
+
+    let mut block_numbers = a_bundle
+        .blocks
+        .keys()
+        .chain(b_bundle.blocks.keys())
+        .copied()
+        .collect::<Vec<_>>();
+    block_numbers.sort_unstable();
+    block_numbers.dedup();
+
+    Ok(block_numbers
+        .into_iter()
+        .filter_map(|block_number| {
+            match (
+                a_bundle.blocks.get(&block_number),
+                b_bundle.blocks.get(&block_number),
+            ) {
+                (Some(a), None) => Some(BlockDiff::Removed(block_number, a.block_type)),
+                (None, Some(b)) => Some(BlockDiff::Added(block_number, b.block_type)),
+                (Some(a), Some(b)) => {
+                    let changes = diff_block(a, &a_data, b, &b_data);
+                    (!changes.is_empty()).then_some(BlockDiff::Changed(block_number, changes))
+                }
+                (None, None) => unreachable!(),
+            }
+        })
+        .collect())
+}
+
+fn parse(data: &[u8]) -> Result<(Bundle, std::borrow::Cow<'_, [u8]>), Error> {
+    // We only want the structure, so don't try to decrypt anything
+    match ValidBundle::parse(data, |_, _| Ok(None))? {
+        ValidBundle::Valid(bundle, _) | ValidBundle::Invalid(bundle, _, _) => {
+            Ok((bundle, data.into()))
+        }
+        // Block offsets refer to the rewritten data
+        ValidBundle::Rewritten(bundle, data, _) => Ok((bundle, data.into_vec().into())),
+    }
+}
+
+fn diff_block(a: &Block, a_data: &[u8], b: &Block, b_data: &[u8]) -> Vec<BlockChange> {
+    let mut changes = Vec::new();
+    if a.block_type != b.block_type {
+        changes.push(BlockChange::BlockType(a.block_type, b.block_type));
+    }
+    if a.flags != b.flags {
+        changes.push(BlockChange::Flags(a.flags.clone(), b.flags.clone()));
+    }
+    if a.crc_type != b.crc_type {
+        changes.push(BlockChange::CrcType(a.crc_type, b.crc_type));
+    } else if crc_value(a, a_data) != crc_value(b, b_data) {
+        changes.push(BlockChange::Crc);
+    }
+    if a.payload(a_data) != b.payload(b_data) {
+        changes.push(BlockChange::Data);
+    }
+    changes
+}
+
+fn crc_value<'a>(block: &Block, data: &'a [u8]) -> &'a [u8] {
+    // The CRC value is always the final bytes of the encoded block
+    let len = match block.crc_type {
+        CrcType::CRC16_X25 => 2,
+        CrcType::CRC32_CASTAGNOLI => 4,
+        _ => 0,
+    };
+    let end = block.data_start + block.data_len;
+    &data[end - len..end]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn build(destination: &str) -> (Bundle, Vec<u8>) {
+        let (_, data) = Builder::new()
+            .source("ipn:1.1".parse().unwrap())
+            .destination(destination.parse().unwrap())
+            .add_extension_block(BlockType::PreviousNode)
+            .data(cbor::encode::emit(&"ipn:3.0".parse::<Eid>().unwrap()))
+            .build()
+            .add_extension_block(BlockType::HopCount)
+            .data(cbor::encode::emit(&HopInfo {
+                limit: 30,
+                count: 0,
+            }))
+            .build()
+            .add_payload_block(vec![1, 2, 3])
+            .build();
+        let (bundle, _) = parse(&data).unwrap();
+        (bundle, data)
+    }
+
+    fn find_block(bundle: &Bundle, block_type: BlockType) -> u64 {
+        *bundle
+            .blocks
+            .iter()
+            .find(|(_, b)| b.block_type == block_type)
+            .unwrap()
+            .0
+    }
+
+    #[test]
+    fn unchanged() {
+        let (_, data) = build("ipn:2.1");
+        assert!(diff(&data, &data).unwrap().is_empty());
+    }
+
+    #[test]
+    fn edited() {
+        let (bundle, data) = build("ipn:2.1");
+        let previous_node = find_block(&bundle, BlockType::PreviousNode);
+        let hop_count = find_block(&bundle, BlockType::HopCount);
+
+        let edited = Editor::new(&bundle, &data)
+            .previous_node(&"ipn:4.0".parse().unwrap())
+            .add_extension_block(BlockType::BundleAge)
+            .data(cbor::encode::emit(0u64))
+            .build()
+            .remove_extension_block(hop_count)
+            .build();
+
+        let diffs = diff(&data, &edited).unwrap();
+        assert!(diffs.contains(&BlockDiff::Removed(hop_count, BlockType::HopCount)));
+        assert!(diffs
+            .iter()
+            .any(|d| matches!(d, BlockDiff::Added(_, BlockType::BundleAge))));
+
+        // The replaced Previous Node block has new data
+        let changed = diffs
+            .iter()
+            .find(|d| d.block_number() == previous_node)
+            .unwrap();
+        let BlockDiff::Changed(_, changes) = changed else {
+            panic!("Previous Node block not reported as changed");
+        };
+        assert!(changes.contains(&BlockChange::Data));
+
+        // The primary and payload blocks are untouched
+        assert!(!diffs.iter().any(|d| d.block_number() <= 1));
+    }
+
+    #[test]
+    fn altered_primary() {
+        let (_, a) = build("ipn:2.1");
+        let (_, b) = build("ipn:5.1");
+
+        let diffs = diff(&a, &b).unwrap();
+        let Some(BlockDiff::Changed(0, changes)) = diffs.first() else {
+            panic!("Primary block not reported as changed");
+        };
+        assert!(changes.contains(&BlockChange::Data));
+    }
+}
@@ -36,14 +36,198 @@ impl<'a> Editor<'a> {
             panic!("Don't add primary or payload blocks!");
         }
 
+        let block_number = self.next_block_number();
+        BlockBuilder::new(self, block_number, block_type)
+    }
+
+    fn next_block_number(&self) -> u64 {
         // Find the lowest unused block_number
         let mut block_number = 2u64;
-        loop {
-            if !self.blocks.contains_key(&block_number) {
-                return BlockBuilder::new(self, block_number, block_type);
-            }
+        while self.blocks.contains_key(&block_number) {
             block_number += 1;
         }
+        block_number
+    }
+
+    /* Add a single BIB signing every block in `targets` with RFC 9173 HMAC-SHA2.
+     * The targets must be unaltered blocks of the original bundle */
+    pub fn sign_hmac_sha2(
+        self,
+        source: Eid,
+        key: &bpsec::KeyMaterial,
+        targets: &[u64],
+    ) -> Result<Self, Error> {
+        self.sign_hmac_sha2_variant(
+            source,
+            bpsec::bib_hmac_sha2::ShaVariant::default(),
+            key,
+            targets,
+        )
+    }
+
+    // As sign_hmac_sha2(), but using a specific SHA-2 variant
+    pub fn sign_hmac_sha2_variant(
+        self,
+        source: Eid,
+        variant: bpsec::bib_hmac_sha2::ShaVariant,
+        key: &bpsec::KeyMaterial,
+        targets: &[u64],
+    ) -> Result<Self, Error> {
+        if targets
+            .iter()
+            .any(|t| !matches!(self.blocks.get(t), Some(BlockTemplate::Keep(_))))
+        {
+            return Err(bpsec::Error::MissingSecurityTarget.into());
+        }
+
+        let block_number = self.next_block_number();
+        let bib = bpsec::bib::OperationSet::sign_hmac_sha2(
+            source,
+            bpsec::bib_hmac_sha2::Parameters {
+                variant,
+                ..Default::default()
+            },
+            key,
+            targets,
+            &Block {
+                block_type: BlockType::BlockIntegrity,
+                flags: BlockFlags::default(),
+                crc_type: self.original.crc_type,
+                data_start: 0,
+                data_len: 0,
+                payload_offset: 0,
+                payload_len: 0,
+                bcb: None,
+            },
+            block_number,
+            self.original,
+            self.source_data,
+        )?;
+
+        Ok(
+            BlockBuilder::new(self, block_number, BlockType::BlockIntegrity)
+                .data(cbor::encode::emit(bib))
+                .build(),
+        )
+    }
+
+    /* Add a BCB encrypting the block `target_number` with RFC 9173 AES-GCM, replacing it with its
+     * ciphertext.  If `parameters.key` holds a CEK wrapped with bpsec::key::KeyAlgorithm::wrap(),
+     * `key` is the KEK, otherwise it is the CEK itself.  The target must be an unaltered block of
+     * the original bundle.  Each target needs its own BCB, and a fresh IV */
+    pub fn encrypt_aes_gcm(
+        mut self,
+        source: Eid,
+        parameters: bpsec::bcb_aes_gcm::Parameters,
+        key: &bpsec::KeyMaterial,
+        target_number: u64,
+    ) -> Result<Self, Error> {
+        if !matches!(
+            self.blocks.get(&target_number),
+            Some(BlockTemplate::Keep(_))
+        ) {
+            return Err(bpsec::Error::MissingSecurityTarget.into());
+        }
+
+        let block_number = self.next_block_number();
+        let bcb_block = Block {
+            block_type: BlockType::BlockSecurity,
+            flags: BlockFlags {
+                // A BCB protecting the payload must be replicated in every fragment
+                must_replicate: target_number == 1,
+                ..Default::default()
+            },
+            crc_type: self.original.crc_type,
+            data_start: 0,
+            data_len: 0,
+            payload_offset: 0,
+            payload_len: 0,
+            bcb: None,
+        };
+        let (bcb, ciphertext) = bpsec::bcb::OperationSet::encrypt_aes_gcm(
+            source,
+            parameters,
+            key,
+            target_number,
+            &bcb_block,
+            block_number,
+            self.original,
+            self.source_data,
+        )?;
+
+        let target = self
+            .original
+            .blocks
+            .get(&target_number)
+            .expect("Mismatched block in bundle!");
+        let mut template =
+            builder::BlockTemplate::new(target.block_type, target.flags.clone(), target.crc_type);
+        template.data(ciphertext.into_vec());
+        self.blocks
+            .insert(target_number, BlockTemplate::Add(template));
+
+        Ok(
+            BlockBuilder::new(self, block_number, BlockType::BlockSecurity)
+                .must_replicate(bcb_block.flags.must_replicate)
+                .data(cbor::encode::emit(bcb))
+                .build(),
+        )
+    }
+
+    /* Decrypt the BCB target `block_number` and put the plaintext back in its place, removing
+     * the target from its BCB, and the BCB itself once it has no targets left.
+     * The target and its BCB must be unaltered blocks of the original bundle */
+    pub fn decrypt_and_strip_bcb(
+        mut self,
+        block_number: u64,
+        f: impl FnMut(&Eid, bpsec::Context) -> Result<Option<bpsec::KeyMaterial>, bpsec::Error>,
+    ) -> Result<Self, Error> {
+        let Some(block) = self.original.blocks.get(&block_number) else {
+            return Err(bpsec::Error::MissingSecurityTarget.into());
+        };
+        let Some(bcb_block_number) = block.bcb else {
+            return Err(bpsec::Error::NotEncrypted(block_number).into());
+        };
+        if [block_number, bcb_block_number]
+            .iter()
+            .any(|b| !matches!(self.blocks.get(b), Some(BlockTemplate::Keep(_))))
+        {
+            return Err(bpsec::Error::MissingSecurityTarget.into());
+        }
+
+        let plaintext = match self
+            .original
+            .block_payload(block_number, self.source_data, f)?
+        {
+            Some(payload::Payload::Owned(plaintext)) => plaintext,
+            _ => return Err(bpsec::Error::DecryptionFailed.into()),
+        };
+
+        let (bcb_block, mut bcb, _) = self.original.parse_payload::<bpsec::bcb::OperationSet>(
+            &bcb_block_number,
+            None,
+            self.source_data,
+        )?;
+        bcb.operations.remove(&block_number);
+        if bcb.operations.is_empty() {
+            self.blocks.remove(&bcb_block_number);
+        } else {
+            let mut template = builder::BlockTemplate::new(
+                BlockType::BlockSecurity,
+                bcb_block.flags.clone(),
+                bcb_block.crc_type,
+            );
+            template.data(cbor::encode::emit(bcb));
+            self.blocks
+                .insert(bcb_block_number, BlockTemplate::Add(template));
+        }
+
+        let mut template =
+            builder::BlockTemplate::new(block.block_type, block.flags.clone(), block.crc_type);
+        template.data(plaintext.into_vec());
+        self.blocks
+            .insert(block_number, BlockTemplate::Add(template));
+        Ok(self)
     }
 
     pub fn replace_extension_block(self, block_type: BlockType) -> BlockBuilder<'a> {
@@ -78,6 +262,66 @@ impl<'a> Editor<'a> {
         }
     }
 
+    pub fn previous_node(self, node_id: &Eid) -> Self {
+        self.replace_extension_block(BlockType::PreviousNode)
+            .data(cbor::encode::emit(node_id))
+            .build()
+    }
+
+    /* Replace the block processing control flags of `block_number`, rewriting its header and CRC.
+     * A BCB targeting the payload must be replicated in every fragment, so that flag cannot be
+     * cleared on one.  Flags are covered by BPSec operations targeting the block, so any BIB or BCB
+     * targeting it will no longer verify */
+    pub fn set_block_flags(mut self, block_number: u64, flags: BlockFlags) -> Result<Self, Error> {
+        if block_number == 0 {
+            panic!("Primary block has no block flags!");
+        }
+
+        let mut template = match self.blocks.remove(&block_number) {
+            None => return Err(Error::MissingBlock(block_number)),
+            Some(BlockTemplate::Add(template)) => template,
+            Some(BlockTemplate::Keep(_)) => {
+                let block = self
+                    .original
+                    .blocks
+                    .get(&block_number)
+                    .expect("Mismatched block in bundle!");
+                let (data, _) =
+                    cbor::decode::parse_value(block.payload(self.source_data), |value, _, _| {
+                        match value {
+                            cbor::decode::Value::Bytes(data) => Ok(data.to_vec()),
+                            cbor::decode::Value::ByteStream(data) => Ok(data.concat()),
+                            value => Err(cbor::decode::Error::IncorrectType(
+                                "Byte String".to_string(),
+                                value.type_name(false),
+                            )),
+                        }
+                    })?;
+
+                let mut template = builder::BlockTemplate::new(
+                    block.block_type,
+                    block.flags.clone(),
+                    block.crc_type,
+                );
+                template.data(data);
+                template
+            }
+        };
+
+        if template.block_type() == BlockType::BlockSecurity
+            && !flags.must_replicate
+            && matches!(self.blocks.get(&1), Some(BlockTemplate::Keep(_)))
+            && self.original.blocks.get(&1).and_then(|block| block.bcb) == Some(block_number)
+        {
+            return Err(bpsec::Error::BCBMustReplicate.into());
+        }
+
+        template.flags(flags);
+        self.blocks
+            .insert(block_number, BlockTemplate::Add(template));
+        Ok(self)
+    }
+
     pub fn remove_extension_block(mut self, block_number: u64) -> Self {
         if block_number == 0 || block_number == 1 {
             panic!("Don't remove primary or payload blocks!");
@@ -94,8 +338,12 @@ impl<'a> Editor<'a> {
             // Emit primary block
             self.build_block(0, primary_block, a);
 
-            // Emit extension blocks
-            for (block_number, block) in std::mem::take(&mut self.blocks) {
+            // Emit extension blocks, in block number order
+            let mut blocks = std::mem::take(&mut self.blocks)
+                .into_iter()
+                .collect::<Vec<_>>();
+            blocks.sort_unstable_by_key(|(block_number, _)| *block_number);
+            for (block_number, block) in blocks {
                 self.build_block(block_number, block, a);
             }
 
@@ -118,9 +366,15 @@ impl<'a> Editor<'a> {
                     .expect("Mismatched block in bundle!")
                     .copy(self.source_data, array);
             }
-            BlockTemplate::Add(template) => {
-                template.build(block_number, array);
-            }
+            BlockTemplate::Add(template) => match self.original.blocks.get(&block_number) {
+                // Only re-encode, and recalculate the CRC of, blocks that have actually changed
+                Some(block) if template.is_unchanged(block, self.source_data) => {
+                    block.copy(self.source_data, array)
+                }
+                _ => {
+                    template.build(block_number, array);
+                }
+            },
         }
     }
 }
@@ -189,3 +443,244 @@ impl<'a> BlockBuilder<'a> {
         self.editor
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(data: &[u8]) -> Bundle {
+        match ValidBundle::parse(data, |_, _| Ok(None)).unwrap() {
+            ValidBundle::Valid(bundle, _) => bundle,
+            _ => panic!("Invalid bundle"),
+        }
+    }
+
+    fn block_data<'a>(bundle: &Bundle, data: &'a [u8], block_type: BlockType) -> &'a [u8] {
+        let block = bundle
+            .blocks
+            .values()
+            .find(|block| block.block_type == block_type)
+            .unwrap();
+        &data[block.data_start..block.data_start + block.data_len]
+    }
+
+    #[test]
+    fn untouched_blocks() {
+        let (_, data) = Builder::new()
+            .source("ipn:1.1".parse().unwrap())
+            .destination("ipn:2.1".parse().unwrap())
+            .crc_type(CrcType::CRC32_CASTAGNOLI)
+            .add_extension_block(BlockType::PreviousNode)
+            .data(cbor::encode::emit(&"ipn:3.0".parse::<Eid>().unwrap()))
+            .build()
+            .add_extension_block(BlockType::HopCount)
+            .crc_type(CrcType::CRC16_X25)
+            .data(cbor::encode::emit(&HopInfo {
+                limit: 30,
+                count: 1,
+            }))
+            .build()
+            .add_payload_block(b"Hello".to_vec())
+            .build();
+        let bundle = parse(&data);
+
+        // Change one block
+        let edited = Editor::new(&bundle, &data)
+            .previous_node(&"ipn:4.0".parse().unwrap())
+            .build();
+        let edited_bundle = parse(&edited);
+        assert_eq!(
+            edited_bundle.previous_node,
+            Some("ipn:4.0".parse().unwrap())
+        );
+        for block_type in [BlockType::HopCount, BlockType::Payload] {
+            assert_eq!(
+                block_data(&bundle, &data, block_type),
+                block_data(&edited_bundle, &edited, block_type)
+            );
+        }
+
+        // Replacing a block with identical content reuses the original bytes
+        let edited = Editor::new(&bundle, &data)
+            .previous_node(&"ipn:3.0".parse().unwrap())
+            .build();
+        let edited_bundle = parse(&edited);
+        assert_eq!(
+            block_data(&bundle, &data, BlockType::PreviousNode),
+            block_data(&edited_bundle, &edited, BlockType::PreviousNode)
+        );
+    }
+
+    #[test]
+    fn set_block_flags() {
+        let (_, data) = Builder::new()
+            .source("ipn:1.1".parse().unwrap())
+            .destination("ipn:2.1".parse().unwrap())
+            .add_extension_block(BlockType::HopCount)
+            .data(cbor::encode::emit(&HopInfo {
+                limit: 30,
+                count: 1,
+            }))
+            .build()
+            .add_payload_block(b"Hello".to_vec())
+            .build();
+        let bundle = parse(&data);
+        assert!(!bundle.blocks[&2].flags.report_on_failure);
+
+        let flags = BlockFlags {
+            report_on_failure: true,
+            ..bundle.blocks[&2].flags.clone()
+        };
+        let edited = Editor::new(&bundle, &data)
+            .set_block_flags(2, flags.clone())
+            .unwrap()
+            .build();
+        let edited_bundle = parse(&edited);
+        assert_eq!(edited_bundle.blocks[&2].flags, flags);
+        assert_eq!(
+            block_data(&bundle, &data, BlockType::Payload),
+            block_data(&edited_bundle, &edited, BlockType::Payload)
+        );
+        assert!(edited_bundle
+            .hop_count
+            .is_some_and(|hop_info| hop_info.limit == 30 && hop_info.count == 1));
+
+        assert!(matches!(
+            Editor::new(&bundle, &data).set_block_flags(3, flags),
+            Err(Error::MissingBlock(3))
+        ));
+    }
+
+    #[test]
+    fn encrypt_bcb() {
+        let (_, data) = Builder::new()
+            .source("ipn:1.1".parse().unwrap())
+            .destination("ipn:2.1".parse().unwrap())
+            .add_payload_block(b"Hello".to_vec())
+            .build();
+        let bundle = parse(&data);
+
+        // Encrypt the payload with a CEK wrapped by the KEK shared with the destination
+        let kek = bpsec::KeyMaterial::SymmetricKey([0x5a; 32].into());
+        let parameters = || bpsec::bcb_aes_gcm::Parameters {
+            iv: hex_literal::hex!("5477656c7665313231323132").into(),
+            variant: bpsec::bcb_aes_gcm::AesVariant::A256GCM,
+            key: bpsec::key::KeyAlgorithm::A256KW
+                .wrap(&kek, &[0x71; 32])
+                .unwrap(),
+            flags: Default::default(),
+        };
+        let encrypted = Editor::new(&bundle, &data)
+            .encrypt_aes_gcm("ipn:1.0".parse().unwrap(), parameters(), &kek, 1)
+            .unwrap()
+            .build();
+
+        let keys = |_: &Eid, _| Ok(Some(kek.clone()));
+        let encrypted_bundle = match ValidBundle::parse(&encrypted, keys).unwrap() {
+            ValidBundle::Valid(bundle, _) => bundle,
+            _ => panic!("Invalid bundle"),
+        };
+        let bcb_block_number = encrypted_bundle.blocks.get(&1).unwrap().bcb.unwrap();
+        assert!(
+            encrypted_bundle.blocks[&bcb_block_number]
+                .flags
+                .must_replicate
+        );
+        assert!(!encrypted.windows(5).any(|w| w == b"Hello"));
+
+        match encrypted_bundle.block_payload(1, &encrypted, keys).unwrap() {
+            Some(payload::Payload::Owned(plaintext)) => assert_eq!(plaintext.as_ref(), b"Hello"),
+            _ => panic!("Payload not decrypted"),
+        }
+
+        // The primary block cannot be encrypted, nor can a block be encrypted twice
+        assert!(Editor::new(&bundle, &data)
+            .encrypt_aes_gcm("ipn:1.0".parse().unwrap(), parameters(), &kek, 0)
+            .is_err());
+        assert!(matches!(
+            Editor::new(&encrypted_bundle, &encrypted).encrypt_aes_gcm(
+                "ipn:1.0".parse().unwrap(),
+                parameters(),
+                &kek,
+                1
+            ),
+            Err(Error::InvalidBPSec(bpsec::Error::InvalidBCBTarget))
+        ));
+
+        // An unsupported variant is refused rather than panicking
+        assert!(matches!(
+            Editor::new(&bundle, &data).encrypt_aes_gcm(
+                "ipn:1.0".parse().unwrap(),
+                bpsec::bcb_aes_gcm::Parameters {
+                    variant: bpsec::bcb_aes_gcm::AesVariant::Unrecognised(99),
+                    ..parameters()
+                },
+                &kek,
+                1
+            ),
+            Err(Error::InvalidBPSec(bpsec::Error::UnsupportedAesVariant(99)))
+        ));
+    }
+
+    #[test]
+    fn strip_bcb() {
+        // RFC9173 Appendix A.2, with the creation timestamp tweaked and a CRC added
+        let key = hex_literal::hex!("6162636465666768696a6b6c6d6e6f70");
+        let data = hex_literal::hex!(
+            "9f89070001820282010282028202018202820201820118281a000f424042e4fe850c0201
+            0058508101020182028202018482014c5477656c7665313231323132820201820358
+            1869c411276fecddc4780df42c8a2af89296fabf34d7fae7008204008181820150ef
+            a4b5ac0108e3816c5606479801bc04850101000058233a09c1e63fe23a7f66a59c73
+            03837241e070b02619fc59c5214a22f08cd70795e73e9aff"
+        );
+        let bundle = match ValidBundle::parse(&data, |_, _| {
+            Ok(Some(bpsec::KeyMaterial::SymmetricKey(key.into())))
+        })
+        .unwrap()
+        {
+            ValidBundle::Valid(bundle, _) => bundle,
+            _ => panic!("Invalid bundle"),
+        };
+        assert_eq!(bundle.blocks.get(&1).unwrap().bcb, Some(2));
+
+        // Without the key there is nothing to strip
+        assert!(Editor::new(&bundle, &data)
+            .decrypt_and_strip_bcb(1, |_, _| Ok(None))
+            .is_err());
+
+        let stripped = Editor::new(&bundle, &data)
+            .decrypt_and_strip_bcb(1, |_, _| {
+                Ok(Some(bpsec::KeyMaterial::SymmetricKey(key.into())))
+            })
+            .unwrap()
+            .build();
+
+        // No key is needed to read the result
+        let stripped_bundle = parse(&stripped);
+        assert!(stripped_bundle
+            .blocks
+            .values()
+            .all(|block| block.block_type != BlockType::BlockSecurity && block.bcb.is_none()));
+        match stripped_bundle
+            .block_payload(1, &stripped, |_, _| Ok(None))
+            .unwrap()
+        {
+            Some(payload::Payload::Range(range)) => {
+                assert_eq!(&stripped[range], b"Ready to generate a 32-byte payload")
+            }
+            _ => panic!("Payload is not plaintext"),
+        }
+
+        // The BCB protecting the payload must stay replicated in every fragment
+        assert!(matches!(
+            Editor::new(&bundle, &data).set_block_flags(2, BlockFlags::default()),
+            Err(Error::InvalidBPSec(bpsec::Error::BCBMustReplicate))
+        ));
+
+        // The payload is no longer encrypted
+        assert!(matches!(
+            Editor::new(&stripped_bundle, &stripped).decrypt_and_strip_bcb(1, |_, _| Ok(None)),
+            Err(Error::InvalidBPSec(bpsec::Error::NotEncrypted(1)))
+        ));
+    }
+}
@@ -13,7 +13,19 @@ fn tests() {
     null_check(&hex!("82 02 82 00 00"));
     null_check(&hex!("82 02 83 00 00 00"));
 
-    // TODO: Add dtn tests
+    dtn_check("//somewhere/");
+    dtn_check("//somewhere/over%2Fthe/rainbow");
+    dtn_check("//some%20where/over%20the/rainbow");
+    dtn_check("//somewhere/caf\u{e9}/\u{2713}");
+    dtn_check("//some:where/over+the/rain@bow;x=1,y=(2)!$&'*");
+    dtn_check("//some~where/over-the_rain.bow");
+    dtn_check("//somewhere/100%25/what%3F/no%23/%5Bv6%5D");
+
+    // Escaping that differs from how the EID is emitted is not canonical
+    dtn_non_canonical("//somewhere/%61", "//somewhere/a");
+    dtn_non_canonical("//somewhere/over%2fthe", "//somewhere/over%2Fthe");
+    dtn_non_canonical("//some%3Awhere/rain%40bow", "//some:where/rain@bow");
+    dtn_non_canonical("//somewhere/caf%C3%A9", "//somewhere/caf\u{e9}");
 
     // Negative tests
     assert!(matches!(
@@ -54,6 +66,37 @@ fn tests() {
     ));
 }
 
+// Round-trip a dtn scheme-specific part through CBOR, expecting byte-for-byte stability
+fn dtn_check(ssp: &str) {
+    let data = cbor::encode::emit(&DtnEid(ssp));
+    let (eid, canonical) = cbor::decode::parse::<(Eid, bool)>(&data).expect("Failed to parse");
+    assert!(canonical, "{ssp} is not canonical");
+    assert_eq!(cbor::encode::emit(&eid), data);
+    assert_eq!(eid.to_string(), format!("dtn:{ssp}"));
+    assert_eq!(eid.to_string().parse::<Eid>().unwrap(), eid);
+}
+
+fn dtn_non_canonical(ssp: &str, expected: &str) {
+    let (eid, canonical) =
+        cbor::decode::parse::<(Eid, bool)>(&cbor::encode::emit(&DtnEid(ssp))).unwrap();
+    assert!(!canonical, "{ssp} is canonical");
+    assert_eq!(
+        cbor::encode::emit(&eid),
+        cbor::encode::emit(&DtnEid(expected))
+    );
+}
+
+struct DtnEid<'a>(&'a str);
+
+impl cbor::encode::ToCbor for &DtnEid<'_> {
+    fn to_cbor(self, encoder: &mut cbor::encode::Encoder) {
+        encoder.emit_array(Some(2), |a| {
+            a.emit(1);
+            a.emit(self.0);
+        })
+    }
+}
+
 fn expect_error(data: &[u8]) -> EidError {
     cbor::decode::parse::<Eid>(data).expect_err("Parsed successfully!")
 }
@@ -33,6 +33,12 @@ pub enum EidError {
     #[error("Unsupported EID scheme {0}")]
     UnsupportedScheme(String),
 
+    #[error("Bad EID key")]
+    BadKey,
+
+    #[error("Bad base64 encoding")]
+    BadBase64(#[from] base64::DecodeError),
+
     #[error("Failed to parse {field}: {source}")]
     InvalidField {
         field: &'static str,
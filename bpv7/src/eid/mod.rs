@@ -1,4 +1,5 @@
 use super::*;
+use base64::prelude::*;
 use thiserror::Error;
 
 mod error;
@@ -12,7 +13,9 @@ mod cbor_tests;
 
 pub use error::EidError;
 
-#[derive(Default, Clone, Hash, PartialEq, Eq, PartialOrd, Ord)]
+/* Legacy 2-element and 3-element ipn EIDs with the same parts are the same endpoint,
+ * so they compare and hash equal, although each is still emitted in its own encoding */
+#[derive(Default, Clone)]
 pub enum Eid {
     #[default]
     Null,
@@ -39,6 +42,262 @@ pub enum Eid {
     },
 }
 
+impl Eid {
+    // The rank of each variant, with both ipn encodings ranked together
+    fn rank(&self) -> u8 {
+        match self {
+            Eid::Null => 0,
+            Eid::LocalNode { .. } => 1,
+            Eid::LegacyIpn { .. } | Eid::Ipn { .. } => 2,
+            Eid::Dtn { .. } => 3,
+            Eid::Unknown { .. } => 4,
+        }
+    }
+}
+
+impl PartialEq for Eid {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == std::cmp::Ordering::Equal
+    }
+}
+
+impl Eq for Eid {}
+
+impl PartialOrd for Eid {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Eid {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        match (self, other) {
+            (Eid::LocalNode { service_number: s1 }, Eid::LocalNode { service_number: s2 }) => {
+                s1.cmp(s2)
+            }
+            (
+                Eid::LegacyIpn {
+                    allocator_id: a1,
+                    node_number: n1,
+                    service_number: s1,
+                }
+                | Eid::Ipn {
+                    allocator_id: a1,
+                    node_number: n1,
+                    service_number: s1,
+                },
+                Eid::LegacyIpn {
+                    allocator_id: a2,
+                    node_number: n2,
+                    service_number: s2,
+                }
+                | Eid::Ipn {
+                    allocator_id: a2,
+                    node_number: n2,
+                    service_number: s2,
+                },
+            ) => (a1, n1, s1).cmp(&(a2, n2, s2)),
+            (
+                Eid::Dtn {
+                    node_name: n1,
+                    demux: d1,
+                },
+                Eid::Dtn {
+                    node_name: n2,
+                    demux: d2,
+                },
+            ) => (n1, d1).cmp(&(n2, d2)),
+            (
+                Eid::Unknown {
+                    scheme: s1,
+                    data: d1,
+                },
+                Eid::Unknown {
+                    scheme: s2,
+                    data: d2,
+                },
+            ) => (s1, d1).cmp(&(s2, d2)),
+            _ => self.rank().cmp(&other.rank()),
+        }
+    }
+}
+
+impl std::hash::Hash for Eid {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.rank().hash(state);
+        match self {
+            Eid::Null => {}
+            Eid::LocalNode { service_number } => service_number.hash(state),
+            Eid::LegacyIpn {
+                allocator_id,
+                node_number,
+                service_number,
+            }
+            | Eid::Ipn {
+                allocator_id,
+                node_number,
+                service_number,
+            } => (allocator_id, node_number, service_number).hash(state),
+            Eid::Dtn { node_name, demux } => (node_name, demux).hash(state),
+            Eid::Unknown { scheme, data } => (scheme, data).hash(state),
+        }
+    }
+}
+
+/* The service part of an endpoint.  Service number 0 and the empty dtn demux
+ * are reserved for the administrative endpoint */
+#[derive(Debug, Clone, Hash, PartialEq, Eq)]
+pub enum Service {
+    Ipn(u32),
+    Dtn(Box<str>),
+}
+
+impl Service {
+    pub fn is_administrative(&self) -> bool {
+        match self {
+            Service::Ipn(service_number) => *service_number == 0,
+            Service::Dtn(demux) => demux.is_empty(),
+        }
+    }
+}
+
+impl std::fmt::Display for Service {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Service::Ipn(service_number) => write!(f, "{service_number}"),
+            Service::Dtn(demux) => write!(f, "{demux}"),
+        }
+    }
+}
+
+impl Eid {
+    /* Is this any spelling of the null endpoint: dtn:none, or an ipn EID with
+     * allocator and node number 0, however it was constructed */
+    pub fn is_null(&self) -> bool {
+        matches!(
+            self,
+            Eid::Null
+                | Eid::Ipn {
+                    allocator_id: 0,
+                    node_number: 0,
+                    ..
+                }
+                | Eid::LegacyIpn {
+                    allocator_id: 0,
+                    node_number: 0,
+                    ..
+                }
+        )
+    }
+
+    // Collapse every spelling of the null endpoint to Eid::Null
+    pub fn normalize(self) -> Self {
+        if self.is_null() {
+            Eid::Null
+        } else {
+            self
+        }
+    }
+
+    /* The service part of the endpoint, the service number of an ipn EID or the demux of a dtn EID,
+     * as used when registering a service.  The null endpoint has no service */
+    pub fn service(&self) -> Option<Service> {
+        if self.is_null() {
+            return None;
+        }
+        match self {
+            Eid::LocalNode { service_number }
+            | Eid::LegacyIpn { service_number, .. }
+            | Eid::Ipn { service_number, .. } => Some(Service::Ipn(*service_number)),
+            Eid::Dtn { demux, .. } => Some(Service::Dtn(demux.join("/").into())),
+            Eid::Null | Eid::Unknown { .. } => None,
+        }
+    }
+
+    // Is this the administrative endpoint of a node: ipn service number 0, or a dtn EID with no demux
+    pub fn is_administrative(&self) -> bool {
+        match self {
+            Eid::LocalNode { service_number }
+            | Eid::LegacyIpn { service_number, .. }
+            | Eid::Ipn { service_number, .. } => *service_number == 0 && !self.is_null(),
+            // 'dtn://node/' parses with a single empty demux part
+            Eid::Dtn { demux, .. } => demux.iter().all(|s| s.is_empty()),
+            Eid::Null | Eid::Unknown { .. } => false,
+        }
+    }
+
+    /* Does this endpoint have at most one member node.  The null endpoint has none, and
+     * RFC 9171 section 4.2.5.1.1 reserves a dtn demux starting with '~' for non-singleton endpoints */
+    pub fn is_singleton(&self) -> bool {
+        match self {
+            _ if self.is_null() => false,
+            Eid::Dtn { demux, .. } => !demux.first().is_some_and(|s| s.starts_with('~')),
+            _ => true,
+        }
+    }
+
+    /* A compact key for use in maps and storage.  Equivalent encodings of the same EID,
+     * e.g. legacy 2-element and 3-element ipn EIDs, produce the same key */
+    pub fn to_key(&self) -> String {
+        BASE64_STANDARD_NO_PAD.encode(match self {
+            Eid::LegacyIpn {
+                allocator_id,
+                node_number,
+                service_number,
+            } => cbor::encode::emit(&Eid::Ipn {
+                allocator_id: *allocator_id,
+                node_number: *node_number,
+                service_number: *service_number,
+            }),
+            _ => cbor::encode::emit(self),
+        })
+    }
+
+    pub fn from_key(k: &str) -> Result<Self, EidError> {
+        let data = BASE64_STANDARD_NO_PAD.decode(k)?;
+        match <Self as cbor::decode::FromCbor>::try_from_cbor(&data)? {
+            Some((eid, _, len)) if len == data.len() => Ok(eid),
+            _ => Err(EidError::BadKey),
+        }
+    }
+}
+
+// RFC 3986 pchar, less the escapes themselves, with anything beyond ASCII left as UTF-8
+fn is_pchar(c: char) -> bool {
+    c.is_ascii_alphanumeric() || "-._~!$&'()*+,;=:@".contains(c) || !c.is_ascii()
+}
+
+fn encode_pchars(s: &str) -> std::borrow::Cow<'_, str> {
+    if s.chars().all(is_pchar) {
+        return s.into();
+    }
+    s.chars()
+        .fold(String::with_capacity(s.len()), |mut r, c| {
+            if is_pchar(c) {
+                r.push(c);
+            } else {
+                r.push_str(&format!("%{:02X}", c as u32));
+            }
+            r
+        })
+        .into()
+}
+
+/* The percent-encoded 'node-name/demux' part of a dtn URI.  Each part is encoded separately,
+ * so a '/' within a demux segment is escaped, and this is the only encoding of an EID:
+ * parsing any other encoding of the same EID is not canonical */
+fn dtn_ssp(node_name: &str, demux: &[Box<str>]) -> String {
+    format!(
+        "{}/{}",
+        encode_pchars(node_name),
+        demux
+            .iter()
+            .map(|s| encode_pchars(s))
+            .collect::<Vec<std::borrow::Cow<str>>>()
+            .join("/")
+    )
+}
+
 impl cbor::encode::ToCbor for &Eid {
     fn to_cbor(self, encoder: &mut cbor::encode::Encoder) {
         encoder.emit_array(Some(2), |a| match self {
@@ -48,15 +307,7 @@ impl cbor::encode::ToCbor for &Eid {
             }
             Eid::Dtn { node_name, demux } => {
                 a.emit(1);
-                a.emit(format!(
-                    "//{}/{}",
-                    urlencoding::encode(node_name),
-                    demux
-                        .iter()
-                        .map(|s| urlencoding::encode(s))
-                        .collect::<Vec<std::borrow::Cow<str>>>()
-                        .join("/")
-                ));
+                a.emit(format!("//{}", dtn_ssp(node_name, demux)));
             }
             Eid::LegacyIpn {
                 allocator_id,
@@ -161,16 +412,7 @@ impl std::fmt::Display for Eid {
                 node_number,
                 service_number,
             } => write!(f, "ipn:{allocator_id}.{node_number}.{service_number}"),
-            Eid::Dtn { node_name, demux } => write!(
-                f,
-                "dtn://{}/{}",
-                urlencoding::encode(node_name),
-                demux
-                    .iter()
-                    .map(|s| urlencoding::encode(s))
-                    .collect::<Vec<std::borrow::Cow<str>>>()
-                    .join("/")
-            ),
+            Eid::Dtn { node_name, demux } => write!(f, "dtn://{}", dtn_ssp(node_name, demux)),
             Eid::Unknown { scheme, data } => {
                 let r = cbor::decode::parse_value(data, |mut value, _, _| {
                     write!(f, "unknown({scheme}):{value:?}").map_err(Into::<DebugError>::into)?;
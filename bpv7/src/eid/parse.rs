@@ -2,18 +2,28 @@ use super::*;
 
 use error::CaptureFieldErr;
 
-fn parse_dtn_parts(s: &str) -> Result<Eid, EidError> {
+/* Parse 'node-name/demux', noting whether each part is percent-encoded exactly as it would be
+ * emitted.  Each part is decoded once, and only allocated for the EID itself */
+fn parse_dtn_parts(s: &str) -> Result<(Eid, bool), EidError> {
     if let Some((s1, s2)) = s.split_once('/') {
         if s1.is_empty() {
             Err(EidError::DtnNodeNameEmpty)
         } else {
-            let node_name = urlencoding::decode(s1)?.into();
-            let demux = s2
-                .split('/')
-                .try_fold(Vec::new(), |mut v: Vec<Box<str>>, s| {
-                    v.push(urlencoding::decode(s)?.into());
+            let mut canonical = true;
+            let mut decode = |s: &str| {
+                let decoded = urlencoding::decode(s)?;
+                canonical = canonical && encode_pchars(&decoded) == s;
+                Ok::<Box<str>, EidError>(decoded.into())
+            };
+
+            let node_name = decode(s1)?;
+            let demux = s2.split('/').try_fold(
+                Vec::with_capacity(s2.split('/').count()),
+                |mut v: Vec<Box<str>>, s| {
+                    v.push(decode(s)?);
                     Ok::<_, EidError>(v)
-                })?;
+                },
+            )?;
 
             for (idx, s) in demux.iter().enumerate() {
                 if s.is_empty() && idx != demux.len() - 1 {
@@ -21,16 +31,24 @@ fn parse_dtn_parts(s: &str) -> Result<Eid, EidError> {
                 }
             }
 
-            Ok(Eid::Dtn {
-                node_name,
-                demux: demux.into(),
-            })
+            Ok((
+                Eid::Dtn {
+                    node_name,
+                    demux: demux.into(),
+                },
+                canonical,
+            ))
         }
     } else {
         Err(EidError::DtnMissingSlash)
     }
 }
 
+// Percent-encoding that differs from how the EID would be emitted is not canonical
+fn dtn_from_cbor(s: &str, shortest: bool) -> Result<(Eid, bool), EidError> {
+    parse_dtn_parts(s).map(|(eid, canonical)| (eid, shortest && canonical))
+}
+
 fn ipn_from_parts(
     elements: usize,
     allocator_id: u32,
@@ -62,17 +80,29 @@ fn ipn_from_parts(
     }
 }
 
+/* The first component of a 2-part ipn EID is the fully-qualified node number, the allocator
+ * identifier packed above the node number as in the 2-element CBOR encoding, and may be written
+ * in hex with a 0x prefix.  A value that fits in 32 bits is a node number in the default
+ * allocator, so 'ipn:1.2' and 'ipn:0.1.2' remain the same EID.  Either way the result is the
+ * same EID as the 3-part form, not LegacyIpn, which only describes the CBOR encoding */
+fn fqnn_from_str(s: &str) -> Result<u64, EidError> {
+    if s == "!" {
+        Ok(u32::MAX as u64)
+    } else if let Some(hex) = s.strip_prefix("0x") {
+        u64::from_str_radix(hex, 16).map_field_err("node number")
+    } else {
+        s.parse().map_field_err("node number")
+    }
+}
+
 fn ipn_from_str(s: &str) -> Result<Eid, EidError> {
     let parts = s.split('.').collect::<Vec<&str>>();
     if parts.len() == 2 {
-        let mut node_number = u32::MAX;
-        if parts[0] != "!" {
-            node_number = parts[0].parse().map_field_err("node number")?;
-        }
+        let fqnn = fqnn_from_str(parts[0])?;
         ipn_from_parts(
             3,
-            0,
-            node_number,
+            (fqnn >> 32) as u32,
+            fqnn as u32,
             parts[1].parse().map_field_err("service number")?,
         )
         .map(|e| e.0)
@@ -95,7 +125,7 @@ impl std::str::FromStr for Eid {
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         if let Some(s) = s.strip_prefix("dtn:") {
             if let Some(s) = s.strip_prefix("//") {
-                parse_dtn_parts(s)
+                parse_dtn_parts(s).map(|(eid, _)| eid)
             } else if s == "none" {
                 Ok(Eid::Null)
             } else {
@@ -160,7 +190,7 @@ impl cbor::decode::FromCbor for Eid {
                         | cbor::decode::Value::Text("none") => Ok((Eid::Null, shortest)),
                         cbor::decode::Value::Text(s) => {
                             if let Some(s) = s.strip_prefix("//") {
-                                parse_dtn_parts(s).map(|e| (e, shortest))
+                                dtn_from_cbor(s, shortest)
                             } else {
                                 Err(EidError::DtnMissingPrefix)
                             }
@@ -179,7 +209,7 @@ impl cbor::decode::FromCbor for Eid {
                                 })?
                                 .strip_prefix("//")
                             {
-                                parse_dtn_parts(s).map(|e| (e, shortest))
+                                dtn_from_cbor(s, shortest)
                             } else {
                                 Err(EidError::DtnMissingPrefix)
                             }
@@ -10,6 +10,14 @@ fn tests() {
     ipn_check("ipn:977000.1.3", 977000, 1, 3);
     ipn_check("ipn:977000.1.0", 977000, 1, 0);
 
+    ipn_check("ipn:4196183048192001.3", 977000, 1, 3);
+    ipn_check("ipn:0xee86800000001.3", 977000, 1, 3);
+    ipn_check("ipn:0x1.2", 0, 1, 2);
+    assert!(matches!(
+        "ipn:4196183048192001.3".parse().unwrap(),
+        Eid::Ipn { .. }
+    ));
+    local_node_check("ipn:4294967295.7", 7);
     local_node_check("ipn:!.7", 7);
     local_node_check("ipn:!.0", 0);
 
@@ -102,6 +110,81 @@ fn tests() {
     );
 }
 
+#[test]
+fn keys() {
+    let key = |s: &str| s.parse::<Eid>().expect("Failed to parse").to_key();
+
+    // Equivalent spellings share a key
+    assert_eq!(key("ipn:1.5"), key("ipn:0.1.5"));
+    assert_eq!(key("dtn://node/a%20b"), key("dtn://node/a b"));
+    assert_eq!(
+        key("ipn:977000.1.1"),
+        Eid::LegacyIpn {
+            allocator_id: 977000,
+            node_number: 1,
+            service_number: 1
+        }
+        .to_key()
+    );
+    assert_ne!(key("ipn:1.5"), key("ipn:1.1.5"));
+    assert_ne!(key("ipn:1.5"), key("dtn://node/svc"));
+
+    // And round-trip to the canonical form
+    for s in [
+        "dtn:none",
+        "ipn:1.5",
+        "ipn:977000.1.1",
+        "ipn:!.7",
+        "dtn://node/a/b",
+    ] {
+        assert_eq!(Eid::from_key(&key(s)).unwrap(), s.parse::<Eid>().unwrap());
+    }
+
+    assert!(matches!(Eid::from_key("!"), Err(EidError::BadBase64(_))));
+    // Trailing data after the EID
+    assert!(matches!(Eid::from_key("ggKCAQUA"), Err(EidError::BadKey)));
+}
+
+#[test]
+fn null_equivalence() {
+    let nulls = [
+        "dtn:none".parse::<Eid>().unwrap(),
+        "ipn:0.0".parse::<Eid>().unwrap(),
+        "ipn:0.0.0".parse::<Eid>().unwrap(),
+        cbor::decode::parse::<Eid>(&[0x82, 0x01, 0x00]).unwrap(),
+        cbor::decode::parse::<Eid>(&[0x82, 0x02, 0x82, 0x00, 0x00]).unwrap(),
+        Eid::Ipn {
+            allocator_id: 0,
+            node_number: 0,
+            service_number: 0,
+        },
+        Eid::LegacyIpn {
+            allocator_id: 0,
+            node_number: 0,
+            service_number: 0,
+        },
+    ];
+    for eid in nulls {
+        assert!(eid.is_null(), "{eid} is not null");
+        assert_eq!(eid.clone().normalize(), Eid::Null);
+
+        // Patterns and maps treat them all the same
+        assert!("dtn:none".parse::<EidPattern>().unwrap().is_match(&eid));
+        assert!("ipn:0.0.0".parse::<EidPattern>().unwrap().is_match(&eid));
+
+        let mut m = EidPatternMap::new();
+        m.insert(&"dtn:none".parse().unwrap(), 1, ());
+        m.insert(&"ipn:0.0.0".parse().unwrap(), 2, ());
+        assert_eq!(m.find(&eid).len(), 2, "{eid} not found");
+    }
+
+    for s in ["ipn:0.1.0", "ipn:!.0", "ipn:1.0.0", "dtn://none/"] {
+        let eid = s.parse::<Eid>().unwrap();
+        assert!(!eid.is_null(), "{s} is null");
+        assert_eq!(eid.clone().normalize(), eid);
+    }
+}
+
 fn expect_error(s: &str) -> EidError {
     s.parse::<Eid>().expect_err("Parsed successfully!")
 }
@@ -120,6 +203,52 @@ fn local_node_check(s: &str, expected_service_number: u32) {
     assert_eq!(expected_service_number, service_number);
 }
 
+#[test]
+fn service() {
+    for (s, expected) in [
+        ("ipn:1.0", Some(Service::Ipn(0))),
+        ("ipn:0.1.0", Some(Service::Ipn(0))),
+        ("ipn:!.0", Some(Service::Ipn(0))),
+        ("ipn:1.7", Some(Service::Ipn(7))),
+        ("ipn:977000.1.3", Some(Service::Ipn(3))),
+        ("dtn://somewhere/", Some(Service::Dtn("".into()))),
+        ("dtn://somewhere/else", Some(Service::Dtn("else".into()))),
+        (
+            "dtn://somewhere/over/the/rainbow",
+            Some(Service::Dtn("over/the/rainbow".into())),
+        ),
+        ("ipn:0.0", None),
+        ("dtn:none", None),
+    ] {
+        let eid: Eid = s.parse().unwrap();
+        assert_eq!(eid.service(), expected, "{s}");
+        assert_eq!(
+            eid.is_administrative(),
+            expected.as_ref().is_some_and(Service::is_administrative),
+            "{s}"
+        );
+    }
+}
+
+#[test]
+fn singleton() {
+    for (s, expected) in [
+        ("ipn:1.0", true),
+        ("ipn:977000.1.3", true),
+        ("ipn:!.7", true),
+        ("dtn://somewhere/", true),
+        ("dtn://somewhere/else", true),
+        ("dtn://somewhere/else/~not", true),
+        ("dtn://somewhere/~group", false),
+        ("dtn://somewhere/~group/member", false),
+        ("dtn://~somewhere/", true),
+        ("ipn:0.0", false),
+        ("dtn:none", false),
+    ] {
+        assert_eq!(s.parse::<Eid>().unwrap().is_singleton(), expected, "{s}");
+    }
+}
+
 fn ipn_check(
     s: &str,
     expected_allocator_id: u32,
@@ -159,3 +288,32 @@ fn dtn_check(s: &str, expected_node_name: &str, expected_demux: &str) {
         expected_demux
     );
 }
+
+#[test]
+fn equivalent_ipn() {
+    use std::collections::{BTreeSet, HashSet};
+    use std::hash::{BuildHasher, RandomState};
+
+    let legacy = Eid::LegacyIpn {
+        allocator_id: 0,
+        node_number: 1,
+        service_number: 5,
+    };
+    let ipn: Eid = "ipn:0.1.5".parse().unwrap();
+    assert!(matches!(ipn, Eid::Ipn { .. }));
+
+    assert_eq!(legacy, ipn);
+    assert_eq!(legacy.cmp(&ipn), std::cmp::Ordering::Equal);
+    let state = RandomState::new();
+    assert_eq!(state.hash_one(&legacy), state.hash_one(&ipn));
+
+    // So sets and maps treat them as one endpoint
+    assert_eq!(HashSet::from([legacy.clone(), ipn.clone()]).len(), 1);
+    assert_eq!(BTreeSet::from([legacy.clone(), ipn.clone()]).len(), 1);
+
+    // But other parts still distinguish them, and the variants keep their order
+    assert_ne!(legacy, "ipn:0.1.6".parse().unwrap());
+    assert!(ipn < "ipn:0.2.0".parse().unwrap());
+    assert!(Eid::Null < ipn && ipn < "dtn://somewhere/".parse().unwrap());
+    assert!(Eid::LocalNode { service_number: 9 } < legacy);
+}
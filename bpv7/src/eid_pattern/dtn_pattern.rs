@@ -17,7 +17,7 @@ impl DtnPatternItem {
 
     pub fn is_match(&self, eid: &Eid) -> bool {
         match self {
-            DtnPatternItem::None => matches!(eid, Eid::Null),
+            DtnPatternItem::None => eid.is_null(),
             DtnPatternItem::DtnSsp(s) => s.is_match(eid),
         }
     }
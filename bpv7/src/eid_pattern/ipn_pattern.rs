@@ -48,11 +48,23 @@ impl IpnPatternItem {
     }
 
     pub(super) fn is_exact(&self) -> Option<Eid> {
-        Some(Eid::Ipn {
-            allocator_id: self.allocator_id.is_exact()?,
-            node_number: self.node_number.is_exact()?,
-            service_number: self.service_number.is_exact()?,
-        })
+        Some(
+            Eid::Ipn {
+                allocator_id: self.allocator_id.is_exact()?,
+                node_number: self.node_number.is_exact()?,
+                service_number: self.service_number.is_exact()?,
+            }
+            .normalize(),
+        )
+    }
+
+    pub(super) fn is_node(&self) -> Option<(u32, u32)> {
+        match self.service_number {
+            IpnPattern::Wildcard => {
+                Some((self.allocator_id.is_exact()?, self.node_number.is_exact()?))
+            }
+            IpnPattern::Range(_) => None,
+        }
     }
 
     /*
@@ -27,7 +27,18 @@ impl EidPattern {
         }
     }
 
-    pub(super) fn is_exact(&self) -> Option<Eid> {
+    // A pattern matching any EID matched by either pattern
+    pub fn union(self, other: EidPattern) -> Self {
+        match (self, other) {
+            (EidPattern::Set(a), EidPattern::Set(b)) => {
+                EidPattern::Set(a.into_vec().into_iter().chain(b.into_vec()).collect())
+            }
+            _ => EidPattern::Any,
+        }
+    }
+
+    // The only EID matched by the pattern, if there is just one
+    pub fn is_exact(&self) -> Option<Eid> {
         match self {
             EidPattern::Any => None,
             EidPattern::Set(items) => {
@@ -39,6 +50,17 @@ impl EidPattern {
             }
         }
     }
+
+    // The allocator and node numbers, if the pattern matches every service of a single ipn node
+    pub fn is_ipn_node(&self) -> Option<(u32, u32)> {
+        match self {
+            EidPattern::Set(items) if items.len() == 1 => match &items[0] {
+                EidPatternItem::IpnPatternItem(i) => i.is_node(),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
 }
 
 /*
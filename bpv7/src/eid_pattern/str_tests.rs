@@ -195,6 +195,37 @@ fn tests() {
     );
 }
 
+#[test]
+fn ipn_node() {
+    assert_eq!(
+        "ipn:0.3.*".parse::<EidPattern>().unwrap().is_ipn_node(),
+        Some((0, 3))
+    );
+    assert_eq!(
+        "ipn:977.3.*".parse::<EidPattern>().unwrap().is_ipn_node(),
+        Some((977, 3))
+    );
+    assert_eq!(
+        "ipn:0.3.4".parse::<EidPattern>().unwrap().is_ipn_node(),
+        None
+    );
+    assert_eq!(
+        "ipn:0.*.*".parse::<EidPattern>().unwrap().is_ipn_node(),
+        None
+    );
+    assert_eq!(
+        "ipn:0.3.*|ipn:0.4.*"
+            .parse::<EidPattern>()
+            .unwrap()
+            .is_ipn_node(),
+        None
+    );
+    assert_eq!(
+        "dtn://node/**".parse::<EidPattern>().unwrap().is_ipn_node(),
+        None
+    );
+}
+
 fn ipn_match(s: &str, expected: IpnPatternItem) {
     match s.parse().expect("Failed to parse") {
         EidPattern::Set(v) => {
@@ -159,6 +159,9 @@ where
     }
 
     pub fn find(&self, eid: &Eid) -> Vec<&T> {
+        // Look up every spelling of the null endpoint as Eid::Null
+        let eid = if eid.is_null() { &Eid::Null } else { eid };
+
         // Get "anys"
         let mut results = self.any.values().collect::<Vec<&T>>();
 
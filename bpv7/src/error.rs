@@ -27,21 +27,48 @@ pub enum Error {
     #[error("{1} block cannot be block number {0}")]
     InvalidBlockNumber(u64, BlockType),
 
+    #[error("Bundle has no block number {0}")]
+    MissingBlock(u64),
+
     #[error("Bundle has multiple {0} blocks")]
     DuplicateBlocks(BlockType),
 
+    #[error("Bundle has more than {0} blocks")]
+    TooManyBlocks(usize),
+
     #[error("Bundle source has no clock, and there is no Bundle Age extension block")]
     MissingBundleAge,
 
     #[error("Block {0} has an unsupported block type or block content sub-type")]
     Unsupported(u64),
 
+    #[error("Invalid class of service {0}")]
+    InvalidClassOfService(u64),
+
     #[error("Invalid bundle flag combination")]
     InvalidFlags,
 
+    #[error("Status reports are requested, but the report-to EID is null")]
+    NullReportTo,
+
+    #[error("Status reports are requested, but the report-to EID {0} is not a singleton endpoint")]
+    NonSingletonReportTo(Eid),
+
+    #[error("The bundle would expire before it was created")]
+    ExpiryBeforeCreation,
+
     #[error("Block {0} is not in canonical form")]
     NonCanonical(u64),
 
+    #[error("Bundle must not be fragmented")]
+    DoNotFragment,
+
+    #[error("Cannot fragment a bundle with an encrypted payload")]
+    EncryptedPayload,
+
+    #[error("Bundle cannot be fragmented to fit within {0} bytes")]
+    FragmentTooSmall(usize),
+
     #[error(transparent)]
     InvalidBPSec(#[from] bpsec::Error),
 
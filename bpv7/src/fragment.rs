@@ -0,0 +1,241 @@
+use super::*;
+
+fn header_len(len: usize) -> usize {
+    cbor::encode::emit(len as u64).len()
+}
+
+impl Bundle {
+    /* Split the bundle into fragments of no more than `max_bundle_size` bytes, per RFC9171 Section 5.8.
+     * Extension blocks flagged 'must replicate' are copied into every fragment, others only into the first */
+    pub fn fragment(
+        &self,
+        source_data: &[u8],
+        max_bundle_size: usize,
+    ) -> Result<Vec<Vec<u8>>, Error> {
+        if source_data.len() <= max_bundle_size {
+            return Ok(vec![source_data.to_vec()]);
+        }
+
+        if self.flags.do_not_fragment {
+            return Err(Error::DoNotFragment);
+        }
+
+        let payload_block = self.blocks.get(&1).ok_or(Error::MissingPayload)?;
+        if payload_block.bcb.is_some() {
+            return Err(Error::EncryptedPayload);
+        }
+
+        let payload =
+            cbor::decode::parse_value(payload_block.payload(source_data), |v, _, tags| match v {
+                cbor::decode::Value::Bytes(data) => Ok(data.to_vec()),
+                cbor::decode::Value::ByteStream(data) => Ok(data.concat()),
+                _ => Err(cbor::decode::Error::IncorrectType(
+                    "Byte String".to_string(),
+                    v.type_name(!tags.is_empty()),
+                )),
+            })
+            .map(|v| v.0)?;
+
+        // Refragmenting keeps the offsets relative to the original payload
+        let (base_offset, total_len) = match &self.id.fragment_info {
+            Some(fragment_info) => (fragment_info.offset, fragment_info.total_len),
+            None => (0, payload.len() as u64),
+        };
+
+        // Keep the extension blocks in their original order
+        let mut extension_blocks = self
+            .blocks
+            .iter()
+            .filter(|(block_number, _)| **block_number > 1)
+            .collect::<Vec<_>>();
+        extension_blocks.sort_by_key(|(_, block)| block.data_start);
+
+        let mut fragments = Vec::new();
+        let mut offset = 0;
+        while offset < payload.len() {
+            let blocks = extension_blocks
+                .iter()
+                .filter(|(_, block)| fragments.is_empty() || block.flags.must_replicate)
+                .map(|(block_number, block)| (**block_number, *block))
+                .collect::<Vec<_>>();
+
+            // Size the fragment with an empty payload and the largest possible offset
+            let overhead = self
+                .emit_fragment(
+                    source_data,
+                    &blocks,
+                    payload_block,
+                    base_offset + payload.len() as u64,
+                    total_len,
+                    &[],
+                )
+                .len();
+            let available = max_bundle_size.saturating_sub(overhead);
+            let len = available
+                .saturating_sub(header_len(available) - 1)
+                .min(payload.len() - offset);
+            if len == 0 {
+                return Err(Error::FragmentTooSmall(max_bundle_size));
+            }
+
+            fragments.push(self.emit_fragment(
+                source_data,
+                &blocks,
+                payload_block,
+                base_offset + offset as u64,
+                total_len,
+                &payload[offset..offset + len],
+            ));
+            offset += len;
+        }
+        Ok(fragments)
+    }
+
+    fn emit_fragment(
+        &self,
+        source_data: &[u8],
+        blocks: &[(u64, &Block)],
+        payload_block: &Block,
+        offset: u64,
+        total_len: u64,
+        payload: &[u8],
+    ) -> Vec<u8> {
+        let mut fragment = Bundle {
+            id: self.id.fragment(offset, total_len),
+            flags: BundleFlags {
+                is_fragment: true,
+                ..self.flags.clone()
+            },
+            crc_type: self.crc_type,
+            destination: self.destination.clone(),
+            report_to: self.report_to.clone(),
+            lifetime: self.lifetime,
+            ..Default::default()
+        };
+
+        cbor::encode::emit_array(None, |a| {
+            fragment.emit_primary_block(a);
+
+            for (_, block) in blocks {
+                block.copy(source_data, a);
+            }
+
+            Block {
+                block_type: BlockType::Payload,
+                flags: payload_block.flags.clone(),
+                crc_type: payload_block.crc_type,
+                data_start: 0,
+                data_len: 0,
+                payload_offset: 0,
+                payload_len: 0,
+                bcb: None,
+            }
+            .emit(1, payload, a);
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn build(do_not_fragment: bool) -> (Bundle, Vec<u8>) {
+        let (_, data) = Builder::new()
+            .flags(BundleFlags {
+                do_not_fragment,
+                ..Default::default()
+            })
+            .source("ipn:1.1".parse().unwrap())
+            .destination("ipn:2.1".parse().unwrap())
+            .add_extension_block(BlockType::PreviousNode)
+            .data(cbor::encode::emit(&"ipn:3.0".parse::<Eid>().unwrap()))
+            .build()
+            .add_extension_block(BlockType::HopCount)
+            .must_replicate(true)
+            .data(cbor::encode::emit(&HopInfo {
+                limit: 30,
+                count: 0,
+            }))
+            .build()
+            .add_payload_block((0..1000).map(|i| i as u8).collect())
+            .build();
+        (parse(&data), data)
+    }
+
+    fn parse(data: &[u8]) -> Bundle {
+        let ValidBundle::Valid(bundle, _) = ValidBundle::parse(data, |_, _| Ok(None)).unwrap()
+        else {
+            panic!("Invalid bundle");
+        };
+        bundle
+    }
+
+    fn payload(bundle: &Bundle, data: &[u8]) -> Vec<u8> {
+        cbor::decode::parse_value(
+            bundle.blocks.get(&1).unwrap().payload(data),
+            |v, _, _| match v {
+                cbor::decode::Value::Bytes(data) => Ok::<_, cbor::decode::Error>(data.to_vec()),
+                _ => panic!("Payload is not a byte string"),
+            },
+        )
+        .unwrap()
+        .0
+    }
+
+    #[test]
+    fn fragment() {
+        let (bundle, data) = build(false);
+        let fragments = bundle.fragment(&data, 300).unwrap();
+        assert!(fragments.len() > 1);
+
+        let mut reassembled = Vec::new();
+        for (i, fragment) in fragments.iter().enumerate() {
+            assert!(fragment.len() <= 300);
+
+            let f = parse(fragment);
+            assert!(f.flags.is_fragment);
+            assert_eq!(f.id.source, bundle.id.source);
+            assert_eq!(f.id.timestamp, bundle.id.timestamp);
+            let fragment_info = f.id.fragment_info.as_ref().unwrap();
+            assert_eq!(fragment_info.offset, reassembled.len() as u64);
+            assert_eq!(fragment_info.total_len, 1000);
+
+            // Only replicated blocks appear after the first fragment
+            assert!(f.hop_count.is_some());
+            assert_eq!(f.previous_node.is_some(), i == 0);
+
+            reassembled.extend(payload(&f, fragment));
+        }
+        assert_eq!(reassembled, payload(&bundle, &data));
+
+        // Refragmenting keeps the original offsets
+        let f = parse(&fragments[1]);
+        let offset = f.id.fragment_info.as_ref().unwrap().offset;
+        let refragments = f.fragment(&fragments[1], 150).unwrap();
+        assert!(refragments.len() > 1);
+        let f = parse(&refragments[0]);
+        assert_eq!(f.id.fragment_info.as_ref().unwrap().offset, offset);
+    }
+
+    #[test]
+    fn no_fragment() {
+        let (bundle, data) = build(true);
+
+        // Small enough already
+        assert_eq!(
+            bundle.fragment(&data, data.len()).unwrap(),
+            vec![data.clone()]
+        );
+
+        assert!(matches!(
+            bundle.fragment(&data, 300),
+            Err(Error::DoNotFragment)
+        ));
+
+        let (bundle, data) = build(false);
+        assert!(matches!(
+            bundle.fragment(&data, 40),
+            Err(Error::FragmentTooSmall(40))
+        ));
+    }
+}
@@ -8,16 +8,21 @@ mod builder;
 mod bundle;
 mod bundle_flags;
 mod bundle_id;
+mod class_of_service;
 mod crc;
 mod creation_timestamp;
+mod diff;
 mod dtn_time;
 mod editor;
 mod eid;
 mod eid_pattern;
 mod eid_pattern_map;
 mod error;
+mod fragment;
 mod hop_info;
+mod payload;
 mod primary_block;
+mod roundtrip;
 mod status_report;
 
 pub mod prelude {
@@ -25,25 +30,31 @@ pub mod prelude {
     pub use super::block_flags::BlockFlags;
     pub use super::block_type::BlockType;
     pub use super::builder::Builder;
-    pub use super::bundle::{Bundle, ValidBundle};
+    pub use super::bundle::{Bundle, ParseLimits, ParseScratch, ReportToPolicy, ValidBundle};
     pub use super::bundle_flags::BundleFlags;
     pub use super::bundle_id::{BundleId, FragmentInfo};
+    pub use super::class_of_service::ClassOfService;
     pub use super::crc::CrcType;
     pub use super::creation_timestamp::CreationTimestamp;
+    pub use super::diff::{diff, BlockChange, BlockDiff};
     pub use super::dtn_time::DtnTime;
     pub use super::editor::Editor;
-    pub use super::eid::{Eid, EidError};
+    pub use super::eid::{Eid, EidError, Service};
     pub use super::eid_pattern::{EidPattern, EidPatternError};
     pub use super::eid_pattern_map::EidPatternMap;
     pub use super::error::Error;
     pub use super::hop_info::HopInfo;
+    pub use super::payload::Payload;
+    pub use super::roundtrip::{roundtrip_check, RoundtripError};
     pub use super::status_report::{
-        AdministrativeRecord, BundleStatusReport, StatusAssertion, StatusReportError,
-        StatusReportReasonCode,
+        AdminRecordType, AdministrativeRecord, BibePdu, BundleStatusReport, StatusAssertion,
+        StatusReportError, StatusReportReasonCode,
     };
 
     pub mod bpsec {
-        pub use super::super::bpsec::{Context, Error, KeyMaterial};
+        pub use super::super::bpsec::{
+            key, verify_mac, Context, Error, KeyMaterial, SecurityBlockInfo,
+        };
     }
 }
 
@@ -0,0 +1,223 @@
+use super::*;
+use bytes::Bytes;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Payload {
+    // The block-type-specific data is a contiguous range of the source data
+    Range(std::ops::Range<usize>),
+    // The data had to be decrypted or reassembled from an indefinite-length byte string
+    Owned(Box<[u8]>),
+}
+
+impl Bundle {
+    /* Get the block-type-specific data of a block, decrypting it if it is the target of a BCB */
+    pub fn block_payload(
+        &self,
+        block_number: u64,
+        source_data: &[u8],
+        mut f: impl FnMut(&Eid, bpsec::Context) -> Result<Option<bpsec::KeyMaterial>, bpsec::Error>,
+    ) -> Result<Option<Payload>, Error> {
+        let Some(block) = self.blocks.get(&block_number) else {
+            return Ok(None);
+        };
+
+        if let Some(bcb_block_number) = block.bcb {
+            let (bcb_block, bcb, _) = self.parse_payload::<bpsec::bcb::OperationSet>(
+                &bcb_block_number,
+                None,
+                source_data,
+            )?;
+            let op = bcb
+                .operations
+                .get(&block_number)
+                .ok_or(bpsec::Error::MissingSecurityTarget)?;
+
+            let key = f(&bcb.source, op.context_id())?;
+            return match op
+                .decrypt(
+                    key.as_ref(),
+                    bpsec::bcb::OperationArgs {
+                        bpsec_source: &bcb.source,
+                        target: block,
+                        target_number: block_number,
+                        source: bcb_block,
+                        source_number: bcb_block_number,
+                        bundle: self,
+                        primary_block: None,
+                        bundle_data: source_data,
+                    },
+                    None,
+                )?
+                .plaintext
+            {
+                Some(plaintext) => Ok(Some(Payload::Owned(plaintext))),
+                None => Err(bpsec::Error::NoKey(bcb.source.clone()).into()),
+            };
+        }
+
+        let data = block.payload(source_data);
+        cbor::decode::parse_value(data, |v, _, tags| match v {
+            cbor::decode::Value::Bytes(payload) => {
+                let start = payload.as_ptr() as usize - source_data.as_ptr() as usize;
+                Ok(Payload::Range(start..start + payload.len()))
+            }
+            cbor::decode::Value::ByteStream(payload) => Ok(Payload::Owned(payload.concat().into())),
+            _ => Err(cbor::decode::Error::IncorrectType(
+                "Byte String".to_string(),
+                v.type_name(!tags.is_empty()),
+            )),
+        })
+        .map(|(payload, _)| Some(payload))
+        .map_err(Into::into)
+    }
+
+    /* Get the payload as Bytes, sharing the buffer of `source_data` where no decryption is required */
+    pub fn payload_bytes(
+        &self,
+        source_data: &Bytes,
+        f: impl FnMut(&Eid, bpsec::Context) -> Result<Option<bpsec::KeyMaterial>, bpsec::Error>,
+    ) -> Result<Option<Bytes>, Error> {
+        Ok(match self.block_payload(1, source_data, f)? {
+            Some(Payload::Range(range)) => Some(source_data.slice(range)),
+            Some(Payload::Owned(data)) => Some(Bytes::from(data.into_vec())),
+            None => None,
+        })
+    }
+
+    /* Classify the administrative record carried in the payload by its leading record type code,
+     * without parsing the record itself.  Returns None if the bundle is not an administrative record */
+    pub fn admin_record_type(&self, source_data: &[u8]) -> Result<Option<AdminRecordType>, Error> {
+        if !self.flags.is_admin_record {
+            return Ok(None);
+        }
+
+        let Some(payload) = self.block_payload(1, source_data, |_, _| Ok(None))? else {
+            return Ok(None);
+        };
+        let data = match &payload {
+            Payload::Range(range) => &source_data[range.clone()],
+            Payload::Owned(data) => data.as_ref(),
+        };
+
+        // A record is a two element array, so peek the type code that follows the array header
+        match data.first() {
+            Some(0x82 | 0x9F) => {}
+            Some(b) => {
+                return Err(cbor::decode::Error::IncorrectType(
+                    "Array".to_string(),
+                    format!("initial byte {b:#04x}"),
+                )
+                .into())
+            }
+            None => return Err(cbor::decode::Error::NotEnoughData.into()),
+        }
+        let code = cbor::decode::parse::<u64>(&data[1..])?;
+        Ok(Some(code.into()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(data: &Bytes, key: Option<&[u8]>) -> Bundle {
+        match ValidBundle::parse(data, |_, _| {
+            Ok(key.map(|k| bpsec::KeyMaterial::SymmetricKey(k.into())))
+        })
+        .unwrap()
+        {
+            ValidBundle::Valid(bundle, _) => bundle,
+            _ => panic!("Invalid bundle"),
+        }
+    }
+
+    #[test]
+    fn admin_record() {
+        let record = AdministrativeRecord::BundleStatusReport(BundleStatusReport {
+            bundle_id: BundleId {
+                source: "ipn:3.1".parse().unwrap(),
+                ..Default::default()
+            },
+            deleted: Some(StatusAssertion(None)),
+            reason: StatusReportReasonCode::LifetimeExpired,
+            ..Default::default()
+        });
+        let (_, data) = Builder::new()
+            .flags(BundleFlags {
+                is_admin_record: true,
+                ..Default::default()
+            })
+            .source("ipn:1.0".parse().unwrap())
+            .destination("ipn:3.1".parse().unwrap())
+            .add_payload_block(cbor::encode::emit(&record))
+            .build();
+        let data = Bytes::from(data);
+        assert_eq!(
+            parse(&data, None).admin_record_type(&data).unwrap(),
+            Some(AdminRecordType::BundleStatusReport)
+        );
+
+        let (_, data) = Builder::new()
+            .source("ipn:1.1".parse().unwrap())
+            .destination("ipn:2.1".parse().unwrap())
+            .add_payload_block(cbor::encode::emit(&record))
+            .build();
+        let data = Bytes::from(data);
+        assert_eq!(parse(&data, None).admin_record_type(&data).unwrap(), None);
+    }
+
+    #[test]
+    fn shared() {
+        let (_, data) = Builder::new()
+            .source("ipn:1.1".parse().unwrap())
+            .destination("ipn:2.1".parse().unwrap())
+            .add_payload_block(b"Hello".to_vec())
+            .build();
+        let data = Bytes::from(data);
+        let bundle = parse(&data, None);
+
+        let payload = bundle
+            .payload_bytes(&data, |_, _| Ok(None))
+            .unwrap()
+            .unwrap();
+        assert_eq!(payload.as_ref(), b"Hello");
+
+        // Points into the original buffer
+        let start = payload.as_ptr() as usize - data.as_ptr() as usize;
+        assert!(start + payload.len() <= data.len());
+        assert_eq!(&data[start..start + payload.len()], b"Hello");
+    }
+
+    #[test]
+    fn decrypted() {
+        // RFC9173 Appendix A.2, with the creation timestamp tweaked and a CRC added
+        let key = hex_literal::hex!("6162636465666768696a6b6c6d6e6f70");
+        let data = Bytes::from_static(&hex_literal::hex!(
+            "9f89070001820282010282028202018202820201820118281a000f424042e4fe850c0201
+            0058508101020182028202018482014c5477656c7665313231323132820201820358
+            1869c411276fecddc4780df42c8a2af89296fabf34d7fae7008204008181820150ef
+            a4b5ac0108e3816c5606479801bc04850101000058233a09c1e63fe23a7f66a59c73
+            03837241e070b02619fc59c5214a22f08cd70795e73e9aff"
+        ));
+        let bundle = parse(&data, Some(&key));
+
+        let payload = bundle
+            .payload_bytes(&data, |_, _| {
+                Ok(Some(bpsec::KeyMaterial::SymmetricKey(key.into())))
+            })
+            .unwrap()
+            .unwrap();
+        assert_eq!(payload.as_ref(), b"Ready to generate a 32-byte payload");
+
+        // An independent buffer
+        let start = data.as_ptr() as usize;
+        let p = payload.as_ptr() as usize;
+        assert!(p + payload.len() <= start || p >= start + data.len());
+
+        // And no key, no payload
+        assert!(matches!(
+            bundle.payload_bytes(&data, |_, _| Ok(None)),
+            Err(Error::InvalidBPSec(bpsec::Error::NoKey(_)))
+        ));
+    }
+}
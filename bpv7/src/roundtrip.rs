@@ -0,0 +1,100 @@
+use super::*;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum RoundtripError {
+    #[error(transparent)]
+    InvalidBundle(#[from] Error),
+
+    #[error("The {0} bundle no longer parses as valid")]
+    NotValid(&'static str),
+
+    #[error("Rewriting the bundle is not idempotent")]
+    NotIdempotent,
+
+    #[error("Rebuilding the bundle changed {0:?}")]
+    Changed(Vec<BlockDiff>),
+}
+
+/* Check that parsing and rebuilding `data` is stable, for use by fuzz targets.
+ * A canonical bundle must rebuild block-for-block identical, and a rewritten bundle must
+ * itself be canonical, so rewriting it again changes nothing.
+ * Invalid bundles have nothing to check */
+pub fn roundtrip_check(
+    data: &[u8],
+    mut f: impl FnMut(&Eid, bpsec::Context) -> Result<Option<bpsec::KeyMaterial>, bpsec::Error>,
+) -> Result<(), RoundtripError> {
+    match ValidBundle::parse(data, &mut f)? {
+        ValidBundle::Valid(bundle, _) => rebuild(&bundle, data, &mut f),
+        ValidBundle::Rewritten(_, data, _) => match ValidBundle::parse(&data, &mut f)? {
+            ValidBundle::Valid(bundle, _) => rebuild(&bundle, &data, &mut f),
+            ValidBundle::Rewritten(..) => Err(RoundtripError::NotIdempotent),
+            ValidBundle::Invalid(..) => Err(RoundtripError::NotValid("rewritten")),
+        },
+        ValidBundle::Invalid(..) => Ok(()),
+    }
+}
+
+fn rebuild(
+    bundle: &Bundle,
+    data: &[u8],
+    f: impl FnMut(&Eid, bpsec::Context) -> Result<Option<bpsec::KeyMaterial>, bpsec::Error>,
+) -> Result<(), RoundtripError> {
+    let rebuilt = Editor::new(bundle, data).build();
+    let ValidBundle::Valid(..) = ValidBundle::parse(&rebuilt, f)? else {
+        return Err(RoundtripError::NotValid("rebuilt"));
+    };
+
+    let changes = diff(data, &rebuilt)?;
+    if changes.is_empty() {
+        Ok(())
+    } else {
+        Err(RoundtripError::Changed(changes))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn build() -> Vec<u8> {
+        Builder::new()
+            .source("ipn:1.1".parse().unwrap())
+            .destination("ipn:2.1".parse().unwrap())
+            .add_extension_block(BlockType::HopCount)
+            .data(cbor::encode::emit(&HopInfo {
+                limit: 30,
+                count: 0,
+            }))
+            .build()
+            .add_payload_block(vec![1, 2, 3])
+            .build()
+            .1
+    }
+
+    #[test]
+    fn canonical() {
+        let data = build();
+        assert!(matches!(
+            ValidBundle::parse(&data, |_, _| Ok(None)),
+            Ok(ValidBundle::Valid(..))
+        ));
+        roundtrip_check(&data, |_, _| Ok(None)).unwrap();
+    }
+
+    #[test]
+    fn rewritten() {
+        // Swap the indefinite length outer array for a definite length one
+        let mut data = build();
+        assert_eq!((data[0], data.pop()), (0x9F, Some(0xFF)));
+        data[0] = 0x83;
+
+        let ValidBundle::Rewritten(_, rewritten, _) =
+            ValidBundle::parse(&data, |_, _| Ok(None)).unwrap()
+        else {
+            panic!("Bundle not rewritten");
+        };
+        assert_eq!(rewritten[0], 0x9F);
+        roundtrip_check(&data, |_, _| Ok(None)).unwrap();
+    }
+}
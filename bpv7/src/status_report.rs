@@ -6,9 +6,15 @@ pub enum StatusReportError {
     #[error("Unknown administrative record type {0}")]
     UnknownAdminRecordType(u64),
 
+    #[error("Unexpected administrative record type {0}")]
+    UnexpectedAdminRecordType(u64),
+
     #[error("Reserved Status Report Reason Code (255)")]
     ReservedStatusReportReason,
 
+    #[error("Administrative record has {0} bytes of trailing data")]
+    AdditionalData(usize),
+
     #[error("Failed to parse {field}: {source}")]
     InvalidField {
         field: &'static str,
@@ -34,7 +40,7 @@ impl<T, E: Into<Box<dyn std::error::Error + Send + Sync>>> CaptureFieldErr<T>
     }
 }
 
-#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum StatusReportReasonCode {
     #[default]
     NoAdditionalInformation,
@@ -57,6 +63,33 @@ pub enum StatusReportReasonCode {
     Unassigned(u64),
 }
 
+impl StatusReportReasonCode {
+    /* The reason codes assigned by RFC 9171 and RFC 9172, with their codes.
+     * 255 is reserved, and every other code converts to Unassigned */
+    pub const REGISTRY: [(StatusReportReasonCode, u64); 17] = [
+        (StatusReportReasonCode::NoAdditionalInformation, 0),
+        (StatusReportReasonCode::LifetimeExpired, 1),
+        (StatusReportReasonCode::ForwardedOverUnidirectionalLink, 2),
+        (StatusReportReasonCode::TransmissionCanceled, 3),
+        (StatusReportReasonCode::DepletedStorage, 4),
+        (StatusReportReasonCode::DestinationEndpointIDUnavailable, 5),
+        (StatusReportReasonCode::NoKnownRouteToDestinationFromHere, 6),
+        (
+            StatusReportReasonCode::NoTimelyContactWithNextNodeOnRoute,
+            7,
+        ),
+        (StatusReportReasonCode::BlockUnintelligible, 8),
+        (StatusReportReasonCode::HopLimitExceeded, 9),
+        (StatusReportReasonCode::TrafficPared, 10),
+        (StatusReportReasonCode::BlockUnsupported, 11),
+        (StatusReportReasonCode::MissingSecurityOperation, 12),
+        (StatusReportReasonCode::UnknownSecurityOperation, 13),
+        (StatusReportReasonCode::UnexpectedSecurityOperation, 14),
+        (StatusReportReasonCode::FailedSecurityOperation, 15),
+        (StatusReportReasonCode::ConflictingSecurityOperation, 16),
+    ];
+}
+
 impl From<StatusReportReasonCode> for u64 {
     fn from(value: StatusReportReasonCode) -> Self {
         match value {
@@ -293,9 +326,118 @@ impl cbor::decode::FromCbor for BundleStatusReport {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AdminRecordType {
+    BundleStatusReport,
+    BundleInBundle,
+    Unknown(u64),
+}
+
+impl From<u64> for AdminRecordType {
+    fn from(value: u64) -> Self {
+        match value {
+            1 => Self::BundleStatusReport,
+            3 => Self::BundleInBundle,
+            v => Self::Unknown(v),
+        }
+    }
+}
+
+/* A Bundle-in-Bundle Encapsulation protocol data unit, see draft-ietf-dtn-bibect.
+ * The encapsulated bundle is carried verbatim, and is not parsed */
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct BibePdu {
+    pub transmission_id: u64,
+    // Zero if custody transfer is not requested
+    pub retransmission_time: DtnTime,
+    pub bundle: Box<[u8]>,
+}
+
+impl BibePdu {
+    // Wrap as the application data unit of an administrative record bundle
+    pub fn wrap(&self) -> Vec<u8> {
+        cbor::encode::emit(&AdministrativeRecord::BundleInBundle(self.clone()))
+    }
+
+    // Unwrap the application data unit of an administrative record bundle
+    pub fn unwrap(payload: &[u8]) -> Result<Self, StatusReportError> {
+        match AdministrativeRecord::parse(payload)? {
+            AdministrativeRecord::BundleInBundle(pdu) => Ok(pdu),
+            AdministrativeRecord::BundleStatusReport(_) => {
+                Err(StatusReportError::UnexpectedAdminRecordType(1))
+            }
+        }
+    }
+}
+
+impl cbor::encode::ToCbor for &BibePdu {
+    fn to_cbor(self, encoder: &mut cbor::encode::Encoder) {
+        encoder.emit_array(Some(3), |a| {
+            a.emit(self.transmission_id);
+            a.emit(self.retransmission_time);
+            a.emit(self.bundle.as_ref());
+        })
+    }
+}
+
+impl cbor::decode::FromCbor for BibePdu {
+    type Error = StatusReportError;
+
+    fn try_from_cbor(data: &[u8]) -> Result<Option<(Self, bool, usize)>, Self::Error> {
+        cbor::decode::try_parse_array(data, |a, mut shortest, tags| {
+            shortest = shortest && tags.is_empty() && a.is_definite();
+
+            let (transmission_id, s) = a.parse().map_field_err("transmission id")?;
+            shortest = shortest && s;
+
+            let (retransmission_time, s) = a.parse().map_field_err("retransmission time")?;
+            shortest = shortest && s;
+
+            let bundle = a
+                .parse_value(|v, s, tags| {
+                    shortest = shortest && s && tags.is_empty();
+                    match v {
+                        cbor::decode::Value::Bytes(data) => Ok(data.into()),
+                        cbor::decode::Value::ByteStream(data) => {
+                            shortest = false;
+                            Ok(data.concat().into())
+                        }
+                        _ => Err(cbor::decode::Error::IncorrectType(
+                            "Byte String".to_string(),
+                            v.type_name(!tags.is_empty()),
+                        )),
+                    }
+                })
+                .map_field_err("encapsulated bundle")?;
+
+            Ok::<_, StatusReportError>((
+                Self {
+                    transmission_id,
+                    retransmission_time,
+                    bundle,
+                },
+                shortest,
+            ))
+        })
+        .map(|o| o.map(|((v, s), len)| (v, s, len)))
+    }
+}
+
 #[derive(Debug)]
 pub enum AdministrativeRecord {
     BundleStatusReport(BundleStatusReport),
+    BundleInBundle(BibePdu),
+}
+
+impl AdministrativeRecord {
+    /* Parse the application data unit of a received administrative record bundle */
+    pub fn parse(payload: &[u8]) -> Result<Self, StatusReportError> {
+        let (record, len) = cbor::decode::parse::<(Self, usize)>(payload)?;
+        if len != payload.len() {
+            return Err(StatusReportError::AdditionalData(payload.len() - len));
+        }
+        Ok(record)
+    }
 }
 
 impl cbor::encode::ToCbor for &AdministrativeRecord {
@@ -305,6 +447,10 @@ impl cbor::encode::ToCbor for &AdministrativeRecord {
                 a.emit(1);
                 a.emit(report);
             }
+            AdministrativeRecord::BundleInBundle(pdu) => {
+                a.emit(3);
+                a.emit(pdu);
+            }
         })
     }
 }
@@ -328,9 +474,167 @@ impl cbor::decode::FromCbor for AdministrativeRecord {
                     let (r, s) = a.parse().map_field_err("bundle status report")?;
                     Ok((Self::BundleStatusReport(r), shortest && s))
                 }
+                3u64 => {
+                    let (r, s) = a.parse().map_field_err("bundle-in-bundle PDU")?;
+                    Ok((Self::BundleInBundle(r), shortest && s))
+                }
                 v => Err(StatusReportError::UnknownAdminRecordType(v)),
             }
         })
         .map(|o| o.map(|((v, s), len)| (v, s, len)))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hex(s: &str) -> Vec<u8> {
+        (0..s.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&s[i..i + 2], 16).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn reason_codes() {
+        for (reason, code) in StatusReportReasonCode::REGISTRY {
+            assert_eq!(u64::from(reason), code);
+            assert_eq!(StatusReportReasonCode::try_from(code).unwrap(), reason);
+        }
+
+        for code in [17, 254, 256] {
+            assert_eq!(
+                StatusReportReasonCode::try_from(code).unwrap(),
+                StatusReportReasonCode::Unassigned(code)
+            );
+            assert_eq!(u64::from(StatusReportReasonCode::Unassigned(code)), code);
+        }
+
+        assert!(matches!(
+            StatusReportReasonCode::try_from(255),
+            Err(StatusReportError::ReservedStatusReportReason)
+        ));
+    }
+
+    #[test]
+    fn round_trip() {
+        let report = BundleStatusReport {
+            bundle_id: BundleId {
+                source: "ipn:1.2".parse().unwrap(),
+                timestamp: CreationTimestamp::now(),
+                fragment_info: Some(FragmentInfo {
+                    offset: 10,
+                    total_len: 100,
+                }),
+            },
+            received: Some(StatusAssertion(Some(DtnTime::now()))),
+            deleted: Some(StatusAssertion(None)),
+            reason: StatusReportReasonCode::LifetimeExpired,
+            ..Default::default()
+        };
+        let data = cbor::encode::emit(&AdministrativeRecord::BundleStatusReport(report.clone()));
+
+        let AdministrativeRecord::BundleStatusReport(parsed) =
+            AdministrativeRecord::parse(&data).unwrap()
+        else {
+            panic!("Not a status report");
+        };
+        assert_eq!(parsed.bundle_id, report.bundle_id);
+        assert_eq!(parsed.reason, report.reason);
+        assert_eq!(
+            parsed.received.unwrap().0.map(|t| t.millisecs()),
+            report.received.unwrap().0.map(|t| t.millisecs())
+        );
+        assert!(parsed.forwarded.is_none());
+        assert!(parsed.delivered.is_none());
+        assert!(matches!(parsed.deleted, Some(StatusAssertion(None))));
+
+        let mut data = data;
+        data.push(0);
+        assert!(matches!(
+            AdministrativeRecord::parse(&data),
+            Err(StatusReportError::AdditionalData(1))
+        ));
+    }
+
+    #[test]
+    fn captured() {
+        // Received report from ipn:2.1, as emitted by dtn7-rs
+        let AdministrativeRecord::BundleStatusReport(report) = AdministrativeRecord::parse(&hex(
+            "8201848482f51a2a05f20081f481f481f4008202820201821a2a05f20000",
+        ))
+        .unwrap() else {
+            panic!("Not a status report");
+        };
+        assert_eq!(
+            report.received.unwrap().0.map(|t| t.millisecs()),
+            Some(0x2a05f200)
+        );
+        assert!(report.forwarded.is_none());
+        assert!(report.delivered.is_none());
+        assert!(report.deleted.is_none());
+        assert_eq!(
+            report.reason,
+            StatusReportReasonCode::NoAdditionalInformation
+        );
+        assert_eq!(report.bundle_id.source, "ipn:2.1".parse().unwrap());
+        assert!(report.bundle_id.fragment_info.is_none());
+
+        assert!(matches!(
+            AdministrativeRecord::parse(&hex("820200")),
+            Err(StatusReportError::UnknownAdminRecordType(2))
+        ));
+    }
+
+    #[test]
+    fn bibe() {
+        let (_, inner) = Builder::new()
+            .source("ipn:1.1".parse().unwrap())
+            .destination("ipn:2.1".parse().unwrap())
+            .add_payload_block(b"Hello".to_vec())
+            .build();
+
+        let pdu = BibePdu {
+            transmission_id: 7,
+            retransmission_time: DtnTime::new(1000),
+            bundle: inner.clone().into(),
+        };
+        let payload = pdu.wrap();
+        let (_, outer) = Builder::new()
+            .flags(BundleFlags {
+                is_admin_record: true,
+                ..Default::default()
+            })
+            .source("ipn:3.0".parse().unwrap())
+            .destination("ipn:4.0".parse().unwrap())
+            .add_payload_block(payload.clone())
+            .build();
+
+        let ValidBundle::Valid(bundle, _) = ValidBundle::parse(&outer, |_, _| Ok(None)).unwrap()
+        else {
+            panic!("Invalid bundle");
+        };
+        assert_eq!(
+            bundle.admin_record_type(&outer).unwrap(),
+            Some(AdminRecordType::BundleInBundle)
+        );
+
+        // The encapsulated bundle is preserved byte for byte
+        let unwrapped = BibePdu::unwrap(&payload).unwrap();
+        assert_eq!(unwrapped, pdu);
+        assert_eq!(unwrapped.bundle.as_ref(), inner.as_slice());
+        assert!(matches!(
+            ValidBundle::parse(&unwrapped.bundle, |_, _| Ok(None)),
+            Ok(ValidBundle::Valid(..))
+        ));
+
+        // Other records are not BIBE PDUs
+        assert!(matches!(
+            BibePdu::unwrap(&hex(
+                "8201848482f51a2a05f20081f481f481f4008202820201821a2a05f20000"
+            )),
+            Err(StatusReportError::UnexpectedAdminRecordType(1))
+        ));
+    }
+}
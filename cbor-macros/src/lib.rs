@@ -0,0 +1,252 @@
+/* Derive macros for hardy_cbor::encode::ToCbor and hardy_cbor::decode::FromCbor.
+ *
+ * A struct is encoded as a definite-length array of its fields in declaration order, which is
+ * the canonical form used throughout BPv7.  ToCbor is implemented for a reference to the struct,
+ * so fields are borrowed rather than moved, and a reference to each field type must implement ToCbor.
+ * Attributes:
+ *
+ *   #[cbor(tag = N)] on the struct tags the array, on a field tags that field's value
+ *   #[cbor(optional)] on a trailing Option<T> field omits it from the array when None
+ *   #[cbor(error = path::Error)] on the struct sets FromCbor::Error, which must implement
+ *     From<hardy_cbor::decode::Error> and From<_> for the error of every field type
+ */
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{format_ident, quote};
+use syn::{parse_macro_input, spanned::Spanned, Data, DeriveInput, Fields, Member};
+
+#[derive(Default)]
+struct StructAttrs {
+    tag: Option<u64>,
+    error: Option<syn::Path>,
+}
+
+struct Field {
+    member: Member,
+    ty: syn::Type,
+    tag: Option<u64>,
+    optional: bool,
+}
+
+fn struct_attrs(input: &DeriveInput) -> syn::Result<StructAttrs> {
+    let mut attrs = StructAttrs::default();
+    for attr in input.attrs.iter().filter(|a| a.path().is_ident("cbor")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("tag") {
+                attrs.tag = Some(meta.value()?.parse::<syn::LitInt>()?.base10_parse()?);
+                Ok(())
+            } else if meta.path.is_ident("error") {
+                attrs.error = Some(meta.value()?.parse()?);
+                Ok(())
+            } else {
+                Err(meta.error("unsupported cbor attribute"))
+            }
+        })?;
+    }
+    Ok(attrs)
+}
+
+fn fields(input: &DeriveInput) -> syn::Result<Vec<Field>> {
+    let Data::Struct(data) = &input.data else {
+        return Err(syn::Error::new(
+            input.span(),
+            "cbor derives only support structs",
+        ));
+    };
+    let fields = match &data.fields {
+        Fields::Named(f) => &f.named,
+        Fields::Unnamed(f) => &f.unnamed,
+        Fields::Unit => {
+            return Err(syn::Error::new(
+                input.span(),
+                "cbor derives do not support unit structs",
+            ))
+        }
+    };
+
+    let mut r = Vec::new();
+    for (i, field) in fields.iter().enumerate() {
+        let mut f = Field {
+            member: field
+                .ident
+                .clone()
+                .map_or_else(|| Member::Unnamed(i.into()), Member::Named),
+            ty: field.ty.clone(),
+            tag: None,
+            optional: false,
+        };
+        for attr in field.attrs.iter().filter(|a| a.path().is_ident("cbor")) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("tag") {
+                    f.tag = Some(meta.value()?.parse::<syn::LitInt>()?.base10_parse()?);
+                    Ok(())
+                } else if meta.path.is_ident("optional") {
+                    f.optional = true;
+                    Ok(())
+                } else {
+                    Err(meta.error("unsupported cbor attribute"))
+                }
+            })?;
+        }
+        if f.optional {
+            // We need the inner type to parse
+            f.ty = option_inner(&field.ty).ok_or_else(|| {
+                syn::Error::new(field.ty.span(), "optional fields must be Option<T>")
+            })?;
+        } else if r.iter().any(|f: &Field| f.optional) {
+            // Only trailing fields can be omitted, or the array becomes ambiguous
+            return Err(syn::Error::new(
+                field.span(),
+                "a required field cannot follow an optional field",
+            ));
+        }
+        r.push(f);
+    }
+    Ok(r)
+}
+
+fn option_inner(ty: &syn::Type) -> Option<syn::Type> {
+    let syn::Type::Path(p) = ty else {
+        return None;
+    };
+    let segment = p.path.segments.last()?;
+    if segment.ident != "Option" {
+        return None;
+    }
+    let syn::PathArguments::AngleBracketed(args) = &segment.arguments else {
+        return None;
+    };
+    match args.args.first()? {
+        syn::GenericArgument::Type(ty) if args.args.len() == 1 => Some(ty.clone()),
+        _ => None,
+    }
+}
+
+#[proc_macro_derive(ToCbor, attributes(cbor))]
+pub fn derive_to_cbor(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    to_cbor(&input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+fn to_cbor(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    let attrs = struct_attrs(input)?;
+    let fields = fields(input)?;
+
+    let required = fields.iter().filter(|f| !f.optional).count();
+    let optional = fields
+        .iter()
+        .filter(|f| f.optional)
+        .map(|f| &f.member)
+        .collect::<Vec<_>>();
+
+    let emits = fields.iter().map(|f| {
+        let member = &f.member;
+        let emit = match f.tag {
+            Some(tag) => quote! { a.emit_tagged(v, [#tag]) },
+            None => quote! { a.emit(v) },
+        };
+        if f.optional {
+            quote! {
+                if let Some(v) = &self.#member {
+                    #emit;
+                }
+            }
+        } else {
+            quote! {
+                let v = &self.#member;
+                #emit;
+            }
+        }
+    });
+
+    let emit_array = match attrs.tag {
+        Some(tag) => quote! { encoder.emit_array_tagged(Some(count), [#tag], |a| { #(#emits)* }) },
+        None => quote! { encoder.emit_array(Some(count), |a| { #(#emits)* }) },
+    };
+
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::hardy_cbor::encode::ToCbor for &#name #ty_generics #where_clause {
+            fn to_cbor(self, encoder: &mut ::hardy_cbor::encode::Encoder) {
+                let count = #required #(+ self.#optional.is_some() as usize)*;
+                #emit_array
+            }
+        }
+    })
+}
+
+#[proc_macro_derive(FromCbor, attributes(cbor))]
+pub fn derive_from_cbor(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    from_cbor(&input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+fn from_cbor(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    let attrs = struct_attrs(input)?;
+    let fields = fields(input)?;
+
+    let parses = fields.iter().enumerate().map(|(i, f)| {
+        let var = format_ident!("f{i}");
+        let ty = &f.ty;
+        let parse = match f.tag {
+            Some(tag) => quote! { a.try_parse_tagged::<#ty>(&[#tag])? },
+            None => quote! { a.try_parse::<(#ty, bool)>()? },
+        };
+        if f.optional {
+            quote! {
+                let #var = match #parse {
+                    Some((v, s)) => {
+                        shortest = shortest && s;
+                        Some(v)
+                    }
+                    None => None,
+                };
+            }
+        } else {
+            quote! {
+                let Some((#var, s)) = #parse else {
+                    return Err(::hardy_cbor::decode::Error::NotEnoughData.into());
+                };
+                shortest = shortest && s;
+            }
+        }
+    });
+
+    let members = fields.iter().enumerate().map(|(i, f)| {
+        let var = format_ident!("f{i}");
+        let member = &f.member;
+        quote! { #member: #var }
+    });
+
+    let check_tags = match attrs.tag {
+        Some(tag) => quote! { ::hardy_cbor::decode::expect_tags(&tags, &[#tag])?; },
+        None => quote! { shortest = shortest && tags.is_empty(); },
+    };
+
+    let error = attrs
+        .error
+        .map_or_else(|| quote! { ::hardy_cbor::decode::Error }, |e| quote! { #e });
+
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::hardy_cbor::decode::FromCbor for #name #ty_generics #where_clause {
+            type Error = #error;
+
+            fn try_from_cbor(data: &[u8]) -> Result<Option<(Self, bool, usize)>, Self::Error> {
+                ::hardy_cbor::decode::try_parse_array(data, |a, shortest, tags| {
+                    let mut shortest = shortest && a.is_definite();
+                    #check_tags
+                    #(#parses)*
+                    Ok::<_, Self::Error>((Self { #(#members),* }, shortest))
+                })
+                .map(|o| o.map(|((v, s), len)| (v, s, len)))
+            }
+        }
+    })
+}
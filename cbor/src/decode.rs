@@ -44,6 +44,12 @@ pub enum Error {
 
     #[error("Loss of floating-point precision")]
     PrecisionLoss,
+
+    #[error("Value is not encoded in shortest form")]
+    NotShortest,
+
+    #[error("Declared length {0} exceeds the remaining data or decode limits")]
+    ExcessiveLength(u64),
 }
 
 pub trait FromCbor: Sized {
@@ -55,6 +61,8 @@ pub trait FromCbor: Sized {
 pub type Sequence<'a> = super::decode_seq::Series<'a, 0>;
 pub type Array<'a> = super::decode_seq::Series<'a, 1>;
 pub type Map<'a> = super::decode_seq::Series<'a, 2>;
+pub use super::decode_lenient::{parse_lenient, OwnedValue};
+pub use super::decode_limits::{check_limits, parse_with_limits, DecodeLimits};
 pub use super::decode_seq::Series;
 
 pub enum Value<'a, 'b: 'a> {
@@ -165,7 +173,7 @@ fn to_array<const N: usize>(data: &[u8]) -> Result<[u8; N], Error> {
     }
 }
 
-fn parse_uint_minor(minor: u8, data: &[u8]) -> Result<(u64, bool, usize), Error> {
+pub(crate) fn parse_uint_minor(minor: u8, data: &[u8]) -> Result<(u64, bool, usize), Error> {
     match minor {
         24 => {
             if data.is_empty() {
@@ -471,6 +479,68 @@ where
     try_parse_map(data, f)?.ok_or(Error::NotEnoughData.into())
 }
 
+/* Parse a definite-length text string, borrowing it from `data` rather than allocating a String.
+ * Tagged and indefinite-length strings cannot be borrowed, and are rejected */
+pub fn try_parse_str(data: &[u8]) -> Result<Option<(&str, bool, usize)>, Error> {
+    if data.is_empty() {
+        return Ok(None);
+    }
+
+    match (data[0] >> 5, data[0] & 0x1F) {
+        (3, minor) if minor != 31 => {
+            let (t, shortest, len) = parse_data_minor(minor, &data[1..])?;
+            Ok(Some((core::str::from_utf8(t)?, shortest, len + 1)))
+        }
+        _ => try_parse_value(data, |value, _, tags| {
+            Err(Error::IncorrectType(
+                "Untagged Definite-length Text String".to_string(),
+                value.type_name(!tags.is_empty()),
+            ))
+        })
+        .map(|_: Option<((), usize)>| None),
+    }
+}
+
+#[inline]
+pub fn parse_str(data: &[u8]) -> Result<(&str, bool, usize), Error> {
+    try_parse_str(data)?.ok_or(Error::NotEnoughData)
+}
+
+// Check that a value carries exactly the tags `expected`, in order
+pub fn expect_tags(tags: &[u64], expected: &[u64]) -> Result<(), Error> {
+    if tags == expected {
+        Ok(())
+    } else {
+        Err(Error::IncorrectType(
+            format!("Value tagged {expected:?}"),
+            format!("Value tagged {tags:?}"),
+        ))
+    }
+}
+
+/* Parse a value that must be preceded by exactly the tags `expected`, which are stripped before
+ * `T` parses the rest, so types that reject tags can still be carried inside one */
+pub fn try_parse_tagged<T>(
+    data: &[u8],
+    expected: &[u64],
+) -> Result<Option<(T, bool, usize)>, T::Error>
+where
+    T: FromCbor,
+    T::Error: From<self::Error>,
+{
+    if data.is_empty() {
+        return Ok(None);
+    }
+
+    let (tags, shortest, offset) = parse_tags(data)?;
+    expect_tags(&tags, expected)?;
+    match T::try_from_cbor(&data[offset..])? {
+        Some((value, s, len)) => Ok(Some((value, shortest && s, offset + len))),
+        None if offset != 0 => Err(Error::JustTags.into()),
+        None => Ok(None),
+    }
+}
+
 pub fn try_parse<T>(data: &[u8]) -> Result<Option<T>, T::Error>
 where
     T: FromCbor,
@@ -0,0 +1,255 @@
+use super::decode::*;
+use alloc::{
+    boxed::Box,
+    string::{String, ToString},
+    vec::Vec,
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum OwnedValue {
+    UnsignedInteger(u64),
+    NegativeInteger(u64),
+    Bytes(Vec<u8>),
+    Text(String),
+    Array(Vec<OwnedValue>),
+    Map(Vec<(OwnedValue, OwnedValue)>),
+    Tagged(u64, Box<OwnedValue>),
+    False,
+    True,
+    Null,
+    Undefined,
+    Simple(u8),
+    Float(f64),
+}
+
+struct Lenient<'a> {
+    data: &'a [u8],
+    offset: usize,
+    errors: Vec<(usize, Error)>,
+    failed: bool,
+}
+
+impl<'a> Lenient<'a> {
+    fn fail<T>(&mut self, offset: usize, e: Error) -> Option<T> {
+        self.errors.push((offset, e));
+        self.failed = true;
+        None
+    }
+
+    fn uint(&mut self, start: usize, minor: u8) -> Option<u64> {
+        match parse_uint_minor(minor, &self.data[self.offset..]) {
+            Ok((v, shortest, len)) => {
+                self.offset += len;
+                if !shortest {
+                    self.errors.push((start, Error::NotShortest));
+                }
+                Some(v)
+            }
+            Err(e) => self.fail(start, e),
+        }
+    }
+
+    fn slice(&mut self, start: usize, minor: u8) -> Option<&'a [u8]> {
+        let len = self.uint(start, minor)?;
+        match usize::try_from(len)
+            .ok()
+            .and_then(|len| self.offset.checked_add(len))
+        {
+            Some(end) if end <= self.data.len() => {
+                let s = &self.data[self.offset..end];
+                self.offset = end;
+                Some(s)
+            }
+            _ => self.fail(start, Error::NotEnoughData),
+        }
+    }
+
+    fn text(&mut self, start: usize, data: &[u8]) -> String {
+        match core::str::from_utf8(data) {
+            Ok(s) => s.to_string(),
+            Err(e) => {
+                self.errors.push((start, e.into()));
+                String::from_utf8_lossy(data).into_owned()
+            }
+        }
+    }
+
+    fn chunked(&mut self, major: u8, max_recursion: usize) -> Vec<u8> {
+        let mut v = Vec::new();
+        while !self.failed {
+            let chunk_start = self.offset;
+            let Some(&b) = self.data.get(chunk_start) else {
+                self.fail::<()>(chunk_start, Error::NotEnoughData);
+                break;
+            };
+            if b == 0xFF {
+                self.offset += 1;
+                break;
+            }
+
+            if b >> 5 != major || b & 0x1F == 31 {
+                // Skip the bad chunk, it is still a well-formed item
+                self.errors.push((chunk_start, Error::InvalidChunk));
+                self.value(max_recursion);
+                continue;
+            }
+
+            self.offset += 1;
+            if let Some(chunk) = self.slice(chunk_start, b & 0x1F) {
+                if major == 3 {
+                    v.extend(self.text(chunk_start, chunk).into_bytes());
+                } else {
+                    v.extend_from_slice(chunk);
+                }
+            }
+        }
+        v
+    }
+
+    fn items(&mut self, count: Option<u64>, max_recursion: usize) -> Vec<OwnedValue> {
+        let mut items = Vec::new();
+        let mut parsed = 0;
+        while !self.failed {
+            match count {
+                Some(count) if parsed >= count => break,
+                Some(_) => {}
+                None => match self.data.get(self.offset) {
+                    None => {
+                        self.fail::<()>(self.offset, Error::NotEnoughData);
+                        break;
+                    }
+                    Some(0xFF) => {
+                        self.offset += 1;
+                        break;
+                    }
+                    _ => {}
+                },
+            }
+            match self.value(max_recursion) {
+                Some(v) => items.push(v),
+                None => break,
+            }
+            parsed += 1;
+        }
+        items
+    }
+
+    fn value(&mut self, max_recursion: usize) -> Option<OwnedValue> {
+        let start = self.offset;
+        let Some(&b) = self.data.get(start) else {
+            return self.fail(start, Error::NotEnoughData);
+        };
+        self.offset += 1;
+
+        // Tags and indefinite-length strings nest too, so count against the recursion limit
+        let nested = matches!((b >> 5, b & 0x1F), (2 | 3, 31) | (4..=6, _));
+        if nested && max_recursion == 0 {
+            return self.fail(start, Error::MaxRecursion);
+        }
+
+        match (b >> 5, b & 0x1F) {
+            (2, 31) => Some(OwnedValue::Bytes(self.chunked(2, max_recursion - 1))),
+            (3, 31) => {
+                let v = self.chunked(3, max_recursion - 1);
+                Some(OwnedValue::Text(String::from_utf8_lossy(&v).into_owned()))
+            }
+            (4 | 5, minor) => {
+                let count = if minor == 31 {
+                    None
+                } else {
+                    Some(self.uint(start, minor)?)
+                };
+                if b >> 5 == 4 {
+                    Some(OwnedValue::Array(self.items(count, max_recursion - 1)))
+                } else {
+                    let mut items =
+                        self.items(count.map(|c| c.saturating_mul(2)), max_recursion - 1);
+                    if items.len() % 2 == 1 {
+                        // Drop the dangling key
+                        self.errors.push((start, Error::PartialMap));
+                        items.pop();
+                    }
+                    let mut pairs = Vec::new();
+                    let mut items = items.into_iter();
+                    while let (Some(k), Some(v)) = (items.next(), items.next()) {
+                        pairs.push((k, v));
+                    }
+                    Some(OwnedValue::Map(pairs))
+                }
+            }
+            (0, minor) => self.uint(start, minor).map(OwnedValue::UnsignedInteger),
+            (1, minor) => self.uint(start, minor).map(OwnedValue::NegativeInteger),
+            (2, minor) => self
+                .slice(start, minor)
+                .map(|s| OwnedValue::Bytes(s.to_vec())),
+            (3, minor) => {
+                let s = self.slice(start, minor)?;
+                Some(OwnedValue::Text(self.text(start, s)))
+            }
+            (6, minor) => {
+                let tag = self.uint(start, minor)?;
+                let v = self.value(max_recursion - 1)?;
+                Some(OwnedValue::Tagged(tag, Box::new(v)))
+            }
+            (7, 20) => Some(OwnedValue::False),
+            (7, 21) => Some(OwnedValue::True),
+            (7, 22) => Some(OwnedValue::Null),
+            (7, 23) => Some(OwnedValue::Undefined),
+            (7, minor @ 0..=19) => Some(OwnedValue::Simple(minor)),
+            (7, 24) => {
+                let Some(&v) = self.data.get(self.offset) else {
+                    return self.fail(start, Error::NotEnoughData);
+                };
+                self.offset += 1;
+                if v < 32 {
+                    self.errors.push((start, Error::InvalidSimpleType(v)));
+                }
+                Some(OwnedValue::Simple(v))
+            }
+            (7, 25) => self.float::<2>(start, |b| half::f16::from_be_bytes(b).into()),
+            (7, 26) => self.float::<4>(start, |b| f32::from_be_bytes(b).into()),
+            (7, 27) => self.float::<8>(start, f64::from_be_bytes),
+            (7, minor) => self.fail(start, Error::InvalidSimpleType(minor)),
+            _ => unreachable!(),
+        }
+    }
+
+    fn float<const N: usize>(
+        &mut self,
+        start: usize,
+        f: impl FnOnce([u8; N]) -> f64,
+    ) -> Option<OwnedValue> {
+        match self
+            .data
+            .get(self.offset..self.offset + N)
+            .and_then(|b| b.try_into().ok())
+        {
+            Some(b) => {
+                self.offset += N;
+                Some(OwnedValue::Float(f(b)))
+            }
+            None => self.fail(start, Error::NotEnoughData),
+        }
+    }
+}
+
+/* Parse a single item, continuing past recoverable errors such as non-shortest encodings,
+ * invalid UTF-8 or bad chunks.  Returns a best-effort value, the length consumed,
+ * and every error found along with the offset of the item that caused it */
+pub fn parse_lenient(
+    data: &[u8],
+    max_recursion: usize,
+) -> (Option<OwnedValue>, usize, Vec<(usize, Error)>) {
+    if data.is_empty() {
+        return (None, 0, Vec::new());
+    }
+
+    let mut p = Lenient {
+        data,
+        offset: 0,
+        errors: Vec::new(),
+        failed: false,
+    };
+    let v = p.value(max_recursion);
+    (v, p.offset, p.errors)
+}
@@ -0,0 +1,148 @@
+use super::decode::*;
+
+/* Caps on the structure of untrusted input, checked before any value is decoded.
+ * Declared lengths are always checked against the remaining data, so a small
+ * input cannot claim a huge string or collection */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DecodeLimits {
+    pub max_depth: usize,
+    pub max_collection_len: usize,
+    pub max_bytes: usize,
+}
+
+impl Default for DecodeLimits {
+    fn default() -> Self {
+        Self {
+            max_depth: 32,
+            max_collection_len: 65536,
+            max_bytes: usize::MAX,
+        }
+    }
+}
+
+struct Checker<'a> {
+    data: &'a [u8],
+    offset: usize,
+    limits: &'a DecodeLimits,
+}
+
+impl Checker<'_> {
+    fn next(&mut self) -> Result<u8, Error> {
+        let b = *self.data.get(self.offset).ok_or(Error::NotEnoughData)?;
+        self.offset += 1;
+        Ok(b)
+    }
+
+    fn uint(&mut self, minor: u8) -> Result<u64, Error> {
+        let (v, _, len) = parse_uint_minor(minor, &self.data[self.offset..])?;
+        self.offset += len;
+        Ok(v)
+    }
+
+    fn remaining(&self) -> u64 {
+        (self.data.len() - self.offset) as u64
+    }
+
+    fn bytes(&mut self, minor: u8) -> Result<(), Error> {
+        let len = self.uint(minor)?;
+        if len > self.remaining() || len > self.limits.max_bytes as u64 {
+            return Err(Error::ExcessiveLength(len));
+        }
+        self.offset += len as usize;
+        Ok(())
+    }
+
+    fn chunks(&mut self, major: u8) -> Result<(), Error> {
+        loop {
+            let b = self.next()?;
+            if b == 0xFF {
+                return Ok(());
+            }
+            if b >> 5 != major || b & 0x1F == 31 {
+                return Err(Error::InvalidChunk);
+            }
+            self.bytes(b & 0x1F)?;
+        }
+    }
+
+    fn items(&mut self, minor: u8, per_entry: u64, depth: usize) -> Result<(), Error> {
+        if depth >= self.limits.max_depth {
+            return Err(Error::MaxRecursion);
+        }
+
+        if minor == 31 {
+            let mut count = 0u64;
+            while self.data.get(self.offset) != Some(&0xFF) {
+                count += 1;
+                if count > (self.limits.max_collection_len as u64).saturating_mul(per_entry) {
+                    return Err(Error::ExcessiveLength(count / per_entry));
+                }
+                self.item(depth + 1)?;
+            }
+            self.offset += 1;
+            Ok(())
+        } else {
+            // Every item is at least one byte long
+            let count = self.uint(minor)?;
+            let items = count.saturating_mul(per_entry);
+            if count > self.limits.max_collection_len as u64 || items > self.remaining() {
+                return Err(Error::ExcessiveLength(count));
+            }
+            for _ in 0..items {
+                self.item(depth + 1)?;
+            }
+            Ok(())
+        }
+    }
+
+    fn item(&mut self, depth: usize) -> Result<(), Error> {
+        let mut b = self.next()?;
+        while b >> 5 == 6 {
+            self.uint(b & 0x1F)?;
+            b = self.next()?;
+        }
+
+        match (b >> 5, b & 0x1F) {
+            (0 | 1, minor) => self.uint(minor).map(|_| ()),
+            (major @ (2 | 3), 31) => self.chunks(major),
+            (2 | 3, minor) => self.bytes(minor),
+            (4, minor) => self.items(minor, 1, depth),
+            (5, minor) => self.items(minor, 2, depth),
+            (7, minor @ 24..=27) => {
+                let len = 1usize << (minor - 24);
+                if (len as u64) > self.remaining() {
+                    return Err(Error::NotEnoughData);
+                }
+                self.offset += len;
+                Ok(())
+            }
+            (7, minor @ 28..=31) => Err(Error::InvalidSimpleType(minor)),
+            _ => Ok(()),
+        }
+    }
+}
+
+/* Walk the first item in `data` without decoding it, failing as soon as a declared length
+ * exceeds the remaining data or `limits`.  Returns the length of the item */
+pub fn check_limits(data: &[u8], limits: &DecodeLimits) -> Result<usize, Error> {
+    if data.len() > limits.max_bytes {
+        return Err(Error::ExcessiveLength(data.len() as u64));
+    }
+
+    let mut c = Checker {
+        data,
+        offset: 0,
+        limits,
+    };
+    c.item(0)?;
+    Ok(c.offset)
+}
+
+pub fn parse_with_limits<T>(data: &[u8], limits: &DecodeLimits) -> Result<T, T::Error>
+where
+    T: FromCbor,
+    T::Error: From<Error>,
+{
+    check_limits(data, limits)?;
+    parse(data)
+}
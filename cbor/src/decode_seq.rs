@@ -162,6 +162,58 @@ impl<'a, const D: usize> Series<'a, D> {
         self.try_parse::<T>()?.ok_or(Error::NotEnoughData.into())
     }
 
+    pub fn try_parse_tagged<T>(&mut self, tags: &[u64]) -> Result<Option<(T, bool)>, T::Error>
+    where
+        T: FromCbor,
+        T::Error: From<self::Error>,
+    {
+        // Check for end of array
+        if self.check_for_end()? {
+            Ok(None)
+        } else {
+            // Parse sub-item
+            let Some((value, shortest, len)) =
+                try_parse_tagged::<T>(&self.data[*self.offset..], tags)?
+            else {
+                return Ok(None);
+            };
+            self.parsed += 1;
+            *self.offset += len;
+            Ok(Some((value, shortest)))
+        }
+    }
+
+    #[inline]
+    pub fn parse_tagged<T>(&mut self, tags: &[u64]) -> Result<(T, bool), T::Error>
+    where
+        T: FromCbor,
+        T::Error: From<self::Error>,
+    {
+        self.try_parse_tagged::<T>(tags)?
+            .ok_or(Error::NotEnoughData.into())
+    }
+
+    // Parse a definite-length text string, borrowed from the underlying data
+    pub fn try_parse_str(&mut self) -> Result<Option<(&'a str, bool)>, Error> {
+        // Check for end of array
+        if self.check_for_end()? {
+            Ok(None)
+        } else {
+            let data = self.data;
+            let Some((s, shortest, len)) = try_parse_str(&data[*self.offset..])? else {
+                return Ok(None);
+            };
+            self.parsed += 1;
+            *self.offset += len;
+            Ok(Some((s, shortest)))
+        }
+    }
+
+    #[inline]
+    pub fn parse_str(&mut self) -> Result<(&'a str, bool), Error> {
+        self.try_parse_str()?.ok_or(Error::NotEnoughData)
+    }
+
     pub fn try_parse_array<T, F, E>(&mut self, f: F) -> Result<Option<T>, E>
     where
         F: FnOnce(&mut Array, bool, Vec<u64>) -> Result<T, E>,
@@ -393,3 +393,159 @@ fn rfc_tests() {
         test_sub_simple(-2, m);
     });
 }
+
+#[test]
+fn lenient() {
+    // Non-shortest array length and integer, invalid UTF-8, invalid simple value and a bad chunk
+    let data = hex!("98041801 62fffe f810 5f416101ff");
+    assert!(parse_value(&data, |mut v, _, _| v.skip(16)).is_err());
+
+    let (v, len, errors) = parse_lenient(&data, 16);
+    assert_eq!(len, data.len());
+    assert_eq!(
+        v,
+        Some(OwnedValue::Array(vec![
+            OwnedValue::UnsignedInteger(1),
+            OwnedValue::Text("\u{FFFD}\u{FFFD}".to_string()),
+            OwnedValue::Simple(16),
+            OwnedValue::Bytes(b"a".to_vec()),
+        ]))
+    );
+    let errors = errors
+        .iter()
+        .map(|(offset, e)| (*offset, e.to_string()))
+        .collect::<Vec<_>>();
+    assert_eq!(errors.len(), 5);
+    assert_eq!(errors[0], (0, Error::NotShortest.to_string()));
+    assert_eq!(errors[1], (2, Error::NotShortest.to_string()));
+    assert_eq!(errors[2].0, 4);
+    assert_eq!(errors[3], (7, Error::InvalidSimpleType(16).to_string()));
+    assert_eq!(errors[4], (12, Error::InvalidChunk.to_string()));
+
+    // Truncated data still produces the items read so far
+    let (v, len, errors) = parse_lenient(&hex!("830102"), 16);
+    assert_eq!(
+        v,
+        Some(OwnedValue::Array(vec![
+            OwnedValue::UnsignedInteger(1),
+            OwnedValue::UnsignedInteger(2)
+        ]))
+    );
+    assert_eq!(len, 3);
+    assert!(matches!(errors[..], [(3, Error::NotEnoughData)]));
+}
+
+#[test]
+fn lenient_recursion() {
+    // Deeply nested tags, and bad chunks that are themselves indefinite-length strings
+    let tags = vec![0xc1u8; 1_000_000];
+    let chunks = [0x5fu8, 0x7f].repeat(500_000);
+    for data in [tags, chunks] {
+        let (_, _, errors) = parse_lenient(&data, 16);
+        assert!(errors.iter().any(|(_, e)| matches!(e, Error::MaxRecursion)));
+    }
+}
+
+#[test]
+fn limits() {
+    let limits = DecodeLimits::default();
+
+    // A bundle-like indefinite array with a byte string claiming 4GB
+    let data = hex!("9f 5affffffff 0102 ff");
+    assert!(matches!(
+        check_limits(&data, &limits),
+        Err(Error::ExcessiveLength(0xFFFFFFFF))
+    ));
+    assert!(matches!(
+        parse_with_limits::<u64>(&data, &limits),
+        Err(Error::ExcessiveLength(0xFFFFFFFF))
+    ));
+
+    // An array claiming 2^32 items
+    let data = hex!("9f 9b0000000100000000 0102 ff");
+    assert!(matches!(
+        check_limits(&data, &limits),
+        Err(Error::ExcessiveLength(0x100000000))
+    ));
+
+    // An array that fits the data, but not the configured cap
+    let data = hex!("84 01020304");
+    assert_eq!(check_limits(&data, &limits).unwrap(), data.len());
+    assert!(matches!(
+        check_limits(
+            &data,
+            &DecodeLimits {
+                max_collection_len: 3,
+                ..limits
+            }
+        ),
+        Err(Error::ExcessiveLength(4))
+    ));
+
+    // Deep nesting
+    let data = [0x81u8; 64];
+    assert!(matches!(
+        check_limits(&data, &limits),
+        Err(Error::MaxRecursion)
+    ));
+
+    // Total size
+    assert!(matches!(
+        check_limits(
+            &hex!("4401020304"),
+            &DecodeLimits {
+                max_bytes: 4,
+                ..limits
+            }
+        ),
+        Err(Error::ExcessiveLength(5))
+    ));
+}
+
+#[test]
+fn borrowed_str() {
+    let data = hex!("6449455446");
+    let (s, shortest, len) = parse_str(&data).unwrap();
+    assert_eq!(s, "IETF");
+    assert!(shortest && len == data.len());
+
+    // The string is borrowed from the source data, but an owned copy can still be taken
+    assert!(data.as_ptr_range().contains(&s.as_ptr()));
+    let owned: String = s.to_owned();
+    assert_eq!(owned, "IETF");
+
+    // Map keys can be matched without allocating
+    let data = hex!("a2 6161 01 6162 02");
+    let sum = parse_map(&data, |m, _, _| {
+        let mut sum = 0;
+        while let Some((key, _)) = m.try_parse_str()? {
+            assert!(data.as_ptr_range().contains(&key.as_ptr()));
+            let v = m.parse::<u64>()?;
+            sum += if key == "a" {
+                v
+            } else {
+                assert_eq!(key, "b");
+                v * 10
+            };
+        }
+        Ok::<_, Error>(sum)
+    })
+    .unwrap()
+    .0;
+    assert_eq!(sum, 21);
+
+    // Strings that cannot be borrowed are rejected
+    assert!(matches!(
+        parse_str(&hex!("7f 6161 6162 ff")),
+        Err(Error::IncorrectType(_, _))
+    ));
+    assert!(matches!(
+        parse_str(&hex!("c0 6161")),
+        Err(Error::IncorrectType(_, _))
+    ));
+    assert!(matches!(
+        parse_str(&hex!("01")),
+        Err(Error::IncorrectType(_, _))
+    ));
+    assert!(matches!(parse_str(&[]), Err(Error::NotEnoughData)));
+}
@@ -0,0 +1,147 @@
+use super::{decode::*, encode::*};
+use alloc::{
+    string::{String, ToString},
+    vec,
+    vec::Vec,
+};
+use hardy_cbor_macros::{FromCbor, ToCbor};
+use hex_literal::hex;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ToCbor, FromCbor)]
+struct Point {
+    x: u64,
+    y: i32,
+}
+
+// The same as Point, written out by hand
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct ManualPoint {
+    x: u64,
+    y: i32,
+}
+
+impl ToCbor for ManualPoint {
+    fn to_cbor(self, encoder: &mut Encoder) {
+        encoder.emit_array(Some(2), |a| {
+            a.emit(self.x);
+            a.emit(self.y);
+        })
+    }
+}
+
+impl FromCbor for ManualPoint {
+    type Error = Error;
+
+    fn try_from_cbor(data: &[u8]) -> Result<Option<(Self, bool, usize)>, Self::Error> {
+        try_parse_array(data, |a, shortest, tags| {
+            let (x, s1) = a.parse()?;
+            let (y, s2) = a.parse()?;
+            Ok::<_, Error>((
+                ManualPoint { x, y },
+                shortest && tags.is_empty() && a.is_definite() && s1 && s2,
+            ))
+        })
+        .map(|o| o.map(|((v, s), len)| (v, s, len)))
+    }
+}
+
+#[derive(Debug, PartialEq, ToCbor, FromCbor)]
+#[cbor(tag = 1234)]
+struct Shape(
+    Point,
+    #[cbor(tag = 24)] u64,
+    bool,
+    #[cbor(optional)] Option<Point>,
+    #[cbor(optional)] Option<u8>,
+);
+
+// Fields that are not Copy are borrowed when emitting
+#[derive(Debug, PartialEq, ToCbor)]
+struct Labelled {
+    label: String,
+    data: Vec<u8>,
+    shape: Shape,
+    #[cbor(optional)]
+    note: Option<String>,
+}
+
+#[test]
+fn matches_manual() {
+    for (x, y) in [(0, 0), (1, -1), (1000, 100000), (u64::MAX, i32::MIN)] {
+        let data = emit(&Point { x, y });
+        assert_eq!(data, emit(ManualPoint { x, y }));
+
+        let (manual, shortest, len) = ManualPoint::try_from_cbor(&data).unwrap().unwrap();
+        assert_eq!(
+            Point::try_from_cbor(&data).unwrap(),
+            Some((
+                Point {
+                    x: manual.x,
+                    y: manual.y
+                },
+                shortest,
+                len
+            ))
+        );
+    }
+}
+
+#[test]
+fn non_canonical() {
+    // Indefinite-length array
+    let (_, shortest, len) = Point::try_from_cbor(&hex!("9f0101ff")).unwrap().unwrap();
+    assert!(!shortest);
+    assert_eq!(len, 4);
+
+    // Overlong integer
+    assert!(!Point::try_from_cbor(&hex!("82180101")).unwrap().unwrap().1);
+
+    // Tagged array
+    assert!(!Point::try_from_cbor(&hex!("c1820101")).unwrap().unwrap().1);
+
+    assert!(Point::try_from_cbor(&hex!("8101")).is_err());
+    assert!(Point::try_from_cbor(&hex!("83010101")).is_err());
+}
+
+#[test]
+fn tags_and_optional() {
+    let shape = Shape(Point { x: 1, y: 2 }, 3, true, None, None);
+    let data = emit(&shape);
+    assert_eq!(data, hex!("d904d283820102d81803f5"));
+    assert_eq!(parse::<Shape>(&data).unwrap(), shape);
+
+    let shape = Shape(
+        Point { x: 1, y: 2 },
+        3,
+        false,
+        Some(Point { x: 4, y: -5 }),
+        Some(6),
+    );
+    let data = emit(&shape);
+    assert_eq!(data, hex!("d904d285820102d81803f482042406"));
+    let (value, shortest, len) = Shape::try_from_cbor(&data).unwrap().unwrap();
+    assert_eq!(value, shape);
+    assert!(shortest);
+    assert_eq!(len, data.len());
+
+    // Missing the array tag
+    assert!(Shape::try_from_cbor(&data[3..]).is_err());
+
+    // Missing the field tag
+    assert!(Shape::try_from_cbor(&hex!("d904d28382010203f5")).is_err());
+}
+
+#[test]
+fn borrowed_fields() {
+    let labelled = Labelled {
+        label: "a".to_string(),
+        data: vec![1, 2],
+        shape: Shape(Point { x: 1, y: 2 }, 3, true, None, None),
+        note: Some("b".to_string()),
+    };
+    let data = emit(&labelled);
+    assert_eq!(data, hex!("846161420102d904d283820102d81803f56162"));
+
+    // The value is still owned, so can be emitted again
+    assert_eq!(emit(&labelled), data);
+}
@@ -528,6 +528,57 @@ where
     }
 }
 
+// References to values, so that borrowed fields can be emitted, e.g. by #[derive(ToCbor)]
+macro_rules! impl_to_cbor_for_ref {
+    ($($t:ty),*) => {
+        $(
+            impl ToCbor for &$t {
+                fn to_cbor(self, encoder: &mut Encoder) {
+                    (*self).to_cbor(encoder)
+                }
+            }
+        )*
+    };
+}
+
+impl_to_cbor_for_ref!(
+    u64,
+    usize,
+    u32,
+    u16,
+    u8,
+    i64,
+    isize,
+    i32,
+    i16,
+    i8,
+    f64,
+    f32,
+    half::f16,
+    bool
+);
+
+impl ToCbor for &String {
+    fn to_cbor(self, encoder: &mut Encoder) {
+        self.as_str().to_cbor(encoder)
+    }
+}
+
+impl ToCbor for &Vec<u8> {
+    fn to_cbor(self, encoder: &mut Encoder) {
+        self.as_slice().to_cbor(encoder)
+    }
+}
+
+impl<'a, T> ToCbor for &'a Option<T>
+where
+    &'a T: ToCbor,
+{
+    fn to_cbor(self, encoder: &mut Encoder) {
+        self.as_ref().to_cbor(encoder)
+    }
+}
+
 pub fn emit<T>(value: T) -> Vec<u8>
 where
     T: ToCbor,
@@ -1,13 +1,25 @@
 #![no_std]
 extern crate alloc;
 
+// The derive macros name this crate by path, which includes our own tests
+#[cfg(test)]
+extern crate self as hardy_cbor;
+
+#[cfg(feature = "derive")]
+pub use hardy_cbor_macros::{FromCbor, ToCbor};
+
 pub mod decode;
 pub mod encode;
 
+mod decode_lenient;
+mod decode_limits;
 mod decode_seq;
 
 #[cfg(test)]
 mod decode_tests;
 
+#[cfg(test)]
+mod derive_tests;
+
 #[cfg(test)]
 mod encode_tests;
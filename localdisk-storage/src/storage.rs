@@ -18,8 +18,27 @@ use std::os::windows::fs::OpenOptionsExt;
 use trace_err::*;
 use tracing::*;
 
+// Corrupt bundles are moved here, beneath the store root, rather than deleted
+const QUARANTINE_DIR: &str = "quarantine";
+
+/* How hard `store` works to ensure a bundle survives a crash or power loss before it returns.
+ * Whatever the mode, a partially written bundle is never visible under its final name:
+ * data is written to a '.tmp' file and renamed, and '.tmp' files are removed at restart */
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Durability {
+    // Leave flushing to the OS, recently stored bundles may be lost
+    None,
+    // The data is on disk before the file is renamed, but the rename itself may be lost
+    #[default]
+    Data,
+    // The data and the directory entry are both on disk before `store` returns
+    DataAndDir,
+}
+
 pub struct Storage {
     store_root: PathBuf,
+    durability: Durability,
 }
 
 impl Storage {
@@ -52,7 +71,14 @@ impl Storage {
             },
         );
 
+        let durability = config.get("durability").map_or(Durability::default(), |v| {
+            v.clone()
+                .try_deserialize()
+                .trace_expect("Invalid 'durability' value in configuration")
+        });
+
         info!("Using bundle store directory: {}", store_root.display());
+        info!("Bundle store durability: {durability:?}");
 
         // Ensure directory exists
         std::fs::create_dir_all(&store_root).trace_expect(&format!(
@@ -60,11 +86,14 @@ impl Storage {
             store_root.display()
         ));
 
-        Arc::new(Storage { store_root })
+        Arc::new(Storage {
+            store_root,
+            durability,
+        })
     }
 }
 
-fn random_file_path(root: &PathBuf) -> Result<PathBuf, std::io::Error> {
+fn random_file_path(root: &PathBuf, durability: Durability) -> Result<PathBuf, std::io::Error> {
     let mut rng = rand::thread_rng();
     loop {
         // Random subdirectory
@@ -78,7 +107,7 @@ fn random_file_path(root: &PathBuf) -> Result<PathBuf, std::io::Error> {
         .collect::<PathBuf>();
 
         // Ensure directory exists
-        std::fs::create_dir_all(&file_path)?;
+        create_dirs(root, &file_path, durability)?;
 
         // Add a random filename
         file_path.push(PathBuf::from(format!("{:x}", rng.gen::<u16>() % 4096)));
@@ -99,6 +128,40 @@ fn random_file_path(root: &PathBuf) -> Result<PathBuf, std::io::Error> {
     }
 }
 
+/* Create each missing directory between `root` and `dir`.
+ * A new directory is only reachable after a crash if its entry in the parent is flushed too */
+fn create_dirs(root: &Path, dir: &Path, durability: Durability) -> Result<(), std::io::Error> {
+    if dir == root || dir.is_dir() {
+        return Ok(());
+    }
+    if let Some(parent) = dir.parent() {
+        create_dirs(root, parent, durability)?;
+    }
+    match std::fs::create_dir(dir) {
+        Ok(()) if durability == Durability::DataAndDir => sync_dir(dir),
+        Ok(()) => Ok(()),
+        // Another thread got there first
+        Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => Ok(()),
+        Err(e) => Err(e),
+    }
+}
+
+// Flush the directory entry of `file_path`, so a rename survives a crash
+fn sync_dir(file_path: &Path) -> Result<(), std::io::Error> {
+    cfg_if::cfg_if! {
+        if #[cfg(unix)] {
+            match file_path.parent() {
+                Some(dir) => std::fs::File::open(dir)?.sync_all(),
+                None => Ok(()),
+            }
+        } else {
+            // Windows has no way to open a directory for flushing, FILE_FLAG_WRITE_THROUGH is the best we can do
+            _ = file_path;
+            Ok(())
+        }
+    }
+}
+
 fn walk_dirs(
     root: &PathBuf,
     dir: PathBuf,
@@ -110,7 +173,10 @@ fn walk_dirs(
         for entry in dir.flatten() {
             if let Ok(file_type) = entry.file_type() {
                 if file_type.is_dir() {
-                    subdirs.push(entry.path());
+                    // Quarantined bundles are left for an operator to inspect
+                    if entry.path() != root.join(QUARANTINE_DIR) {
+                        subdirs.push(entry.path());
+                    }
                     remove = false;
                 } else if file_type.is_file() {
                     // Drop anything .tmp
@@ -261,12 +327,13 @@ impl BundleStorage for Storage {
 
     async fn store(&self, data: &[u8]) -> storage::Result<Arc<str>> {
         let root = self.store_root.clone();
+        let durability = self.durability;
 
         // Spawn a thread to try to maintain linearity
         let data = Box::from(data);
         let storage_name = tokio::task::spawn_blocking(move || {
             // Create random filename
-            let mut storage_name = random_file_path(&root)?;
+            let mut storage_name = random_file_path(&root, durability)?;
 
             /*
             create a new temp file (alongside the original)
@@ -282,11 +349,13 @@ impl BundleStorage for Storage {
             // Open the file as direct as possible
             let mut options = std::fs::OpenOptions::new();
             options.write(true).create_new(true);
-            cfg_if::cfg_if! {
-                if #[cfg(unix)] {
-                    options.custom_flags(libc::O_SYNC);
-                } else if #[cfg(windows)] {
-                    options.custom_flags(winapi::FILE_FLAG_WRITE_THROUGH);
+            if durability != Durability::None {
+                cfg_if::cfg_if! {
+                    if #[cfg(unix)] {
+                        options.custom_flags(libc::O_SYNC);
+                    } else if #[cfg(windows)] {
+                        options.custom_flags(winapi::FILE_FLAG_WRITE_THROUGH);
+                    }
                 }
             }
             let mut file = options.open(&storage_name)?;
@@ -296,7 +365,11 @@ impl BundleStorage for Storage {
                 file.write_all(&data)?;
 
                 // Sync everything
-                file.sync_all()
+                if durability != Durability::None {
+                    file.sync_all()
+                } else {
+                    Ok(())
+                }
             } {
                 _ = std::fs::remove_file(&storage_name);
                 return Err(e);
@@ -310,7 +383,13 @@ impl BundleStorage for Storage {
                 return Err(e);
             }
 
-            // No idea how to fsync the directory in portable Rust!
+            if durability == Durability::DataAndDir {
+                // Don't leave a file behind that the caller has not been told about
+                if let Err(e) = sync_dir(&storage_name) {
+                    _ = std::fs::remove_file(&storage_name);
+                    return Err(e);
+                }
+            }
 
             Ok(storage_name)
         })
@@ -337,4 +416,133 @@ impl BundleStorage for Storage {
             }
         }
     }
+
+    #[instrument(skip(self))]
+    async fn quarantine(&self, storage_name: &str) -> storage::Result<()> {
+        // Flatten the name, so the quarantine directory is easy to inspect
+        let quarantine_name = storage_name.replace(std::path::MAIN_SEPARATOR, "-");
+        let storage_name = self.store_root.join(PathBuf::from_str(storage_name)?);
+        let quarantine_dir = self.store_root.join(QUARANTINE_DIR);
+        warn!(
+            "Moving corrupt bundle file {} to {}",
+            storage_name.display(),
+            quarantine_dir.display()
+        );
+
+        tokio::fs::create_dir_all(&quarantine_dir).await?;
+        match tokio::fs::rename(&storage_name, quarantine_dir.join(quarantine_name)).await {
+            Ok(_) => Ok(()),
+            Err(e) => {
+                // The bundle may have been removed while it was being checked
+                if let std::io::ErrorKind::NotFound = e.kind() {
+                    Ok(())
+                } else {
+                    Err(e.into())
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_storage(name: &str, durability: Durability) -> Storage {
+        let store_root =
+            std::env::temp_dir().join(format!("hardy-localdisk-{name}-{}", std::process::id()));
+        _ = std::fs::remove_dir_all(&store_root);
+        std::fs::create_dir_all(&store_root).unwrap();
+        Storage {
+            store_root,
+            durability,
+        }
+    }
+
+    #[tokio::test]
+    async fn durable() {
+        for durability in [Durability::None, Durability::Data, Durability::DataAndDir] {
+            let storage = test_storage(&format!("{durability:?}"), durability);
+            let storage_name = storage.store(b"Hello").await.unwrap();
+
+            // A fresh instance sees the data as soon as store returns
+            let fresh = Storage {
+                store_root: storage.store_root.clone(),
+                durability,
+            };
+            let data = fresh.load(&storage_name).await.unwrap().unwrap();
+            assert_eq!(data.as_ref().as_ref(), b"Hello");
+
+            std::fs::remove_dir_all(&storage.store_root).unwrap();
+        }
+    }
+
+    #[test]
+    fn create_dirs_nested() {
+        let storage = test_storage("create-dirs", Durability::DataAndDir);
+        let dir = storage.store_root.join("a/b/c");
+        create_dirs(&storage.store_root, &dir, Durability::DataAndDir).unwrap();
+        assert!(dir.is_dir());
+
+        // Existing directories are not an error
+        create_dirs(&storage.store_root, &dir, Durability::DataAndDir).unwrap();
+
+        std::fs::remove_dir_all(&storage.store_root).unwrap();
+    }
+
+    #[tokio::test]
+    async fn recover() {
+        let storage = test_storage("recover", Durability::Data);
+        let storage_name = storage.store(b"Hello").await.unwrap();
+
+        // Simulate writes interrupted before the rename, and before any data was written
+        let dir = storage.store_root.join("1/2/3");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("4.tmp"), b"Hel").unwrap();
+        std::fs::write(dir.join("5"), b"").unwrap();
+
+        let (tx, mut rx) = tokio::sync::mpsc::channel(16);
+        storage.list(tx).await.unwrap();
+        let mut listed = Vec::new();
+        while let Some((name, _)) = rx.recv().await {
+            listed.push(name);
+        }
+        assert_eq!(listed, vec![storage_name]);
+
+        // The debris has been removed
+        assert!(!dir.join("4.tmp").exists());
+        assert!(!dir.join("5").exists());
+
+        std::fs::remove_dir_all(&storage.store_root).unwrap();
+    }
+
+    #[tokio::test]
+    async fn quarantine() {
+        let storage = test_storage("quarantine", Durability::Data);
+        let good = storage.store(b"Hello").await.unwrap();
+        let bad = storage.store(b"World").await.unwrap();
+
+        storage.quarantine(&bad).await.unwrap();
+
+        // The data is kept for inspection, but is no longer a bundle
+        let quarantined = storage
+            .store_root
+            .join(QUARANTINE_DIR)
+            .join(bad.replace(std::path::MAIN_SEPARATOR, "-"));
+        assert_eq!(std::fs::read(quarantined).unwrap(), b"World");
+        assert!(storage.load(&bad).await.unwrap().is_none());
+
+        let (tx, mut rx) = tokio::sync::mpsc::channel(16);
+        storage.list(tx).await.unwrap();
+        let mut listed = Vec::new();
+        while let Some((name, _)) = rx.recv().await {
+            listed.push(name);
+        }
+        assert_eq!(listed, vec![good]);
+
+        // Quarantining data that has already gone is not an error
+        storage.quarantine(&bad).await.unwrap();
+
+        std::fs::remove_dir_all(&storage.store_root).unwrap();
+    }
 }
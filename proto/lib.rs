@@ -5,3 +5,11 @@ pub mod cla {
 pub mod application {
     tonic::include_proto!("application");
 }
+
+pub mod routes {
+    tonic::include_proto!("routes");
+}
+
+pub mod bundles {
+    tonic::include_proto!("bundles");
+}
@@ -0,0 +1,2 @@
+-- NULL for bundles without a Class of Service extension block
+ALTER TABLE bundles ADD COLUMN class_of_service INTEGER;
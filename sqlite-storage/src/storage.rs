@@ -211,6 +211,15 @@ impl Storage {
         migrate::migrate(&mut connection, upgrade)
             .trace_expect("Failed to migrate metadata store database");
 
+        // Indexes that only speed up queries are not part of the schema, so adding one does not require an upgrade
+        connection
+            .execute_batch(
+                r#"
+            CREATE INDEX IF NOT EXISTS idx_bundles_status ON bundles (status);
+            CREATE INDEX IF NOT EXISTS idx_bundles_destination ON bundles (destination);"#,
+            )
+            .trace_expect("Failed to index metadata store database");
+
         // Do an optimize check
         connection
             .execute_batch(r#"PRAGMA optimize=0x10002;"#)
@@ -262,6 +271,62 @@ fn encode_eid(eid: &bpv7::Eid) -> rusqlite::types::Value {
     rusqlite::types::Value::Blob(cbor::encode::emit(eid))
 }
 
+/* Destinations are stored as CBOR, so the bundles for a pattern that matches an exact EID, or every
+ * service of an ipn node, occupy ranges of the destination index.  Each range is [start, end), so
+ * an exact EID is the range from its encoding to its encoding followed by a zero byte.
+ * Returns None for patterns that cannot be expressed as ranges */
+fn destination_ranges(pattern: &bpv7::EidPattern) -> Option<Vec<(Vec<u8>, Vec<u8>)>> {
+    // Legacy ipn EIDs with a non-zero allocator have a different encoding of the same endpoint
+    let encodings = |eid: bpv7::Eid| {
+        let mut v = vec![cbor::encode::emit(&eid)];
+        if let bpv7::Eid::Ipn {
+            allocator_id,
+            node_number,
+            service_number,
+        } = eid
+        {
+            if allocator_id != 0 {
+                v.push(cbor::encode::emit(&bpv7::Eid::LegacyIpn {
+                    allocator_id,
+                    node_number,
+                    service_number,
+                }));
+            }
+        }
+        v
+    };
+
+    if let Some(eid) = pattern.is_exact() {
+        return Some(
+            encodings(eid)
+                .into_iter()
+                .map(|start| {
+                    let mut end = start.clone();
+                    end.push(0);
+                    (start, end)
+                })
+                .collect(),
+        );
+    }
+
+    // The service number is encoded last, and service 0 is encoded as a single byte
+    let (allocator_id, node_number) = pattern.is_ipn_node()?;
+    encodings(bpv7::Eid::Ipn {
+        allocator_id,
+        node_number,
+        service_number: 0,
+    })
+    .into_iter()
+    .map(|mut start| {
+        start.pop();
+        let mut end = start.clone();
+        while end.pop()? == u8::MAX {}
+        end.push(start[end.len()] + 1);
+        Some((start, end))
+    })
+    .collect()
+}
+
 fn decode_eid(
     row: &rusqlite::Row,
     idx: impl rusqlite::RowIndex,
@@ -345,15 +410,16 @@ fn unpack_bundles(mut rows: rusqlite::Rows<'_>, tx: &storage::Sender) -> storage
            18: bundles.hop_limit,
            19: bundles.wait_until,
            20: bundles.ack_handle,
-           21: bundle_blocks.block_num,
-           22: bundle_blocks.block_type,
-           23: bundle_blocks.block_flags,
-           24: bundle_blocks.block_crc_type,
-           25: bundle_blocks.data_start,
-           26: bundle_blocks.data_len,
-           27: bundle_blocks.payload_offset,
-           28: bundle_blocks.payload_len,
-           29: bundle_blocks.bcb,
+           21: bundles.class_of_service,
+           22: bundle_blocks.block_num,
+           23: bundle_blocks.block_type,
+           24: bundle_blocks.block_flags,
+           25: bundle_blocks.block_crc_type,
+           26: bundle_blocks.data_start,
+           27: bundle_blocks.data_len,
+           28: bundle_blocks.payload_offset,
+           29: bundle_blocks.payload_len,
+           30: bundle_blocks.bcb,
     */
 
     while let Some(mut row) = rows.next()? {
@@ -407,19 +473,23 @@ fn unpack_bundles(mut rows: rusqlite::Rows<'_>, tx: &storage::Sender) -> storage
                 }),
                 v => panic!("EID encoded as unusual sqlite type: {:?}", v),
             },
+            class_of_service: row
+                .get::<_, Option<i64>>(21)?
+                .map(|v| as_u64(v).try_into())
+                .transpose()?,
         };
 
         loop {
-            let block_number = as_u64(row.get(21)?);
+            let block_number = as_u64(row.get(22)?);
             let block = bpv7::Block {
-                block_type: as_u64(row.get(22)?).into(),
-                flags: as_u64(row.get(23)?).into(),
-                crc_type: as_u64(row.get(24)?).into(),
-                data_start: as_u64(row.get(25)?) as usize,
-                data_len: as_u64(row.get(26)?) as usize,
-                payload_offset: as_u64(row.get(27)?) as usize,
-                payload_len: as_u64(row.get(28)?) as usize,
-                bcb: row.get::<_, Option<i64>>(29)?.map(as_u64),
+                block_type: as_u64(row.get(23)?).into(),
+                flags: as_u64(row.get(24)?).into(),
+                crc_type: as_u64(row.get(25)?).into(),
+                data_start: as_u64(row.get(26)?) as usize,
+                data_len: as_u64(row.get(27)?) as usize,
+                payload_offset: as_u64(row.get(28)?) as usize,
+                payload_len: as_u64(row.get(29)?) as usize,
+                bcb: row.get::<_, Option<i64>>(30)?.map(as_u64),
             };
 
             if bundle.blocks.insert(block_number, block).is_some() {
@@ -475,6 +545,7 @@ impl storage::MetadataStorage for Storage {
                     hop_limit,
                     wait_until,
                     ack_handle,
+                    class_of_service,
                     block_num,
                     block_type,
                     block_flags,
@@ -563,19 +634,23 @@ impl storage::MetadataStorage for Storage {
                     }),
                     v => panic!("EID encoded as unusual sqlite type: {:?}", v),
                 },
+                class_of_service: row
+                    .get::<_, Option<i64>>(21)?
+                    .map(|v| as_u64(v).try_into())
+                    .transpose()?,
             };
 
             loop {
-                let block_number = as_u64(row.get(21)?);
+                let block_number = as_u64(row.get(22)?);
                 let block = bpv7::Block {
-                    block_type: as_u64(row.get(22)?).into(),
-                    flags: as_u64(row.get(23)?).into(),
-                    crc_type: as_u64(row.get(24)?).into(),
-                    data_start: as_u64(row.get(25)?) as usize,
-                    data_len: as_u64(row.get(26)?) as usize,
-                    payload_offset: as_u64(row.get(27)?) as usize,
-                    payload_len: as_u64(row.get(28)?) as usize,
-                    bcb: row.get::<_, Option<i64>>(29)?.map(as_u64),
+                    block_type: as_u64(row.get(23)?).into(),
+                    flags: as_u64(row.get(24)?).into(),
+                    crc_type: as_u64(row.get(25)?).into(),
+                    data_start: as_u64(row.get(26)?) as usize,
+                    data_len: as_u64(row.get(27)?) as usize,
+                    payload_offset: as_u64(row.get(28)?) as usize,
+                    payload_len: as_u64(row.get(29)?) as usize,
+                    bcb: row.get::<_, Option<i64>>(30)?.map(as_u64),
                 };
 
                 if bundle.blocks.insert(block_number, block).is_some() {
@@ -631,9 +706,10 @@ impl storage::MetadataStorage for Storage {
                     hop_count,
                     hop_limit,
                     wait_until,
-                    ack_handle
+                    ack_handle,
+                    class_of_service
                     )
-                VALUES (?1,?2,?3,?4,?5,?6,?7,?8,?9,?10,?11,?12,?13,?14,?15,?16,?17,?18,?19)
+                VALUES (?1,?2,?3,?4,?5,?6,?7,?8,?9,?10,?11,?12,?13,?14,?15,?16,?17,?18,?19,?20)
                 RETURNING id;"#,
                 )?
                 .query_row(
@@ -664,7 +740,8 @@ impl storage::MetadataStorage for Storage {
                         bundle.hop_count.as_ref().map(|h| as_i64(h.count)),
                         bundle.hop_count.as_ref().map(|h| as_i64(h.limit)),
                         until,
-                        ack_handle
+                        ack_handle,
+                        bundle.class_of_service.map(as_i64)
                     ),
                     |row| Ok(as_u64(row.get(0)?)),
                 );
@@ -962,6 +1039,7 @@ impl storage::MetadataStorage for Storage {
                         hop_limit,
                         wait_until,
                         ack_handle,
+                        class_of_service,
                         block_num,
                         block_type,
                         block_flags,
@@ -1013,7 +1091,8 @@ impl storage::MetadataStorage for Storage {
                                 hop_count,
                                 hop_limit,
                                 wait_until,
-                                ack_handle
+                                ack_handle,
+                                class_of_service
                             FROM unconfirmed_bundles
                             JOIN bundles ON id = unconfirmed_bundles.bundle_id
                             LIMIT 16
@@ -1070,6 +1149,7 @@ impl storage::MetadataStorage for Storage {
                         hop_limit,
                         wait_until,
                         ack_handle,
+                        class_of_service,
                         block_num,
                         block_type,
                         block_flags,
@@ -1092,4 +1172,161 @@ impl storage::MetadataStorage for Storage {
         })
         .await
     }
+
+    #[instrument(skip(self, tx))]
+    async fn poll_received_between(
+        &self,
+        window: std::ops::Range<time::OffsetDateTime>,
+        tx: storage::Sender,
+    ) -> storage::Result<()> {
+        self.pooled_connection(move |conn| {
+            unpack_bundles(
+                conn.prepare_cached(
+                    r#"SELECT 
+                        bundles.id,
+                        status,
+                        storage_name,
+                        hash,
+                        received_at,
+                        flags,
+                        crc_type,
+                        source,
+                        destination,
+                        report_to,
+                        creation_time,
+                        creation_seq_num,
+                        lifetime,                    
+                        fragment_offset,
+                        fragment_total_len,
+                        previous_node,
+                        age,
+                        hop_count,
+                        hop_limit,
+                        wait_until,
+                        ack_handle,
+                        class_of_service,
+                        block_num,
+                        block_type,
+                        block_flags,
+                        block_crc_type,
+                        data_start,
+                        data_len,
+                        payload_offset,
+                        payload_len,
+                        bcb
+                    FROM bundles
+                    JOIN bundle_blocks ON bundle_blocks.bundle_id = bundles.id
+                    WHERE status != ?1 AND received_at IS NOT NULL
+                        AND unixepoch(received_at) >= unixepoch(?2)
+                        AND unixepoch(received_at) < unixepoch(?3);"#,
+                )?
+                .query((
+                    StatusCodes::Tombstone as i64,
+                    window.start,
+                    window.end,
+                ))?,
+                &tx,
+            )
+        })
+        .await
+    }
+
+    /* Exact EIDs and ipn nodes are found using the destination index, see destination_ranges().
+     * Other EID patterns cannot be expressed in SQL, so every bundle is returned for the caller to filter */
+    #[instrument(skip(self, tx))]
+    async fn poll_for_destination(
+        &self,
+        destination: bpv7::EidPattern,
+        tx: storage::Sender,
+    ) -> storage::Result<()> {
+        let ranges = destination_ranges(&destination).unwrap_or_default();
+        self.pooled_connection(move |conn| {
+            let mut filter = String::new();
+            let mut params = vec![rusqlite::types::Value::Integer(
+                StatusCodes::Tombstone as i64,
+            )];
+            for (start, end) in ranges {
+                filter.push_str(if filter.is_empty() { " AND (" } else { " OR " });
+                filter.push_str(&format!(
+                    "(destination >= ?{} AND destination < ?{})",
+                    params.len() + 1,
+                    params.len() + 2
+                ));
+                params.push(rusqlite::types::Value::Blob(start));
+                params.push(rusqlite::types::Value::Blob(end));
+            }
+            if !filter.is_empty() {
+                filter.push(')');
+            }
+
+            unpack_bundles(
+                conn.prepare_cached(&format!(
+                    r#"SELECT 
+                        bundles.id,
+                        status,
+                        storage_name,
+                        hash,
+                        received_at,
+                        flags,
+                        crc_type,
+                        source,
+                        destination,
+                        report_to,
+                        creation_time,
+                        creation_seq_num,
+                        lifetime,                    
+                        fragment_offset,
+                        fragment_total_len,
+                        previous_node,
+                        age,
+                        hop_count,
+                        hop_limit,
+                        wait_until,
+                        ack_handle,
+                        class_of_service,
+                        block_num,
+                        block_type,
+                        block_flags,
+                        block_crc_type,
+                        data_start,
+                        data_len,
+                        payload_offset,
+                        payload_len,
+                        bcb
+                    FROM bundles
+                    JOIN bundle_blocks ON bundle_blocks.bundle_id = bundles.id
+                    WHERE status != ?1{filter};"#
+                ))?
+                .query(rusqlite::params_from_iter(params))?,
+                &tx,
+            )
+        })
+        .await
+    }
+
+    #[instrument(skip(self))]
+    async fn count_by_status(&self) -> storage::Result<metadata::BundleCounts> {
+        self.pooled_connection(|conn| {
+            let mut stmt =
+                conn.prepare_cached(r#"SELECT status, COUNT(*) FROM bundles GROUP BY status;"#)?;
+            let mut rows = stmt.query(())?;
+            let mut counts = metadata::BundleCounts::default();
+            while let Some(row) = rows.next()? {
+                let count = as_u64(row.get(1)?);
+                let counter = match row.get::<_, i64>(0)?.into() {
+                    StatusCodes::IngressPending => &mut counts.ingress_pending,
+                    StatusCodes::DispatchPending => &mut counts.dispatch_pending,
+                    StatusCodes::ReassemblyPending => &mut counts.reassembly_pending,
+                    StatusCodes::CollectionPending => &mut counts.collection_pending,
+                    StatusCodes::ForwardPending => &mut counts.forward_pending,
+                    StatusCodes::ForwardAckPending => &mut counts.forward_ack_pending,
+                    StatusCodes::Waiting => &mut counts.waiting,
+                    StatusCodes::Tombstone => &mut counts.tombstone,
+                };
+                *counter = count;
+            }
+            Ok(counts)
+        })
+        .await
+    }
 }
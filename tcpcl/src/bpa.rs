@@ -86,6 +86,26 @@ impl Bpa {
             .send(bundle)
             .await
     }
+
+    pub async fn add_neighbour(
+        &self,
+        neighbour: &bpv7::Eid,
+        max_bundle_size: u64,
+    ) -> Result<(), tonic::Status> {
+        self.endpoint
+            .as_ref()
+            .trace_expect("Called add_neighbour on disconnected BPA endpoint")
+            .add_neighbour(neighbour, max_bundle_size)
+            .await
+    }
+
+    pub async fn remove_neighbour(&self, neighbour: &bpv7::Eid) -> Result<(), tonic::Status> {
+        self.endpoint
+            .as_ref()
+            .trace_expect("Called remove_neighbour on disconnected BPA endpoint")
+            .remove_neighbour(neighbour)
+            .await
+    }
 }
 
 impl BpaEndpoint {
@@ -126,14 +146,61 @@ impl BpaEndpoint {
         }
     }
 
+    /* While the BPA signals backpressure, back off and retry, which stalls reading from the peer.
+     * Give up once the backoff has grown to MAX_BACKOFF */
     pub async fn send(&self, bundle: Bytes) -> Result<(), tonic::Status> {
+        const MAX_BACKOFF: tokio::time::Duration = tokio::time::Duration::from_secs(2);
+
+        let mut backoff = tokio::time::Duration::from_millis(10);
+        loop {
+            match self
+                .channel
+                .lock()
+                .await
+                .receive_bundle(ReceiveBundleRequest {
+                    handle: self.handle,
+                    source: Bytes::new(),
+                    bundle: bundle.clone(),
+                })
+                .await
+            {
+                Err(status)
+                    if status.code() == tonic::Code::ResourceExhausted && backoff < MAX_BACKOFF =>
+                {
+                    trace!("BPA is applying backpressure, retrying in {backoff:?}");
+                    tokio::time::sleep(backoff).await;
+                    backoff *= 2;
+                }
+                r => return r.map(|_| ()),
+            }
+        }
+    }
+
+    pub async fn add_neighbour(
+        &self,
+        neighbour: &bpv7::Eid,
+        max_bundle_size: u64,
+    ) -> Result<(), tonic::Status> {
+        self.channel
+            .lock()
+            .await
+            .add_neighbour(AddNeighbourRequest {
+                handle: self.handle,
+                priority: 0,
+                neighbour: neighbour.to_string(),
+                max_bundle_size: Some(max_bundle_size),
+            })
+            .await
+            .map(|_| ())
+    }
+
+    pub async fn remove_neighbour(&self, neighbour: &bpv7::Eid) -> Result<(), tonic::Status> {
         self.channel
             .lock()
             .await
-            .receive_bundle(ReceiveBundleRequest {
+            .remove_neighbour(RemoveNeighbourRequest {
                 handle: self.handle,
-                source: Bytes::new(),
-                bundle,
+                neighbour: neighbour.to_string(),
             })
             .await
             .map(|_| ())
@@ -656,11 +656,13 @@ impl TransferSegmentMessage {
             // Not enough data to read data
             return Ok(None);
         }
+        // The data follows the header and extensions, which have already been parsed
+        src.advance(consumed + 8);
         Ok(Some(Message::TransferSegment(TransferSegmentMessage {
             message_flags,
             transfer_id,
             transfer_extensions,
-            data: src.split_to(consumed + 8 + data_length as usize).into(),
+            data: src.split_to(data_length as usize).into(),
         })))
     }
 }
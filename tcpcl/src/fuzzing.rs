@@ -1,11 +1,13 @@
-// This file is only used for fuzzing
+// This file is only used for fuzzing, and to share the codec with hardy-tools
 
 pub mod listener;
 pub mod utils;
 
-mod codec;
+pub mod codec;
 mod connection;
+mod registry;
 mod session;
+mod transfer;
 
 use fuzz_macros::instrument;
 use hardy_bpv7::prelude as bpv7;
@@ -31,6 +33,21 @@ pub mod bpa {
         pub async fn send(&self, _bundle: tokio_util::bytes::Bytes) -> Result<(), tonic::Status> {
             Ok(())
         }
+
+        pub async fn add_neighbour(
+            &self,
+            _neighbour: &super::bpv7::Eid,
+            _max_bundle_size: u64,
+        ) -> Result<(), tonic::Status> {
+            Ok(())
+        }
+
+        pub async fn remove_neighbour(
+            &self,
+            _neighbour: &super::bpv7::Eid,
+        ) -> Result<(), tonic::Status> {
+            Ok(())
+        }
     }
 }
 
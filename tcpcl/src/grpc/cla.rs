@@ -2,6 +2,7 @@ use super::*;
 use cla_server::{Cla, ClaServer};
 use hardy_proto::cla::*;
 use tonic::{Request, Response, Status};
+use tower::{Service as _, ServiceExt};
 
 pub struct Service {}
 
@@ -18,9 +19,48 @@ impl Cla for Service {
         &self,
         request: Request<ForwardBundleRequest>,
     ) -> Result<Response<ForwardBundleResponse>, Status> {
-        // Implement the logic for the forward_bundle function here
-        let _request = request.into_inner();
-        todo!()
+        let request = request.into_inner();
+        let destination = request
+            .destination
+            .parse::<bpv7::Eid>()
+            .map_err(|e| Status::invalid_argument(format!("Invalid destination: {e}")))?;
+
+        let Some((client, transfers)) = registry::find(&destination) else {
+            return Err(Status::not_found(format!("No session with {destination}")));
+        };
+
+        // One bundle at a time per session, so the transfer in progress is ours
+        let mut client = client.lock().await;
+
+        // If the BPA gives up waiting, cancel the transfer rather than finish sending it
+        let guard = CancelOnDrop(Some(transfers));
+
+        let r = client
+            .ready()
+            .await
+            .map_err(|e| Status::unavailable(e.to_string()))?
+            .call(request.bundle.into())
+            .await
+            .map_err(|e| Status::unavailable(e.to_string()))?;
+
+        guard.disarm();
+        r.map(Response::new)
+    }
+}
+
+struct CancelOnDrop(Option<transfer::Transfers>);
+
+impl CancelOnDrop {
+    fn disarm(mut self) {
+        self.0 = None;
+    }
+}
+
+impl Drop for CancelOnDrop {
+    fn drop(&mut self) {
+        if let Some(transfers) = self.0.take() {
+            transfers.cancel_current();
+        }
     }
 }
 
@@ -8,29 +8,121 @@ use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tower::{Service, ServiceExt};
 use utils::settings;
 
+#[derive(Clone, Default)]
+struct SocketOptions {
+    nodelay: bool,
+    keepalive: Option<socket2::TcpKeepalive>,
+    send_buffer_size: Option<usize>,
+    recv_buffer_size: Option<usize>,
+}
+
+impl SocketOptions {
+    fn new(config: &config::Config) -> Self {
+        let keepalive = settings::get_with_default(config, "tcp_keepalive", 0u64)
+            .trace_expect("Invalid 'tcp_keepalive' value in configuration");
+        let keepalive_interval = settings::get_with_default(config, "tcp_keepalive_interval", 0u64)
+            .trace_expect("Invalid 'tcp_keepalive_interval' value in configuration");
+        let send_buffer_size = settings::get_with_default(config, "tcp_send_buffer", 0usize)
+            .trace_expect("Invalid 'tcp_send_buffer' value in configuration");
+        let recv_buffer_size = settings::get_with_default(config, "tcp_recv_buffer", 0usize)
+            .trace_expect("Invalid 'tcp_recv_buffer' value in configuration");
+
+        Self {
+            nodelay: settings::get_with_default(config, "tcp_nodelay", false)
+                .trace_expect("Invalid 'tcp_nodelay' value in configuration"),
+            keepalive: (keepalive != 0).then(|| {
+                let params = socket2::TcpKeepalive::new()
+                    .with_time(std::time::Duration::from_secs(keepalive));
+                match keepalive_interval {
+                    0 => params,
+                    interval => params.with_interval(std::time::Duration::from_secs(interval)),
+                }
+            }),
+            send_buffer_size: (send_buffer_size != 0).then_some(send_buffer_size),
+            recv_buffer_size: (recv_buffer_size != 0).then_some(recv_buffer_size),
+        }
+    }
+
+    /* Buffer sizes are set before listen(), so that accepted sockets inherit them,
+     * and the TCP window scale is negotiated to suit large buffers */
+    fn apply_listener(&self, socket: &socket2::Socket) -> std::io::Result<()> {
+        if let Some(size) = self.send_buffer_size {
+            socket.set_send_buffer_size(size)?;
+        }
+        if let Some(size) = self.recv_buffer_size {
+            socket.set_recv_buffer_size(size)?;
+        }
+        Ok(())
+    }
+
+    fn apply_stream(&self, stream: &tokio::net::TcpStream) -> std::io::Result<()> {
+        stream.set_nodelay(self.nodelay)?;
+        if let Some(keepalive) = &self.keepalive {
+            socket2::SockRef::from(stream).set_tcp_keepalive(keepalive)?;
+        }
+        Ok(())
+    }
+}
+
 #[derive(Clone)]
 struct Config {
-    tcp_address: SocketAddr,
+    tcp_addresses: Vec<SocketAddr>,
+    dual_stack: bool,
     contact_timeout: u16,
     use_tls: bool,
+    socket_options: SocketOptions,
 }
 
 impl Config {
     fn new(config: &config::Config) -> Self {
+        let tcp_address = settings::get_with_default::<SocketAddr, SocketAddr>(
+            config,
+            "tcp_address",
+            "[::1]:4556".parse().unwrap(),
+        )
+        .trace_expect("Invalid 'tcp_address' value in configuration");
+
         Self {
-            tcp_address: settings::get_with_default::<SocketAddr, SocketAddr>(
+            tcp_addresses: settings::get_with_default::<Vec<SocketAddr>, _>(
                 config,
-                "tcp_address",
-                "[::1]:4556".parse().unwrap(),
+                "tcp_addresses",
+                vec![tcp_address],
             )
-            .trace_expect("Invalid 'tcp_address' value in configuration"),
+            .trace_expect("Invalid 'tcp_addresses' value in configuration"),
+            dual_stack: settings::get_with_default(config, "dual_stack", false)
+                .trace_expect("Invalid 'dual_stack' value in configuration"),
             contact_timeout: settings::get_with_default(config, "contact_timeout", 15u16)
                 .trace_expect("Invalid 'contact_timeout' value in configuration"),
             use_tls: false,
+            socket_options: SocketOptions::new(config),
         }
     }
 }
 
+/* Bind a listening socket, allowing IPv6 sockets to also accept IPv4 connections
+ * if `dual_stack` is set */
+fn bind(
+    address: SocketAddr,
+    dual_stack: bool,
+    options: &SocketOptions,
+) -> std::io::Result<tokio::net::TcpListener> {
+    let socket = socket2::Socket::new(
+        socket2::Domain::for_address(address),
+        socket2::Type::STREAM,
+        Some(socket2::Protocol::TCP),
+    )?;
+    if address.is_ipv6() {
+        socket.set_only_v6(!dual_stack)?;
+    }
+    #[cfg(unix)]
+    socket.set_reuse_address(true)?;
+    options.apply_listener(&socket)?;
+    socket.set_nonblocking(true)?;
+    socket.bind(&address.into())?;
+    socket.listen(1024)?;
+    tokio::net::TcpListener::from_std(socket.into())
+}
+
 async fn new_contact(
     config: Config,
     bpa: bpa::Bpa,
@@ -39,6 +131,8 @@ async fn new_contact(
     addr: SocketAddr,
     cancel_token: tokio_util::sync::CancellationToken,
 ) -> Result<(), session::Error> {
+    config.socket_options.apply_stream(&stream)?;
+
     // Receive contact header
     let mut buffer = [0u8; 6];
     match tokio::time::timeout(
@@ -146,17 +240,17 @@ impl tower::Service<()> for Listener {
 #[instrument(skip_all)]
 async fn accept(
     config: Config,
+    address: SocketAddr,
     bpa: bpa::Bpa,
     session_config: session::Config,
     cancel_token: tokio_util::sync::CancellationToken,
 ) {
     let listener = Listener::new(
-        tokio::net::TcpListener::bind(config.tcp_address)
-            .await
-            .trace_expect("Failed to bind TCP listener"),
+        bind(address, config.dual_stack, &config.socket_options)
+            .trace_expect(&format!("Failed to bind TCP listener to {address}")),
     );
 
-    info!("TCP server listening on {}", config.tcp_address);
+    info!("TCP server listening on {address}");
 
     // TODO: We can layer services here
     let mut svc = tower::ServiceBuilder::new()
@@ -218,6 +312,85 @@ pub fn init(
         warn!("RFC9174 specifies contact timeout SHOULD be a maximum of 60 seconds");
     }
 
-    // Start listening
-    task_set.spawn(accept(config, bpa, session_config, cancel_token));
+    // Start a listener per address
+    for address in config.tcp_addresses.clone() {
+        task_set.spawn(accept(
+            config.clone(),
+            address,
+            bpa.clone(),
+            session_config.clone(),
+            cancel_token.clone(),
+        ));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn connect(listener: &tokio::net::TcpListener, address: SocketAddr) {
+        let port = listener.local_addr().unwrap().port();
+        let (connected, accepted) = tokio::join!(
+            tokio::net::TcpStream::connect(SocketAddr::new(address.ip(), port)),
+            listener.accept()
+        );
+        let (_, peer) = accepted.unwrap();
+        assert_eq!(connected.unwrap().local_addr().unwrap().port(), peer.port());
+    }
+
+    #[tokio::test]
+    async fn multiple_addresses() {
+        let v4: SocketAddr = "127.0.0.1:0".parse().unwrap();
+        let v6: SocketAddr = "[::1]:0".parse().unwrap();
+
+        let listener_v4 = bind(v4, false, &SocketOptions::default()).unwrap();
+        let listener_v6 = bind(v6, false, &SocketOptions::default()).unwrap();
+        connect(&listener_v4, v4).await;
+        connect(&listener_v6, v6).await;
+    }
+
+    #[tokio::test]
+    async fn dual_stack() {
+        // A dual-stack IPv6 socket accepts IPv4 connections as mapped addresses
+        let listener = bind("[::]:0".parse().unwrap(), true, &SocketOptions::default()).unwrap();
+        connect(&listener, "127.0.0.1:0".parse().unwrap()).await;
+        connect(&listener, "[::1]:0".parse().unwrap()).await;
+    }
+
+    #[tokio::test]
+    async fn socket_options() {
+        let options = SocketOptions::new(
+            &config::Config::builder()
+                .set_override("tcp_nodelay", true)
+                .unwrap()
+                .set_override("tcp_keepalive", 30)
+                .unwrap()
+                .set_override("tcp_send_buffer", 32768)
+                .unwrap()
+                .set_override("tcp_recv_buffer", 32768)
+                .unwrap()
+                .build()
+                .unwrap(),
+        );
+
+        let listener = bind("127.0.0.1:0".parse().unwrap(), false, &options).unwrap();
+        let (connected, accepted) = tokio::join!(
+            tokio::net::TcpStream::connect(listener.local_addr().unwrap()),
+            listener.accept()
+        );
+        let _connected = connected.unwrap();
+        let (stream, _) = accepted.unwrap();
+        options.apply_stream(&stream).unwrap();
+
+        let socket = socket2::SockRef::from(&stream);
+        assert!(socket.nodelay().unwrap());
+        assert!(socket.keepalive().unwrap());
+
+        // Linux doubles the requested size, to allow for bookkeeping overhead
+        #[cfg(target_os = "linux")]
+        {
+            assert_eq!(socket.send_buffer_size().unwrap(), 2 * 32768);
+            assert_eq!(socket.recv_buffer_size().unwrap(), 2 * 32768);
+        }
+    }
 }
@@ -3,7 +3,9 @@ mod codec;
 mod connection;
 mod grpc;
 mod listener;
+mod registry;
 mod session;
+mod transfer;
 mod utils;
 
 // Buildtime info
@@ -0,0 +1,83 @@
+use super::*;
+use std::{
+    collections::HashMap,
+    net::SocketAddr,
+    sync::{Arc, LazyLock, Mutex},
+};
+
+struct Peer {
+    client: Arc<tokio::sync::Mutex<connection::Client>>,
+    transfers: transfer::Transfers,
+    node_id: Option<bpv7::Eid>,
+}
+
+// The established sessions, so bundles can be forwarded over them and their transfers managed
+static PEERS: LazyLock<Mutex<HashMap<SocketAddr, Peer>>> = LazyLock::new(Default::default);
+
+fn lock() -> std::sync::MutexGuard<'static, HashMap<SocketAddr, Peer>> {
+    PEERS.lock().trace_expect("Failed to lock peers mutex")
+}
+
+pub fn register(
+    client: connection::Client,
+    transfers: transfer::Transfers,
+    addr: SocketAddr,
+    node_id: Option<bpv7::Eid>,
+) {
+    lock().insert(
+        addr,
+        Peer {
+            client: Arc::new(tokio::sync::Mutex::new(client)),
+            transfers,
+            node_id,
+        },
+    );
+}
+
+pub fn unregister(addr: &SocketAddr) {
+    lock().remove(addr);
+}
+
+// Find the session with the peer `node_id`
+pub fn find(
+    node_id: &bpv7::Eid,
+) -> Option<(
+    Arc<tokio::sync::Mutex<connection::Client>>,
+    transfer::Transfers,
+)> {
+    lock()
+        .values()
+        .find(|peer| peer.node_id.as_ref() == Some(node_id))
+        .map(|peer| (peer.client.clone(), peer.transfers.clone()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn register() {
+        let addr: SocketAddr = "[::1]:4556".parse().unwrap();
+        let node_id: bpv7::Eid = "ipn:1.0".parse().unwrap();
+        let (send_request, _recv_request) = tokio::sync::mpsc::channel(1);
+        let (_send_response, recv_response) = tokio::sync::mpsc::unbounded_channel();
+        let transfers = transfer::Transfers::default();
+
+        super::register(
+            connection::new_client(send_request, recv_response),
+            transfers.clone(),
+            addr,
+            Some(node_id.clone()),
+        );
+
+        // The peer's transfers can be reached by its node id
+        transfers.start(7, 100);
+        let (_, found) = find(&node_id).unwrap();
+        assert_eq!(found.progress().unwrap().transfer_id, 7);
+        assert!(found.cancel_current());
+        assert!(find(&"ipn:2.0".parse().unwrap()).is_none());
+
+        unregister(&addr);
+        assert!(find(&node_id).is_none());
+    }
+}
@@ -40,6 +40,9 @@ const DEFAULT_KEEPALIVE_INTERVAL: u16 = 60;
 const DEFAULT_SEGMENT_MRU: u64 = 16384;
 const DEFAULT_TRANSFER_MRU: u64 = 0x4000_0000; // 4GiB
 
+// A session is idle, and torn down, if nothing is received for this many keepalive intervals
+const IDLE_KEEPALIVE_INTERVALS: u32 = 2;
+
 #[derive(Clone)]
 pub struct Config {
     pub keepalive_interval: u16,
@@ -98,12 +101,15 @@ struct XferAck {
     flags: codec::TransferSegmentMessageFlags,
     transfer_id: u64,
     acknowledged_length: usize,
+    // The empty segment that ends a cancelled transfer, the client has already been told
+    cancelled: bool,
 }
 
 enum SendSegmentResult {
     Ok,
     Terminate(codec::SessionTermMessage),
     Refused(codec::TransferRefuseReasonCode),
+    Cancelled,
 }
 
 enum SendResult {
@@ -123,13 +129,16 @@ where
     bpa: bpa::Bpa,
     keepalive_interval: u16,
     last_sent: tokio::time::Instant,
+    last_received: tokio::time::Instant,
     segment_mtu: usize,
     transfer_mru: usize,
+    peer_transfer_mru: usize,
     rcv: Receiver<Vec<u8>>,
     snd: UnboundedSender<Result<ForwardBundleResponse, tonic::Status>>,
     transfer_id: u64,
     acks: VecDeque<XferAck>,
     ingress_bundle: Option<BytesMut>,
+    transfers: transfer::Transfers,
 }
 
 impl<T> Session<T>
@@ -139,12 +148,14 @@ where
         + std::marker::Unpin,
     session::Error: From<<T as futures::Sink<codec::Message>>::Error>,
 {
+    #[allow(clippy::too_many_arguments)]
     fn new(
         transport: T,
         bpa: bpa::Bpa,
         keepalive_interval: u16,
         segment_mtu: usize,
         transfer_mru: usize,
+        peer_transfer_mru: usize,
         rcv: Receiver<Vec<u8>>,
         snd: UnboundedSender<Result<ForwardBundleResponse, tonic::Status>>,
     ) -> Self {
@@ -153,26 +164,37 @@ where
             bpa,
             keepalive_interval,
             last_sent: tokio::time::Instant::now(),
+            last_received: tokio::time::Instant::now(),
             segment_mtu,
             transfer_mru,
+            peer_transfer_mru,
             rcv,
             snd,
             transfer_id: 0,
             acks: VecDeque::new(),
             ingress_bundle: None,
+            transfers: transfer::Transfers::default(),
         }
     }
 
+    fn transfers(&self) -> transfer::Transfers {
+        self.transfers.clone()
+    }
+
     async fn process_msg(
         &mut self,
         msg: Option<Result<codec::Message, codec::Error>>,
     ) -> Result<(), Error> {
+        if let Some(Ok(_)) = &msg {
+            self.last_received = tokio::time::Instant::now();
+        }
+
         match msg {
             Some(Ok(codec::Message::SessionInit(_))) => {
                 self.unexpected(codec::MessageType::SESS_INIT).await
             }
             Some(Ok(codec::Message::SessionTerm(_))) => unreachable!(),
-            Some(Ok(codec::Message::Keepalive)) => todo!(),
+            Some(Ok(codec::Message::Keepalive)) => Ok(()),
             Some(Ok(codec::Message::TransferSegment(msg))) => self.recv(msg).await,
             Some(Ok(codec::Message::TransferAck(ack))) => self.ack_segment(ack).await,
             Some(Ok(codec::Message::TransferRefuse(refusal))) => self.refuse(refusal).await,
@@ -224,11 +246,10 @@ where
     async fn recv(&mut self, msg: codec::TransferSegmentMessage) -> Result<(), Error> {
         if msg.message_flags.start {
             if self.ingress_bundle.is_some() {
-                // Out of order bundle!
-                self.ingress_bundle = None;
-            } else {
-                self.ingress_bundle = Some(BytesMut::with_capacity(msg.data.len()));
+                // The peer has abandoned the previous transfer, discard it and start afresh
+                trace!("Discarding incomplete transfer");
             }
+            self.ingress_bundle = Some(BytesMut::with_capacity(msg.data.len()));
         }
 
         let Some(bundle) = &mut self.ingress_bundle else {
@@ -304,7 +325,7 @@ where
                 ack.acknowledged_length, msg.acknowledged_length
             );
             self.unexpected(codec::MessageType::XFER_ACK).await
-        } else if ack.flags.end {
+        } else if ack.flags.end && !ack.cancelled {
             // Let the client know send is complete
             self.respond(Ok(ForwardBundleResponse {
                 result: forward_bundle_response::ForwardingResult::Sent as i32,
@@ -334,8 +355,12 @@ where
                 .map(|_| None)
         } else {
             // Remove the ack from the queue
-            self.acks.pop_front();
-            Ok(Some(msg.reason_code))
+            if self.acks.pop_front().is_some_and(|ack| ack.cancelled) {
+                // Refusing a cancelled transfer is no concern of the current one
+                Ok(None)
+            } else {
+                Ok(Some(msg.reason_code))
+            }
         }
     }
 
@@ -351,7 +376,10 @@ where
         }
 
         // Remove the ack from the queue
-        self.acks.pop_front();
+        if self.acks.pop_front().is_some_and(|ack| ack.cancelled) {
+            // The client has already been told
+            return Ok(());
+        }
 
         let response = match msg.reason_code {
             codec::TransferRefuseReasonCode::Completed => Ok(ForwardBundleResponse {
@@ -387,12 +415,13 @@ where
         self.respond(response)
     }
 
-    async fn send_segment(
+    fn queue_segment(
         &mut self,
         flags: codec::TransferSegmentMessageFlags,
         data: Bytes,
         acknowledged_length: usize,
-    ) -> Result<SendSegmentResult, Error> {
+        cancelled: bool,
+    ) -> codec::Message {
         // Inc transfer id
         let transfer_id = self.transfer_id;
         self.transfer_id += 1;
@@ -402,18 +431,36 @@ where
             flags: flags.clone(),
             transfer_id,
             acknowledged_length,
+            cancelled,
         });
 
-        self.transport
-            .feed(codec::Message::TransferSegment(
-                codec::TransferSegmentMessage {
-                    message_flags: flags,
-                    transfer_id,
-                    data,
-                    ..Default::default()
-                },
-            ))
-            .await?;
+        codec::Message::TransferSegment(codec::TransferSegmentMessage {
+            message_flags: flags,
+            transfer_id,
+            data,
+            ..Default::default()
+        })
+    }
+
+    async fn send_segment(
+        &mut self,
+        flags: codec::TransferSegmentMessageFlags,
+        data: Bytes,
+        acknowledged_length: usize,
+    ) -> Result<SendSegmentResult, Error> {
+        let msg = self.queue_segment(flags, data, acknowledged_length, false);
+
+        /* The segment is not encoded until the transport is ready for it,
+         * so a send stalled behind a slow peer can be abandoned */
+        tokio::select! {
+            biased;
+            _ = self.transfers.cancelled() => {
+                self.acks.pop_back();
+                self.transfer_id -= 1;
+                return Ok(SendSegmentResult::Cancelled);
+            }
+            r = self.transport.feed(msg) => r?,
+        }
 
         self.last_sent = tokio::time::Instant::now();
 
@@ -442,13 +489,19 @@ where
         Ok(SendSegmentResult::Ok)
     }
 
-    async fn send_once(&mut self, mut bundle: Bytes) -> Result<SendSegmentResult, Error> {
+    async fn send_once(&mut self, bundle: Bytes) -> Result<SendSegmentResult, Error> {
+        self.transfers.start(self.transfer_id, bundle.len());
+        let r = self.send_segments(bundle).await;
+        self.transfers.finish();
+        r
+    }
+
+    async fn send_segments(&mut self, mut bundle: Bytes) -> Result<SendSegmentResult, Error> {
         let mut start = true;
 
         // Segment if needed
         let mut acknowledged_length = 0;
         while bundle.len() > self.segment_mtu {
-            acknowledged_length += self.segment_mtu;
             match self
                 .send_segment(
                     codec::TransferSegmentMessageFlags {
@@ -457,35 +510,78 @@ where
                         ..Default::default()
                     },
                     bundle.split_to(self.segment_mtu),
-                    acknowledged_length,
+                    acknowledged_length + self.segment_mtu,
                 )
                 .await?
             {
                 SendSegmentResult::Ok => {}
+                SendSegmentResult::Cancelled if !start => {
+                    return self.send_cancelled(acknowledged_length).await
+                }
                 r => return Ok(r),
             }
 
+            acknowledged_length += self.segment_mtu;
+            self.transfers.advance(acknowledged_length);
             start = false;
         }
 
         // Send the last segment
-        acknowledged_length += self.segment_mtu;
-        self.send_segment(
+        let total_length = acknowledged_length + bundle.len();
+        match self
+            .send_segment(
+                codec::TransferSegmentMessageFlags {
+                    start,
+                    end: true,
+                    ..Default::default()
+                },
+                bundle,
+                total_length,
+            )
+            .await?
+        {
+            SendSegmentResult::Cancelled if !start => {
+                self.send_cancelled(acknowledged_length).await
+            }
+            r => Ok(r),
+        }
+    }
+
+    async fn send_cancelled(
+        &mut self,
+        acknowledged_length: usize,
+    ) -> Result<SendSegmentResult, Error> {
+        /* Part of the bundle has already been sent, so end the transfer with an empty segment,
+         * rather than leave the peer waiting for a final segment that will never come */
+        let msg = self.queue_segment(
             codec::TransferSegmentMessageFlags {
-                start,
                 end: true,
                 ..Default::default()
             },
-            bundle,
+            Bytes::new(),
             acknowledged_length,
-        )
-        .await
+            true,
+        );
+        self.transport.send(msg).await?;
+        self.last_sent = tokio::time::Instant::now();
+        Ok(SendSegmentResult::Cancelled)
     }
 
     async fn send(&mut self, bundle: Bytes) -> Result<SendResult, Error> {
         /* TODO:  We currently report retry-able transfer failures as 'congestion',
          * but we need a configurable fixed delay, but there has to be a better feedback mechanism */
 
+        // The peer will refuse anything bigger than its transfer MRU, so don't even try
+        if bundle.len() > self.peer_transfer_mru {
+            return self
+                .respond(Err(tonic::Status::out_of_range(format!(
+                    "Bundle of {} bytes exceeds the peer's transfer MRU of {} bytes",
+                    bundle.len(),
+                    self.peer_transfer_mru
+                ))))
+                .map(|_| SendResult::Ok);
+        }
+
         // Check we can send the segments without rolling over the transfer id
         if self
             .transfer_id
@@ -524,6 +620,9 @@ where
                         )),
                     }));
                 }
+                SendSegmentResult::Cancelled => {
+                    break self.respond(Err(tonic::Status::cancelled("Transfer cancelled")))
+                }
                 SendSegmentResult::Refused(codec::TransferRefuseReasonCode::Retransmit) => { /* Send again */ }
                 SendSegmentResult::Refused(codec::TransferRefuseReasonCode::NotAcceptable) => {
                     break self.respond(Err(tonic::Status::invalid_argument("Not acceptable")))
@@ -546,6 +645,22 @@ where
             .map(|_| self.last_sent = tokio::time::Instant::now())
     }
 
+    async fn idle_timeout(mut self) -> Result<(), Error> {
+        // The peer has gone quiet, so don't wait for a SESS_TERM reply that will never come
+        info!("No message received from peer within {IDLE_KEEPALIVE_INTERVALS} keepalive intervals, closing session");
+
+        // Best effort
+        _ = self
+            .transport
+            .send(codec::Message::SessionTerm(codec::SessionTermMessage {
+                reason_code: codec::SessionTermReasonCode::IdleTimeout,
+                ..Default::default()
+            }))
+            .await;
+        _ = self.transport.close().await;
+        Err(Error::Timeout)
+    }
+
     async fn shutdown(mut self, reason_code: codec::SessionTermReasonCode) -> Result<(), Error> {
         // The local client has closed the channel
 
@@ -682,13 +797,14 @@ where
                             self.send_keepalive().await?;
                         }
                     },
-                    msg = tokio::time::timeout(
-                        keepalive.saturating_mul(2),
+                    // Measured from the last message received, so sending our own keepalives does not extend it
+                    msg = tokio::time::timeout_at(
+                        self.last_received + keepalive.saturating_mul(IDLE_KEEPALIVE_INTERVALS),
                         self.transport.next(),
                     ) => match msg {
                         Ok(Some(Ok(codec::Message::SessionTerm(msg)))) => return self.terminate(msg).await,
                         Ok(msg) => self.process_msg(msg).await?,
-                        Err(_) => return Err(Error::Timeout),
+                        Err(_) => return self.idle_timeout().await,
                     },
                 }
             }
@@ -781,47 +897,68 @@ where
     let (send_response, recv_response) =
         unbounded_channel::<Result<ForwardBundleResponse, tonic::Status>>();
 
-    // Register the client for addr
-    register_client(
-        connection::new_client(send_request, recv_response),
-        addr,
-        peer_init.node_id,
-    )
-    .await?;
-
-    // And finally process session messages
-    let r = Session::new(
+    let session = Session::new(
         transport,
-        bpa,
+        bpa.clone(),
         keepalive_interval,
         segment_mtu
             .map(|mtu| mtu.min(peer_init.segment_mru as usize))
             .unwrap_or(peer_init.segment_mru as usize),
         config.transfer_mru as usize,
+        peer_init.transfer_mru as usize,
         recv_request,
         send_response,
+    );
+
+    // Register the client for addr
+    register_client(
+        connection::new_client(send_request, recv_response),
+        session.transfers(),
+        addr,
+        peer_init.node_id.clone(),
     )
-    .run()
-    .await
-    .inspect(|_| trace!("Session with {addr} closed gracefully"))
-    .inspect_err(|e| error!("Session with {addr} failed: {e}"));
+    .await?;
+
+    // Tell the BPA the largest bundle the peer will accept, so it can fragment before forwarding
+    if let Some(node_id) = &peer_init.node_id {
+        if let Err(e) = bpa.add_neighbour(node_id, peer_init.transfer_mru).await {
+            // Don't leave the client registered for a session that never ran
+            unregister_client(addr).await?;
+            return Err(e.into());
+        }
+    }
+
+    // And finally process session messages
+    let r = session
+        .run()
+        .await
+        .inspect(|_| trace!("Session with {addr} closed gracefully"))
+        .inspect_err(|e| error!("Session with {addr} failed: {e}"));
 
     // Unregister the client for addr, whatever happens
     unregister_client(addr).await?;
+    if let Some(node_id) = &peer_init.node_id {
+        if let Err(e) = bpa.remove_neighbour(node_id).await {
+            warn!("Failed to remove neighbour {node_id}: {e}");
+        }
+    }
 
     r
 }
 
 async fn register_client(
-    _client: connection::Client,
-    _addr: SocketAddr,
-    _node_id: Option<bpv7::Eid>,
+    client: connection::Client,
+    transfers: transfer::Transfers,
+    addr: SocketAddr,
+    node_id: Option<bpv7::Eid>,
 ) -> Result<(), Error> {
-    todo!()
+    registry::register(client, transfers, addr, node_id);
+    Ok(())
 }
 
-async fn unregister_client(_addr: SocketAddr) -> Result<(), Error> {
-    todo!()
+async fn unregister_client(addr: SocketAddr) -> Result<(), Error> {
+    registry::unregister(&addr);
+    Ok(())
 }
 
 pub async fn next_with_timeout<T>(
@@ -833,21 +970,18 @@ where
     T: futures::StreamExt<Item = Result<codec::Message, codec::Error>> + std::marker::Unpin,
 {
     // Read the next message with timeout
-    tokio::select! {
-        r = tokio::time::timeout(
-            tokio::time::Duration::from_secs(timeout as u64),
-            transport.next(),
-        ) => match r {
-            Ok(Some(Ok(m))) => {
-                Ok(m)
-            }
-            Ok(Some(Err(e))) => {
-                Err(e.into())
-            }
-            Ok(None) => Err(Error::Hangup),
-            Err(_) => Err(Error::Timeout)
-        },
-        _ = cancel_token.cancelled() => Err(Error::Cancelled)
+    match hardy_async::time::timeout(
+        tokio::time::Duration::from_secs(timeout as u64),
+        cancel_token,
+        transport.next(),
+    )
+    .await
+    {
+        Ok(Some(Ok(m))) => Ok(m),
+        Ok(Some(Err(e))) => Err(e.into()),
+        Ok(None) => Err(Error::Hangup),
+        Err(hardy_async::time::TimeoutOrCancelled::Timeout) => Err(Error::Timeout),
+        Err(hardy_async::time::TimeoutOrCancelled::Cancelled) => Err(Error::Cancelled),
     }
 }
 
@@ -902,3 +1036,324 @@ where
 
     transport.close().await.map_err(Into::into)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::{SinkExt, StreamExt};
+
+    type Transport = tokio_util::codec::Framed<tokio::io::DuplexStream, codec::MessageCodec>;
+    type Responses = UnboundedReceiver<Result<ForwardBundleResponse, tonic::Status>>;
+
+    // A session over an in-memory pipe, with the peer's end of the pipe and the CLA's channel ends
+    fn test_session(
+        keepalive_interval: u16,
+        segment_mtu: usize,
+        transfer_mru: usize,
+        peer_transfer_mru: usize,
+    ) -> (
+        Session<Transport>,
+        tokio::io::DuplexStream,
+        Sender<Vec<u8>>,
+        Responses,
+    ) {
+        let (local, remote) = tokio::io::duplex(4096);
+        let config = config::Config::builder()
+            .set_override("bpa_address", "http://[::1]:50051")
+            .unwrap()
+            .build()
+            .unwrap();
+        let (send_request, recv_request) = channel(1);
+        let (send_response, recv_response) = unbounded_channel();
+
+        let session = Session::new(
+            codec::MessageCodec::new_framed(local),
+            bpa::Bpa::new(&config),
+            keepalive_interval,
+            segment_mtu,
+            transfer_mru,
+            peer_transfer_mru,
+            recv_request,
+            send_response,
+        );
+        (session, remote, send_request, recv_response)
+    }
+
+    #[tokio::test]
+    async fn peer_transfer_mru() {
+        let (mut session, _remote, _send_request, mut recv_response) =
+            test_session(0, 16, 1024, 100);
+
+        // Bundles beyond the peer's MRU are refused without being offered to the peer
+        assert!(matches!(
+            session.send(Bytes::from(vec![0u8; 200])).await.unwrap(),
+            SendResult::Ok
+        ));
+        let status = recv_response.recv().await.unwrap().unwrap_err();
+        assert_eq!(status.code(), tonic::Code::OutOfRange);
+        assert_eq!(session.transfer_id, 0);
+        assert!(session.acks.is_empty());
+    }
+
+    #[tokio::test]
+    async fn segmented_transfer() {
+        let (mut session, remote, _send_request, mut recv_response) =
+            test_session(0, 16, 1024, 1024);
+
+        // A peer that reassembles the bundle, acknowledging each segment with the length received so far
+        let peer = tokio::spawn(async move {
+            let mut remote = codec::MessageCodec::new_framed(remote);
+            let mut received = Vec::new();
+            while let Some(Ok(codec::Message::TransferSegment(msg))) = remote.next().await {
+                received.extend_from_slice(&msg.data);
+                remote
+                    .send(codec::Message::TransferAck(codec::TransferAckMessage {
+                        message_flags: msg.message_flags.clone(),
+                        transfer_id: msg.transfer_id,
+                        acknowledged_length: received.len() as u64,
+                    }))
+                    .await
+                    .unwrap();
+                if msg.message_flags.end {
+                    break;
+                }
+            }
+            received
+        });
+
+        // The last segment is shorter than the segment MTU
+        let bundle = (0..40).collect::<Vec<u8>>();
+        assert!(matches!(
+            session.send(Bytes::from(bundle.clone())).await.unwrap(),
+            SendResult::Ok
+        ));
+        session.transport.flush().await.unwrap();
+        assert_eq!(peer.await.unwrap(), bundle);
+
+        // Every acknowledgement matches, so the send completes
+        while !session.acks.is_empty() {
+            let msg = session.transport.next().await;
+            session.process_msg(msg).await.unwrap();
+        }
+        let response = recv_response.try_recv().unwrap().unwrap();
+        assert_eq!(
+            response.result,
+            forward_bundle_response::ForwardingResult::Sent as i32
+        );
+    }
+
+    #[tokio::test]
+    async fn cancel_transfer() {
+        // The pipe is far smaller than the bundle, so sending stalls until the peer reads
+        let (mut session, remote, _send_request, mut recv_response) =
+            test_session(0, 1024, 1 << 20, 1 << 20);
+        let transfers = session.transfers();
+        assert!(transfers.progress().is_none());
+
+        let sender = tokio::spawn(async move {
+            session.send(Bytes::from(vec![0u8; 1 << 20])).await.unwrap();
+            session
+        });
+
+        // Wait for the transfer to stall against the slow reader
+        let mut progress = None;
+        while progress.is_none_or(|p: transfer::TransferProgress| p.sent == 0) {
+            tokio::time::sleep(tokio::time::Duration::from_millis(10)).await;
+            progress = transfers.progress();
+        }
+        let progress = progress.unwrap();
+        assert_eq!(progress.total, 1 << 20);
+        assert!(progress.sent < progress.total);
+
+        // Only the transfer in progress can be cancelled
+        assert!(!transfers.cancel(progress.transfer_id + 1));
+        assert!(transfers.cancel(progress.transfer_id));
+
+        // Let the peer read, so the sender can see the cancellation
+        let reader = tokio::spawn(async move {
+            let mut remote = codec::MessageCodec::new_framed(remote);
+            let mut segments = Vec::new();
+            while let Some(Ok(codec::Message::TransferSegment(msg))) = remote.next().await {
+                segments.push(msg);
+            }
+            segments
+        });
+
+        let session = sender.await.unwrap();
+        let status = recv_response.recv().await.unwrap().unwrap_err();
+        assert_eq!(status.code(), tonic::Code::Cancelled);
+        assert!(transfers.progress().is_none());
+
+        // The session survives, and the transfer is ended early with an empty final segment
+        drop(session);
+        let segments = reader.await.unwrap();
+        assert!(segments.len() < (1 << 20) / 1024);
+        let (last, rest) = segments.split_last().unwrap();
+        assert!(last.message_flags.end && last.data.is_empty());
+        assert!(rest.iter().all(|msg| !msg.message_flags.end));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn idle_timeout() {
+        let (session, remote, _send_request, _recv_response) = test_session(10, 16, 1024, 1024);
+
+        // A peer that says nothing at all, but records what it is sent
+        let peer = tokio::spawn(async move {
+            let mut remote = codec::MessageCodec::new_framed(remote);
+            let mut msgs = Vec::new();
+            while let Some(Ok(msg)) = remote.next().await {
+                msgs.push(msg);
+            }
+            msgs
+        });
+
+        let start = tokio::time::Instant::now();
+        let r = session.run().await;
+
+        // Our own keepalives must not keep the session alive
+        assert!(matches!(r, Err(Error::Timeout)));
+        assert_eq!(start.elapsed(), tokio::time::Duration::from_secs(20));
+
+        let msgs = peer.await.unwrap();
+        assert!(matches!(msgs.first(), Some(codec::Message::Keepalive)));
+        assert!(matches!(
+            msgs.last(),
+            Some(codec::Message::SessionTerm(codec::SessionTermMessage {
+                reason_code: codec::SessionTermReasonCode::IdleTimeout,
+                ..
+            }))
+        ));
+    }
+
+    // Just enough of the BPA to record the neighbours a CLA adds and removes
+    #[derive(Clone, Default)]
+    struct Neighbours(std::sync::Arc<std::sync::Mutex<Vec<String>>>);
+
+    #[tonic::async_trait]
+    impl cla_sink_server::ClaSink for Neighbours {
+        async fn register_cla(
+            &self,
+            _request: tonic::Request<RegisterClaRequest>,
+        ) -> Result<tonic::Response<RegisterClaResponse>, tonic::Status> {
+            Ok(tonic::Response::new(RegisterClaResponse { handle: 1 }))
+        }
+
+        async fn unregister_cla(
+            &self,
+            _request: tonic::Request<UnregisterClaRequest>,
+        ) -> Result<tonic::Response<UnregisterClaResponse>, tonic::Status> {
+            Ok(tonic::Response::new(UnregisterClaResponse {}))
+        }
+
+        async fn receive_bundle(
+            &self,
+            _request: tonic::Request<ReceiveBundleRequest>,
+        ) -> Result<tonic::Response<ReceiveBundleResponse>, tonic::Status> {
+            Err(tonic::Status::unimplemented("receive_bundle"))
+        }
+
+        async fn confirm_forwarding(
+            &self,
+            _request: tonic::Request<ConfirmForwardingRequest>,
+        ) -> Result<tonic::Response<ConfirmForwardingResponse>, tonic::Status> {
+            Err(tonic::Status::unimplemented("confirm_forwarding"))
+        }
+
+        async fn add_neighbour(
+            &self,
+            request: tonic::Request<AddNeighbourRequest>,
+        ) -> Result<tonic::Response<AddNeighbourResponse>, tonic::Status> {
+            self.0
+                .lock()
+                .unwrap()
+                .push(format!("add {}", request.into_inner().neighbour));
+            Ok(tonic::Response::new(AddNeighbourResponse {}))
+        }
+
+        async fn remove_neighbour(
+            &self,
+            request: tonic::Request<RemoveNeighbourRequest>,
+        ) -> Result<tonic::Response<RemoveNeighbourResponse>, tonic::Status> {
+            self.0
+                .lock()
+                .unwrap()
+                .push(format!("remove {}", request.into_inner().neighbour));
+            Ok(tonic::Response::new(RemoveNeighbourResponse {}))
+        }
+
+        async fn report_link_quality(
+            &self,
+            _request: tonic::Request<ReportLinkQualityRequest>,
+        ) -> Result<tonic::Response<ReportLinkQualityResponse>, tonic::Status> {
+            Err(tonic::Status::unimplemented("report_link_quality"))
+        }
+
+        async fn list_neighbours(
+            &self,
+            _request: tonic::Request<ListNeighboursRequest>,
+        ) -> Result<tonic::Response<ListNeighboursResponse>, tonic::Status> {
+            Err(tonic::Status::unimplemented("list_neighbours"))
+        }
+    }
+
+    #[tokio::test]
+    async fn idle_peer_removed() {
+        let neighbours = Neighbours::default();
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let bpa_address = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(
+            tonic::transport::Server::builder()
+                .add_service(cla_sink_server::ClaSinkServer::new(neighbours.clone()))
+                .serve_with_incoming(tokio_stream::wrappers::TcpListenerStream::new(listener)),
+        );
+
+        let config = config::Config::builder()
+            .set_override("bpa_address", bpa_address)
+            .unwrap()
+            .set_override("keepalive_interval", 1)
+            .unwrap()
+            .build()
+            .unwrap();
+        let mut bpa = bpa::Bpa::new(&config);
+        bpa.connect().await;
+
+        // A peer that introduces itself, then falls silent
+        let (local, remote) = tokio::io::duplex(4096);
+        let peer = tokio::spawn(async move {
+            let mut remote = codec::MessageCodec::new_framed(remote);
+            remote
+                .send(codec::Message::SessionInit(codec::SessionInitMessage {
+                    keepalive_interval: 1,
+                    segment_mru: 1024,
+                    transfer_mru: 1024,
+                    node_id: Some("ipn:2.0".parse().unwrap()),
+                    ..Default::default()
+                }))
+                .await
+                .unwrap();
+            while let Some(Ok(_)) = remote.next().await {}
+        });
+
+        let start = std::time::Instant::now();
+        let r = new_passive(
+            Config::new(&config),
+            bpa,
+            "127.0.0.1:4556".parse().unwrap(),
+            None,
+            codec::MessageCodec::new_framed(local),
+            tokio_util::sync::CancellationToken::new(),
+        )
+        .await;
+
+        // The session closes after two missed keepalive intervals, and the BPA forgets the peer
+        assert!(matches!(r, Err(Error::Timeout)));
+        let elapsed = start.elapsed();
+        assert!(elapsed >= std::time::Duration::from_secs(2));
+        assert!(elapsed < std::time::Duration::from_secs(4));
+        assert_eq!(
+            *neighbours.0.lock().unwrap(),
+            vec!["add ipn:2.0".to_string(), "remove ipn:2.0".to_string()]
+        );
+        peer.await.unwrap();
+    }
+}
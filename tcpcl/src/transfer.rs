@@ -0,0 +1,96 @@
+use super::*;
+use std::sync::{Arc, Mutex};
+
+// How far through sending a bundle a session is
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TransferProgress {
+    // The transfer id of the first segment of the bundle
+    pub transfer_id: u64,
+    pub sent: usize,
+    pub total: usize,
+}
+
+#[derive(Default)]
+struct State {
+    current: Option<TransferProgress>,
+    cancelled: bool,
+}
+
+#[derive(Default)]
+struct Inner {
+    state: Mutex<State>,
+    notify: tokio::sync::Notify,
+}
+
+/* A handle on the outbound transfer of a session, to monitor its progress and cancel it.
+ * A cancelled transfer is ended early with an empty final segment, so the peer discards the
+ * partial bundle, and the session itself carries on */
+#[derive(Clone, Default)]
+pub struct Transfers(Arc<Inner>);
+
+impl Transfers {
+    fn lock(&self) -> std::sync::MutexGuard<'_, State> {
+        self.0
+            .state
+            .lock()
+            .trace_expect("Failed to lock transfers mutex")
+    }
+
+    pub fn progress(&self) -> Option<TransferProgress> {
+        self.lock().current
+    }
+
+    // Cancel the transfer `transfer_id`, returns false if it is not in progress
+    pub fn cancel(&self, transfer_id: u64) -> bool {
+        let mut state = self.lock();
+        if state.current.map(|c| c.transfer_id) == Some(transfer_id) {
+            state.cancelled = true;
+            self.0.notify.notify_waiters();
+            true
+        } else {
+            false
+        }
+    }
+
+    // Cancel whatever transfer is in progress
+    pub fn cancel_current(&self) -> bool {
+        self.progress().is_some_and(|p| self.cancel(p.transfer_id))
+    }
+
+    pub(crate) fn start(&self, transfer_id: u64, total: usize) {
+        *self.lock() = State {
+            current: Some(TransferProgress {
+                transfer_id,
+                sent: 0,
+                total,
+            }),
+            cancelled: false,
+        };
+    }
+
+    pub(crate) fn advance(&self, sent: usize) {
+        if let Some(current) = &mut self.lock().current {
+            current.sent = sent;
+        }
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.lock().cancelled
+    }
+
+    // Resolves once the current transfer is cancelled
+    pub(crate) async fn cancelled(&self) {
+        loop {
+            // Created before checking, so a cancel in between is not missed
+            let notified = self.0.notify.notified();
+            if self.is_cancelled() {
+                return;
+            }
+            notified.await;
+        }
+    }
+
+    pub(crate) fn finish(&self) {
+        *self.lock() = State::default();
+    }
+}
@@ -0,0 +1,367 @@
+use bytes::Bytes;
+use hardy_bpv7::prelude::*;
+use hardy_proto::cla::*;
+use std::net::SocketAddr;
+use tonic::{Request, Response, Status};
+
+#[derive(clap::Args, Debug)]
+pub struct Args {
+    /// gRPC address of the BPA
+    #[arg(short, long, default_value = "http://[::1]:50051")]
+    bpa: String,
+
+    /// Local address to serve the null CLA gRPC endpoint on
+    #[arg(long, default_value = "[::1]:50060")]
+    listen: SocketAddr,
+
+    #[arg(short, long)]
+    source: Eid,
+
+    #[arg(short, long)]
+    destination: Eid,
+
+    /// Payload size of each bundle in bytes
+    #[arg(long, default_value_t = 1024)]
+    size: usize,
+
+    /// Bundles per second, unlimited if not set
+    #[arg(short, long)]
+    rate: Option<u32>,
+
+    /// Total number of bundles to send
+    #[arg(short, long)]
+    count: Option<u64>,
+
+    /// Stop sending after this duration
+    #[arg(short = 't', long)]
+    duration: Option<humantime::Duration>,
+
+    #[arg(short, long)]
+    lifetime: Option<humantime::Duration>,
+}
+
+#[derive(Debug, Default)]
+pub struct Stats {
+    pub sent: u64,
+    pub bytes: u64,
+    pub backpressure: u64,
+    pub failed: u64,
+    pub elapsed: std::time::Duration,
+}
+
+impl std::fmt::Display for Stats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let secs = self.elapsed.as_secs_f64().max(f64::EPSILON);
+        write!(
+            f,
+            "sent {} bundles ({} bytes) in {:.3}s: {:.1} bundles/s, {:.1} KiB/s, {} backpressure events, {} failures",
+            self.sent,
+            self.bytes,
+            secs,
+            self.sent as f64 / secs,
+            self.bytes as f64 / secs / 1024.0,
+            self.backpressure,
+            self.failed
+        )
+    }
+}
+
+trait Sink {
+    async fn send(&mut self, bundle: Bytes) -> Result<(), Status>;
+}
+
+struct BpaSink {
+    client: cla_sink_client::ClaSinkClient<tonic::transport::Channel>,
+    handle: u32,
+}
+
+impl Sink for BpaSink {
+    async fn send(&mut self, bundle: Bytes) -> Result<(), Status> {
+        self.client
+            .receive_bundle(ReceiveBundleRequest {
+                handle: self.handle,
+                source: Bytes::new(),
+                bundle,
+            })
+            .await
+            .map(|_| ())
+    }
+}
+
+// The flood CLA never forwards anything, it only exists to satisfy registration
+struct NullCla;
+
+#[tonic::async_trait]
+impl cla_server::Cla for NullCla {
+    async fn forward_bundle(
+        &self,
+        _request: Request<ForwardBundleRequest>,
+    ) -> Result<Response<ForwardBundleResponse>, Status> {
+        Ok(Response::new(ForwardBundleResponse {
+            result: forward_bundle_response::ForwardingResult::Sent as i32,
+            delay: None,
+        }))
+    }
+}
+
+// The lifetime in milliseconds, if one was requested
+fn lifetime(args: &Args) -> Result<Option<u64>, String> {
+    let Some(lifetime) = args.lifetime else {
+        return Ok(None);
+    };
+    match u64::try_from(lifetime.as_millis()) {
+        Ok(0) => Err("Lifetime must be at least 1ms".to_string()),
+        Ok(millis) => Ok(Some(millis)),
+        Err(_) => Err(format!("Lifetime {lifetime} is too long")),
+    }
+}
+
+// Send the bundles, recording them in `stats` as they go, so they are not lost if interrupted
+async fn run(args: &Args, lifetime: Option<u64>, sink: &mut impl Sink, stats: &mut Stats) {
+    let payload = (0..args.size).map(|i| i as u8).collect::<Vec<u8>>();
+
+    let mut interval = args.rate.map(|rate| {
+        let mut interval =
+            tokio::time::interval(std::time::Duration::from_secs_f64(1.0 / rate.max(1) as f64));
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+        interval
+    });
+
+    // How long to wait before trying again after the BPA pushes back, doubling while it persists
+    const MIN_BACKOFF: std::time::Duration = std::time::Duration::from_millis(1);
+    const MAX_BACKOFF: std::time::Duration = std::time::Duration::from_secs(1);
+    let mut backoff = MIN_BACKOFF;
+
+    let start = std::time::Instant::now();
+    loop {
+        if args.count.is_some_and(|count| stats.sent >= count) {
+            break;
+        }
+        if args
+            .duration
+            .is_some_and(|duration| start.elapsed() >= *duration)
+        {
+            break;
+        }
+
+        if let Some(interval) = &mut interval {
+            interval.tick().await;
+        }
+
+        let mut b = Builder::new()
+            .source(args.source.clone())
+            .destination(args.destination.clone());
+        if let Some(lifetime) = lifetime {
+            b = b.lifetime(lifetime);
+        }
+        let data = b.add_payload_block(payload.clone()).build().1;
+        let len = data.len() as u64;
+
+        match sink.send(data.into()).await {
+            Ok(()) => {
+                stats.sent += 1;
+                stats.bytes += len;
+                backoff = MIN_BACKOFF;
+            }
+            Err(s)
+                if matches!(
+                    s.code(),
+                    tonic::Code::ResourceExhausted | tonic::Code::Unavailable
+                ) =>
+            {
+                stats.backpressure += 1;
+                tokio::time::sleep(backoff).await;
+                backoff = (backoff * 2).min(MAX_BACKOFF);
+            }
+            Err(s) => {
+                eprintln!("Failed to send bundle: {s}");
+                stats.failed += 1;
+            }
+        }
+    }
+}
+
+pub async fn exec(args: Args) {
+    let lifetime = match lifetime(&args) {
+        Ok(lifetime) => lifetime,
+        Err(e) => {
+            eprintln!("{e}");
+            return;
+        }
+    };
+
+    let mut client = match cla_sink_client::ClaSinkClient::connect(args.bpa.clone()).await {
+        Ok(client) => client,
+        Err(e) => {
+            eprintln!("Failed to connect to BPA {}: {e}", args.bpa);
+            return;
+        }
+    };
+
+    // Serve the null CLA so the BPA can connect back to us
+    let (shutdown_tx, shutdown_rx) = tokio::sync::oneshot::channel::<()>();
+    let server = tokio::spawn(
+        tonic::transport::Server::builder()
+            .add_service(cla_server::ClaServer::new(NullCla))
+            .serve_with_shutdown(args.listen, async {
+                _ = shutdown_rx.await;
+            }),
+    );
+
+    match client
+        .register_cla(RegisterClaRequest {
+            ident: format!("flood-{}", std::process::id()),
+            name: "flood".to_string(),
+            grpc_address: format!("http://{}", args.listen),
+        })
+        .await
+    {
+        Ok(response) => {
+            let handle = response.into_inner().handle;
+            let mut sink = BpaSink { client, handle };
+
+            let mut stats = Stats::default();
+            let start = std::time::Instant::now();
+            tokio::select! {
+                _ = run(&args, lifetime, &mut sink, &mut stats) => {},
+                _ = tokio::signal::ctrl_c() => eprintln!("Interrupted"),
+            }
+            stats.elapsed = start.elapsed();
+            println!("{stats}");
+
+            if let Err(e) = sink
+                .client
+                .unregister_cla(UnregisterClaRequest { handle })
+                .await
+            {
+                eprintln!("Failed to unregister with BPA: {e}");
+            }
+        }
+        Err(e) => eprintln!("Failed to register with BPA: {e}"),
+    }
+
+    _ = shutdown_tx.send(());
+    _ = server.await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hardy_bpa::*;
+    use std::sync::Arc;
+
+    // Hands bundles straight to an in-process BPA, as its gRPC CLA sink would
+    struct DispatcherSink(Arc<dispatcher::Dispatcher>);
+
+    impl Sink for DispatcherSink {
+        async fn send(&mut self, bundle: Bytes) -> Result<(), Status> {
+            self.0.receive_bundle(bundle, None).await.map_err(|e| {
+                if e.is::<dispatcher::Backpressure>() {
+                    Status::resource_exhausted(e.to_string())
+                } else if e.is::<store::StorageDegraded>() {
+                    Status::unavailable(e.to_string())
+                } else {
+                    Status::from_error(e)
+                }
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn smoke() {
+        let config = config::Config::builder()
+            .set_default("administrative_endpoint", "ipn:9.0")
+            .unwrap()
+            .set_default("metadata_storage", "mem-storage")
+            .unwrap()
+            .set_default("bundle_storage", "mem-storage")
+            .unwrap()
+            .build()
+            .unwrap();
+
+        // A BPA with a null CLA reaching the destination node
+        let admin_endpoints = utils::admin_endpoints::AdminEndpoints::init(&config);
+        let store = store::Store::new(&config, false, Arc::new(utils::clock::SystemClock));
+        let fib = fib::Fib::new(&config);
+        let cla_registry = cla_registry::ClaRegistry::new(&config, fib.clone());
+        let (handle, cla) = cla_registry
+            .register_null_cla("null0", "Null")
+            .await
+            .unwrap();
+        cla_registry
+            .add_neighbour(AddNeighbourRequest {
+                handle,
+                priority: 0,
+                neighbour: "ipn:2.*".to_string(),
+                max_bundle_size: None,
+            })
+            .await
+            .unwrap();
+
+        let mut task_set = tokio::task::JoinSet::new();
+        let cancel_token = tokio_util::sync::CancellationToken::new();
+        let dispatcher = dispatcher::Dispatcher::new(
+            &config,
+            admin_endpoints.clone(),
+            store,
+            cla_registry,
+            app_registry::AppRegistry::new(&config, admin_endpoints),
+            fib,
+            &mut task_set,
+            cancel_token.clone(),
+        );
+
+        let args = Args {
+            bpa: String::new(),
+            listen: "[::1]:0".parse().unwrap(),
+            source: "ipn:1.1".parse().unwrap(),
+            destination: "ipn:2.1".parse().unwrap(),
+            size: 64,
+            rate: None,
+            count: Some(50),
+            duration: None,
+            lifetime: None,
+        };
+
+        let mut stats = Stats::default();
+        run(
+            &args,
+            Some(60_000),
+            &mut DispatcherSink(dispatcher),
+            &mut stats,
+        )
+        .await;
+        assert_eq!(stats.sent, 50);
+        assert_eq!(stats.failed, 0);
+
+        // Every bundle sent is forwarded on to the destination
+        for _ in 0..100 {
+            if cla.forwarded() >= stats.sent as usize {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        assert_eq!(cla.forwarded(), stats.sent as usize);
+
+        cancel_token.cancel();
+        while task_set.join_next().await.is_some() {}
+    }
+
+    #[test]
+    fn lifetime_checked() {
+        let args = |lifetime: &str| Args {
+            bpa: String::new(),
+            listen: "[::1]:0".parse().unwrap(),
+            source: "ipn:1.1".parse().unwrap(),
+            destination: "ipn:2.1".parse().unwrap(),
+            size: 64,
+            rate: None,
+            count: None,
+            duration: None,
+            lifetime: Some(lifetime.parse().unwrap()),
+        };
+        assert_eq!(lifetime(&args("1h")), Ok(Some(3_600_000)));
+        assert!(lifetime(&args("0s")).is_err());
+        assert!(lifetime(&args("100000000000000000s")).is_err());
+    }
+}
@@ -0,0 +1,28 @@
+mod flood;
+mod ping;
+
+use clap::{Parser, Subcommand};
+
+#[derive(Parser, Debug)]
+#[command(version, about, long_about = None)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Generate bundles at a fixed rate and size to measure BPA throughput
+    Flood(flood::Args),
+
+    /// Send bundles to an echo endpoint through a TCPCLv4 next-hop and time the echoes
+    Ping(ping::Args),
+}
+
+#[tokio::main]
+async fn main() {
+    match Cli::parse().command {
+        Command::Flood(args) => flood::exec(args).await,
+        Command::Ping(args) => ping::exec(args).await,
+    }
+}
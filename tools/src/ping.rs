@@ -0,0 +1,377 @@
+use bytes::Bytes;
+use futures::{SinkExt, StreamExt};
+use hardy_bpv7::prelude::*;
+use hardy_tcpcl::codec::{self, Message, MessageCodec};
+use std::io;
+use std::net::SocketAddr;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+#[derive(clap::Args, Debug)]
+pub struct Args {
+    /// The echo endpoint to ping
+    destination: Eid,
+
+    /// Address of the TCPCLv4 next-hop to send the pings through
+    address: SocketAddr,
+
+    /// Node ID of this node, and source of the pings
+    #[arg(short, long, default_value = "ipn:1.0")]
+    source: Eid,
+
+    /// Number of pings to send
+    #[arg(short, long, default_value_t = 4)]
+    count: u64,
+
+    /// Time between pings
+    #[arg(short, long, default_value = "1s")]
+    interval: humantime::Duration,
+
+    /// Time to wait for each echo
+    #[arg(short, long, default_value = "5s")]
+    wait: humantime::Duration,
+}
+
+/* Just enough of a TCPCLv4 (RFC 9174) session to exchange bundles with a peer, so ping can
+ * talk directly to any TCPCLv4 implementation without a local BPA.  Messages are framed by
+ * the hardy-tcpcl codec, only the contact header is exchanged by hand */
+const SEGMENT_MRU: u64 = 16384;
+const TRANSFER_MRU: u64 = 0x4000_0000;
+
+fn invalid(msg: String) -> codec::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg).into()
+}
+
+struct Session<S> {
+    transport: tokio_util::codec::Framed<S, MessageCodec>,
+    peer_node_id: Option<Eid>,
+    segment_mru: usize,
+    transfer_id: u64,
+    ingress: Vec<u8>,
+}
+
+impl<S: AsyncRead + AsyncWrite + Unpin> Session<S> {
+    // Exchange contact headers and SESS_INIT messages, with keepalives disabled
+    async fn connect(mut stream: S, node_id: &Eid) -> Result<Self, codec::Error> {
+        stream.write_all(b"dtn!\x04\x00").await?;
+
+        let mut contact = [0u8; 6];
+        stream.read_exact(&mut contact).await?;
+        if &contact[0..4] != b"dtn!" {
+            return Err(invalid("Peer sent an invalid contact header".to_string()));
+        }
+        if contact[4] != 4 {
+            return Err(invalid(format!(
+                "Peer uses unsupported TCPCL version {}",
+                contact[4]
+            )));
+        }
+
+        let mut transport = MessageCodec::new_framed(stream);
+        transport
+            .send(Message::SessionInit(codec::SessionInitMessage {
+                segment_mru: SEGMENT_MRU,
+                transfer_mru: TRANSFER_MRU,
+                node_id: Some(node_id.clone()),
+                ..Default::default()
+            }))
+            .await?;
+
+        let init = match transport.next().await.transpose()? {
+            Some(Message::SessionInit(init)) => init,
+            Some(msg) => return Err(invalid(format!("Expected SESS_INIT, got {msg:?}"))),
+            None => return Err(invalid("Peer closed the connection".to_string())),
+        };
+
+        Ok(Self {
+            transport,
+            peer_node_id: init.node_id,
+            segment_mru: usize::try_from(init.segment_mru)
+                .unwrap_or(usize::MAX)
+                .max(1),
+            transfer_id: 0,
+            ingress: Vec::new(),
+        })
+    }
+
+    async fn send_bundle(&mut self, data: &[u8]) -> Result<(), codec::Error> {
+        let transfer_id = self.transfer_id;
+        self.transfer_id += 1;
+
+        let segments = data.chunks(self.segment_mru).count();
+        for (i, segment) in data.chunks(self.segment_mru).enumerate() {
+            self.transport
+                .feed(Message::TransferSegment(codec::TransferSegmentMessage {
+                    message_flags: codec::TransferSegmentMessageFlags {
+                        start: i == 0,
+                        end: i == segments - 1,
+                        ..Default::default()
+                    },
+                    transfer_id,
+                    data: Bytes::copy_from_slice(segment),
+                    ..Default::default()
+                }))
+                .await?;
+        }
+        self.transport.flush().await
+    }
+
+    // Receive the next complete bundle, or None if the peer ended the session
+    async fn recv_bundle(&mut self) -> Result<Option<Vec<u8>>, codec::Error> {
+        while let Some(msg) = self.transport.next().await.transpose()? {
+            match msg {
+                Message::TransferSegment(msg) => {
+                    if msg.message_flags.start {
+                        self.ingress.clear();
+                    }
+                    if (msg.data.len() as u64).saturating_add(self.ingress.len() as u64)
+                        > TRANSFER_MRU
+                    {
+                        return Err(invalid(format!("Transfer {} exceeds MRU", msg.transfer_id)));
+                    }
+                    self.ingress.extend_from_slice(&msg.data);
+
+                    self.transport
+                        .send(Message::TransferAck(codec::TransferAckMessage {
+                            message_flags: msg.message_flags.clone(),
+                            transfer_id: msg.transfer_id,
+                            acknowledged_length: self.ingress.len() as u64,
+                        }))
+                        .await?;
+
+                    if msg.message_flags.end {
+                        return Ok(Some(std::mem::take(&mut self.ingress)));
+                    }
+                }
+                Message::TransferAck(_) | Message::Keepalive => {}
+                Message::TransferRefuse(msg) => eprintln!(
+                    "Peer refused transfer {}, reason {:?}",
+                    msg.transfer_id, msg.reason_code
+                ),
+                Message::SessionTerm(msg) => {
+                    if !msg.message_flags.reply {
+                        self.transport
+                            .send(Message::SessionTerm(codec::SessionTermMessage {
+                                message_flags: codec::SessionTermMessageFlags {
+                                    reply: true,
+                                    ..Default::default()
+                                },
+                                reason_code: msg.reason_code,
+                            }))
+                            .await?;
+                    }
+                    return Ok(None);
+                }
+                Message::Reject(msg) => {
+                    return Err(invalid(format!(
+                        "Peer rejected message {:#x}, reason {:?}",
+                        msg.rejected_message, msg.reason_code
+                    )))
+                }
+                Message::SessionInit(_) => return Err(invalid("Unexpected SESS_INIT".to_string())),
+            }
+        }
+        Ok(None)
+    }
+
+    async fn terminate(&mut self) -> Result<(), codec::Error> {
+        self.transport
+            .send(Message::SessionTerm(Default::default()))
+            .await?;
+        self.transport.close().await
+    }
+}
+
+fn payload(data: Vec<u8>) -> Option<(Bundle, Bytes)> {
+    let data = Bytes::from(data);
+    let ValidBundle::Valid(bundle, _) = ValidBundle::parse(&data, |_, _| Ok(None)).ok()? else {
+        return None;
+    };
+    let payload = bundle.payload_bytes(&data, |_, _| Ok(None)).ok()??;
+    Some((bundle, payload))
+}
+
+#[derive(Debug, Default)]
+pub struct Stats {
+    pub sent: u64,
+    pub received: u64,
+    pub rtts: Vec<std::time::Duration>,
+}
+
+impl std::fmt::Display for Stats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let loss = self.sent.saturating_sub(self.received) as f64 * 100.0 / self.sent.max(1) as f64;
+        write!(
+            f,
+            "{} pings sent, {} echoes received, {loss:.1}% loss",
+            self.sent, self.received
+        )?;
+        if let (Some(min), Some(max)) = (self.rtts.iter().min(), self.rtts.iter().max()) {
+            let avg = self.rtts.iter().sum::<std::time::Duration>() / self.rtts.len() as u32;
+            write!(f, ", rtt min/avg/max = {min:?}/{avg:?}/{max:?}")?;
+        }
+        Ok(())
+    }
+}
+
+// Wait for the echo of ping `seq`, discarding anything else, returning the source of the echo
+async fn wait_echo<S: AsyncRead + AsyncWrite + Unpin>(
+    session: &mut Session<S>,
+    seq: u64,
+) -> Result<Option<Eid>, codec::Error> {
+    while let Some(data) = session.recv_bundle().await? {
+        if let Some((bundle, payload)) = payload(data) {
+            if payload.as_ref() == seq.to_be_bytes() {
+                return Ok(Some(bundle.id.source));
+            }
+        }
+    }
+    Ok(None)
+}
+
+// Send the pings, recording them in `stats` as they go, so they are not lost if interrupted
+async fn run<S: AsyncRead + AsyncWrite + Unpin>(
+    args: &Args,
+    session: &mut Session<S>,
+    stats: &mut Stats,
+) -> Result<(), codec::Error> {
+    for seq in 0..args.count {
+        if seq != 0 {
+            tokio::time::sleep(*args.interval).await;
+        }
+
+        let data = Builder::new()
+            .source(args.source.clone())
+            .destination(args.destination.clone())
+            .add_payload_block(seq.to_be_bytes().to_vec())
+            .build()
+            .1;
+
+        let sent = std::time::Instant::now();
+        session.send_bundle(&data).await?;
+        stats.sent += 1;
+
+        match tokio::time::timeout(*args.wait, wait_echo(session, seq)).await {
+            Ok(Ok(Some(from))) => {
+                let rtt = sent.elapsed();
+                println!("Echo from {from}: seq={seq} time={rtt:?}");
+                stats.received += 1;
+                stats.rtts.push(rtt);
+            }
+            Ok(Ok(None)) => {
+                eprintln!("Peer ended the session");
+                break;
+            }
+            Ok(Err(e)) => return Err(e),
+            Err(_) => println!("No echo for seq={seq}"),
+        }
+    }
+    Ok(())
+}
+
+pub async fn exec(args: Args) {
+    let stream = match tokio::net::TcpStream::connect(args.address).await {
+        Ok(stream) => stream,
+        Err(e) => {
+            eprintln!("Failed to connect to next-hop {}: {e}", args.address);
+            return;
+        }
+    };
+    let mut session = match Session::connect(stream, &args.source).await {
+        Ok(session) => session,
+        Err(e) => {
+            eprintln!("Failed to establish TCPCLv4 session: {e}");
+            return;
+        }
+    };
+    println!(
+        "Pinging {} via {} ({})",
+        args.destination,
+        session
+            .peer_node_id
+            .as_ref()
+            .map_or("an anonymous peer".to_string(), Eid::to_string),
+        args.address
+    );
+
+    let mut stats = Stats::default();
+    tokio::select! {
+        r = run(&args, &mut session, &mut stats) => if let Err(e) = r {
+            eprintln!("Session failed: {e}");
+        },
+        _ = tokio::signal::ctrl_c() => eprintln!("Interrupted"),
+    }
+    println!("{stats}");
+
+    _ = session.terminate().await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A TCPCLv4 peer that echoes every bundle back to its source
+    async fn echo_peer(listener: tokio::net::TcpListener) {
+        let (stream, _) = listener.accept().await.unwrap();
+        let mut session = Session::connect(stream, &"ipn:2.0".parse().unwrap())
+            .await
+            .unwrap();
+        while let Some(data) = session.recv_bundle().await.unwrap() {
+            let (bundle, payload) = payload(data).unwrap();
+            let reply = Builder::new()
+                .source(bundle.destination.clone())
+                .destination(bundle.id.source.clone())
+                .add_payload_block(payload.to_vec())
+                .build()
+                .1;
+            session.send_bundle(&reply).await.unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn loopback() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let peer = tokio::spawn(echo_peer(listener));
+
+        let args = Args {
+            destination: "ipn:2.7".parse().unwrap(),
+            address,
+            source: "ipn:1.0".parse().unwrap(),
+            count: 3,
+            interval: std::time::Duration::ZERO.into(),
+            wait: std::time::Duration::from_secs(5).into(),
+        };
+
+        let stream = tokio::net::TcpStream::connect(address).await.unwrap();
+        let mut session = Session::connect(stream, &args.source).await.unwrap();
+        assert_eq!(session.peer_node_id, Some("ipn:2.0".parse().unwrap()));
+
+        let mut stats = Stats::default();
+        run(&args, &mut session, &mut stats).await.unwrap();
+        assert_eq!(stats.sent, 3);
+        assert_eq!(stats.received, 3);
+        assert_eq!(stats.rtts.len(), 3);
+
+        session.terminate().await.unwrap();
+        peer.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn segmented() {
+        let (local, remote) = tokio::io::duplex(1 << 16);
+        let peer = tokio::spawn(async move {
+            let mut session = Session::connect(remote, &"ipn:2.0".parse().unwrap())
+                .await
+                .unwrap();
+            session.recv_bundle().await.unwrap().unwrap()
+        });
+
+        // Bundles bigger than the peer's segment MRU are split across segments
+        let mut session = Session::connect(local, &"ipn:1.0".parse().unwrap())
+            .await
+            .unwrap();
+        let data = vec![0x5a; SEGMENT_MRU as usize * 2 + 1];
+        session.send_bundle(&data).await.unwrap();
+        assert_eq!(peer.await.unwrap(), data);
+    }
+}